@@ -1,7 +1,15 @@
 use rand::{thread_rng, RngCore};
-use sled::{transaction::ConflictableTransactionError, Db, Transactional};
+use sled::{
+    transaction::{ConflictableTransactionError, ConflictableTransactionResult, TransactionalTree},
+    Db, Transactional,
+};
 
-use std::{convert::TryInto, path::PathBuf, string::String};
+use std::{
+    convert::TryInto,
+    path::PathBuf,
+    string::String,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 pub fn dirlist(args: Vec<String>) {
     if args.len() < 3 {
@@ -25,6 +33,7 @@ pub fn dirlist(args: Vec<String>) {
             Ok((id_bytes, dir_bytes)) => {
                 let dir_id = u64::from_be_bytes(id_bytes.as_ref().try_into().unwrap());
                 let parent_id = u64::from_be_bytes(dir_bytes[0..8].try_into().unwrap());
+                // Older versions of Kasten stored the IDs of the childs before the name:
                 let name_start =
                     18 + (8 * u16::from_be_bytes(dir_bytes[16..18].try_into().unwrap()) as usize);
                 let dirname = String::from_utf8(Vec::from(&dir_bytes[name_start..])).unwrap();
@@ -65,61 +74,222 @@ pub fn diradd(args: Vec<String>) {
     let permissions_tree = sled_db
         .open_tree(b"fs_node_permissions")
         .expect("Could not open permissions tree.");
+    let dir_stats_tree = sled_db
+        .open_tree(b"dir_stats")
+        .expect("Could not open dir-stats tree.");
+    let dir_child_tree = sled_db
+        .open_tree(b"dir_childs")
+        .expect("Could not open dir-childs tree.");
+    let child_position_tree = sled_db
+        .open_tree(b"dir_child_positions")
+        .expect("Could not open dir-child-positions tree.");
+    let name_tree = sled_db
+        .open_tree(b"fs_node_names")
+        .expect("Could not open fs-node-names tree.");
 
     // Byte representation of new dir:
     let mut data = Vec::from(&parent_id.to_be_bytes()[..]);
     data.extend_from_slice(&owner_id.to_be_bytes());
-    data.push(0); // Child number
-    data.push(0); // Child number
+    data.push(0); // Child number (unused)
+    data.push(0); // Child number (unused)
     data.extend_from_slice(name.as_bytes());
 
-    (&dir_tree, &file_tree, &permissions_tree)
-        .transaction(|(dir_t, file_t, perm_t)| {
-            // Generate new dir-id:
-            let mut rng = thread_rng();
-            let mut dir_id = rng.next_u64();
-            while dir_t.get(&dir_id.to_be_bytes())?.is_some()
-                || file_t.get(&dir_id.to_be_bytes())?.is_some()
-                || dir_id == 0
-            {
-                dir_id = rng.next_u64();
-            }
-            let parent_bytes = if let Some(b) = dir_t.get(parent_id.to_be_bytes())? {
-                b
-            } else {
-                return Err(ConflictableTransactionError::Abort(
-                    "The parent directory does not exist.",
-                ));
-            };
-            let mut new_parent_bytes = Vec::from(&parent_bytes[0..16]);
-
-            // Increase child-number:
-            let mut child_number = u16::from_be_bytes(parent_bytes[16..18].try_into().unwrap());
-            // TODO: Handle overflow:
-            child_number += 1;
-            new_parent_bytes.push(child_number.to_be_bytes()[0]);
-            new_parent_bytes.push(child_number.to_be_bytes()[1]);
-            // Add old childs:
-            new_parent_bytes
-                .extend_from_slice(&parent_bytes[18..(18 + (child_number as usize - 1) * 8)]);
-            // Add new child:
-            new_parent_bytes.extend_from_slice(&dir_id.to_be_bytes());
-            // Add name of parent:
-            new_parent_bytes
-                .extend_from_slice(&parent_bytes[(18 + (child_number as usize - 1) * 8)..]);
-
-            // Insert new parent directory:
-            dir_t.insert(&parent_id.to_be_bytes(), new_parent_bytes)?;
-            // Insert directory into dir-tree:
-            dir_t.insert(&dir_id.to_be_bytes(), data.as_slice())?;
-            // Insert permissions into permissions-tree:
-            perm_t.insert(&dir_id.to_be_bytes(), &[0u8, 0u8, 0u8, 0u8])?;
-
-            Ok(())
-        })
+    (
+        &dir_tree,
+        &file_tree,
+        &permissions_tree,
+        &dir_stats_tree,
+        &dir_child_tree,
+        &child_position_tree,
+        &name_tree,
+    )
+        .transaction(
+            |(dir_t, file_t, perm_t, stats_t, child_t, position_t, name_t)| {
+                // Generate new dir-id:
+                let mut rng = thread_rng();
+                let mut dir_id = rng.next_u64();
+                while dir_t.get(&dir_id.to_be_bytes())?.is_some()
+                    || file_t.get(&dir_id.to_be_bytes())?.is_some()
+                    || dir_id == 0
+                {
+                    dir_id = rng.next_u64();
+                }
+                if dir_t.get(parent_id.to_be_bytes())?.is_none() {
+                    return Err(ConflictableTransactionError::Abort(
+                        "The parent directory does not exist.",
+                    ));
+                }
+                let name_key = name_key(parent_id, &name);
+                if name_t.get(&name_key)?.is_some() {
+                    return Err(ConflictableTransactionError::Abort(
+                        "The parent directory already contains a child with this name.",
+                    ));
+                }
+
+                // Insert directory into dir-tree:
+                dir_t.insert(&dir_id.to_be_bytes(), data.as_slice())?;
+                stats_t.insert(&dir_id.to_be_bytes(), new_dir_stats())?;
+                add_child_t(stats_t, child_t, position_t, parent_id, dir_id)?;
+                name_t.insert(name_key, &dir_id.to_be_bytes())?;
+                // Insert permissions into permissions-tree:
+                perm_t.insert(&dir_id.to_be_bytes(), &[0u8, 0u8, 0u8, 0u8])?;
+
+                Ok(())
+            },
+        )
         .expect("Could not insert directory: Transaction failed.");
 }
 
+/**
+ * Lists the FsNode given by `child_id` as last child of the directory given by `parent_id` in the
+ * trees, in which Kasten stores the childs of directories, and updates the child count and
+ * modification date of the directory.
+ */
+pub fn add_child_t(
+    stats_t: &TransactionalTree,
+    child_t: &TransactionalTree,
+    position_t: &TransactionalTree,
+    parent_id: u64,
+    child_id: u64,
+) -> ConflictableTransactionResult<(), &'static str> {
+    let mut stats = dir_stats_t(stats_t, parent_id)?;
+    let position = u64::from_be_bytes(stats[32..40].try_into().unwrap());
+    child_t.insert(child_key(parent_id, position), &child_id.to_be_bytes())?;
+    position_t.insert(&child_id.to_be_bytes(), &position.to_be_bytes())?;
+
+    stats[8..16].copy_from_slice(&now().to_be_bytes());
+    stats[32..40].copy_from_slice(&(position + 1).to_be_bytes());
+    stats_t.insert(&parent_id.to_be_bytes(), stats)?;
+    Ok(())
+}
+
+/**
+ * Removes the FsNode given by `child_id` from the childs of the directory given by `parent_id`
+ * (see `add_child_t()`). The last child of the directory takes its position.
+ */
+pub fn remove_child_t(
+    stats_t: &TransactionalTree,
+    child_t: &TransactionalTree,
+    position_t: &TransactionalTree,
+    parent_id: u64,
+    child_id: u64,
+) -> ConflictableTransactionResult<(), &'static str> {
+    let position = match position_t.get(child_id.to_be_bytes())? {
+        Some(bytes) => u64::from_be_bytes(bytes.as_ref().try_into().unwrap()),
+        None => return Ok(()),
+    };
+    // The FsNode may be listed by another directory (e.g. the one, it was trashed from):
+    if child_t.get(child_key(parent_id, position))?.as_deref() != Some(&child_id.to_be_bytes()) {
+        return Ok(());
+    }
+    position_t.remove(&child_id.to_be_bytes())?;
+
+    let mut stats = dir_stats_t(stats_t, parent_id)?;
+    let last_position = u64::from_be_bytes(stats[32..40].try_into().unwrap())
+        .checked_sub(1)
+        .ok_or(ConflictableTransactionError::Abort(
+            "The child count of the parent directory is wrong.",
+        ))?;
+    let last_id = child_t.remove(child_key(parent_id, last_position))?.ok_or(
+        ConflictableTransactionError::Abort("The childs of the parent directory are incomplete."),
+    )?;
+    if position != last_position {
+        child_t.insert(child_key(parent_id, position), last_id.clone())?;
+        position_t.insert(last_id, &position.to_be_bytes())?;
+    }
+
+    stats[8..16].copy_from_slice(&now().to_be_bytes());
+    stats[32..40].copy_from_slice(&last_position.to_be_bytes());
+    stats_t.insert(&parent_id.to_be_bytes(), stats)?;
+    Ok(())
+}
+
+/**
+ * Adds `size_diff` to the size and `count_diff` to the file count of the directory given by
+ * `dir_id` and of all its ancestors, that list it (see `add_child_t()`).
+ */
+pub fn add_to_dir_stats_t(
+    dir_t: &TransactionalTree,
+    stats_t: &TransactionalTree,
+    child_t: &TransactionalTree,
+    position_t: &TransactionalTree,
+    dir_id: u64,
+    size_diff: i64,
+    count_diff: i64,
+) -> ConflictableTransactionResult<(), &'static str> {
+    let mut current_id = dir_id;
+    while let Some(dir_bytes) = dir_t.get(current_id.to_be_bytes())? {
+        let mut stats = dir_stats_t(stats_t, current_id)?;
+        let size = u64::from_be_bytes(stats[16..24].try_into().unwrap());
+        let file_count = u64::from_be_bytes(stats[24..32].try_into().unwrap());
+        stats[16..24].copy_from_slice(&size.saturating_add_signed(size_diff).to_be_bytes());
+        stats[24..32].copy_from_slice(&file_count.saturating_add_signed(count_diff).to_be_bytes());
+        stats_t.insert(&current_id.to_be_bytes(), stats)?;
+
+        // Directories in the trash are not listed by their parent:
+        let parent_id = u64::from_be_bytes(dir_bytes[0..8].try_into().unwrap());
+        let is_listed = match position_t.get(current_id.to_be_bytes())? {
+            Some(position) => {
+                let position = u64::from_be_bytes(position.as_ref().try_into().unwrap());
+                child_t.get(child_key(parent_id, position))?.as_deref()
+                    == Some(&current_id.to_be_bytes()[..])
+            }
+            None => false,
+        };
+        if !is_listed {
+            break;
+        }
+        current_id = parent_id;
+    }
+    Ok(())
+}
+
+/// Returns the statistics of a new, empty directory: creation date, modification date, size, file
+/// count and child count.
+pub fn new_dir_stats() -> Vec<u8> {
+    let mut stats = Vec::with_capacity(40);
+    stats.extend_from_slice(&now().to_be_bytes());
+    stats.extend_from_slice(&now().to_be_bytes());
+    stats.extend_from_slice(&[0u8; 24]);
+    stats
+}
+
+/// Returns the statistics of the directory given by `dir_id` (see `new_dir_stats()`).
+fn dir_stats_t(
+    stats_t: &TransactionalTree,
+    dir_id: u64,
+) -> ConflictableTransactionResult<Vec<u8>, &'static str> {
+    let mut stats = match stats_t.get(dir_id.to_be_bytes())? {
+        Some(bytes) => bytes.to_vec(),
+        None => new_dir_stats(),
+    };
+    // Statistics written by older versions of Kasten have no child count:
+    stats.resize(40, 0);
+    Ok(stats)
+}
+
+/// Returns the key of the child named `name` of the directory given by `parent_id` in the index of
+/// names of Kasten.
+pub fn name_key(parent_id: u64, name: &str) -> Vec<u8> {
+    let mut key = Vec::from(&parent_id.to_be_bytes()[..]);
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+fn child_key(dir_id: u64, position: u64) -> Vec<u8> {
+    let mut key = Vec::from(&dir_id.to_be_bytes()[..]);
+    key.extend_from_slice(&position.to_be_bytes());
+    key
+}
+
+/// Returns the current time as UNIX timestamp.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
 fn open_db(location: &str) -> Result<Db, &'static str> {
     let db_location = PathBuf::from(location);
     if !db_location.is_dir() {
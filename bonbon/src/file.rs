@@ -1,4 +1,9 @@
-use sled::Db;
+use sled::{
+    transaction::{ConflictableTransactionError, TransactionalTree},
+    Db, Transactional,
+};
+
+use crate::dir;
 
 use std::{convert::TryInto, path::PathBuf, string::String};
 
@@ -62,15 +67,314 @@ pub fn filerm(args: Vec<String>) {
     let permissions_tree = sled_db
         .open_tree(b"fs_node_permissions")
         .expect("Could not open files tree.");
+    let dir_stats_tree = sled_db
+        .open_tree(b"dir_stats")
+        .expect("Could not open dir-stats tree.");
+    let dir_child_tree = sled_db
+        .open_tree(b"dir_childs")
+        .expect("Could not open dir-childs tree.");
+    let child_position_tree = sled_db
+        .open_tree(b"dir_child_positions")
+        .expect("Could not open dir-child-positions tree.");
+    let name_tree = sled_db
+        .open_tree(b"fs_node_names")
+        .expect("Could not open fs-node-names tree.");
+
+    let dir_tree = sled_db
+        .open_tree(b"dirs")
+        .expect("Could not open dirs tree.");
+    let version_tree = sled_db
+        .open_tree(b"file_versions")
+        .expect("Could not open versions tree.");
+    let version_limit_tree = sled_db
+        .open_tree(b"file_version_limits")
+        .expect("Could not open version limits tree.");
+    let blob_ref_tree = sled_db
+        .open_tree(b"blob_refs")
+        .expect("Could not open blob refs tree.");
+    let share_tree = sled_db
+        .open_tree(b"fs_node_shares")
+        .expect("Could not open shares tree.");
+    let node_share_tree = sled_db
+        .open_tree(b"fs_node_share_tokens")
+        .expect("Could not open share tokens tree.");
+
+    // The versions and public links of the file are removed in the same transaction:
+    let version_keys: Vec<_> = version_tree
+        .scan_prefix(id)
+        .keys()
+        .collect::<Result<_, _>>()
+        .expect("Error while reading from DB.");
+    let share_keys: Vec<_> = node_share_tree
+        .scan_prefix(id)
+        .keys()
+        .collect::<Result<_, _>>()
+        .expect("Error while reading from DB.");
+
+    let res = (
+        &file_tree,
+        &dir_tree,
+        &permissions_tree,
+        &dir_stats_tree,
+        &dir_child_tree,
+        &child_position_tree,
+        &name_tree,
+        &version_tree,
+        &version_limit_tree,
+        &blob_ref_tree,
+        &share_tree,
+        &node_share_tree,
+    )
+        .transaction(
+            |(
+                file_t,
+                dir_t,
+                perm_t,
+                stats_t,
+                child_t,
+                position_t,
+                name_t,
+                version_t,
+                limit_t,
+                blob_ref_t,
+                share_t,
+                node_share_t,
+            )| {
+                let file_bytes = match file_t.remove(&id)? {
+                    Some(bytes) => bytes,
+                    None => {
+                        return Err(ConflictableTransactionError::Abort(
+                            "The file does not exist.",
+                        ))
+                    }
+                };
+                perm_t.remove(&id)?;
+                limit_t.remove(&id)?;
+                for key in &version_keys {
+                    if let Some(value) = version_t.remove(key)? {
+                        // Unreferenced blobs are removed by `bonbon filesweep --remove`:
+                        release_blob_t(blob_ref_t, &value[24..56])?;
+                    }
+                }
+                for key in &share_keys {
+                    share_t.remove(&key[8..])?;
+                    node_share_t.remove(key)?;
+                }
+                let parent_id = u64::from_be_bytes(file_bytes[0..8].try_into().unwrap());
+                let file_id = u64::from_be_bytes(id);
+                let (filename, _) = parse_db_string(&file_bytes[16..]);
+                let name_key = dir::name_key(parent_id, &filename);
+                if name_t.get(&name_key)?.as_deref() == Some(&id[..]) {
+                    name_t.remove(name_key)?;
+                }
+                // Files in the trash are not counted by the ancestors anymore:
+                if position_t.get(id)?.is_some() {
+                    let size = file_size(&file_bytes) as i64;
+                    dir::add_to_dir_stats_t(
+                        dir_t, stats_t, child_t, position_t, parent_id, -size, -1,
+                    )?;
+                }
+                dir::remove_child_t(stats_t, child_t, position_t, parent_id, file_id)
+            },
+        );
+    if let Err(e) = res {
+        println!("Error while removing the file: {}", e);
+    }
+}
 
-    if let Err(_) = file_tree.remove(id) {
-        println!("Error while removing from files-tree.");
+pub fn filesweep(args: Vec<String>) {
+    if args.len() < 4 {
+        println!("Usage: bonbon filesweep <db-location> <file-location> [--remove]");
+        return;
     }
-    if let Err(_) = permissions_tree.remove(id) {
-        println!("Error while removing from fs_node_permissions-tree.");
+    if let Err(e) = check_local_backend() {
+        println!("{}", e);
+        return;
+    }
+    let file_location = PathBuf::from(args[3].as_str());
+    let remove = args.get(4).map(|a| a == "--remove").unwrap_or(false);
+
+    let sled_db = match open_db(args[2].as_str()) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let file_tree = sled_db
+        .open_tree(b"files")
+        .expect("Could not open files tree.");
+    let dir_tree = sled_db
+        .open_tree(b"dirs")
+        .expect("Could not open dirs tree.");
+    let permissions_tree = sled_db
+        .open_tree(b"fs_node_permissions")
+        .expect("Could not open permissions tree.");
+    let share_tree = sled_db
+        .open_tree(b"fs_node_shares")
+        .expect("Could not open shares tree.");
+    let node_share_tree = sled_db
+        .open_tree(b"fs_node_share_tokens")
+        .expect("Could not open share tokens tree.");
+    let trashed_tree = sled_db
+        .open_tree(b"trashed_fs_nodes")
+        .expect("Could not open trashed nodes tree.");
+    let version_tree = sled_db
+        .open_tree(b"file_versions")
+        .expect("Could not open versions tree.");
+    let version_limit_tree = sled_db
+        .open_tree(b"file_version_limits")
+        .expect("Could not open version limits tree.");
+    let blob_ref_tree = sled_db
+        .open_tree(b"blob_refs")
+        .expect("Could not open blob refs tree.");
+    let encrypted_blob_tree = sled_db
+        .open_tree(b"encrypted_blobs")
+        .expect("Could not open encrypted blobs tree.");
+
+    // Files, whose parent directory does not exist anymore:
+    let mut dangling_ids = Vec::new();
+    for res in &file_tree {
+        match res {
+            Ok((id_bytes, file_bytes)) => {
+                if !trashed_tree.contains_key(&id_bytes).unwrap()
+                    && !dir_tree.contains_key(&file_bytes[0..8]).unwrap()
+                {
+                    dangling_ids.push(u64::from_be_bytes(id_bytes.as_ref().try_into().unwrap()));
+                }
+            }
+            Err(e) => {
+                println!("Error while reading from DB:\n{}", e);
+                return;
+            }
+        }
+    }
+    for id in &dangling_ids {
+        let (filename, _) =
+            parse_db_string(&file_tree.get(id.to_be_bytes()).unwrap().unwrap()[16..]);
+        println!("Dangling file: {:x} \t{}", id, filename);
+        if remove {
+            let id = id.to_be_bytes();
+            file_tree
+                .remove(id)
+                .expect("Error while removing from files-tree.");
+            permissions_tree
+                .remove(id)
+                .expect("Error while removing from fs_node_permissions-tree.");
+            version_limit_tree
+                .remove(id)
+                .expect("Error while removing from file_version_limits-tree.");
+            for res in version_tree.scan_prefix(id) {
+                let (key, value) = res.expect("Error while reading from DB.");
+                // Release the blob of the version together with the version:
+                let hash = &value[24..56];
+                let refs = (&version_tree, &blob_ref_tree)
+                    .transaction(|(version_t, blob_ref_t)| {
+                        version_t.remove(&key)?;
+                        release_blob_t::<()>(blob_ref_t, hash)
+                    })
+                    .expect("Error while removing from file_versions-tree.");
+                if refs == 0 {
+                    let name: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+                    if let Err(e) = std::fs::remove_file(file_location.join(&name)) {
+                        println!("Could not remove {}: {}", name, e);
+                    }
+                    encrypted_blob_tree
+                        .remove(hash)
+                        .expect("Error while removing from encrypted_blobs-tree.");
+                }
+            }
+            for res in node_share_tree.scan_prefix(id) {
+                let (key, _) = res.expect("Error while reading from DB.");
+                share_tree
+                    .remove(&key[8..])
+                    .expect("Error while removing from fs_node_shares-tree.");
+                node_share_tree
+                    .remove(key)
+                    .expect("Error while removing from fs_node_share_tokens-tree.");
+            }
+        }
+    }
+
+    // Blobs, that are not referenced by any version:
+    let entries = match std::fs::read_dir(&file_location) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Could not read file-location: {}", e);
+            return;
+        }
+    };
+    for entry in entries {
+        let entry = entry.expect("Could not read file-location.");
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let hash = parse_hash(&name);
+        let is_orphaned = if let Some(hash) = &hash {
+            !blob_ref_tree.contains_key(hash).unwrap()
+        } else if let Ok(file_id) = u64::from_str_radix(&name, 16) {
+            // Content saved by a version of Kasten without content-addressed storage:
+            !file_tree.contains_key(file_id.to_be_bytes()).unwrap()
+        } else {
+            // Leftovers of interrupted writes:
+            name.ends_with(".tmp") || name.ends_with(".plain")
+        };
+        if is_orphaned {
+            println!("Orphaned blob: {}", name);
+            if remove {
+                if let Err(e) = std::fs::remove_file(entry.path()) {
+                    println!("Could not remove {}: {}", name, e);
+                } else if let Some(hash) = &hash {
+                    encrypted_blob_tree
+                        .remove(hash)
+                        .expect("Error while removing from encrypted_blobs-tree.");
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Returns an error, if Kasten stores the content of files in another backend than the local
+ * file location (see `KASTEN_STORAGE_BACKEND`). bonbon only works on the local file location.
+ */
+pub fn check_local_backend() -> Result<(), String> {
+    match std::env::var("KASTEN_STORAGE_BACKEND") {
+        Ok(backend) if backend != "local" => Err(format!(
+            "The storage backend {} is not supported, only the local file location can be used.",
+            backend
+        )),
+        _ => Ok(()),
     }
 }
 
+/// Removes a reference to the blob given by `hash` inside of a transaction and returns the
+/// remaining number of references. The entry of the blob is removed, when there are no references
+/// left.
+fn release_blob_t<E>(
+    blob_ref_t: &TransactionalTree,
+    hash: &[u8],
+) -> Result<u64, ConflictableTransactionError<E>> {
+    let refs = blob_ref_t
+        .get(hash)?
+        .map(|v| u64::from_be_bytes(v[0..8].try_into().unwrap()))
+        .unwrap_or(0)
+        .saturating_sub(1);
+    if refs > 0 {
+        blob_ref_t.insert(hash, &refs.to_be_bytes())?;
+    } else {
+        blob_ref_t.remove(hash)?;
+    }
+    Ok(refs)
+}
+
+fn parse_hash(name: &str) -> Option<Vec<u8>> {
+    if name.len() != 64 || !name.is_ascii() {
+        return None;
+    }
+    (0..32)
+        .map(|i| u8::from_str_radix(&name[(2 * i)..(2 * i + 2)], 16).ok())
+        .collect()
+}
+
 fn open_db(location: &str) -> Result<Db, &'static str> {
     let db_location = PathBuf::from(location);
     if !db_location.is_dir() {
@@ -80,6 +384,19 @@ fn open_db(location: &str) -> Result<Db, &'static str> {
     Ok(sled::open(db_location.as_path()).expect("Could not open database."))
 }
 
+/// Returns the size of the content of a file given by its value of the files tree.
+fn file_size(file_bytes: &[u8]) -> u64 {
+    let name_end = 18 + u16::from_be_bytes(file_bytes[16..18].try_into().unwrap()) as usize;
+    let type_end = name_end
+        + 2
+        + u16::from_be_bytes(file_bytes[name_end..(name_end + 2)].try_into().unwrap()) as usize;
+    // Files written by older versions of Kasten have no content information:
+    file_bytes
+        .get((type_end + 16)..(type_end + 24))
+        .map(|size| u64::from_be_bytes(size.try_into().unwrap()))
+        .unwrap_or(0)
+}
+
 fn parse_db_string(bytes: &[u8]) -> (String, usize) {
     let length = u16::from_be_bytes(bytes[0..2].try_into().unwrap()) as usize;
 
@@ -0,0 +1,168 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use rand::{thread_rng, RngCore};
+
+use crate::file;
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    string::String,
+};
+
+// Layout of the header of an encrypted blob (see kasten/src/controller/blob.rs):
+const MAGIC: &[u8; 8] = b"KASTENC1";
+const NONCE_LEN: usize = 12;
+const WRAPPED_KEY_LEN: usize = 32 + 16;
+const HEADER_LEN: usize = MAGIC.len() + NONCE_LEN + WRAPPED_KEY_LEN;
+/// Extension of the temporary files, that rotated blobs are written to.
+const TMP_EXTENSION: &str = "rotating";
+
+pub fn rotatekey(args: Vec<String>) {
+    if args.len() < 5 {
+        println!("Usage: bonbon rotatekey <file-location> <old-key-file> <new-key-file>");
+        return;
+    }
+    if let Err(e) = file::check_local_backend() {
+        println!("{}", e);
+        return;
+    }
+
+    let old_key = match read_key(args[3].as_str()) {
+        Ok(k) => k,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let new_key = match read_key(args[4].as_str()) {
+        Ok(k) => k,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    let entries = match std::fs::read_dir(args[2].as_str()) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Could not read file location:\n{}", e);
+            return;
+        }
+    };
+    let (mut rotated, mut skipped, mut failed) = (0, 0, 0);
+    for entry in entries {
+        let path = match entry {
+            Ok(e) => e.path(),
+            Err(e) => {
+                println!("Error while reading file location:\n{}", e);
+                return;
+            }
+        };
+        // Skip the temporary files left by an interrupted rotation:
+        if !path.is_file() || path.extension().is_some_and(|ext| ext == TMP_EXTENSION) {
+            continue;
+        }
+
+        match rotate_blob(&path, &old_key, &new_key) {
+            Ok(true) => rotated += 1,
+            // Not encrypted or already rotated:
+            Ok(false) => skipped += 1,
+            Err(e) => {
+                println!("Could not rotate key of {}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "Rotated {} blobs, skipped {} blobs, {} errors.",
+        rotated, skipped, failed
+    );
+}
+
+/// Wraps the data key of the blob at `path` with `new_key` instead of `old_key`. Returns false,
+/// if the blob is not encrypted or its data key is already wrapped with `new_key`.
+fn rotate_blob(path: &Path, old_key: &Aes256Gcm, new_key: &Aes256Gcm) -> Result<bool, String> {
+    let mut blob = File::open(path).map_err(|e| e.to_string())?;
+    let mut header = [0u8; HEADER_LEN];
+    if blob.read_exact(&mut header).is_err() || &header[..MAGIC.len()] != MAGIC {
+        return Ok(false);
+    }
+
+    let nonce = Nonce::from_slice(&header[MAGIC.len()..(MAGIC.len() + NONCE_LEN)]);
+    let wrapped_key = &header[(MAGIC.len() + NONCE_LEN)..];
+    let data_key = match old_key.decrypt(nonce, wrapped_key) {
+        Ok(k) => k,
+        Err(_) => {
+            if new_key.decrypt(nonce, wrapped_key).is_ok() {
+                return Ok(false);
+            }
+            return Err("The data key can not be unwrapped with the old key.".to_string());
+        }
+    };
+
+    let mut new_nonce = [0u8; NONCE_LEN];
+    thread_rng().fill_bytes(&mut new_nonce);
+    let new_wrapped_key = new_key
+        .encrypt(Nonce::from_slice(&new_nonce), data_key.as_slice())
+        .map_err(|_| "Could not wrap the data key.".to_string())?;
+
+    // The blob is written to a temporary file, that replaces the blob afterwards, so that a crash
+    // never leaves a blob with a partially written header:
+    let tmp_path = path.with_extension(TMP_EXTENSION);
+    let res = write_rotated_blob(&mut blob, &tmp_path, &new_nonce, &new_wrapped_key)
+        .and_then(|_| std::fs::rename(&tmp_path, path));
+    if let Err(e) = res {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e.to_string());
+    }
+    // Persist the rename:
+    if let Some(dir) = path.parent() {
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(true)
+}
+
+/// Writes the blob with the given nonce and wrapped key to `tmp_path`. Only the nonce and the
+/// wrapped key are replaced, the content stays the same.
+fn write_rotated_blob(
+    blob: &mut File,
+    tmp_path: &Path,
+    nonce: &[u8],
+    wrapped_key: &[u8],
+) -> io::Result<()> {
+    let mut tmp = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(tmp_path)?;
+    tmp.write_all(MAGIC)?;
+    tmp.write_all(nonce)?;
+    tmp.write_all(wrapped_key)?;
+    blob.seek(SeekFrom::Start(HEADER_LEN as u64))?;
+    io::copy(blob, &mut tmp)?;
+    tmp.sync_all()
+}
+
+/// Reads a master key given as 64 hexadecimal digits from the file at `path`.
+fn read_key(path: &str) -> Result<Aes256Gcm, String> {
+    let hex = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read key file {}:\n{}", path, e))?;
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(format!("Invalid key in {}.", path));
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[(2 * i)..(2 * i + 2)], 16)
+            .map_err(|_| format!("Invalid key in {}.", path))?;
+    }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
@@ -1,5 +1,6 @@
 mod dir;
 mod file;
+mod key;
 mod user;
 
 fn main() {
@@ -31,6 +32,12 @@ fn main() {
         "filerm" => {
             file::filerm(args);
         }
+        "filesweep" => {
+            file::filesweep(args);
+        }
+        "rotatekey" => {
+            key::rotatekey(args);
+        }
         "help" | "-h" | "--help" => {
             println!("Usage: bonbon <COMMAND> [<ARGUMENTS>]");
             println!("COMMANDS:");
@@ -41,6 +48,9 @@ fn main() {
             println!("'diradd <db-location> <dirname> <parent_id> <owner_id>'");
             println!("'filelist <db-location>'");
             println!("'filerm <db-location> <file-id>'");
+            println!("'filesweep <db-location> <file-location> [--remove]'");
+            println!("'rotatekey <file-location> <old-key-file> <new-key-file>'");
+            println!("filesweep and rotatekey only support the local storage backend.");
         }
         _ => {
             println!("COMMANDS:\n'useradd'\n'userlist'\n'userrm'\n'help'");
@@ -1,3 +1,5 @@
+use crate::dir;
+
 use argon2::{
     password_hash::{PasswordHasher, SaltString},
     Argon2,
@@ -49,6 +51,9 @@ pub fn useradd(args: Vec<String>) {
     let permissions_tree = sled_db
         .open_tree("fs_node_permissions")
         .expect("Could not open fs-node-permissions tree.");
+    let dir_stats_tree = sled_db
+        .open_tree(b"dir_stats")
+        .expect("Could not open dir-stats tree.");
 
     // Check if username allready exists:
     let username = args[3].as_bytes();
@@ -107,7 +112,7 @@ pub fn useradd(args: Vec<String>) {
     dir_bytes.extend_from_slice(b"home");
 
     // Insert values into trees in a single transaction:
-    (&username_id_tree, &userid_name_tree, &userid_pwd_tree, &userid_rootdir_tree, &user_groups_tree, &dir_tree, &permissions_tree).transaction(|(id_t, name_t, pwd_t, root_t, groups_t, dir_t, perm_t)| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
+    (&username_id_tree, &userid_name_tree, &userid_pwd_tree, &userid_rootdir_tree, &user_groups_tree, &dir_tree, &permissions_tree, &dir_stats_tree).transaction(|(id_t, name_t, pwd_t, root_t, groups_t, dir_t, perm_t, stats_t)| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
         id_t.insert(username, &user_id)?;
         name_t.insert(&user_id, username)?;
         pwd_t.insert(&user_id, password_hash.as_bytes())?;
@@ -115,6 +120,7 @@ pub fn useradd(args: Vec<String>) {
         groups_t.insert(&user_id, &[])?;
         dir_t.insert(&dir_id.to_be_bytes(), dir_bytes.as_slice())?;
         perm_t.insert(&dir_id.to_be_bytes(), &[0u8, 0u8, 0u8, 0u8])?;
+        stats_t.insert(&dir_id.to_be_bytes(), dir::new_dir_stats())?;
         Ok(())
     }).expect("Could not apply transaction.");
 }
@@ -191,6 +197,9 @@ pub fn userrm(args: Vec<String>) {
     let file_tree = sled_db
         .open_tree(b"files")
         .expect("Could not open file tree.");
+    let dir_child_tree = sled_db
+        .open_tree(b"dir_childs")
+        .expect("Could not open dir-childs tree.");
 
     // Removing from userid_name_tree and username_id_tree:
     match userid_name_tree.remove(user_id.to_be_bytes()) {
@@ -253,6 +262,16 @@ pub fn userrm(args: Vec<String>) {
         match dir_tree.get(&next_node) {
             Ok(Some(bytes)) => {
                 // Add childs to stack:
+                for res in dir_child_tree.scan_prefix(&next_node) {
+                    match res {
+                        Ok((_, child_id)) => fsnode_stack.push(child_id),
+                        Err(e) => {
+                            println!("Error while reading from DB: {}", e);
+                            return;
+                        }
+                    }
+                }
+                // Childs listed by older versions of Kasten:
                 let child_number = u16::from_be_bytes(bytes[16..18].try_into().unwrap()) as usize;
                 for i in 0..child_number {
                     fsnode_stack.push(IVec::from(&bytes[(18 + i * 8)..(26 + i * 8)]));
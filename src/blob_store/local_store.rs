@@ -0,0 +1,192 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use super::{BlobContent, BlobMeta, BlobStore};
+use crate::Error;
+
+/// A BlobStore, that saves every blob as a file named like the blob in a local directory.
+pub struct LocalStore {
+    location: PathBuf,
+}
+
+impl LocalStore {
+    /// Creates a LocalStore saving its blobs in the directory at `location`.
+    pub fn new(location: PathBuf) -> Self {
+        LocalStore { location }
+    }
+}
+
+impl BlobStore for LocalStore {
+    fn put(&self, name: &str, src: &Path) -> Result<(), Error> {
+        let dest = self.location.join(name);
+        if let Err(e) = fs::rename(src, &dest) {
+            // TODO Logging
+            println!("Could not move content to blob storage: {}", e);
+            fs::copy(src, &dest)?;
+            fs::remove_file(src)?;
+        }
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Result<BlobContent, Error> {
+        Ok(Box::new(fs::File::open(self.location.join(name))?))
+    }
+
+    fn get_range(&self, name: &str, start: u64, end: u64) -> Result<BlobContent, Error> {
+        let mut file = fs::File::open(self.location.join(name))?;
+        file.seek(SeekFrom::Start(start))?;
+        Ok(Box::new(file.take(end.saturating_sub(start))))
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Error> {
+        match fs::remove_file(self.location.join(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(Error::from(e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.location.join(name).is_file())
+    }
+
+    fn size(&self, name: &str) -> Result<u64, Error> {
+        Ok(fs::metadata(self.location.join(name))?.len())
+    }
+
+    fn list(&self) -> Result<Vec<BlobMeta>, Error> {
+        let mut res = Vec::new();
+        for entry in fs::read_dir(&self.location)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                res.push(BlobMeta {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    modified: metadata.modified()?,
+                });
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// A LocalStore in a new temporary directory, which is removed afterwards.
+    struct TestStore {
+        store: LocalStore,
+        location: PathBuf,
+    }
+
+    impl TestStore {
+        fn new(name: &str) -> Self {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos();
+            let location = std::env::temp_dir().join(format!(
+                "kasten-local-store-{}-{}-{}",
+                name,
+                std::process::id(),
+                nanos
+            ));
+            fs::create_dir_all(location.join("blobs")).unwrap();
+            TestStore {
+                store: LocalStore::new(location.join("blobs")),
+                location,
+            }
+        }
+
+        /// Writes `content` to a file outside of the store and puts it into the store as `name`.
+        fn put(&self, name: &str, content: &[u8]) {
+            let src = self.location.join("src");
+            fs::write(&src, content).unwrap();
+            self.store.put(name, &src).unwrap();
+            assert!(!src.exists());
+        }
+    }
+
+    impl Drop for TestStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.location);
+        }
+    }
+
+    fn read_all(mut content: BlobContent) -> Vec<u8> {
+        let mut res = Vec::new();
+        content.read_to_end(&mut res).unwrap();
+        res
+    }
+
+    #[test]
+    fn put_get_round_trip() {
+        let test = TestStore::new("round-trip");
+        test.put("blob", b"Hello, Kasten!");
+
+        assert!(test.store.exists("blob").unwrap());
+        assert_eq!(test.store.size("blob").unwrap(), 14);
+        assert_eq!(read_all(test.store.get("blob").unwrap()), b"Hello, Kasten!");
+        let names: Vec<String> = test
+            .store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|meta| meta.name)
+            .collect();
+        assert_eq!(names, ["blob"]);
+    }
+
+    #[test]
+    fn get_range_returns_requested_bytes() {
+        let test = TestStore::new("range");
+        test.put("blob", b"0123456789");
+
+        assert_eq!(
+            read_all(test.store.get_range("blob", 2, 5).unwrap()),
+            b"234"
+        );
+        assert_eq!(
+            read_all(test.store.get_range("blob", 7, 10).unwrap()),
+            b"789"
+        );
+        assert!(read_all(test.store.get_range("blob", 4, 4).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn put_replaces_existing_blob() {
+        let test = TestStore::new("replace");
+        test.put("blob", b"old");
+        test.put("blob", b"new content");
+
+        assert_eq!(read_all(test.store.get("blob").unwrap()), b"new content");
+    }
+
+    #[test]
+    fn delete_removes_blob() {
+        let test = TestStore::new("delete");
+        test.put("blob", b"content");
+
+        test.store.delete("blob").unwrap();
+        assert!(!test.store.exists("blob").unwrap());
+        assert!(test.store.list().unwrap().is_empty());
+        // Removing a missing blob is no error:
+        test.store.delete("blob").unwrap();
+    }
+
+    #[test]
+    fn missing_blob_is_not_found() {
+        let test = TestStore::new("missing");
+
+        assert!(!test.store.exists("blob").unwrap());
+        match test.store.get("blob") {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            _ => panic!("Expected an error of kind NotFound."),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::{io::Read, path::Path, time::SystemTime};
+
+use crate::Error;
+
+mod local_store;
+pub use local_store::LocalStore;
+mod s3_store;
+pub use s3_store::S3Store;
+
+/// A stream of the content of a blob (or a part of it).
+pub type BlobContent = Box<dyn Read + Send + Sync>;
+
+/// Information about a blob in a BlobStore.
+#[derive(Debug)]
+pub struct BlobMeta {
+    pub name: String,
+    pub modified: SystemTime,
+}
+
+/**
+ * A storage backend for the content of files.
+ *
+ * Blobs are identified by their name and are never modified after they were written. Errors of
+ * the backend are returned as `Error::IoError`; missing blobs result in an error of the kind
+ * `std::io::ErrorKind::NotFound`.
+ *
+ * All methods (and the returned streams) block the calling thread, async code has to use them
+ * through `controller::blob::run_blocking()`.
+ */
+pub trait BlobStore: Send + Sync {
+    /// Moves the local file at `src` into the store as the blob given by `name`. An existing blob
+    /// with the same name is replaced. `src` is removed afterwards.
+    fn put(&self, name: &str, src: &Path) -> Result<(), Error>;
+
+    /// Returns a stream of the content of the blob given by `name`.
+    fn get(&self, name: &str) -> Result<BlobContent, Error>;
+
+    /// Returns a stream of the bytes `start..end` of the blob given by `name`. `end` must not be
+    /// greater than the size of the blob.
+    fn get_range(&self, name: &str, start: u64, end: u64) -> Result<BlobContent, Error>;
+
+    /// Removes the blob given by `name`. Removing a blob, that does not exist, is no error.
+    fn delete(&self, name: &str) -> Result<(), Error>;
+
+    /// Returns true, if there is a blob with the given name.
+    fn exists(&self, name: &str) -> Result<bool, Error>;
+
+    /// Returns the size of the blob given by `name` in bytes.
+    fn size(&self, name: &str) -> Result<u64, Error>;
+
+    /// Returns information about all blobs in the store.
+    fn list(&self) -> Result<Vec<BlobMeta>, Error>;
+}
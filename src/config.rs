@@ -1,13 +1,43 @@
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, sync::Arc};
+
+use crate::{
+    blob_store::{BlobStore, LocalStore, S3Store},
+    controller::{blob::MasterKey, moving::ConflictPolicy},
+};
 
 static DATABASE_LOCATION: &str = "./var/server-sled-db";
 static FILE_LOCATION: &str = "./var/files/";
 static STATIC_FILES: &str = "./static/";
+static UPLOAD_LOCATION: &str = "./var/uploads/";
+static TRASH_RETENTION_DAYS: i64 = 30;
+static MAX_FILE_VERSIONS: u32 = 10;
+static STORAGE_BACKEND: &str = "local";
+static S3_REGION: &str = "us-east-1";
+static NAME_CONFLICT_POLICY: &str = "fail";
+static VERIFY_BLOBS: bool = false;
+static MAX_UPLOAD_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+static MAX_UPLOADS_PER_USER: usize = 10;
 
+#[derive(Clone)]
 pub struct Config {
     pub database_location: PathBuf,
     pub file_location: PathBuf,
     pub static_files: PathBuf,
+    pub upload_location: PathBuf,
+    pub trash_retention_days: i64,
+    pub max_file_versions: u32,
+    pub master_key: Option<MasterKey>,
+    pub blob_store: Arc<dyn BlobStore>,
+    pub name_conflict_policy: ConflictPolicy,
+    /// Whether the content of all blobs is rehashed on every sweep of the storage.
+    pub verify_blobs: bool,
+    /// Maximum length of a resumable upload in bytes.
+    pub max_upload_size: u64,
+    /// Maximum number of unfinished resumable uploads of a single user.
+    pub max_uploads_per_user: usize,
+    /// Key signing the access tokens of password protected links. It is generated on every start,
+    /// so that the access tokens expire with a restart.
+    pub share_access_key: [u8; 32],
 }
 
 impl Config {
@@ -19,10 +49,68 @@ impl Config {
             env::var("KASTEN_FILE_LOCATION").unwrap_or_else(|_| String::from(FILE_LOCATION));
         let static_files =
             env::var("KASTEN_STATIC_FILES").unwrap_or_else(|_| String::from(STATIC_FILES));
+        let upload_location =
+            env::var("KASTEN_UPLOAD_LOCATION").unwrap_or_else(|_| String::from(UPLOAD_LOCATION));
+        let trash_retention_days = env::var("KASTEN_TRASH_RETENTION_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(TRASH_RETENTION_DAYS);
+        let max_file_versions = env::var("KASTEN_MAX_FILE_VERSIONS")
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .unwrap_or(MAX_FILE_VERSIONS);
+        let master_key = if let Ok(path) = env::var("KASTEN_MASTER_KEY_FILE") {
+            let hex = std::fs::read_to_string(path).expect("Could not read master key file.");
+            Some(MasterKey::from_hex(&hex).expect("Master key file does not contain a valid key."))
+        } else {
+            env::var("KASTEN_MASTER_KEY")
+                .ok()
+                .map(|hex| MasterKey::from_hex(&hex).expect("KASTEN_MASTER_KEY is no valid key."))
+        };
+        let blob_store: Arc<dyn BlobStore> = match env::var("KASTEN_STORAGE_BACKEND")
+            .unwrap_or_else(|_| String::from(STORAGE_BACKEND))
+            .as_str()
+        {
+            "local" => Arc::new(LocalStore::new(PathBuf::from(&file_location))),
+            "s3" => Arc::new(S3Store::new(
+                &env::var("KASTEN_S3_ENDPOINT").expect("KASTEN_S3_ENDPOINT is not set."),
+                &env::var("KASTEN_S3_BUCKET").expect("KASTEN_S3_BUCKET is not set."),
+                &env::var("KASTEN_S3_REGION").unwrap_or_else(|_| String::from(S3_REGION)),
+                &env::var("KASTEN_S3_ACCESS_KEY").expect("KASTEN_S3_ACCESS_KEY is not set."),
+                &env::var("KASTEN_S3_SECRET_KEY").expect("KASTEN_S3_SECRET_KEY is not set."),
+            )),
+            backend => panic!("Unknown storage backend: {}", backend),
+        };
+        let name_conflict_policy = env::var("KASTEN_NAME_CONFLICT_POLICY")
+            .unwrap_or_else(|_| String::from(NAME_CONFLICT_POLICY))
+            .parse()
+            .expect("KASTEN_NAME_CONFLICT_POLICY must be one of fail, rename or overwrite.");
+        let verify_blobs = env::var("KASTEN_VERIFY_BLOBS")
+            .ok()
+            .and_then(|verify| verify.parse().ok())
+            .unwrap_or(VERIFY_BLOBS);
+        let max_upload_size = env::var("KASTEN_MAX_UPLOAD_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(MAX_UPLOAD_SIZE);
+        let max_uploads_per_user = env::var("KASTEN_MAX_UPLOADS_PER_USER")
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .unwrap_or(MAX_UPLOADS_PER_USER);
         Config {
             database_location: PathBuf::from(db_location),
             file_location: PathBuf::from(file_location),
             static_files: PathBuf::from(static_files),
+            upload_location: PathBuf::from(upload_location),
+            trash_retention_days,
+            max_file_versions,
+            master_key,
+            blob_store,
+            name_conflict_policy,
+            verify_blobs,
+            max_upload_size,
+            max_uploads_per_user,
+            share_access_key: rand::random(),
         }
     }
 }
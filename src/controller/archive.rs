@@ -0,0 +1,265 @@
+use tar::{Builder, EntryType, Header};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use crate::{
+    config::Config,
+    controller::blob,
+    database::Database,
+    models::{Dir, FileVersion, FsNode, Rights},
+    Error,
+};
+
+/// The archive formats, in which a directory can be exported.
+#[derive(Clone, Copy, Debug)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+        }
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "application/zip",
+            ArchiveFormat::Tar => "application/x-tar",
+        }
+    }
+}
+
+/// A single entry of an archive, given by its path inside the archive. Files are given by their
+/// latest version, or None if no content was written to them yet.
+#[derive(Debug)]
+pub enum ArchiveEntry {
+    Dir {
+        path: String,
+    },
+    File {
+        path: String,
+        version: Option<FileVersion>,
+    },
+}
+
+/**
+ * Collects the entries of an archive containing the directory given by `dir_id` and all its
+ * descendants.
+ *
+ * Only files, whose content the user given by `user_id` may read (`Rights::READ`), and
+ * directories, which the user may list (`Rights::LIST`), are included. The content of other
+ * directories is skipped entirely. All paths start with the name of the given directory. Slashes
+ * inside of names are replaced and identical paths are made unique by appending a counter, so
+ * that every entry can be written to the archive.
+ *
+ * If there is no directory with the given ID, `Error::NoSuchDir` is returned. If the user may
+ * not list the given directory, `Error::MissingAuthorization` is returned. If the content of an
+ * included file is missing in the blob storage, `Error::InconsistentDbState` is returned, so that
+ * no archive is started, which could not be finished.
+ */
+pub fn collect_entries(
+    dir_id: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<Vec<ArchiveEntry>, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let root = db.get_dir(dir_id)?.ok_or(Error::NoSuchDir)?;
+    if !root.has_rights(&user, Rights::LIST) {
+        return Err(Error::MissingAuthorization);
+    }
+
+    collect_readable_entries(root, db, config, |node, rights| {
+        node.has_rights(&user, rights)
+    })
+}
+
+/// Collects the entries of the given directory and its descendants, for which `has_rights`
+/// returns true given `Rights::READ` for files and `Rights::LIST` for directories.
+fn collect_readable_entries<P>(
+    root: Dir,
+    db: &Database,
+    config: &Config,
+    has_rights: P,
+) -> Result<Vec<ArchiveEntry>, Error>
+where
+    P: Fn(&dyn FsNode, Rights) -> bool,
+{
+    let mut entries = Vec::new();
+    let mut used_paths = HashSet::new();
+    let mut todo_stack: Vec<(Dir, String)> = Vec::new();
+
+    let root_path = unique_path(&mut used_paths, "", &root.name);
+    entries.push(ArchiveEntry::Dir {
+        path: root_path.clone(),
+    });
+    todo_stack.push((root, root_path));
+
+    while let Some((dir, dir_path)) = todo_stack.pop() {
+        for file in db.get_files_by_parent(dir.id)? {
+            if has_rights(&file, Rights::READ) {
+                let version = db.get_latest_version(file.id)?;
+                if let Some(version) = &version {
+                    if !config
+                        .blob_store
+                        .exists(&version.content_hash.to_string())?
+                    {
+                        return Err(Error::InconsistentDbState);
+                    }
+                }
+                entries.push(ArchiveEntry::File {
+                    path: unique_path(&mut used_paths, &dir_path, &file.name),
+                    version,
+                });
+            }
+        }
+        for child in db.get_dirs_by_parent(dir.id)? {
+            if has_rights(&child, Rights::LIST) {
+                let child_path = unique_path(&mut used_paths, &dir_path, &child.name);
+                entries.push(ArchiveEntry::Dir {
+                    path: child_path.clone(),
+                });
+                todo_stack.push((child, child_path));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/**
+ * Writes an archive of the given format containing the given entries to `out`.
+ *
+ * The content of the files is read from the blob storage one file at a time and streamed into
+ * the archive, so the archive is never kept in memory as a whole. `out` does not need to be
+ * seekable.
+ */
+pub fn write_archive<W: Write>(
+    format: ArchiveFormat,
+    entries: &[ArchiveEntry],
+    db: &Database,
+    config: &Config,
+    out: W,
+) -> Result<(), Error> {
+    match format {
+        ArchiveFormat::Zip => write_zip(entries, db, config, out),
+        ArchiveFormat::Tar => write_tar(entries, db, config, out),
+    }
+}
+
+fn write_zip<W: Write>(
+    entries: &[ArchiveEntry],
+    db: &Database,
+    config: &Config,
+    out: W,
+) -> Result<(), Error> {
+    let mut zip = ZipWriter::new_stream(out);
+
+    for entry in entries {
+        match entry {
+            ArchiveEntry::Dir { path } => {
+                zip.add_directory(path.as_str(), SimpleFileOptions::default())
+                    .map_err(zip_error)?;
+            }
+            ArchiveEntry::File {
+                path,
+                version: None,
+            } => {
+                zip.start_file(path.as_str(), SimpleFileOptions::default())
+                    .map_err(zip_error)?;
+            }
+            ArchiveEntry::File {
+                path,
+                version: Some(version),
+            } => {
+                let mut content = blob::open(version, db, config)?;
+                let options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .large_file(content.len() >= u32::MAX as u64);
+                zip.start_file(path.as_str(), options).map_err(zip_error)?;
+                io::copy(&mut content, &mut zip)?;
+            }
+        }
+    }
+
+    zip.finish().map_err(zip_error)?.flush()?;
+    Ok(())
+}
+
+fn write_tar<W: Write>(
+    entries: &[ArchiveEntry],
+    db: &Database,
+    config: &Config,
+    out: W,
+) -> Result<(), Error> {
+    let mut tar = Builder::new(out);
+
+    for entry in entries {
+        match entry {
+            ArchiveEntry::Dir { path } => {
+                let mut header = Header::new_gnu();
+                header.set_entry_type(EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                tar.append_data(&mut header, path, io::empty())?;
+            }
+            ArchiveEntry::File { path, version } => {
+                let mut header = Header::new_gnu();
+                header.set_entry_type(EntryType::Regular);
+                header.set_mode(0o644);
+                match version {
+                    Some(version) => {
+                        let content = blob::open(version, db, config)?;
+                        header.set_size(content.len());
+                        tar.append_data(&mut header, path, content)?;
+                    }
+                    None => {
+                        header.set_size(0);
+                        tar.append_data(&mut header, path, io::empty())?;
+                    }
+                }
+            }
+        }
+    }
+
+    tar.into_inner()?.flush()?;
+    Ok(())
+}
+
+fn zip_error(err: zip::result::ZipError) -> Error {
+    match err {
+        zip::result::ZipError::Io(e) => Error::IoError(e),
+        _ => Error::EncodingError,
+    }
+}
+
+/// Returns the path of a child named `name` inside of `parent_path`, that is not contained in
+/// `used_paths` yet, and adds it to `used_paths`.
+fn unique_path(used_paths: &mut HashSet<String>, parent_path: &str, name: &str) -> String {
+    let name = match name.replace('/', "_").as_str() {
+        "" | "." | ".." => String::from("_"),
+        n => String::from(n),
+    };
+    let base = if parent_path.is_empty() {
+        name
+    } else {
+        format!("{}/{}", parent_path, name)
+    };
+
+    let mut path = base.clone();
+    let mut counter = 1;
+    while used_paths.contains(&path) {
+        path = format!("{} ({})", base, counter);
+        counter += 1;
+    }
+    used_paths.insert(path.clone());
+    path
+}
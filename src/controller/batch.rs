@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    config::Config,
+    controller::{
+        self, copy,
+        moving::{self, ConflictPolicy},
+        path::{get_node, PathTarget},
+        search, trash,
+    },
+    database::{Database, FsChange},
+    models::{numbered_name, FsNode, Rights, User},
+    webapi::{BatchOperationMsg, MoveMsg},
+    Error,
+};
+
+/// Maximum number of operations in a single batch.
+const MAX_BATCH_SIZE: usize = 1000;
+
+/// The kind of a permission, that is granted to or revoked from a Group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    Write,
+}
+
+/// The result of a single operation of a batch.
+#[derive(Debug)]
+pub enum BatchResult {
+    /// The operation succeeded. The File or Dir after the operation (before it was deleted for a
+    /// deletion) is given.
+    Done(Box<PathTarget>),
+    /// The operation failed with the given Error.
+    Failed(Error),
+    /// The operation was not executed, because another operation of an atomic batch failed.
+    Skipped,
+}
+
+/**
+ * Executes the given operations for the user given by `user_id` in the given order and returns
+ * their results. Every operation requires the same permissions and fails with the same Errors as
+ * the corresponding single function (`trash::move_to_trash()`, `moving::move_file()`,
+ * `copy::copy_file()`, `controller::add_read_permission()`, ...).
+ *
+ * If `atomic` is false, the operations are executed one after another and a failed operation
+ * does not affect the others. If `atomic` is true, the operations are executed in a single
+ * transaction (see `run_atomic()`).
+ *
+ * If there are more than `MAX_BATCH_SIZE` operations, `Error::BadCall` is returned.
+ */
+pub fn run_batch(
+    operations: Vec<BatchOperationMsg>,
+    atomic: bool,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<Vec<BatchResult>, Error> {
+    if operations.len() > MAX_BATCH_SIZE {
+        return Err(Error::BadCall);
+    }
+    if atomic {
+        return run_atomic(operations, user_id, db, config);
+    }
+
+    Ok(operations
+        .into_iter()
+        .map(
+            |operation| match run_operation(operation, user_id, db, config) {
+                Ok(node) => BatchResult::Done(Box::new(node)),
+                Err(err) => BatchResult::Failed(err),
+            },
+        )
+        .collect())
+}
+
+/// Executes a single operation of a batch and returns the changed File or Dir.
+fn run_operation(
+    operation: BatchOperationMsg,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<PathTarget, Error> {
+    match operation {
+        BatchOperationMsg::Delete { id } => {
+            let node = get_node(id.inner(), db)?;
+            trash::move_to_trash(id.inner(), user_id, db)?;
+            Ok(node)
+        }
+        BatchOperationMsg::Move { id, move_msg } => {
+            let policy = move_msg.on_conflict.unwrap_or(config.name_conflict_policy);
+            let parent_id = move_msg.parent_id.map(|id| id.inner());
+            match get_node(id.inner(), db)? {
+                PathTarget::File(file) => {
+                    moving::move_file(file.id, parent_id, move_msg.name, policy, user_id, db)
+                        .map(PathTarget::File)
+                }
+                PathTarget::Dir(dir) => {
+                    moving::move_dir(dir.id, parent_id, move_msg.name, policy, user_id, db)
+                        .map(PathTarget::Dir)
+                }
+            }
+        }
+        BatchOperationMsg::Copy { id, copy_msg } => match get_node(id.inner(), db)? {
+            PathTarget::File(file) => {
+                copy::copy_file(file.id, copy_msg, user_id, db, config).map(PathTarget::File)
+            }
+            PathTarget::Dir(dir) => {
+                copy::copy_dir(dir.id, copy_msg, user_id, db, config).map(PathTarget::Dir)
+            }
+        },
+        BatchOperationMsg::Grant {
+            id,
+            group_id,
+            permission,
+        } => {
+            match permission {
+                Permission::Read => {
+                    controller::add_read_permission(id.inner(), group_id.inner(), user_id, db)?
+                }
+                Permission::Write => {
+                    controller::add_write_permission(id.inner(), group_id.inner(), user_id, db)?
+                }
+            }
+            get_node(id.inner(), db)
+        }
+        BatchOperationMsg::Revoke {
+            id,
+            group_id,
+            permission,
+        } => {
+            match permission {
+                Permission::Read => {
+                    controller::remove_read_permission(id.inner(), group_id.inner(), user_id, db)?
+                }
+                Permission::Write => {
+                    controller::remove_write_permission(id.inner(), group_id.inner(), user_id, db)?
+                }
+            }
+            get_node(id.inner(), db)
+        }
+    }
+}
+
+/**
+ * Executes the given operations in a single transaction (see `Database::apply_changes()`), so
+ * that either all or none of them are applied.
+ *
+ * The permissions of the user are checked before the transaction, name conflicts are resolved by
+ * the given or configured policy at the same time. Since a change could grant rights for the
+ * following operations, an operation fails with `Error::BadCall`, if its FsNode, its target
+ * directory or one of their ancestors is changed by a previous operation. Copies are not
+ * supported in atomic batches (`Error::BadCall`). If an operation fails, the result of this
+ * operation is its Error and all other operations are `BatchResult::Skipped`.
+ */
+fn run_atomic(
+    operations: Vec<BatchOperationMsg>,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<Vec<BatchResult>, Error> {
+    if operations
+        .iter()
+        .any(|operation| matches!(operation, BatchOperationMsg::Copy { .. }))
+    {
+        return Err(Error::BadCall);
+    }
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let operation_count = operations.len();
+
+    // Collect the changes of all operations and the operation each change belongs to:
+    let mut planner = ChangePlanner {
+        user: &user,
+        db,
+        config,
+        locations: HashMap::new(),
+        deleted: HashSet::new(),
+        touched: HashSet::new(),
+        changes: Vec::new(),
+    };
+    let mut change_operations = Vec::new();
+    let mut nodes = Vec::with_capacity(operations.len());
+    for (i, operation) in operations.into_iter().enumerate() {
+        match planner.plan(operation) {
+            Ok(node) => nodes.push(node),
+            Err(err) => return Ok(failed_batch(operation_count, i, err)),
+        }
+        change_operations.resize(planner.changes.len(), i);
+    }
+
+    match db.apply_changes(&planner.changes)? {
+        Ok(()) => {
+            for change in &planner.changes {
+                if let FsChange::Move { id, .. } = change {
+                    search::index_metadata(*id, db)?;
+                }
+            }
+            nodes
+                .into_iter()
+                .map(|node| match node {
+                    // The deleted FsNode is returned as it was before:
+                    Planned::Deleted(node) => Ok(BatchResult::Done(Box::new(node))),
+                    Planned::Changed(node) => match get_node(node.id(), db) {
+                        Ok(changed) => Ok(BatchResult::Done(Box::new(changed))),
+                        // The FsNode was deleted by a later operation:
+                        Err(Error::NoSuchTarget) => Ok(BatchResult::Done(Box::new(node))),
+                        Err(err) => Err(err),
+                    },
+                })
+                .collect()
+        }
+        Err((i, err)) => Ok(failed_batch(operation_count, change_operations[i], err)),
+    }
+}
+
+/// Returns the results of an atomic batch with `len` operations, of which the operation at
+/// `index` failed with the given Error.
+fn failed_batch(len: usize, index: usize, err: Error) -> Vec<BatchResult> {
+    let mut results: Vec<BatchResult> = (0..len).map(|_| BatchResult::Skipped).collect();
+    results[index] = BatchResult::Failed(err);
+    results
+}
+
+/// The planned outcome of an operation of an atomic batch.
+enum Planned {
+    /// The given FsNode will be moved into the trash.
+    Deleted(PathTarget),
+    /// The given FsNode will be changed.
+    Changed(PathTarget),
+}
+
+/// Translates the operations of an atomic batch into changes of the DB.
+struct ChangePlanner<'a> {
+    user: &'a User,
+    db: &'a Database,
+    config: &'a Config,
+    /// The parent and name of all FsNodes, that are moved by previous operations.
+    locations: HashMap<u64, (u64, String)>,
+    /// The IDs of all FsNodes, that are deleted by previous operations.
+    deleted: HashSet<u64>,
+    /// The IDs of all FsNodes, that are changed in any way by previous operations.
+    touched: HashSet<u64>,
+    changes: Vec<FsChange>,
+}
+
+impl ChangePlanner<'_> {
+    /// Checks the permissions for the given operation and adds its changes.
+    fn plan(&mut self, operation: BatchOperationMsg) -> Result<Planned, Error> {
+        let id = match &operation {
+            BatchOperationMsg::Delete { id }
+            | BatchOperationMsg::Move { id, .. }
+            | BatchOperationMsg::Copy { id, .. }
+            | BatchOperationMsg::Grant { id, .. }
+            | BatchOperationMsg::Revoke { id, .. } => id.inner(),
+        };
+        self.check_untouched(id)?;
+        let planned = self.plan_unchecked(operation)?;
+        self.touched.insert(id);
+        Ok(planned)
+    }
+
+    /// Checks the permissions for the given operation and adds its changes without checking the
+    /// previous operations (see `check_untouched()`).
+    fn plan_unchecked(&mut self, operation: BatchOperationMsg) -> Result<Planned, Error> {
+        match operation {
+            BatchOperationMsg::Delete { id } => {
+                let node = get_node(id.inner(), self.db)?;
+                self.check_rights(&node, Rights::DELETE)?;
+                self.changes.push(FsChange::Trash { id: id.inner() });
+                self.deleted.insert(id.inner());
+                Ok(Planned::Deleted(node))
+            }
+            BatchOperationMsg::Move { id, move_msg } => {
+                Ok(Planned::Changed(self.plan_move(id.inner(), move_msg)?))
+            }
+            BatchOperationMsg::Grant {
+                id,
+                group_id,
+                permission,
+            } => {
+                let node = get_node(id.inner(), self.db)?;
+                self.check_rights(&node, Rights::SHARE)?;
+                self.db
+                    .get_group(group_id.inner())?
+                    .ok_or(Error::NoSuchTarget)?;
+                self.changes.push(FsChange::AddGroup {
+                    id: id.inner(),
+                    group_id: group_id.inner(),
+                    write: permission == Permission::Write,
+                });
+                Ok(Planned::Changed(node))
+            }
+            BatchOperationMsg::Revoke {
+                id,
+                group_id,
+                permission,
+            } => {
+                let node = get_node(id.inner(), self.db)?;
+                self.check_rights(&node, Rights::SHARE)?;
+                self.changes.push(FsChange::RemoveGroup {
+                    id: id.inner(),
+                    group_id: group_id.inner(),
+                    write: permission == Permission::Write,
+                });
+                Ok(Planned::Changed(node))
+            }
+            BatchOperationMsg::Copy { .. } => Err(Error::BadCall),
+        }
+    }
+
+    /// Checks the permissions for moving the FsNode given by `id` (see `moving::move_file()`) and
+    /// adds the changes, that move it and handle a name conflict. The FsNode is returned.
+    fn plan_move(&mut self, id: u64, move_msg: MoveMsg) -> Result<PathTarget, Error> {
+        let node = get_node(id, self.db)?;
+        let (old_parent_id, old_name) = (node.parent_id(), String::from(node.name()));
+        let parent_id = move_msg.parent_id.map_or(old_parent_id, |id| id.inner());
+        let name = move_msg.name.unwrap_or_else(|| old_name.clone());
+        if parent_id == old_parent_id && name == old_name {
+            return Ok(node);
+        }
+        controller::check_name(&name)?;
+        self.check_untouched(parent_id)?;
+
+        // Root directories have no parent and can not be moved:
+        self.db
+            .get_dir(old_parent_id)?
+            .ok_or(Error::ForbiddenAction)?;
+        let new_parent = self.db.get_dir(parent_id)?.ok_or(Error::NoSuchDir)?;
+        moving::check_move_rights(&node, &new_parent, &name, self.user)?;
+
+        // Name conflicts with policy `ConflictPolicy::Fail` are detected by the transaction:
+        let mut target_name = name.clone();
+        if let Some(existing_id) = self.find_child(id, parent_id, &name)? {
+            match move_msg
+                .on_conflict
+                .unwrap_or(self.config.name_conflict_policy)
+            {
+                ConflictPolicy::Fail => {}
+                ConflictPolicy::Rename => {
+                    let is_file = matches!(node, PathTarget::File(_));
+                    target_name = self.free_name(id, parent_id, &name, is_file)?;
+                }
+                ConflictPolicy::Overwrite => {
+                    self.check_untouched(existing_id)?;
+                    let existing = get_node(existing_id, self.db)?;
+                    if moving::is_ancestor(existing_id, old_parent_id, self.db)? {
+                        return Err(Error::ForbiddenAction);
+                    }
+                    self.check_rights(&existing, Rights::DELETE)?;
+                    self.changes.push(FsChange::Trash { id: existing_id });
+                    self.deleted.insert(existing_id);
+                    self.touched.insert(existing_id);
+                }
+            }
+        }
+
+        self.changes.push(FsChange::Move {
+            id,
+            parent_id,
+            name: target_name.clone(),
+        });
+        self.locations.insert(id, (parent_id, target_name));
+        Ok(node)
+    }
+
+    /// Returns the ID of the child of the directory given by `parent_id`, that is named `name`
+    /// after the previous operations (ignoring the FsNode given by `except_id`).
+    fn find_child(&self, except_id: u64, parent_id: u64, name: &str) -> Result<Option<u64>, Error> {
+        let moved_child =
+            self.locations
+                .iter()
+                .find(|(id, (location_parent_id, location_name))| {
+                    **id != except_id
+                        && *location_parent_id == parent_id
+                        && location_name == name
+                        && !self.deleted.contains(id)
+                });
+        if let Some((id, _)) = moved_child {
+            return Ok(Some(*id));
+        }
+
+        // Ignore childs, that are moved or deleted by previous operations:
+        Ok(moving::find_child(except_id, parent_id, name, self.db)?
+            .filter(|id| !self.locations.contains_key(id) && !self.deleted.contains(id)))
+    }
+
+    /// Returns the first numbered name (see `moving::free_name()`), that is not used by a child of
+    /// the directory given by `parent_id` after the previous operations.
+    fn free_name(
+        &self,
+        except_id: u64,
+        parent_id: u64,
+        name: &str,
+        is_file: bool,
+    ) -> Result<String, Error> {
+        let mut number = 1;
+        loop {
+            let candidate = numbered_name(name, number, is_file);
+            if self.find_child(except_id, parent_id, &candidate)?.is_none() {
+                return Ok(candidate);
+            }
+            number += 1;
+        }
+    }
+
+    /// Returns `Error::BadCall`, if the FsNode given by `id` or one of its ancestors is changed by
+    /// a previous operation, so that the permissions checked before the transaction may not hold
+    /// anymore.
+    fn check_untouched(&self, id: u64) -> Result<(), Error> {
+        let mut current_id = id;
+        while current_id != 0 {
+            if self.touched.contains(&current_id) {
+                return Err(Error::BadCall);
+            }
+            current_id = match get_node(current_id, self.db) {
+                Ok(node) => node.parent_id(),
+                Err(Error::NoSuchTarget) => return Ok(()),
+                Err(err) => return Err(err),
+            };
+        }
+        Ok(())
+    }
+
+    /// Makes sure the user has all of the given rights on the given FsNode.
+    fn check_rights(&self, node: &PathTarget, rights: Rights) -> Result<(), Error> {
+        if !node.has_rights(self.user, rights) {
+            return Err(Error::MissingAuthorization);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,620 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use rand::{thread_rng, RngCore};
+use rocket::{fs::TempFile, tokio::task};
+use sha2::{Digest, Sha256};
+
+use std::{
+    convert::TryInto,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::{
+    blob_store::{BlobContent, BlobStore},
+    config::Config,
+    database::Database,
+    models::{ContentHash, FileVersion},
+    Error,
+};
+
+// Encrypted blobs start with this magic number followed by the nonce and the wrapped data key:
+const MAGIC: &[u8; 8] = b"KASTENC1";
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + NONCE_LEN + KEY_LEN + TAG_LEN;
+// The content is encrypted in chunks of this size, so that ranges can be read without decrypting
+// the whole blob:
+const CHUNK_LEN: usize = 64 * 1024;
+const ENCRYPTED_CHUNK_LEN: usize = CHUNK_LEN + TAG_LEN;
+
+/**
+ * The master key of the server, which is used to wrap the data keys of the encrypted blobs.
+ *
+ * Every blob is encrypted with its own random data key (AES-256-GCM). The data key is stored in
+ * the header of the blob, encrypted with the master key.
+ */
+#[derive(Clone)]
+pub struct MasterKey([u8; KEY_LEN]);
+
+impl MasterKey {
+    /// Parses a master key given as 64 hexadecimal digits. Surrounding whitespace is ignored.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim();
+        if hex.len() != 2 * KEY_LEN || !hex.is_ascii() {
+            return None;
+        }
+        let mut key = [0u8; KEY_LEN];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[(2 * i)..(2 * i + 2)], 16).ok()?;
+        }
+        Some(MasterKey(key))
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+}
+
+/// Serializes adding new blobs and removing unreferenced blobs, so that a blob is never removed
+/// while a new reference to it is added.
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/**
+ * Moves the plaintext content at `src` to the blob storage and returns its hash and length.
+ * The returned hash holds a new reference to the blob, which has to be released by `release()`,
+ * when it is not needed anymore.
+ *
+ * Blobs are saved under their hash in `config.blob_store`, so identical content is only stored
+ * once. If a master key is configured, the content is encrypted. `src` is removed afterwards.
+ */
+pub fn store(src: &Path, db: &Database, config: &Config) -> Result<(ContentHash, u64), Error> {
+    let encrypted = config.master_key.is_some();
+    let (blob_path, res) = match &config.master_key {
+        Some(master_key) => {
+            let tmp_path = temp_path(config, "tmp")?;
+            let res = encrypt(src, &tmp_path, master_key)
+                .and_then(|res| std::fs::remove_file(src).map(|_| res).map_err(Error::from));
+            (tmp_path, res)
+        }
+        None => (PathBuf::from(src), hash_file(src)),
+    };
+    let res = res.and_then(|(hash, len)| {
+        add_blob(&blob_path, &hash, encrypted, db, config).map(|_| (hash, len))
+    });
+    if res.is_err() {
+        let _ = std::fs::remove_file(&blob_path);
+    }
+    res
+}
+
+/**
+ * Moves the content of the given `TempFile` to the blob storage. See `store()`.
+ *
+ * Hashing, encryption and the transfer to the storage are done by a blocking task.
+ */
+pub async fn store_temp_file(
+    content: &mut TempFile<'_>,
+    db: &Database,
+    config: &Config,
+) -> Result<(ContentHash, u64), Error> {
+    let plain_path = temp_path(config, "plain")?;
+    if let Err(e) = content.persist_to(&plain_path).await {
+        // TODO Logging
+        println!("Could not persist TempFile: {}", e);
+        content.move_copy_to(&plain_path).await?;
+    }
+
+    run_blocking(db, config, move |db, config| store(&plain_path, db, config)).await
+}
+
+/**
+ * Runs `f` with clones of `db` and `config` as a blocking task and returns its result.
+ *
+ * The BlobStores do blocking I/O (e.g. the requests of `S3Store`), so async code has to access the
+ * blob storage (also indirectly, e.g. by `version::add_version()`) through this function.
+ */
+pub async fn run_blocking<T, F>(db: &Database, config: &Config, f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&Database, &Config) -> Result<T, Error> + Send + 'static,
+{
+    let (db, config) = (db.clone(), config.clone());
+    task::spawn_blocking(move || f(&db, &config))
+        .await
+        .map_err(|e| Error::from(io::Error::other(e)))?
+}
+
+/**
+ * Copies the content of the blob given by `name` in the BlobStore `legacy_store`, which was
+ * written by a version of Kasten without content-addressed storage, to the blob storage. See
+ * `store()`. The blob in `legacy_store` is left untouched, so that it can be removed, after the
+ * returned reference was passed to a FileVersion.
+ */
+pub fn import(
+    legacy_store: &Arc<dyn BlobStore>,
+    name: &str,
+    db: &Database,
+    config: &Config,
+) -> Result<(ContentHash, u64), Error> {
+    // Legacy blobs were not flagged, so the encryption has to be detected by their header:
+    let encrypted = has_encryption_header(legacy_store, name)?;
+    let mut content = open_blob(
+        legacy_store,
+        name,
+        encrypted,
+        SystemTime::UNIX_EPOCH,
+        config,
+    )?;
+    let plain_path = temp_path(config, "plain")?;
+    let res = std::fs::File::create(&plain_path)
+        .and_then(|mut plain_file| io::copy(&mut content, &mut plain_file))
+        .map_err(Error::from)
+        .and_then(|_| store(&plain_path, db, config));
+    if res.is_err() {
+        let _ = std::fs::remove_file(&plain_path);
+    }
+    res
+}
+
+/// Releases a reference to the blob given by `hash`. The blob is removed from the storage, when
+/// there are no references left.
+pub fn release(hash: &ContentHash, db: &Database, config: &Config) -> Result<(), Error> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if db.remove_blob_ref(hash)? == 0 {
+        config.blob_store.delete(&hash.to_string())?;
+        db.set_blob_encrypted(hash, false)?;
+    }
+    Ok(())
+}
+
+/**
+ * Removes the blob given by `hash` from the storage, if there are no references to it. The
+ * references are checked again while holding the lock, so that a blob, which got a new reference
+ * since it was found to be orphaned, is kept. Returns true, if the blob was removed.
+ */
+pub fn remove_unreferenced(
+    hash: &ContentHash,
+    db: &Database,
+    config: &Config,
+) -> Result<bool, Error> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if db.get_blob_refs(hash)? > 0 {
+        return Ok(false);
+    }
+    config.blob_store.delete(&hash.to_string())?;
+    db.set_blob_encrypted(hash, false)?;
+    Ok(true)
+}
+
+/**
+ * Removes the FileVersion given by `file_id` and `version` and its reference to its blob from the
+ * DB (see `Database::remove_version()`) and returns it. The blob is removed from the storage, when
+ * there are no references left.
+ */
+pub fn remove_version(
+    file_id: u64,
+    version: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<FileVersion, Error> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (version, refs) = db.remove_version(file_id, version)?;
+    if refs == 0 {
+        config
+            .blob_store
+            .delete(&version.content_hash.to_string())?;
+        db.set_blob_encrypted(&version.content_hash, false)?;
+    }
+    Ok(version)
+}
+
+/// Opens the content of the given FileVersion for reading. Encrypted blobs are decrypted
+/// transparently, unencrypted blobs (written without a master key) are read as they are.
+pub fn open(version: &FileVersion, db: &Database, config: &Config) -> Result<BlobReader, Error> {
+    open_blob(
+        &config.blob_store,
+        &version.content_hash.to_string(),
+        db.is_blob_encrypted(&version.content_hash)?,
+        SystemTime::from(version.creation_date),
+        config,
+    )
+}
+
+/// Returns true, if the plaintext content of the blob given by `hash` still matches the hash.
+/// Blobs, that can not be decrypted, do not match.
+pub fn verify(hash: &ContentHash, db: &Database, config: &Config) -> Result<bool, Error> {
+    let name = hash.to_string();
+    let encrypted = db.is_blob_encrypted(hash)?;
+    let mut content = match open_blob(
+        &config.blob_store,
+        &name,
+        encrypted,
+        SystemTime::UNIX_EPOCH,
+        config,
+    ) {
+        Ok(c) => c,
+        Err(Error::EncodingError) => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    let mut hasher = Sha256::new();
+    match io::copy(&mut content, &mut hasher) {
+        Ok(_) => Ok(hasher.finalize().as_slice() == hash.0),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => Ok(false),
+        Err(e) => Err(Error::from(e)),
+    }
+}
+
+/**
+ * Returns true, if the blob given by `name` in `store` starts with the header of an encrypted
+ * blob. Plaintext content may start with the same bytes, so this is only used for blobs, which
+ * were stored before the encryption was recorded in the DB (see `Database::is_blob_encrypted()`).
+ */
+pub fn has_encryption_header(store: &Arc<dyn BlobStore>, name: &str) -> Result<bool, Error> {
+    let size = store.size(name)?;
+    if size < (HEADER_LEN + TAG_LEN) as u64 {
+        return Ok(false);
+    }
+    let mut magic = [0u8; MAGIC.len()];
+    store
+        .get_range(name, 0, MAGIC.len() as u64)?
+        .read_exact(&mut magic)?;
+    Ok(&magic == MAGIC)
+}
+
+/// Opens the blob given by `name` in `store`, which is decrypted, if `encrypted` is set. Encrypted
+/// blobs with an invalid header result in `Error::EncodingError`.
+fn open_blob(
+    store: &Arc<dyn BlobStore>,
+    name: &str,
+    encrypted: bool,
+    modified: SystemTime,
+    config: &Config,
+) -> Result<BlobReader, Error> {
+    let size = store.size(name)?;
+    let mut stream = BlobStream {
+        store: store.clone(),
+        name: String::from(name),
+        len: size,
+        position: 0,
+        content: None,
+    };
+
+    if !encrypted {
+        return Ok(BlobReader {
+            len: size,
+            modified,
+            inner: BlobInner::Plain(Box::new(stream)),
+        });
+    }
+
+    let mut header = [0u8; HEADER_LEN];
+    if size < (HEADER_LEN + TAG_LEN) as u64
+        || stream.read_exact(&mut header).is_err()
+        || &header[..MAGIC.len()] != MAGIC
+    {
+        return Err(Error::EncodingError);
+    }
+    let master_key = config.master_key.as_ref().ok_or(Error::EncodingError)?;
+    let data_key = unwrap_key(&header, master_key).ok_or(Error::EncodingError)?;
+
+    let body_len = size - HEADER_LEN as u64;
+    let chunk_number = body_len.div_ceil(ENCRYPTED_CHUNK_LEN as u64);
+    let len = body_len - chunk_number * TAG_LEN as u64;
+
+    Ok(BlobReader {
+        len,
+        modified,
+        inner: BlobInner::Encrypted(Box::new(Decryptor {
+            file: stream,
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key)),
+            body_len,
+            chunk_number,
+            position: 0,
+            chunk: None,
+        })),
+    })
+}
+
+/// A reader for the plaintext content of a blob, that supports seeking.
+pub struct BlobReader {
+    len: u64,
+    modified: SystemTime,
+    inner: BlobInner,
+}
+
+enum BlobInner {
+    Plain(Box<BlobStream>),
+    Encrypted(Box<Decryptor>),
+}
+
+impl BlobReader {
+    /// Returns the length of the plaintext content.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns the time at which the content was written.
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+}
+
+impl Read for BlobReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            BlobInner::Plain(stream) => stream.read(buf),
+            BlobInner::Encrypted(decryptor) => decryptor.read(buf),
+        }
+    }
+}
+
+impl Seek for BlobReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match &mut self.inner {
+            BlobInner::Plain(stream) => stream.seek(position),
+            BlobInner::Encrypted(decryptor) => {
+                let target = match position {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::End(offset) => checked_offset(self.len, offset),
+                    SeekFrom::Current(offset) => checked_offset(decryptor.position, offset),
+                }
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position.")
+                })?;
+                decryptor.position = target;
+                Ok(target)
+            }
+        }
+    }
+}
+
+/**
+ * Reads the content of a blob from a BlobStore. The content is requested lazily from the current
+ * position to the end of the blob, so that sequential reads only need a single request.
+ */
+struct BlobStream {
+    store: Arc<dyn BlobStore>,
+    name: String,
+    len: u64,
+    position: u64,
+    content: Option<BlobContent>,
+}
+
+impl Read for BlobStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.content.is_none() {
+            let content = if self.position == 0 {
+                self.store.get(&self.name)
+            } else {
+                self.store.get_range(&self.name, self.position, self.len)
+            };
+            let content = match content {
+                Ok(c) => c,
+                Err(Error::IoError(e)) => return Err(e),
+                Err(err) => return Err(io::Error::other(err.to_string())),
+            };
+            self.content = Some(content);
+        }
+
+        let read = self.content.as_mut().unwrap().read(buf)?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The blob ended unexpectedly.",
+            ));
+        }
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for BlobStream {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let target = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => checked_offset(self.len, offset),
+            SeekFrom::Current(offset) => checked_offset(self.position, offset),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position."))?;
+        if target != self.position {
+            self.content = None;
+            self.position = target;
+        }
+        Ok(target)
+    }
+}
+
+/// Decrypts the chunks of an encrypted blob on demand.
+struct Decryptor {
+    file: BlobStream,
+    cipher: Aes256Gcm,
+    body_len: u64,
+    chunk_number: u64,
+    // Position inside the plaintext:
+    position: u64,
+    // Index and plaintext of the last decrypted chunk:
+    chunk: Option<(u64, Vec<u8>)>,
+}
+
+impl Decryptor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let index = self.position / CHUNK_LEN as u64;
+        if index >= self.chunk_number {
+            return Ok(0);
+        }
+        if self.chunk.as_ref().map(|(i, _)| *i) != Some(index) {
+            let offset = index * ENCRYPTED_CHUNK_LEN as u64;
+            let mut ciphertext =
+                vec![0u8; (self.body_len - offset).min(ENCRYPTED_CHUNK_LEN as u64) as usize];
+            self.file
+                .seek(SeekFrom::Start(HEADER_LEN as u64 + offset))?;
+            self.file.read_exact(&mut ciphertext)?;
+            let plaintext = self
+                .cipher
+                .decrypt(
+                    &chunk_nonce(index, index + 1 == self.chunk_number),
+                    ciphertext.as_slice(),
+                )
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Could not decrypt blob.")
+                })?;
+            self.chunk = Some((index, plaintext));
+        }
+
+        let chunk = &self.chunk.as_ref().unwrap().1;
+        let start = (self.position - index * CHUNK_LEN as u64) as usize;
+        let read = buf.len().min(chunk.len().saturating_sub(start));
+        buf[..read].copy_from_slice(&chunk[start..(start + read)]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+/// Encrypts the content at `src` with a new data key and writes it to `dest`. The hash and the
+/// length of the plaintext are returned.
+fn encrypt(src: &Path, dest: &Path, master_key: &MasterKey) -> Result<(ContentHash, u64), Error> {
+    let mut input = std::fs::File::open(src)?;
+    let len = input.metadata()?.len();
+    let mut output = std::fs::File::create(dest)?;
+
+    let mut data_key = [0u8; KEY_LEN];
+    thread_rng().fill_bytes(&mut data_key);
+    output.write_all(&wrap_key(&data_key, master_key)?)?;
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key));
+    // There is always at least one (possibly empty) chunk:
+    let chunk_number = len.div_ceil(CHUNK_LEN as u64).max(1);
+    let mut buffer = vec![0u8; CHUNK_LEN];
+    let mut hasher = Sha256::new();
+    for index in 0..chunk_number {
+        let chunk_len = (len - index * CHUNK_LEN as u64).min(CHUNK_LEN as u64) as usize;
+        input.read_exact(&mut buffer[..chunk_len])?;
+        hasher.update(&buffer[..chunk_len]);
+        let ciphertext = cipher
+            .encrypt(
+                &chunk_nonce(index, index + 1 == chunk_number),
+                &buffer[..chunk_len],
+            )
+            .map_err(|_| Error::EncodingError)?;
+        output.write_all(&ciphertext)?;
+    }
+    output.sync_all()?;
+
+    Ok((ContentHash(hasher.finalize().into()), len))
+}
+
+/// Returns the hash and the length of the content at `path`.
+fn hash_file(path: &Path) -> Result<(ContentHash, u64), Error> {
+    let mut hasher = Sha256::new();
+    let len = io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok((ContentHash(hasher.finalize().into()), len))
+}
+
+/// Returns a new path for a temporary file with the given extension in `config.upload_location`.
+fn temp_path(config: &Config, extension: &str) -> Result<PathBuf, Error> {
+    std::fs::create_dir_all(&config.upload_location)?;
+    Ok(config
+        .upload_location
+        .join(format!("{:x}.{}", thread_rng().next_u64(), extension)))
+}
+
+/**
+ * Moves the prepared blob at `path` into the BlobStore under the name given by `hash` and adds a
+ * reference to it. Whether the prepared blob is `encrypted` is recorded in the DB. If the blob is
+ * already stored, the prepared blob is removed instead and the stored blob keeps its encryption.
+ */
+fn add_blob(
+    path: &Path,
+    hash: &ContentHash,
+    encrypted: bool,
+    db: &Database,
+    config: &Config,
+) -> Result<(), Error> {
+    let name = hash.to_string();
+    let needs_put = {
+        let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let needs_put = if db.add_blob_ref(hash)? == 1 {
+            Ok(true)
+        } else {
+            config.blob_store.exists(&name).map(|exists| !exists)
+        };
+        let res = match needs_put {
+            Ok(true) => db.set_blob_encrypted(hash, encrypted).map(|_| true),
+            res => res,
+        };
+        if res.is_err() {
+            db.remove_blob_ref(hash)?;
+        }
+        res?
+    };
+
+    // The new reference prevents the blob from being removed in the meantime:
+    if needs_put {
+        if let Err(err) = config.blob_store.put(&name, path) {
+            release(hash, db, config)?;
+            return Err(err);
+        }
+    } else {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Returns the header of an encrypted blob containing the given data key wrapped by the master
+/// key.
+fn wrap_key(data_key: &[u8; KEY_LEN], master_key: &MasterKey) -> Result<Vec<u8>, Error> {
+    let mut nonce = [0u8; NONCE_LEN];
+    thread_rng().fill_bytes(&mut nonce);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&nonce);
+    header.extend_from_slice(
+        &master_key
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), &data_key[..])
+            .map_err(|_| Error::EncodingError)?,
+    );
+    Ok(header)
+}
+
+/// Returns the data key of the given header, if it can be unwrapped by the master key.
+fn unwrap_key(header: &[u8; HEADER_LEN], master_key: &MasterKey) -> Option<[u8; KEY_LEN]> {
+    let nonce = &header[MAGIC.len()..(MAGIC.len() + NONCE_LEN)];
+    master_key
+        .cipher()
+        .decrypt(
+            Nonce::from_slice(nonce),
+            &header[(MAGIC.len() + NONCE_LEN)..],
+        )
+        .ok()?
+        .as_slice()
+        .try_into()
+        .ok()
+}
+
+/// Returns the nonce of the chunk with the given index. The last chunk is marked, so that a
+/// truncated blob can not be decrypted.
+fn chunk_nonce(index: u64, is_last: bool) -> Nonce<<Aes256Gcm as aes_gcm::AeadCore>::NonceSize> {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[0..8].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_LEN - 1] = is_last as u8;
+    *Nonce::from_slice(&nonce)
+}
+
+fn checked_offset(base: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        base.checked_sub(offset.unsigned_abs())
+    } else {
+        base.checked_add(offset as u64)
+    }
+}
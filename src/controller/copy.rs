@@ -0,0 +1,302 @@
+use crate::{
+    config::Config,
+    controller::{
+        self,
+        moving::{self, ConflictPolicy},
+        search, trash, version,
+    },
+    database::Database,
+    models::{Dir, DirBuilder, File, FileBuilder, FsNode, Rights, User},
+    webapi::CopyMsg,
+    Error,
+};
+
+/**
+ * Copies the File given by `file_id` into the directory given by the field `parent_id` of
+ * `copy_infos`. The copy is owned by the user given by `user_id` and references the same blob as
+ * the current content of the original file, so the content is not duplicated in the storage.
+ * Previous versions are not copied. The new File is returned.
+ *
+ * The user needs `Rights::READ` on the file and `Rights::CREATE` on the target directory,
+ * otherwise `Error::MissingAuthorization` is returned. If there is no such file,
+ * `Error::NoSuchFile` is returned. See `copy_target()` for the handling of name conflicts.
+ */
+pub fn copy_file(
+    file_id: u64,
+    copy_infos: CopyMsg,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<File, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let file = db.get_file(file_id)?.ok_or(Error::NoSuchFile)?;
+    if !file.has_rights(&user, Rights::READ) {
+        return Err(Error::MissingAuthorization);
+    }
+    let parent_id = target_dir(&copy_infos, &user, db)?;
+    let target = copy_target(&file, true, parent_id, &copy_infos, user_id, db, config)?;
+
+    let copy_permissions = copy_infos.copy_permissions.unwrap_or(false);
+    let name = target.initial_name.clone();
+    let mut new_file = copy_file_entry(&file, parent_id, name, copy_permissions, user_id, db)?;
+    let res = copy_content(&file, &mut new_file, user_id, db, config)
+        .and_then(|_| place_copy(new_file.id, parent_id, &target, user_id, db));
+    if let Err(err) = res {
+        // Remove the incomplete copy:
+        trash::move_to_trash(new_file.id, user_id, db)?;
+        trash::remove_from_trash(new_file.id, user_id, db, config)?;
+        return Err(err);
+    }
+    if target.existing_id.is_some() {
+        search::index_metadata(new_file.id, db)?;
+    }
+
+    db.get_file(new_file.id)?.ok_or(Error::NoSuchFile)
+}
+
+/**
+ * Copies the Dir given by `dir_id` and all its descendants into the directory given by the field
+ * `parent_id` of `copy_infos`. All copies are owned by the user given by `user_id`, descendants,
+ * which the user may not read (see `copy_childs()`), are skipped. The content of the copied files
+ * is not duplicated in the storage (see `copy_file()`). The new Dir is returned.
+ *
+ * The user needs `Rights::LIST` on the directory and `Rights::CREATE` on the target directory,
+ * otherwise `Error::MissingAuthorization` is returned. If there is no such directory,
+ * `Error::NoSuchDir` is returned. A directory can not be copied into itself or one of its
+ * descendants (`Error::ForbiddenAction`). See `copy_target()` for the handling of name conflicts.
+ * If an error occurs while copying the descendants, the incomplete copy is removed.
+ */
+pub fn copy_dir(
+    dir_id: u64,
+    copy_infos: CopyMsg,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<Dir, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let dir = db.get_dir(dir_id)?.ok_or(Error::NoSuchDir)?;
+    if !dir.has_rights(&user, Rights::LIST) {
+        return Err(Error::MissingAuthorization);
+    }
+    let parent_id = target_dir(&copy_infos, &user, db)?;
+    if moving::is_ancestor(dir.id, parent_id, db)? {
+        return Err(Error::ForbiddenAction);
+    }
+    let target = copy_target(&dir, false, parent_id, &copy_infos, user_id, db, config)?;
+
+    let copy_permissions = copy_infos.copy_permissions.unwrap_or(false);
+    let name = target.initial_name.clone();
+    let new_dir = copy_dir_entry(&dir, parent_id, name, copy_permissions, user_id, db)?;
+    let res = copy_childs(&dir, new_dir.id, copy_permissions, &user, db, config)
+        .and_then(|_| place_copy(new_dir.id, parent_id, &target, user_id, db));
+    if let Err(err) = res {
+        // Remove the incomplete copy:
+        trash::move_to_trash(new_dir.id, user_id, db)?;
+        trash::remove_from_trash(new_dir.id, user_id, db, config)?;
+        return Err(err);
+    }
+
+    db.get_dir(new_dir.id)?.ok_or(Error::NoSuchDir)
+}
+
+/// Returns the ID of the target directory given by `copy_infos`, if the given user may create
+/// childs in it.
+fn target_dir(copy_infos: &CopyMsg, user: &User, db: &Database) -> Result<u64, Error> {
+    let parent = db
+        .get_dir(copy_infos.parent_id.ok_or(Error::BadCall)?.as_int())?
+        .ok_or(Error::NoSuchDir)?;
+    if !parent.has_rights(user, Rights::CREATE) {
+        return Err(Error::MissingAuthorization);
+    }
+    Ok(parent.id)
+}
+
+/// The name of a copy and the child, which it overwrites (see `copy_target()`).
+struct CopyTarget {
+    /// The name, under which the copy is created.
+    initial_name: String,
+    /// The name of the finished copy.
+    name: String,
+    /// The child of the target directory, that is replaced by the finished copy.
+    existing_id: Option<u64>,
+}
+
+/**
+ * Returns the name of the copy of the given FsNode in the directory given by `parent_id`. This is
+ * the field `name` of `copy_infos` or the name of the original.
+ *
+ * If the target directory already contains a child with this name, the conflict is handled as
+ * given by the field `on_conflict` of `copy_infos` or the configured default policy (see
+ * `moving::move_file()`). When overwriting, the copy is created under a free name and replaces
+ * the existing child only when it is complete (see `place_copy()`). Overwriting the original or
+ * one of its ancestors results in `Error::ForbiddenAction`, overwriting a child, which the user
+ * may not delete, in `Error::MissingAuthorization`.
+ */
+fn copy_target<N: FsNode>(
+    node: &N,
+    is_file: bool,
+    parent_id: u64,
+    copy_infos: &CopyMsg,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<CopyTarget, Error> {
+    let name = copy_infos
+        .name
+        .clone()
+        .unwrap_or_else(|| String::from(node.name()));
+    controller::check_name(&name)?;
+    let existing_id = match moving::find_child(0, parent_id, &name, db)? {
+        Some(id) => id,
+        None => {
+            return Ok(CopyTarget {
+                initial_name: name.clone(),
+                name,
+                existing_id: None,
+            })
+        }
+    };
+
+    match copy_infos
+        .on_conflict
+        .unwrap_or(config.name_conflict_policy)
+    {
+        ConflictPolicy::Fail => Err(Error::TargetExists),
+        ConflictPolicy::Rename => {
+            let name = moving::free_name(0, parent_id, &name, is_file, db)?;
+            Ok(CopyTarget {
+                initial_name: name.clone(),
+                name,
+                existing_id: None,
+            })
+        }
+        ConflictPolicy::Overwrite => {
+            if existing_id == node.id() || moving::is_ancestor(existing_id, node.parent_id(), db)? {
+                return Err(Error::ForbiddenAction);
+            }
+            controller::check_rights(existing_id, Rights::DELETE, user_id, db)?;
+            Ok(CopyTarget {
+                initial_name: moving::free_name(0, parent_id, &name, is_file, db)?,
+                name,
+                existing_id: Some(existing_id),
+            })
+        }
+    }
+}
+
+/// Replaces the child overwritten by the finished copy given by `copy_id` (see
+/// `moving::replace_fs_node()`).
+fn place_copy(
+    copy_id: u64,
+    parent_id: u64,
+    target: &CopyTarget,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    let existing_id = match target.existing_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    match moving::replace_fs_node(copy_id, existing_id, parent_id, &target.name, user_id, db) {
+        // The existing child was removed in parallel:
+        Err(Error::NoSuchTarget) => db.move_fs_node(copy_id, parent_id, &target.name),
+        res => res,
+    }
+}
+
+/// Inserts a copy of the given File without content with the given name into the directory given
+/// by `parent_id`.
+fn copy_file_entry(
+    file: &File,
+    parent_id: u64,
+    name: String,
+    copy_permissions: bool,
+    user_id: u64,
+    db: &Database,
+) -> Result<File, Error> {
+    let mut new_file = FileBuilder::new()
+        .with_parent_id(parent_id)
+        .with_owner_id(user_id)
+        .with_name(name)
+        .build();
+    new_file.media_type = file.media_type.clone();
+    if copy_permissions {
+        new_file.acl = file.acl.clone();
+        new_file.inherit_permissions = file.inherit_permissions;
+    }
+    db.insert_new_file(&mut new_file)?;
+    search::index_metadata(new_file.id, db)?;
+
+    Ok(new_file)
+}
+
+/// Sets the content of `new_file` to the current content of `file`. The content is not copied,
+/// the new version references the same blob.
+fn copy_content(
+    file: &File,
+    new_file: &mut File,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<(), Error> {
+    if let Some(version) = db.get_latest_version(file.id)? {
+        new_file.media_type = version.media_type.clone();
+        version::add_shared_version(new_file, user_id, &version, db, config)?;
+    }
+
+    Ok(())
+}
+
+/// Inserts a copy of the given Dir without its childs with the given name into the directory
+/// given by `parent_id`.
+fn copy_dir_entry(
+    dir: &Dir,
+    parent_id: u64,
+    name: String,
+    copy_permissions: bool,
+    user_id: u64,
+    db: &Database,
+) -> Result<Dir, Error> {
+    let mut new_dir = DirBuilder::new()
+        .with_id(0)
+        .with_parent_id(parent_id)
+        .with_owner_id(user_id)
+        .with_name(name)
+        .build();
+    if copy_permissions {
+        new_dir.acl = dir.acl.clone();
+        new_dir.inherit_permissions = dir.inherit_permissions;
+    }
+    db.insert_new_dir(&mut new_dir)?;
+
+    Ok(new_dir)
+}
+
+/// Copies all files, which the given user may read (`Rights::READ`), and all directories, which
+/// the user may list (`Rights::LIST`), of the given Dir recursively into the directory given by
+/// `target_id`.
+fn copy_childs(
+    dir: &Dir,
+    target_id: u64,
+    copy_permissions: bool,
+    user: &User,
+    db: &Database,
+    config: &Config,
+) -> Result<(), Error> {
+    for file in db.get_files_by_parent(dir.id)? {
+        if file.has_rights(user, Rights::READ) {
+            let name = file.name.clone();
+            let mut new_file =
+                copy_file_entry(&file, target_id, name, copy_permissions, user.id, db)?;
+            copy_content(&file, &mut new_file, user.id, db, config)?;
+        }
+    }
+    for child in db.get_dirs_by_parent(dir.id)? {
+        if child.has_rights(user, Rights::LIST) {
+            let name = child.name.clone();
+            let new_child = copy_dir_entry(&child, target_id, name, copy_permissions, user.id, db)?;
+            copy_childs(&child, new_child.id, copy_permissions, user, db, config)?;
+        }
+    }
+    Ok(())
+}
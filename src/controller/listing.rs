@@ -0,0 +1,348 @@
+use serde::{Deserialize, Serialize};
+
+use std::{cmp::Ordering, convert::TryInto, str::FromStr};
+
+use crate::{
+    controller::{
+        self,
+        path::{get_node, PathTarget},
+    },
+    database::Database,
+    models::{Dir, FsNode},
+    Error,
+};
+
+/// Number of childs returned by a listing, if the query does not give a limit.
+pub const DEFAULT_LIMIT: usize = 100;
+/// Maximal number of childs returned by a single listing.
+pub const MAX_LIMIT: usize = 1000;
+/// Number of names read from the name index at once, while listing childs sorted by name.
+const NAME_BATCH_SIZE: usize = 256;
+
+/// The order of the childs in a listing of a directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Sorted (byte-wise) by name.
+    Name,
+    /// Sorted by the size of the content.
+    Size,
+    /// Sorted by the modification date.
+    Mtime,
+    /// Directories first, then files grouped by media type, each sorted by name.
+    Type,
+}
+
+impl FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(SortKey::Name),
+            "size" => Ok(SortKey::Size),
+            "mtime" => Ok(SortKey::Mtime),
+            "type" => Ok(SortKey::Type),
+            _ => Err(Error::BadCall),
+        }
+    }
+}
+
+/// Describes which childs of a directory are listed in which order.
+///
+/// `media_type` is either a complete media type like `image/png` or a main type followed by `/*`
+/// like `image/*` and only matches files. `name_pattern` may contain the wildcards `*` (any
+/// sequence of characters) and `?` (a single character). `cursor` is the `next_cursor` of the
+/// previous page of the same listing.
+#[derive(Clone, Debug)]
+pub struct ListingQuery {
+    pub sort: SortKey,
+    pub descending: bool,
+    pub media_type: Option<String>,
+    pub name_pattern: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: usize,
+}
+
+impl Default for ListingQuery {
+    fn default() -> Self {
+        ListingQuery {
+            sort: SortKey::Name,
+            descending: false,
+            media_type: None,
+            name_pattern: None,
+            cursor: None,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+/// A page of the childs of a directory.
+#[derive(Debug)]
+pub struct Listing {
+    pub entries: Vec<PathTarget>,
+    /// The cursor of the next page, if there are more matching childs.
+    pub next_cursor: Option<String>,
+}
+
+/**
+ * Returns the childs of the Dir given by `dir_id`, that match `query`, in the order given by
+ * `query` (see `list_childs()`).
+ *
+ * The user given by `user_id` needs read permissions on the directory, otherwise
+ * `Error::MissingAuthorization` is returned. If there is no such directory, `Error::NoSuchDir` is
+ * returned.
+ */
+pub fn list_dir(
+    dir_id: u64,
+    query: &ListingQuery,
+    user_id: Option<u64>,
+    db: &Database,
+) -> Result<Listing, Error> {
+    let dir = controller::get_dir_info(dir_id, user_id, db)?;
+    list_childs(&dir, query, db)
+}
+
+/**
+ * Returns at most `query.limit` childs of the given Dir, that match the filters of `query`, in the
+ * order given by `query`. Childs with the same sort key are ordered by their IDs. The permissions
+ * on the directory are not checked.
+ *
+ * Listings sorted by name are read from the name index page by page, all other orders have to
+ * read all childs of the directory. If the limit is 0 or larger than `MAX_LIMIT` or the cursor is
+ * invalid, `Error::BadCall` is returned.
+ */
+pub fn list_childs(dir: &Dir, query: &ListingQuery, db: &Database) -> Result<Listing, Error> {
+    if query.limit == 0 || query.limit > MAX_LIMIT {
+        return Err(Error::BadCall);
+    }
+    let cursor = match &query.cursor {
+        Some(cursor) => Some(parse_cursor(cursor)?),
+        None => None,
+    };
+
+    let mut entries = if query.sort == SortKey::Name {
+        childs_by_name(dir.id, query, cursor, db)?
+    } else {
+        childs_by_key(dir.id, query, cursor, db)?
+    };
+
+    let next_cursor = if entries.len() > query.limit {
+        entries.truncate(query.limit);
+        entries
+            .last()
+            .map(|(key, node)| format_cursor(key, node.id()))
+    } else {
+        None
+    };
+
+    Ok(Listing {
+        entries: entries.into_iter().map(|(_, node)| node).collect(),
+        next_cursor,
+    })
+}
+
+/// Returns up to `query.limit + 1` matching childs of the directory given by `dir_id` after
+/// `cursor` in the order of their names and their sort keys.
+fn childs_by_name(
+    dir_id: u64,
+    query: &ListingQuery,
+    cursor: Option<(Vec<u8>, u64)>,
+    db: &Database,
+) -> Result<Vec<(Vec<u8>, PathTarget)>, Error> {
+    let mut after = match cursor {
+        Some((key, _)) => Some(String::from_utf8(key).map_err(|_| Error::BadCall)?),
+        None => None,
+    };
+
+    let mut entries = Vec::new();
+    loop {
+        let batch =
+            db.get_childs_by_name(dir_id, after.as_deref(), query.descending, NAME_BATCH_SIZE)?;
+        let batch_len = batch.len();
+        for (name, child_id) in batch {
+            match get_node(child_id, db) {
+                Ok(node) if matches(&node, query) => {
+                    entries.push((Vec::from(name.as_bytes()), node));
+                    if entries.len() > query.limit {
+                        return Ok(entries);
+                    }
+                }
+                // The child may have been removed in parallel:
+                Ok(_) | Err(Error::NoSuchTarget) => {}
+                Err(err) => return Err(err),
+            }
+            after = Some(name);
+        }
+        if batch_len < NAME_BATCH_SIZE {
+            return Ok(entries);
+        }
+    }
+}
+
+/// Returns up to `query.limit + 1` matching childs of the directory given by `dir_id` after
+/// `cursor` in the order given by `query` and their sort keys. All childs of the directory are
+/// read and sorted.
+fn childs_by_key(
+    dir_id: u64,
+    query: &ListingQuery,
+    cursor: Option<(Vec<u8>, u64)>,
+    db: &Database,
+) -> Result<Vec<(Vec<u8>, PathTarget)>, Error> {
+    let mut entries: Vec<(Vec<u8>, PathTarget)> = db
+        .get_dirs_by_parent(dir_id)?
+        .into_iter()
+        .map(PathTarget::Dir)
+        .chain(
+            db.get_files_by_parent(dir_id)?
+                .into_iter()
+                .map(PathTarget::File),
+        )
+        .filter(|node| matches(node, query))
+        .map(|node| (sort_key(&node, query.sort), node))
+        .collect();
+
+    let compare = |a: (&[u8], u64), b: (&[u8], u64)| {
+        if query.descending {
+            b.cmp(&a)
+        } else {
+            a.cmp(&b)
+        }
+    };
+    entries.sort_by(|(a_key, a), (b_key, b)| compare((a_key, a.id()), (b_key, b.id())));
+
+    Ok(entries
+        .into_iter()
+        .filter(|(key, node)| match &cursor {
+            Some((cursor_key, cursor_id)) => {
+                compare((key, node.id()), (cursor_key, *cursor_id)) == Ordering::Greater
+            }
+            None => true,
+        })
+        .take(query.limit + 1)
+        .collect())
+}
+
+/// Returns true, if the given File or Dir matches the filters of `query`.
+fn matches(node: &PathTarget, query: &ListingQuery) -> bool {
+    if let Some(media_type) = &query.media_type {
+        let file_type = match node {
+            PathTarget::File(file) => &file.media_type,
+            PathTarget::Dir(_) => return false,
+        };
+        if !media_type_matches(media_type, file_type) {
+            return false;
+        }
+    }
+
+    match &query.name_pattern {
+        Some(pattern) => glob_matches(pattern, node.name()),
+        None => true,
+    }
+}
+
+/// Returns true, if `media_type` matches `pattern`, which is either a complete media type or a
+/// main type followed by `/*`.
+pub fn media_type_matches(pattern: &str, media_type: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(main_type) => media_type.starts_with(&format!("{}/", main_type)),
+        None => media_type == pattern,
+    }
+}
+
+/// Returns true, if `name` matches `pattern`, which may contain the wildcards `*` and `?`.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and the position in the name it was tried at:
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, star_n)) => {
+                    backtrack = Some((star, star_n + 1));
+                    p = star + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/**
+ * Returns the bytes, by which the given File or Dir is sorted in a listing sorted by `sort`.
+ *
+ * All keys end with the name, so that childs with the same size, modification date or type are
+ * sorted by name. Modification dates are stored with a flipped sign bit, so that dates before 1970
+ * are sorted correctly. Directories have the type key `[0] + name`, files `[1] + media_type +
+ * [0] + name`.
+ */
+fn sort_key(node: &PathTarget, sort: SortKey) -> Vec<u8> {
+    let mut key = match (sort, node) {
+        (SortKey::Name, _) => Vec::new(),
+        (SortKey::Size, PathTarget::File(file)) => Vec::from(&file.size.to_be_bytes()[..]),
+        (SortKey::Size, PathTarget::Dir(dir)) => Vec::from(&dir.size.to_be_bytes()[..]),
+        (SortKey::Mtime, node) => {
+            let date = match node {
+                PathTarget::File(file) => file.modification_date,
+                PathTarget::Dir(dir) => dir.modification_date,
+            };
+            let timestamp = (date.timestamp() as u64) ^ (1 << 63);
+            Vec::from(&timestamp.to_be_bytes()[..])
+        }
+        (SortKey::Type, PathTarget::Dir(_)) => vec![0],
+        (SortKey::Type, PathTarget::File(file)) => {
+            let mut key = vec![1];
+            key.extend_from_slice(file.media_type.as_bytes());
+            key.push(0);
+            key
+        }
+    };
+    key.extend_from_slice(node.name().as_bytes());
+    key
+}
+
+/// Returns the opaque representation of a position in a listing: The sort key and the ID of the
+/// last returned child in hexadecimal digits.
+fn format_cursor(key: &[u8], id: u64) -> String {
+    key.iter()
+        .chain(id.to_be_bytes().iter())
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Parses a cursor created by `format_cursor()`.
+fn parse_cursor(cursor: &str) -> Result<(Vec<u8>, u64), Error> {
+    if cursor.len() < 16 || !cursor.is_ascii() {
+        return Err(Error::BadCall);
+    }
+    let bytes = cursor
+        .as_bytes()
+        .chunks(2)
+        .map(|digits| {
+            std::str::from_utf8(digits)
+                .ok()
+                .filter(|digits| digits.len() == 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or(Error::BadCall)
+        })
+        .collect::<Result<Vec<u8>, Error>>()?;
+
+    let (key, id) = bytes.split_at(bytes.len() - 8);
+    Ok((Vec::from(key), u64::from_be_bytes(id.try_into().unwrap())))
+}
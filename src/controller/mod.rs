@@ -2,14 +2,29 @@ use rocket::{fs::TempFile, http::MediaType};
 
 use crate::{
     config::Config,
+    controller::blob::BlobReader,
     database::Database,
-    models::{Dir, DirBuilder, File, FileBuilder, FsNode},
-    webapi::{DirMsg, FileMsg},
+    models::{Acl, Dir, DirBuilder, File, FileBuilder, FsNode, Id, Rights},
+    webapi::{DirMsg, FileMsg, PermissionsMsg, RightsEntryMsg},
     Error,
 };
 
+pub mod archive;
+pub mod batch;
+pub mod blob;
+pub mod copy;
 pub mod group;
+pub mod listing;
+pub mod moving;
+pub mod ownership;
+pub mod path;
+pub mod search;
+pub mod share;
+pub mod storage;
+pub mod trash;
+pub mod upload;
 pub mod user;
+pub mod version;
 
 /**
  * Adds a directory (`Dir`) to the database.
@@ -19,7 +34,9 @@ pub mod user;
  * argument `dir_infos`.
  * If the id given by `user_id` does not correspond to a user who has the necessary rights (for the
  * parent directory), an `Err` is returned.
- * If a necessary field is missing, an `Err` is returned.
+ * If a necessary field is missing or the name is invalid (see `check_name()`), an `Err` is
+ * returned. If the parent directory already contains a file or directory with the same name,
+ * `Error::TargetExists` is returned.
  * Otherwise the new directory is returned.
  */
 pub fn add_dir(db: &Database, dir_infos: DirMsg, user_id: u64) -> Result<Dir, Error> {
@@ -28,11 +45,18 @@ pub fn add_dir(db: &Database, dir_infos: DirMsg, user_id: u64) -> Result<Dir, Er
         .parent_id
         .ok_or(Error::BadCall)
         .map(|p_id| db.get_dir(p_id.as_int())?.ok_or(Error::NoSuchDir))??
-        .may_write(&db.get_user(user_id)?.ok_or(Error::BadCall)?)
+        .has_rights(
+            &db.get_user(user_id)?.ok_or(Error::BadCall)?,
+            Rights::CREATE,
+        )
     {
         return Err(Error::MissingAuthorization);
     }
 
+    if let Some(name) = &dir_infos.name {
+        check_name(name)?;
+    }
+
     let mut dir_builder = DirBuilder::new()
         .with_id(0)
         .with_parent_id(dir_infos.parent_id.unwrap().as_int())
@@ -58,7 +82,12 @@ pub fn get_dir_info(dir_id: u64, user_id: Option<u64>, db: &Database) -> Result<
     // Check, if the user is allowed to access the directory:
     let dir = db.get_dir(dir_id)?.ok_or(Error::NoSuchDir)?;
 
-    if user_id.is_some() && dir.may_read(&db.get_user(user_id.unwrap())?.ok_or(Error::BadCall)?) {
+    if user_id.is_some()
+        && dir.has_rights(
+            &db.get_user(user_id.unwrap())?.ok_or(Error::BadCall)?,
+            Rights::LIST,
+        )
+    {
         Ok(dir)
     } else {
         Err(Error::MissingAuthorization)
@@ -68,12 +97,21 @@ pub fn get_dir_info(dir_id: u64, user_id: Option<u64>, db: &Database) -> Result<
 /**
  * Updates the metadata of a directory given by field `id` of the given `DirMsg` to the values
  * given by the not `None` fields of the same struct.
- * This function will ignore the child_ids field of Dir and therefore it will not remove or add any
- * childs from a directory, even if the child_ids field in the request body does not contain all or
- * none of the directory's childs. Read- and writeable groups are ignored in the same way.
- * The given updates will be written to the database.
+ * This function will ignore the child_count field of Dir, childs are only added or removed by
+ * adding, moving or deleting them. Read- and writeable groups are ignored in the same way.
+ * Changes of `parent_id` or `name` move the directory (see `moving::move_dir()`), name conflicts
+ * are handled by the configured default policy.
+ * The user needs `Rights::LIST` on the directory, otherwise `Error::MissingAuthorization` is
+ * returned. The rights for moving it are checked by `moving::move_dir()`.
+ * The owner can not be changed this way (see `ownership::offer_ownership()`), if `owner_id`
+ * differs from the current owner, `Error::BadCall` is returned.
  */
-pub fn update_dir_infos(dir_info: DirMsg, user_id: u64, db: &Database) -> Result<Dir, Error> {
+pub fn update_dir_infos(
+    dir_info: DirMsg,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<Dir, Error> {
     // Get old Dir from DB:
     let mut dir = db
         .get_dir(dir_info.id.ok_or(Error::BadCall)?.as_int())?
@@ -84,18 +122,30 @@ pub fn update_dir_infos(dir_info: DirMsg, user_id: u64, db: &Database) -> Result
             Error::NoSuchDir
         })?;
 
-    // Make sure the user has the necessary rights:
-    if !dir.may_write(&db.get_user(user_id)?.ok_or(Error::BadCall)?) {
-        // TODO: Logging
-        println!("User tried to update a directory which he doesn't own.");
+    // Make sure the user may see the directory at all, before anything about it is revealed:
+    if !dir.has_rights(&db.get_user(user_id)?.ok_or(Error::BadCall)?, Rights::LIST) {
         return Err(Error::MissingAuthorization);
     }
 
-    // Set changed fields:
-    dir_info.apply_changes(&mut dir);
+    // The owner is only changed by accepting an ownership offer:
+    if dir_info
+        .owner_id
+        .is_some_and(|owner_id| owner_id.as_int() != dir.owner_id)
+    {
+        return Err(Error::BadCall);
+    }
 
-    // Write updated dir to DB:
-    db.update_dir(&dir)?;
+    // Move or rename the directory:
+    if dir_info.parent_id.is_some() || dir_info.name.is_some() {
+        dir = moving::move_dir(
+            dir.id,
+            dir_info.parent_id.map(|id| id.as_int()),
+            dir_info.name.clone(),
+            config.name_conflict_policy,
+            user_id,
+            db,
+        )?;
+    }
 
     Ok(dir)
 }
@@ -106,7 +156,9 @@ pub fn update_dir_infos(dir_info: DirMsg, user_id: u64, db: &Database) -> Result
  * directory. Other fields like name and parent_id should be given by the argument `file_info`.
  * If the id given by `user_id` does not correspond to a User who has the necessary rights for this
  * action (on the parent directory), an Err is retuned.
- * If a necessary field is missing, an Err is returned.
+ * If a necessary field is missing or the name is invalid (see `check_name()`), an Err is returned.
+ * If the parent directory already contains a file or directory with the same name,
+ * `Error::TargetExists` is returned.
  * Otherwise the new File is returned.
  */
 pub fn add_file(db: &Database, file_info: FileMsg, user_id: u64) -> Result<File, Error> {
@@ -115,11 +167,18 @@ pub fn add_file(db: &Database, file_info: FileMsg, user_id: u64) -> Result<File,
         .parent_id
         .ok_or(Error::BadCall)
         .map(|p_id| db.get_dir(p_id.as_int())?.ok_or(Error::NoSuchDir))??
-        .may_write(&db.get_user(user_id)?.ok_or(Error::BadCall)?)
+        .has_rights(
+            &db.get_user(user_id)?.ok_or(Error::BadCall)?,
+            Rights::CREATE,
+        )
     {
         return Err(Error::MissingAuthorization);
     }
 
+    if let Some(name) = &file_info.name {
+        check_name(name)?;
+    }
+
     let mut file_builder = FileBuilder::new()
         .with_parent_id(file_info.parent_id.unwrap().as_int())
         .with_owner_id(user_id);
@@ -130,6 +189,7 @@ pub fn add_file(db: &Database, file_info: FileMsg, user_id: u64) -> Result<File,
 
     // Add new file:
     db.insert_new_file(&mut new_file)?;
+    search::index_metadata(new_file.id, db)?;
 
     Ok(new_file)
 }
@@ -143,7 +203,7 @@ pub fn get_file_info(file_id: u64, user_id: u64, db: &Database) -> Result<File,
     let file = db.get_file(file_id)?.ok_or(Error::NoSuchFile)?;
 
     // Check, if the user is allowed to access the file:
-    if !file.may_read(&db.get_user(user_id)?.ok_or(Error::BadCall)?) {
+    if !file.has_rights(&db.get_user(user_id)?.ok_or(Error::BadCall)?, Rights::LIST) {
         // TODO: Match against existing rules
         Err(Error::MissingAuthorization)
     } else {
@@ -157,9 +217,19 @@ pub fn get_file_info(file_id: u64, user_id: u64, db: &Database) -> Result<File,
  * This function will ignore the `read_group_ids` and `write_group_ids` fields of File and
  * therefore it will not remove or add any readable or writeable groups from a file, even if these
  * fields in the request body do not contain all or none of the files's groups.
- * The given updates will be written to the database.
+ * Changes of `parent_id` or `name` move the file (see `moving::move_file()`), name conflicts are
+ * handled by the configured default policy.
+ * The user needs `Rights::LIST` on the file, otherwise `Error::MissingAuthorization` is
+ * returned. The rights for moving it are checked by `moving::move_file()`.
+ * The owner can not be changed this way (see `ownership::offer_ownership()`), if `owner_id`
+ * differs from the current owner, `Error::BadCall` is returned.
  */
-pub fn update_file_infos(file_info: FileMsg, user_id: u64, db: &Database) -> Result<File, Error> {
+pub fn update_file_infos(
+    file_info: FileMsg,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<File, Error> {
     // Get old File from DB:
     let mut file = db
         .get_file(file_info.id.ok_or(Error::BadCall)?.as_int())?
@@ -169,25 +239,37 @@ pub fn update_file_infos(file_info: FileMsg, user_id: u64, db: &Database) -> Res
             Error::NoSuchFile
         })?;
 
-    // Make sure the user has the necessary rights:
-    if !file.may_write(&db.get_user(user_id)?.ok_or(Error::BadCall)?) {
-        // TODO: Logging
-        println!("User tried to update a file which he doesn't own.");
+    // Make sure the user may see the file at all, before anything about it is revealed:
+    if !file.has_rights(&db.get_user(user_id)?.ok_or(Error::BadCall)?, Rights::LIST) {
         return Err(Error::MissingAuthorization);
     }
 
-    // Set changed fields:
-    file_info.apply_changes(&mut file);
+    // The owner is only changed by accepting an ownership offer:
+    if file_info
+        .owner_id
+        .is_some_and(|owner_id| owner_id.as_int() != file.owner_id)
+    {
+        return Err(Error::BadCall);
+    }
 
-    // Write updated dir to DB:
-    db.update_file(&file)?;
+    // Move or rename the file:
+    if file_info.parent_id.is_some() || file_info.name.is_some() {
+        file = moving::move_file(
+            file.id,
+            file_info.parent_id.map(|id| id.as_int()),
+            file_info.name.clone(),
+            config.name_conflict_policy,
+            user_id,
+            db,
+        )?;
+    }
 
     Ok(file)
 }
 
 /**
  * Updates the content of a file given by `file_id` to the content of the given `TempFile`
- * `new_content`.
+ * `new_content`. The previous content is kept as a version (see `version::add_version()`).
  *
  * The function checks whether the user given by `user_id` has the necessary rights to update the
  * file and returns an Error if not.
@@ -203,7 +285,7 @@ pub async fn update_file_content(
     let mut file = db.get_file(file_id)?.ok_or(Error::NoSuchFile)?;
 
     // Check users permissions:
-    if !file.may_write(&db.get_user(user_id)?.ok_or(Error::BadCall)?) {
+    if !file.has_rights(&db.get_user(user_id)?.ok_or(Error::BadCall)?, Rights::WRITE) {
         return Err(Error::MissingAuthorization);
     }
 
@@ -211,41 +293,37 @@ pub async fn update_file_content(
     file.media_type = format!("{}", media_type);
     db.update_file(&file)?;
 
-    // Move temporary file to permanent path:
-    let mut new_path = config.file_location.clone();
-    new_path.push(format!("{:x}", file.id));
-    if let Err(e) = new_content.persist_to(&new_path).await {
-        // TODO Logging
-        println!("Could not persist TempFile: {}", e);
-        new_content.move_copy_to(new_path).await?;
-    }
+    // Move temporary file to the blob storage:
+    let (content_hash, size) = blob::store_temp_file(&mut new_content, db, config).await?;
+    blob::run_blocking(db, config, move |db, config| {
+        version::add_version(&mut file, user_id, content_hash, size, db, config)?;
 
-    // Send file information as respose:
-    Ok(file)
+        // Send file information as respose:
+        Ok(file)
+    })
+    .await
 }
 
 /**
- * Returnes a handle to the file in which the content of the file given by `file_id` is saved.
+ * Returnes a handle to the blob in which the content of the file given by `file_id` is saved.
  * The function checks whether the user given by `user_id` has the necessary rights to read the
- * file and returns an Error if not.
+ * file and returns an Error if not. If no content was written to the file yet,
+ * `Error::NoSuchTarget` is returned.
  */
 pub async fn get_file_content(
     file_id: u64,
     user_id: u64,
     db: &Database,
     config: &Config,
-) -> Result<std::fs::File, Error> {
-    // TODO: Refactor as soon as Result.flatten is stabilized.
-    match get_file_info(file_id, user_id, db).map(|file| {
-        // Respond with streamed file:
-        let mut file_path = config.file_location.clone();
-        file_path.push(format!("{:x}", file.id));
-        std::fs::File::open(file_path).map_err(Error::from)
-    }) {
-        Ok(Ok(file)) => Ok(file),
-        Ok(Err(e)) => Err(e),
-        Err(err) => Err(err),
+) -> Result<BlobReader, Error> {
+    let file = db.get_file(file_id)?.ok_or(Error::NoSuchFile)?;
+    if !file.has_rights(&db.get_user(user_id)?.ok_or(Error::BadCall)?, Rights::READ) {
+        return Err(Error::MissingAuthorization);
     }
+    blob::run_blocking(db, config, move |db, config| {
+        version::open_content(file.id, db, config)
+    })
+    .await
 }
 
 /**
@@ -277,17 +355,7 @@ pub fn add_read_permission(
     db.get_group(group_id)?.ok_or(Error::NoSuchTarget)?;
 
     // Make sure the FsNode exists and the user has the permission to change its permissions:
-    if let Some(file) = db.get_file(fs_node_id)? {
-        if file.owner_id() != user_id {
-            return Err(Error::MissingAuthorization);
-        }
-    } else if let Some(dir) = db.get_dir(fs_node_id)? {
-        if dir.owner_id() != user_id {
-            return Err(Error::MissingAuthorization);
-        }
-    } else {
-        return Err(Error::NoSuchTarget);
-    }
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
 
     db.add_readable_group(fs_node_id, group_id)
 }
@@ -320,17 +388,378 @@ pub fn add_write_permission(
     db.get_group(group_id)?.ok_or(Error::NoSuchTarget)?;
 
     // Make sure the FsNode exists and the user has the permission to change its permissions:
-    if let Some(file) = db.get_file(fs_node_id)? {
-        if file.owner_id() != user_id {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+
+    db.add_writeable_group(fs_node_id, group_id)
+}
+
+/**
+ * Revoke read permissions on a given FsNode from members of a given Group.
+ *
+ * Removes the Group given by `group_id` from the `readable_groups` of the File or Dir given by
+ * `fs_node_id`. The Group does not have to exist anymore.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode, `Err(Error::MissingAuthorization)` is retuned.
+ */
+pub fn remove_read_permission(
+    fs_node_id: u64,
+    group_id: u64,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+
+    db.remove_readable_group(fs_node_id, group_id)
+}
+
+/**
+ * Revoke write permissions on a given FsNode from members of a given Group.
+ *
+ * Removes the Group given by `group_id` from the `writeable_groups` of the File or Dir given by
+ * `fs_node_id`. The Group does not have to exist anymore.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode, `Err(Error::MissingAuthorization)` is retuned.
+ */
+pub fn remove_write_permission(
+    fs_node_id: u64,
+    group_id: u64,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+
+    db.remove_writeable_group(fs_node_id, group_id)
+}
+
+/**
+ * Give read permissions on a given FsNode to a single User.
+ *
+ * Adds the User given by `reader_id` to the `readable_users` of the File or Dir given by
+ * `fs_node_id`.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If there is no user with the ID `reader_id`, `Err(Error::BadCall)` is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode, `Err(Error::MissingAuthorization)` is retuned.
+ */
+pub fn add_user_read_permission(
+    fs_node_id: u64,
+    reader_id: u64,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+    db.get_user(reader_id)?.ok_or(Error::BadCall)?;
+
+    db.add_readable_user(fs_node_id, reader_id)
+}
+
+/**
+ * Give write permissions on a given FsNode to a single User.
+ *
+ * Adds the User given by `writer_id` to the `writeable_users` of the File or Dir given by
+ * `fs_node_id`.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If there is no user with the ID `writer_id`, `Err(Error::BadCall)` is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode, `Err(Error::MissingAuthorization)` is retuned.
+ */
+pub fn add_user_write_permission(
+    fs_node_id: u64,
+    writer_id: u64,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+    db.get_user(writer_id)?.ok_or(Error::BadCall)?;
+
+    db.add_writeable_user(fs_node_id, writer_id)
+}
+
+/**
+ * Revoke read permissions on a given FsNode from a single User.
+ *
+ * Removes the User given by `reader_id` from the `readable_users` of the File or Dir given by
+ * `fs_node_id`. The User does not have to exist anymore.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode, `Err(Error::MissingAuthorization)` is retuned.
+ */
+pub fn remove_user_read_permission(
+    fs_node_id: u64,
+    reader_id: u64,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+
+    db.remove_readable_user(fs_node_id, reader_id)
+}
+
+/**
+ * Revoke write permissions on a given FsNode from a single User.
+ *
+ * Removes the User given by `writer_id` from the `writeable_users` of the File or Dir given by
+ * `fs_node_id`. The User does not have to exist anymore.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode, `Err(Error::MissingAuthorization)` is retuned.
+ */
+pub fn remove_user_write_permission(
+    fs_node_id: u64,
+    writer_id: u64,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+
+    db.remove_writeable_user(fs_node_id, writer_id)
+}
+
+/**
+ * Set the additional rights of a given Group on a given FsNode.
+ *
+ * Sets the `group_rights` of the Group given by `group_id` on the File or Dir given by
+ * `fs_node_id` to `rights`. Empty rights remove the entry of the Group, in this case the Group
+ * does not have to exist anymore.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If the rights are not empty and there is no group with the ID `group_id`, `Err(Error::BadCall)`
+ * is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode, `Err(Error::MissingAuthorization)` is retuned.
+ */
+pub fn set_group_rights(
+    fs_node_id: u64,
+    group_id: u64,
+    rights: Rights,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+    if !rights.is_empty() {
+        db.get_group(group_id)?.ok_or(Error::BadCall)?;
+    }
+
+    db.set_group_rights(fs_node_id, group_id, rights)
+}
+
+/**
+ * Set the additional rights of a single User on a given FsNode.
+ *
+ * Sets the `user_rights` of the User given by `target_id` on the File or Dir given by
+ * `fs_node_id` to `rights`. Empty rights remove the entry of the User, in this case the User does
+ * not have to exist anymore.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If the rights are not empty and there is no user with the ID `target_id`, `Err(Error::BadCall)`
+ * is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode or grants rights, which they do not have themselves,
+ * `Err(Error::MissingAuthorization)` is retuned.
+ */
+pub fn set_user_rights(
+    fs_node_id: u64,
+    target_id: u64,
+    rights: Rights,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    let node = get_node_permissions(fs_node_id, user_id, db)?;
+    if !node.rights.contains(Rights::SHARE)
+        || !(node.rights | node.acl.rights_of_user(target_id)).contains(rights)
+    {
+        return Err(Error::MissingAuthorization);
+    }
+    if !rights.is_empty() {
+        db.get_user(target_id)?.ok_or(Error::BadCall)?;
+    }
+
+    db.set_user_rights(fs_node_id, target_id, rights)
+}
+
+/**
+ * Replace all group and user permissions on a given FsNode.
+ *
+ * Sets the readable and writeable groups and users and the additional rights of groups and users
+ * of the File or Dir given by `fs_node_id` to the ones given by `permissions`. Duplicate IDs are
+ * ignored, the rights of duplicate entries are combined and entries without rights are dropped.
+ * If `permissions.inherit_permissions` is not None, it also sets, whether the FsNode inherits
+ * the permissions of its ancestors.
+ *
+ * If there is no file or dir with the given ID in the DB, `Err(Error::NoSuchTarget)` is returned.
+ * If one of the groups or users does not exist, `Err(Error::BadCall)` is returned.
+ * If the user given by `user_id` does not have the necessary permission to change the permissions
+ * of the given FsNode, `Err(Error::MissingAuthorization)` is retuned. This is also the case, if
+ * a group or user is granted rights, which they did not have on the FsNode before and which the
+ * user does not have themselves, or if the user is not the owner and changes
+ * `inherit_permissions`.
+ */
+pub fn set_permissions(
+    fs_node_id: u64,
+    permissions: PermissionsMsg,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    let node = get_node_permissions(fs_node_id, user_id, db)?;
+    if !node.rights.contains(Rights::SHARE) {
+        return Err(Error::MissingAuthorization);
+    }
+    if let Some(inherit_permissions) = permissions.inherit_permissions {
+        if inherit_permissions != node.inherit_permissions && node.owner_id != user_id {
             return Err(Error::MissingAuthorization);
         }
-    } else if let Some(dir) = db.get_dir(fs_node_id)? {
-        if dir.owner_id() != user_id {
+    }
+
+    let ids = |ids: Vec<Id>| -> Vec<u64> { ids.into_iter().map(|id| id.as_int()).collect() };
+    let entries = |entries: Vec<RightsEntryMsg>| -> Vec<(u64, Rights)> {
+        entries
+            .into_iter()
+            .filter(|entry| !entry.rights.is_empty())
+            .map(|entry| (entry.id.as_int(), entry.rights))
+            .collect()
+    };
+    let requested = Acl {
+        read_group_ids: ids(permissions.read_group_ids),
+        write_group_ids: ids(permissions.write_group_ids),
+        read_user_ids: ids(permissions.read_user_ids),
+        write_user_ids: ids(permissions.write_user_ids),
+        group_rights: entries(permissions.group_rights),
+        user_rights: entries(permissions.user_rights),
+    };
+    // Merging into an empty Acl removes the duplicates:
+    let mut acl = Acl::default();
+    acl.merge(&requested);
+
+    for group_id in acl
+        .read_group_ids
+        .iter()
+        .chain(&acl.write_group_ids)
+        .chain(acl.group_rights.iter().map(|(id, _)| id))
+    {
+        db.get_group(*group_id)?.ok_or(Error::BadCall)?;
+    }
+    for user_id in acl
+        .read_user_ids
+        .iter()
+        .chain(&acl.write_user_ids)
+        .chain(acl.user_rights.iter().map(|(id, _)| id))
+    {
+        db.get_user(*user_id)?.ok_or(Error::BadCall)?;
+    }
+
+    // Only rights, which the user has or which were granted before, may be granted:
+    let may_grant = |new: Rights, old: Rights| (node.rights | old).contains(new);
+    let group_ids = acl
+        .read_group_ids
+        .iter()
+        .chain(&acl.write_group_ids)
+        .chain(acl.group_rights.iter().map(|(id, _)| id));
+    for group_id in group_ids {
+        let (new, old) = (
+            acl.rights_of_group(*group_id),
+            node.acl.rights_of_group(*group_id),
+        );
+        if !may_grant(new, old) {
             return Err(Error::MissingAuthorization);
         }
+    }
+    let user_ids = acl
+        .read_user_ids
+        .iter()
+        .chain(&acl.write_user_ids)
+        .chain(acl.user_rights.iter().map(|(id, _)| id));
+    for target_id in user_ids {
+        let (new, old) = (
+            acl.rights_of_user(*target_id),
+            node.acl.rights_of_user(*target_id),
+        );
+        if !may_grant(new, old) {
+            return Err(Error::MissingAuthorization);
+        }
+    }
+
+    db.set_permissions(fs_node_id, &acl, permissions.inherit_permissions)
+}
+
+/// The permissions of a File or Dir from the view of a single user (see
+/// `get_node_permissions()`).
+struct NodePermissions {
+    owner_id: u64,
+    /// The rights of the user on the FsNode.
+    rights: Rights,
+    /// The own (not inherited) groups, users and rights of the FsNode.
+    acl: Acl,
+    inherit_permissions: bool,
+}
+
+/**
+ * Returns the permissions of the File or Dir given by `fs_node_id` together with the rights of
+ * the user given by `user_id` on it. If there is no such FsNode, `Error::NoSuchTarget` is
+ * returned.
+ */
+fn get_node_permissions(
+    fs_node_id: u64,
+    user_id: u64,
+    db: &Database,
+) -> Result<NodePermissions, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    if let Some(file) = db.get_file(fs_node_id)? {
+        Ok(NodePermissions {
+            owner_id: file.owner_id,
+            rights: file.rights(&user),
+            acl: file.acl,
+            inherit_permissions: file.inherit_permissions,
+        })
+    } else if let Some(dir) = db.get_dir(fs_node_id)? {
+        Ok(NodePermissions {
+            owner_id: dir.owner_id,
+            rights: dir.rights(&user),
+            acl: dir.acl,
+            inherit_permissions: dir.inherit_permissions,
+        })
+    } else {
+        Err(Error::NoSuchTarget)
+    }
+}
+
+/**
+ * Returns `Error::MissingAuthorization`, if the user given by `user_id` does not have all of the
+ * given rights on the File or Dir given by `fs_node_id`. If there is no such FsNode,
+ * `Error::NoSuchTarget` is returned.
+ */
+fn check_rights(fs_node_id: u64, rights: Rights, user_id: u64, db: &Database) -> Result<(), Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let has_rights = if let Some(file) = db.get_file(fs_node_id)? {
+        file.has_rights(&user, rights)
+    } else if let Some(dir) = db.get_dir(fs_node_id)? {
+        dir.has_rights(&user, rights)
     } else {
         return Err(Error::NoSuchTarget);
+    };
+
+    if has_rights {
+        Ok(())
+    } else {
+        Err(Error::MissingAuthorization)
     }
+}
 
-    db.add_writeable_group(fs_node_id, group_id)
+/**
+ * Returns `Error::BadCall`, if the given name can not be the name of a File or Dir: Names must not
+ * be empty, contain a `/` or be one of the path segments `.` and `..`.
+ */
+pub fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        Err(Error::BadCall)
+    } else {
+        Ok(())
+    }
 }
@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+use crate::{
+    controller::{self, search},
+    database::{Database, FsChange},
+    models::{numbered_name, Dir, File, FsNode, Rights, User},
+    Error,
+};
+
+/// Number of times a move is retried, if the target name was taken in parallel.
+const MAX_MOVE_ATTEMPTS: usize = 8;
+
+/**
+ * Determines what happens, if a File or Dir is moved or renamed and the target directory already
+ * contains another child with the same name.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// The move fails with `Error::TargetExists`.
+    Fail,
+    /// A suffix like ` (1)` is added to the name (before the extension of files).
+    Rename,
+    /// The existing child is moved into the trash.
+    Overwrite,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(ConflictPolicy::Fail),
+            "rename" => Ok(ConflictPolicy::Rename),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            _ => Err(Error::BadCall),
+        }
+    }
+}
+
+/**
+ * Moves the File given by `file_id` into the directory given by `parent_id` and renames it to
+ * `name`. If `parent_id` or `name` is None, the current parent or name is kept. The moved File is
+ * returned.
+ *
+ * The user given by `user_id` needs the rights checked by `check_move_rights()`, otherwise
+ * `Error::MissingAuthorization` is returned. If there is no such file,
+ * `Error::NoSuchFile` is returned. Name conflicts are handled as given by `policy` (see
+ * `move_fs_node()`).
+ */
+pub fn move_file(
+    file_id: u64,
+    parent_id: Option<u64>,
+    name: Option<String>,
+    policy: ConflictPolicy,
+    user_id: u64,
+    db: &Database,
+) -> Result<File, Error> {
+    let file = db.get_file(file_id)?.ok_or(Error::NoSuchFile)?;
+    move_fs_node(&file, true, parent_id, name, policy, user_id, db)?;
+    db.get_file(file_id)?.ok_or(Error::NoSuchFile)
+}
+
+/**
+ * Moves the Dir given by `dir_id` into the directory given by `parent_id` and renames it to
+ * `name`. If `parent_id` or `name` is None, the current parent or name is kept. The moved Dir is
+ * returned.
+ *
+ * The user given by `user_id` needs the rights checked by `check_move_rights()`, otherwise
+ * `Error::MissingAuthorization` is returned. If there is no such directory, `Error::NoSuchDir` is
+ * returned. A directory can not be moved into itself or one of its descendants and root
+ * directories can not be moved at all (`Error::ForbiddenAction`). Name conflicts are handled as
+ * given by `policy` (see `move_fs_node()`).
+ */
+pub fn move_dir(
+    dir_id: u64,
+    parent_id: Option<u64>,
+    name: Option<String>,
+    policy: ConflictPolicy,
+    user_id: u64,
+    db: &Database,
+) -> Result<Dir, Error> {
+    let dir = db.get_dir(dir_id)?.ok_or(Error::NoSuchDir)?;
+    move_fs_node(&dir, false, parent_id, name, policy, user_id, db)?;
+    db.get_dir(dir_id)?.ok_or(Error::NoSuchDir)
+}
+
+/**
+ * Moves the given FsNode into the directory given by `parent_id` and renames it to `name`. Invalid
+ * names (see `controller::check_name()`) result in `Error::BadCall`.
+ *
+ * If the new parent already contains another child with the same name, the conflict is handled
+ * as given by `policy`:
+ * - `ConflictPolicy::Fail` returns `Error::TargetExists`.
+ * - `ConflictPolicy::Rename` uses the first free name with a suffix ` (1)`, ` (2)`, ...
+ * - `ConflictPolicy::Overwrite` moves the existing child into the trash in the same transaction
+ *   (see `replace_fs_node()`). This requires the same permissions as deleting it and fails with
+ *   `Error::ForbiddenAction`, if the existing child is an ancestor of the moved FsNode.
+ */
+fn move_fs_node<N: FsNode>(
+    node: &N,
+    is_file: bool,
+    parent_id: Option<u64>,
+    name: Option<String>,
+    policy: ConflictPolicy,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let parent_id = parent_id.unwrap_or_else(|| node.parent_id());
+    let name = name.unwrap_or_else(|| String::from(node.name()));
+    if parent_id == node.parent_id() && name == node.name() {
+        return Ok(());
+    }
+    controller::check_name(&name)?;
+
+    // Root directories have no parent and can not be moved:
+    db.get_dir(node.parent_id())?
+        .ok_or(Error::ForbiddenAction)?;
+    let new_parent = db.get_dir(parent_id)?.ok_or(Error::NoSuchDir)?;
+    check_move_rights(node, &new_parent, &name, &user)?;
+
+    let mut target_name = name.clone();
+    for _ in 0..MAX_MOVE_ATTEMPTS {
+        match db.move_fs_node(node.id(), parent_id, &target_name) {
+            Err(Error::TargetExists) => match policy {
+                ConflictPolicy::Fail => return Err(Error::TargetExists),
+                ConflictPolicy::Rename => {
+                    target_name = free_name(node.id(), parent_id, &name, is_file, db)?;
+                }
+                ConflictPolicy::Overwrite => {
+                    if let Some(existing_id) = find_child(node.id(), parent_id, &target_name, db)? {
+                        if is_ancestor(existing_id, node.parent_id(), db)? {
+                            return Err(Error::ForbiddenAction);
+                        }
+                        match replace_fs_node(
+                            node.id(),
+                            existing_id,
+                            parent_id,
+                            &target_name,
+                            user_id,
+                            db,
+                        ) {
+                            Ok(()) => return finish_move(node, is_file, db),
+                            // The existing child was removed or replaced in parallel:
+                            Err(Error::NoSuchTarget) | Err(Error::TargetExists) => {}
+                            Err(err) => return Err(err),
+                        }
+                    }
+                }
+            },
+            Ok(()) => return finish_move(node, is_file, db),
+            Err(err) => return Err(err),
+        }
+    }
+    Err(Error::TargetExists)
+}
+
+/// Updates the search index after the given FsNode was moved.
+fn finish_move<N: FsNode>(node: &N, is_file: bool, db: &Database) -> Result<(), Error> {
+    if is_file {
+        search::index_metadata(node.id(), db)?;
+    }
+    Ok(())
+}
+
+/**
+ * Moves the child given by `existing_id` into the trash and the FsNode given by `id` into the
+ * directory given by `parent_id` under the name `name` in a single transaction, so that the name
+ * never refers to an incomplete FsNode or to nothing at all.
+ *
+ * The user given by `user_id` needs the same permissions as for deleting the existing child (see
+ * `trash::move_to_trash()`). If the existing child was removed in parallel, `Error::NoSuchTarget`
+ * is returned. See `Database::move_fs_node()` for other errors.
+ */
+pub fn replace_fs_node(
+    id: u64,
+    existing_id: u64,
+    parent_id: u64,
+    name: &str,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    controller::check_rights(existing_id, Rights::DELETE, user_id, db)?;
+
+    let changes = [
+        FsChange::Trash { id: existing_id },
+        FsChange::Move {
+            id,
+            parent_id,
+            name: String::from(name),
+        },
+    ];
+    db.apply_changes(&changes)?.map_err(|(_, err)| err)
+}
+
+/**
+ * Makes sure the given user may move the given FsNode into `new_parent` and rename it to `name`:
+ * Changing the name requires `Rights::RENAME` on the FsNode. Moving it into another directory
+ * requires `Rights::DELETE` on the FsNode and `Rights::CREATE` on the new parent. Otherwise
+ * `Error::MissingAuthorization` is returned.
+ */
+pub fn check_move_rights<N: FsNode>(
+    node: &N,
+    new_parent: &Dir,
+    name: &str,
+    user: &User,
+) -> Result<(), Error> {
+    let mut node_rights = Rights::NONE;
+    if name != node.name() {
+        node_rights |= Rights::RENAME;
+    }
+    if new_parent.id != node.parent_id() {
+        node_rights |= Rights::DELETE;
+        if !new_parent.has_rights(user, Rights::CREATE) {
+            return Err(Error::MissingAuthorization);
+        }
+    }
+    if !node.has_rights(user, node_rights) {
+        return Err(Error::MissingAuthorization);
+    }
+    Ok(())
+}
+
+/// Returns the ID of the child of the directory given by `parent_id`, that is named `name`
+/// (ignoring the FsNode given by `except_id`).
+pub fn find_child(
+    except_id: u64,
+    parent_id: u64,
+    name: &str,
+    db: &Database,
+) -> Result<Option<u64>, Error> {
+    Ok(db
+        .get_child_id(parent_id, name)?
+        .filter(|id| *id != except_id))
+}
+
+/// Returns true, if the directory given by `ancestor_id` is the directory given by `dir_id` or one
+/// of its ancestors.
+pub fn is_ancestor(ancestor_id: u64, dir_id: u64, db: &Database) -> Result<bool, Error> {
+    let mut current_id = dir_id;
+    while current_id != 0 {
+        if current_id == ancestor_id {
+            return Ok(true);
+        }
+        current_id = match db.get_dir(current_id)? {
+            Some(dir) => dir.parent_id,
+            None => return Ok(false),
+        };
+    }
+    Ok(false)
+}
+
+/// Returns the first name of the form `<name> (<n>)`, that is not used by a child of the
+/// directory given by `parent_id` (ignoring the FsNode given by `except_id`). For files the suffix
+/// is inserted before the extension.
+pub fn free_name(
+    except_id: u64,
+    parent_id: u64,
+    name: &str,
+    is_file: bool,
+    db: &Database,
+) -> Result<String, Error> {
+    let mut number = 1;
+    loop {
+        let candidate = numbered_name(name, number, is_file);
+        if find_child(except_id, parent_id, &candidate, db)?.is_none() {
+            return Ok(candidate);
+        }
+        number += 1;
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::offset::Utc;
+
+use crate::{
+    controller::{path::get_node, search},
+    database::Database,
+    models::{FsNode, OwnershipOffer},
+    webapi::OwnershipOfferMsg,
+    Error,
+};
+
+/**
+ * Offers the ownership of the File or Dir given by `offer_infos.fs_node_id` to the User given by
+ * `offer_infos.new_owner_id`. A previous offer for the same FsNode is replaced. The ownership is
+ * only transferred, when the new owner accepts the offer (see `accept_offer()`).
+ *
+ * Only the owner of a FsNode may offer it, otherwise `Error::MissingAuthorization` is returned.
+ * If there is no such FsNode, `Error::NoSuchTarget` is returned. If a field is missing, there is
+ * no such User or the User already owns the FsNode, `Error::BadCall` is returned. Root
+ * directories can not be transferred (`Error::ForbiddenAction`).
+ */
+pub fn offer_ownership(
+    offer_infos: OwnershipOfferMsg,
+    user_id: u64,
+    db: &Database,
+) -> Result<OwnershipOffer, Error> {
+    let fs_node_id = offer_infos.fs_node_id.ok_or(Error::BadCall)?.as_int();
+    let new_owner_id = offer_infos.new_owner_id.ok_or(Error::BadCall)?.as_int();
+    let node = get_node(fs_node_id, db)?;
+    if node.owner_id() != user_id {
+        return Err(Error::MissingAuthorization);
+    }
+    if new_owner_id == user_id || db.get_user(new_owner_id)?.is_none() {
+        return Err(Error::BadCall);
+    }
+    // Root directories have no parent:
+    if db.get_dir(node.parent_id())?.is_none() {
+        return Err(Error::ForbiddenAction);
+    }
+
+    let offer = OwnershipOffer {
+        fs_node_id,
+        new_owner_id,
+        owner_id: user_id,
+        creation_date: Utc::now(),
+    };
+    db.insert_ownership_offer(&offer)?;
+
+    Ok(offer)
+}
+
+/**
+ * Returns the offers, that were made by or to the User given by `user_id`, the most recent first.
+ * Offers for FsNodes in the trash or FsNodes, which do not belong to the User, who made the
+ * offer, anymore, are skipped.
+ */
+pub fn get_offers(user_id: u64, db: &Database) -> Result<Vec<OwnershipOffer>, Error> {
+    let mut offers = Vec::new();
+    for offer in db.get_ownership_offers_by_user(user_id)? {
+        match get_node(offer.fs_node_id, db) {
+            Ok(node) if node.owner_id() == offer.owner_id => offers.push(offer),
+            Ok(_) | Err(Error::NoSuchTarget) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    offers.sort_by_key(|offer| std::cmp::Reverse(offer.creation_date));
+
+    Ok(offers)
+}
+
+/**
+ * Accepts the offer for the File or Dir given by `fs_node_id`, that was made to the User given by
+ * `user_id`, and returns it.
+ *
+ * The User becomes the owner of the FsNode and of all its descendants, which belong to the User,
+ * who made the offer. The FsNode stays in its directory and keeps its groups and users.
+ * If there is no such offer, or the FsNode is in the trash or does not belong to the User, who
+ * made the offer, anymore, `Error::NoSuchTarget` is returned.
+ */
+pub fn accept_offer(fs_node_id: u64, user_id: u64, db: &Database) -> Result<OwnershipOffer, Error> {
+    let (offer, file_ids) = db.accept_ownership_offer(fs_node_id, user_id)?;
+    // The name of the owner is part of the search index:
+    for file_id in file_ids {
+        search::index_metadata(file_id, db)?;
+    }
+
+    Ok(offer)
+}
+
+/**
+ * Removes the offer for the File or Dir given by `fs_node_id` and returns it.
+ *
+ * The offer may be withdrawn by the User, who made it, and declined by the User, to whom it was
+ * made. Otherwise `Error::MissingAuthorization` is returned. If there is no such offer,
+ * `Error::NoSuchTarget` is returned.
+ */
+pub fn remove_offer(fs_node_id: u64, user_id: u64, db: &Database) -> Result<OwnershipOffer, Error> {
+    let offer = db
+        .get_ownership_offer(fs_node_id)?
+        .ok_or(Error::NoSuchTarget)?;
+    if offer.owner_id != user_id && offer.new_owner_id != user_id {
+        return Err(Error::MissingAuthorization);
+    }
+
+    db.remove_ownership_offer(fs_node_id)
+}
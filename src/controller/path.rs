@@ -0,0 +1,298 @@
+use rocket::{fs::TempFile, http::MediaType};
+
+use crate::{
+    config::Config,
+    controller::{
+        self,
+        blob::{self, BlobReader},
+        search, trash, version,
+    },
+    database::Database,
+    models::{Acl, Dir, DirBuilder, File, FileBuilder, FsNode, Rights, User},
+    Error,
+};
+
+/// A File or Dir, e.g. the target of a path.
+#[derive(Debug)]
+pub enum PathTarget {
+    File(File),
+    Dir(Dir),
+}
+
+impl FsNode for PathTarget {
+    fn id(&self) -> u64 {
+        match self {
+            PathTarget::File(file) => file.id(),
+            PathTarget::Dir(dir) => dir.id(),
+        }
+    }
+    fn name(&self) -> &str {
+        match self {
+            PathTarget::File(file) => file.name(),
+            PathTarget::Dir(dir) => dir.name(),
+        }
+    }
+    fn parent_id(&self) -> u64 {
+        match self {
+            PathTarget::File(file) => file.parent_id(),
+            PathTarget::Dir(dir) => dir.parent_id(),
+        }
+    }
+    fn owner_id(&self) -> u64 {
+        match self {
+            PathTarget::File(file) => file.owner_id(),
+            PathTarget::Dir(dir) => dir.owner_id(),
+        }
+    }
+    fn acl(&self) -> &Acl {
+        match self {
+            PathTarget::File(file) => file.acl(),
+            PathTarget::Dir(dir) => dir.acl(),
+        }
+    }
+    fn inherited_acl(&self) -> &Acl {
+        match self {
+            PathTarget::File(file) => file.inherited_acl(),
+            PathTarget::Dir(dir) => dir.inherited_acl(),
+        }
+    }
+}
+
+/// Returns the File or Dir given by `id` or `Error::NoSuchTarget`, if there is no such FsNode.
+pub fn get_node(id: u64, db: &Database) -> Result<PathTarget, Error> {
+    if let Some(dir) = db.get_dir(id)? {
+        Ok(PathTarget::Dir(dir))
+    } else if let Some(file) = db.get_file(id)? {
+        Ok(PathTarget::File(file))
+    } else {
+        Err(Error::NoSuchTarget)
+    }
+}
+
+/**
+ * Returns the File or Dir given by `path` relative to the root directory of the user given by
+ * `user_id`. The segments of `path` are the names of the directories leading to the target and
+ * the name of the target itself. Empty segments are ignored, so the empty path denotes the root
+ * directory of the user.
+ *
+ * The user needs `Rights::LIST` on every directory along the path and on the target itself,
+ * otherwise `Error::MissingAuthorization` is returned. If a segment does not exist or names a
+ * file, but is followed by further segments, `Error::NoSuchTarget` is returned. The segments `.`
+ * and `..` are not supported (`Error::BadCall`).
+ */
+pub fn resolve_path(path: &[&str], user_id: u64, db: &Database) -> Result<PathTarget, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let segments = segments(path)?;
+    let root = root_dir(&user, db)?;
+    let (name, dir_names) = match segments.split_last() {
+        Some(split) => split,
+        None => return Ok(PathTarget::Dir(root)),
+    };
+
+    let parent = walk_dirs(root, dir_names, &user, db)?;
+    let target = get_child(&parent, name, db)?.ok_or(Error::NoSuchTarget)?;
+    if !target.has_rights(&user, Rights::LIST) {
+        return Err(Error::MissingAuthorization);
+    }
+
+    Ok(target)
+}
+
+/**
+ * Returns the Dir given by `path` (see `resolve_path()`) and creates it and all missing
+ * directories along the path, like `mkdir -p`. New directories are owned by the user given by
+ * `user_id`.
+ *
+ * The user needs `Rights::LIST` on all existing directories along the path and `Rights::CREATE`
+ * on the parents of the new directories, otherwise `Error::MissingAuthorization` is returned. If
+ * a segment names an existing file, `Error::TargetExists` is returned.
+ */
+pub fn make_dirs(path: &[&str], user_id: u64, db: &Database) -> Result<Dir, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let segments = segments(path)?;
+
+    let mut dir = root_dir(&user, db)?;
+    for name in segments {
+        dir = match get_child(&dir, name, db)? {
+            Some(PathTarget::Dir(child)) if child.has_rights(&user, Rights::LIST) => child,
+            Some(PathTarget::Dir(_)) => return Err(Error::MissingAuthorization),
+            Some(PathTarget::File(_)) => return Err(Error::TargetExists),
+            None => create_dir(&dir, name, &user, db)?,
+        };
+    }
+
+    Ok(dir)
+}
+
+/**
+ * Returns the File given by `path` (see `resolve_path()`) and a handle to its current content.
+ *
+ * The user needs `Rights::READ` on the file, otherwise `Error::MissingAuthorization` is returned.
+ * If the path names a directory, `Error::NoSuchFile` is returned. If no content was written to
+ * the file yet, `Error::NoSuchTarget` is returned.
+ */
+pub fn get_content(
+    path: &[&str],
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<(File, BlobReader), Error> {
+    match resolve_path(path, user_id, db)? {
+        PathTarget::File(file) => {
+            let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+            if !file.has_rights(&user, Rights::READ) {
+                return Err(Error::MissingAuthorization);
+            }
+            let content = version::open_content(file.id, db, config)?;
+            Ok((file, content))
+        }
+        PathTarget::Dir(_) => Err(Error::NoSuchFile),
+    }
+}
+
+/**
+ * Sets the content of the File given by `path` (see `resolve_path()`) to `new_content`. If there
+ * is no such file, it is created in the directory given by the preceding segments, which has to
+ * exist already. The changed or new File is returned.
+ *
+ * Replacing the content requires the same permissions as `controller::update_file_content()`,
+ * creating a new file requires `Rights::CREATE` on its parent directory. Otherwise
+ * `Error::MissingAuthorization` is returned. If the path names a directory,
+ * `Error::TargetExists` is returned.
+ */
+pub async fn put_content(
+    path: &[&str],
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+    new_content: TempFile<'_>,
+    media_type: &MediaType,
+) -> Result<File, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let segments = segments(path)?;
+    let root = root_dir(&user, db)?;
+    let (name, dir_names) = segments.split_last().ok_or(Error::TargetExists)?;
+    let parent = walk_dirs(root, dir_names, &user, db)?;
+
+    let file = match get_child(&parent, name, db)? {
+        Some(PathTarget::File(file)) => {
+            return controller::update_file_content(
+                file.id,
+                user_id,
+                db,
+                config,
+                new_content,
+                media_type,
+            )
+            .await;
+        }
+        Some(PathTarget::Dir(_)) => return Err(Error::TargetExists),
+        None => create_file(&parent, name, &user, db)?,
+    };
+
+    match controller::update_file_content(file.id, user_id, db, config, new_content, media_type)
+        .await
+    {
+        Ok(file) => Ok(file),
+        Err(err) => {
+            // Remove the new file without content:
+            trash::move_to_trash(file.id, user_id, db)?;
+            blob::run_blocking(db, config, move |db, config| {
+                trash::remove_from_trash(file.id, user_id, db, config)
+            })
+            .await?;
+            Err(err)
+        }
+    }
+}
+
+/// Returns the non-empty segments of the given path, if they are valid names (see
+/// `controller::check_name()`).
+fn segments<'a>(path: &[&'a str]) -> Result<Vec<&'a str>, Error> {
+    let segments: Vec<&str> = path
+        .iter()
+        .copied()
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    for segment in &segments {
+        controller::check_name(segment)?;
+    }
+    Ok(segments)
+}
+
+fn root_dir(user: &User, db: &Database) -> Result<Dir, Error> {
+    let root = db.get_dir(user.root_dir_id)?.ok_or(Error::NoSuchDir)?;
+    if !root.has_rights(user, Rights::LIST) {
+        return Err(Error::MissingAuthorization);
+    }
+    Ok(root)
+}
+
+/// Follows the directories given by `names` starting at `dir` and returns the last one. The user
+/// needs `Rights::LIST` on every directory.
+fn walk_dirs(mut dir: Dir, names: &[&str], user: &User, db: &Database) -> Result<Dir, Error> {
+    for name in names {
+        dir = match get_child(&dir, name, db)? {
+            Some(PathTarget::Dir(child)) => child,
+            Some(PathTarget::File(_)) | None => return Err(Error::NoSuchTarget),
+        };
+        if !dir.has_rights(user, Rights::LIST) {
+            return Err(Error::MissingAuthorization);
+        }
+    }
+    Ok(dir)
+}
+
+/// Returns the child of the given Dir named `name`.
+fn get_child(dir: &Dir, name: &str, db: &Database) -> Result<Option<PathTarget>, Error> {
+    let child_id = match db.get_child_id(dir.id, name)? {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    if let Some(child) = db.get_dir(child_id)? {
+        return Ok(Some(PathTarget::Dir(child)));
+    }
+    Ok(db.get_file(child_id)?.map(PathTarget::File))
+}
+
+/// Inserts a new Dir named `name` into the given directory, if the given user may create childs
+/// in it.
+fn create_dir(parent: &Dir, name: &str, user: &User, db: &Database) -> Result<Dir, Error> {
+    if !parent.has_rights(user, Rights::CREATE) {
+        return Err(Error::MissingAuthorization);
+    }
+
+    let mut new_dir = DirBuilder::new()
+        .with_id(0)
+        .with_parent_id(parent.id)
+        .with_owner_id(user.id)
+        .with_name(String::from(name))
+        .build();
+    match db.insert_new_dir(&mut new_dir) {
+        Ok(()) => Ok(new_dir),
+        // The name was taken in parallel:
+        Err(Error::TargetExists) => match get_child(parent, name, db)? {
+            Some(PathTarget::Dir(dir)) if dir.has_rights(user, Rights::LIST) => Ok(dir),
+            _ => Err(Error::TargetExists),
+        },
+        Err(err) => Err(err),
+    }
+}
+
+/// Inserts a new File without content named `name` into the given directory, if the given user
+/// may create childs in it.
+fn create_file(parent: &Dir, name: &str, user: &User, db: &Database) -> Result<File, Error> {
+    if !parent.has_rights(user, Rights::CREATE) {
+        return Err(Error::MissingAuthorization);
+    }
+
+    let mut new_file = FileBuilder::new()
+        .with_parent_id(parent.id)
+        .with_owner_id(user.id)
+        .with_name(String::from(name))
+        .build();
+    db.insert_new_file(&mut new_file)?;
+    search::index_metadata(new_file.id, db)?;
+
+    Ok(new_file)
+}
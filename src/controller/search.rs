@@ -0,0 +1,303 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+};
+
+use crate::{
+    config::Config,
+    controller::{listing, version},
+    database::Database,
+    models::{File, FsNode, Rights},
+    Error,
+};
+
+/// Number of results returned by a search, if the query does not give a limit.
+pub const DEFAULT_LIMIT: usize = 50;
+/// Maximal number of results returned by a single search.
+pub const MAX_LIMIT: usize = 1000;
+/// Number of bytes of the content of a file, that are indexed.
+const MAX_INDEXED_CONTENT: u64 = 1024 * 1024;
+/// Maximal number of different terms, that are indexed from the content of a file.
+const MAX_CONTENT_TERMS: usize = 10_000;
+/// Maximal number of characters of an indexed term. Longer words are not indexed.
+const MAX_TERM_LENGTH: usize = 64;
+/// Minimal number of characters of a search term, so that it also matches longer terms starting
+/// with it. Shorter search terms only match equal terms.
+const MIN_PREFIX_LENGTH: usize = 2;
+
+/// Bit of an indexed term, that is part of the name of the file.
+const NAME_FIELD: u8 = 1;
+/// Bit of an indexed term, that is part of the media type of the file.
+const MEDIA_TYPE_FIELD: u8 = 2;
+/// Bit of an indexed term, that is part of the name of the owner of the file.
+const OWNER_FIELD: u8 = 4;
+/// Bit of an indexed term, that is part of the content of the file.
+const CONTENT_FIELD: u8 = 8;
+/// The fields of an indexed term, their names and the score of a match.
+const FIELDS: [(u8, &str, u32); 4] = [
+    (NAME_FIELD, "name", 8),
+    (MEDIA_TYPE_FIELD, "media_type", 2),
+    (OWNER_FIELD, "owner", 2),
+    (CONTENT_FIELD, "content", 1),
+];
+
+/// Describes which files are searched. `media_type` is a pattern like in a `ListingQuery` and
+/// `owner` is the name of a user.
+#[derive(Clone, Debug)]
+pub struct SearchQuery {
+    pub text: String,
+    pub media_type: Option<String>,
+    pub owner: Option<String>,
+    pub limit: usize,
+}
+
+/// A File found by a search.
+#[derive(Debug)]
+pub struct SearchHit {
+    pub file: File,
+    /// The sum of the scores of the best matching field of every search term.
+    pub score: u32,
+    /// The names of the fields, in which the search terms were found.
+    pub fields: Vec<&'static str>,
+}
+
+/**
+ * Returns the files, that contain all terms of `query.text` in their name, media type, owner name
+ * or (for text files) content, the best matches first. A search term matches all indexed terms,
+ * that start with it.
+ *
+ * Only files, whose content the user given by `user_id` may read, and which match the filters of
+ * `query`, are returned. If the query contains no terms, the limit is 0 or larger than
+ * `MAX_LIMIT`, `Error::BadCall` is returned.
+ */
+pub fn search(query: &SearchQuery, user_id: u64, db: &Database) -> Result<Vec<SearchHit>, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    if query.limit == 0 || query.limit > MAX_LIMIT {
+        return Err(Error::BadCall);
+    }
+    let terms: HashSet<String> = terms(&query.text).collect();
+    if terms.is_empty() {
+        return Err(Error::BadCall);
+    }
+    let owner_id = match &query.owner {
+        Some(name) => match db.get_userid_by_name(name)? {
+            Some(id) => Some(id),
+            None => return Ok(Vec::new()),
+        },
+        None => None,
+    };
+
+    // Intersect the matches of all terms:
+    let mut candidates: Option<HashMap<u64, (u32, u8)>> = None;
+    for term in &terms {
+        let prefix = term.chars().count() >= MIN_PREFIX_LENGTH;
+        let matches = db.find_search_term(term, prefix)?;
+        candidates = Some(match candidates {
+            None => matches
+                .into_iter()
+                .map(|(id, fields)| (id, (score(fields), fields)))
+                .collect(),
+            Some(previous) => previous
+                .into_iter()
+                .filter_map(|(id, (previous_score, previous_fields))| {
+                    let fields = *matches.get(&id)?;
+                    Some((
+                        id,
+                        (previous_score + score(fields), previous_fields | fields),
+                    ))
+                })
+                .collect(),
+        });
+    }
+    let mut candidates: Vec<(u64, (u32, u8))> =
+        candidates.unwrap_or_default().into_iter().collect();
+    candidates.sort_by_key(|(id, (score, _))| (std::cmp::Reverse(*score), *id));
+
+    let mut hits = Vec::new();
+    for (file_id, (score, fields)) in candidates {
+        // Files in the trash are not returned:
+        let file = match db.get_file(file_id)? {
+            Some(file) => file,
+            None => continue,
+        };
+        if !file.has_rights(&user, Rights::READ)
+            || (owner_id.is_some() && owner_id != Some(file.owner_id))
+        {
+            continue;
+        }
+        if let Some(pattern) = &query.media_type {
+            if !listing::media_type_matches(pattern, &file.media_type) {
+                continue;
+            }
+        }
+
+        hits.push(SearchHit {
+            file,
+            score,
+            fields: FIELDS
+                .iter()
+                .filter(|(field, _, _)| fields & field != 0)
+                .map(|(_, name, _)| *name)
+                .collect(),
+        });
+        if hits.len() == query.limit {
+            break;
+        }
+    }
+    Ok(hits)
+}
+
+/**
+ * Adds the given File to the search index or updates its entry. The name, media type and owner
+ * name of the file are indexed. If the file has a text-like media type, the first
+ * `MAX_INDEXED_CONTENT` bytes of its current content are indexed too.
+ */
+pub fn index_file(file: &File, db: &Database, config: &Config) -> Result<(), Error> {
+    let mut terms = metadata_terms(file, db)?;
+    if file.content_hash.is_some() && is_text(&file.media_type) {
+        let mut content = Vec::new();
+        version::open_content(file.id, db, config)?
+            .take(MAX_INDEXED_CONTENT)
+            .read_to_end(&mut content)?;
+        let mut content_terms = 0;
+        for term in self::terms(&String::from_utf8_lossy(&content)) {
+            if content_terms == MAX_CONTENT_TERMS && !terms.contains_key(&term) {
+                continue;
+            }
+            let fields = terms.entry(term).or_insert(0);
+            if *fields & CONTENT_FIELD == 0 {
+                *fields |= CONTENT_FIELD;
+                content_terms += 1;
+            }
+        }
+    }
+
+    db.set_search_terms(file.id, &terms)
+}
+
+/**
+ * Updates the name, media type and owner name of the file given by `file_id` in the search index.
+ * The indexed content is kept. Files in the trash are ignored.
+ */
+pub fn index_metadata(file_id: u64, db: &Database) -> Result<(), Error> {
+    let file = match db.get_file(file_id)? {
+        Some(file) => file,
+        None => return Ok(()),
+    };
+
+    let mut terms = metadata_terms(&file, db)?;
+    for (term, fields) in db.get_search_terms(file_id)?.unwrap_or_default() {
+        if fields & CONTENT_FIELD != 0 {
+            *terms.entry(term).or_insert(0) |= CONTENT_FIELD;
+        }
+    }
+
+    db.set_search_terms(file_id, &terms)
+}
+
+/// Removes the files given by `file_ids` from the search index.
+pub fn remove_files(file_ids: &[u64], db: &Database) -> Result<(), Error> {
+    for file_id in file_ids {
+        db.remove_search_terms(*file_id)?;
+    }
+    Ok(())
+}
+
+/**
+ * Adds the File given by `fs_node_id` or all files below the Dir given by `fs_node_id`, that are
+ * not in the search index yet, to the index (e.g. after they were restored from the trash).
+ */
+pub fn index_missing(fs_node_id: u64, db: &Database, config: &Config) -> Result<(), Error> {
+    if let Some(file) = db.get_file(fs_node_id)? {
+        if db.get_search_terms(file.id)?.is_none() {
+            index_file(&file, db, config)?;
+        }
+        return Ok(());
+    }
+
+    let mut dir_ids = vec![fs_node_id];
+    while let Some(dir_id) = dir_ids.pop() {
+        for file in db.get_files_by_parent(dir_id)? {
+            if db.get_search_terms(file.id)?.is_none() {
+                index_file(&file, db, config)?;
+            }
+        }
+        dir_ids.extend(db.get_dirs_by_parent(dir_id)?.iter().map(|dir| dir.id));
+    }
+    Ok(())
+}
+
+/**
+ * Brings the search index up to date with the files in the DB: All files, that are not in the
+ * trash and not indexed yet, are added and the entries of removed files are deleted. Returns the
+ * number of added files.
+ *
+ * This is necessary, when Kasten is started with a DB of an older version without search index.
+ */
+pub fn update_index(db: &Database, config: &Config) -> Result<usize, Error> {
+    let file_ids: HashSet<u64> = db.get_file_ids()?.into_iter().collect();
+    for indexed_id in db.get_indexed_file_ids()? {
+        if !file_ids.contains(&indexed_id) {
+            db.remove_search_terms(indexed_id)?;
+        }
+    }
+
+    let mut added = 0;
+    for file_id in file_ids {
+        if db.get_search_terms(file_id)?.is_some() {
+            continue;
+        }
+        // Files in the trash are indexed, when they are restored:
+        if let Some(file) = db.get_file(file_id)? {
+            index_file(&file, db, config)?;
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// Returns the lowercase words of the given text, that can be indexed.
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && word.chars().count() <= MAX_TERM_LENGTH)
+        .map(str::to_lowercase)
+}
+
+/// Returns the indexed terms of the name, media type and owner name of the given File.
+fn metadata_terms(file: &File, db: &Database) -> Result<HashMap<String, u8>, Error> {
+    let mut res = HashMap::new();
+    for term in terms(&file.name) {
+        *res.entry(term).or_insert(0) |= NAME_FIELD;
+    }
+    for term in terms(&file.media_type) {
+        *res.entry(term).or_insert(0) |= MEDIA_TYPE_FIELD;
+    }
+    if let Some(owner) = db.get_user(file.owner_id)? {
+        for term in terms(&owner.name) {
+            *res.entry(term).or_insert(0) |= OWNER_FIELD;
+        }
+    }
+    Ok(res)
+}
+
+/// Returns the score of a match of a search term in the given fields.
+fn score(fields: u8) -> u32 {
+    FIELDS
+        .iter()
+        .filter(|(field, _, _)| fields & field != 0)
+        .map(|(_, _, score)| *score)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns true, if the content of files with the given media type is indexed.
+fn is_text(media_type: &str) -> bool {
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    essence.starts_with("text/")
+        || essence.ends_with("+xml")
+        || essence.ends_with("+json")
+        || matches!(
+            essence,
+            "application/json" | "application/xml" | "application/javascript"
+        )
+}
@@ -0,0 +1,409 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{
+    offset::{TimeZone, Utc},
+    DateTime, Duration,
+};
+use hmac::{Hmac, Mac};
+use rand::thread_rng;
+use sha2::Sha256;
+
+use crate::{
+    config::Config,
+    controller::{
+        archive::{self, ArchiveEntry},
+        blob::BlobReader,
+        check_rights,
+        listing::{self, Listing, ListingQuery},
+        path::PathTarget,
+        version,
+    },
+    database::Database,
+    models::{Dir, File, FsNode, Rights, Share, User},
+    webapi::ShareMsg,
+    Error,
+};
+
+/// Number of hours, after which an access token for a password protected link expires.
+pub const ACCESS_DURATION_HOURS: i64 = 12;
+
+/**
+ * Creates a new public link for the File or Dir given by `share_infos.fs_node_id`.
+ *
+ * The optional fields `expiration_date` (seconds since the UNIX epoch), `password` and
+ * `download_limit` of `share_infos` restrict the usage of the link.
+ * If a necessary field is missing or a given restriction can never be fulfilled,
+ * `Error::BadCall` is returned. If there is no such FsNode, `Error::NoSuchTarget` is returned.
+ * Sharing a FsNode requires `Rights::SHARE` on it, otherwise `Error::MissingAuthorization` is
+ * returned.
+ * Otherwise the new Share including its token is returned.
+ */
+pub fn create_share(share_infos: ShareMsg, user_id: u64, db: &Database) -> Result<Share, Error> {
+    let fs_node_id = share_infos.fs_node_id.ok_or(Error::BadCall)?.as_int();
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+
+    let expiration_date = match share_infos.expiration_date {
+        Some(timestamp) => {
+            let date: DateTime<Utc> = Utc
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .ok_or(Error::BadCall)?;
+            if date <= Utc::now() {
+                return Err(Error::BadCall);
+            }
+            Some(date)
+        }
+        None => None,
+    };
+    if share_infos.download_limit == Some(0) {
+        return Err(Error::BadCall);
+    }
+    let pwd_hash = share_infos.password.map(|password| {
+        let salt = SaltString::generate(&mut thread_rng());
+        Argon2::default()
+            .hash_password(password.as_bytes(), salt.as_ref())
+            .expect("Could not hash password.")
+            .to_string()
+    });
+
+    let mut share = Share {
+        token: String::new(),
+        fs_node_id,
+        owner_id: user_id,
+        creation_date: Utc::now(),
+        expiration_date,
+        pwd_hash,
+        download_limit: share_infos.download_limit,
+        download_count: 0,
+    };
+    db.insert_new_share(&mut share)?;
+
+    Ok(share)
+}
+
+/**
+ * Returns all public links of the File or Dir given by `fs_node_id`.
+ *
+ * If there is no such FsNode, `Error::NoSuchTarget` is returned. If the user given by `user_id`
+ * does not have `Rights::SHARE` on the FsNode, `Error::MissingAuthorization` is returned.
+ */
+pub fn get_shares(fs_node_id: u64, user_id: u64, db: &Database) -> Result<Vec<Share>, Error> {
+    check_rights(fs_node_id, Rights::SHARE, user_id, db)?;
+
+    db.get_shares_by_node(fs_node_id)
+}
+
+/**
+ * Removes the public link given by `token`.
+ *
+ * The link may be removed by its creator and by the users with `Rights::SHARE` on the shared
+ * FsNode. Otherwise `Error::MissingAuthorization` is returned. If there is no such link,
+ * `Error::NoSuchTarget` is returned.
+ */
+pub fn remove_share(token: &str, user_id: u64, db: &Database) -> Result<Share, Error> {
+    let share = db.get_share(token)?.ok_or(Error::NoSuchTarget)?;
+    if share.owner_id != user_id
+        && check_rights(share.fs_node_id, Rights::SHARE, user_id, db).is_err()
+    {
+        return Err(Error::MissingAuthorization);
+    }
+
+    db.remove_share(token)
+}
+
+/**
+ * Checks the password of the public link given by `token` and returns the link together with an
+ * access token, that opens the link without the password (see `open_share()`). The access token
+ * expires after `ACCESS_DURATION_HOURS` and when the password of the link changes.
+ *
+ * If there is no such link or the shared FsNode is in the trash, `Error::NoSuchTarget` is returned.
+ * If the link has expired, `Error::ForbiddenAction` is returned. If the password is wrong,
+ * `Error::MissingAuthorization` is returned.
+ */
+pub fn unlock_share(
+    token: &str,
+    password: &str,
+    db: &Database,
+    config: &Config,
+) -> Result<(Share, String), Error> {
+    let share = get_open_share(token, db)?;
+
+    if let Some(pwd_hash) = &share.pwd_hash {
+        let parsed_hash = PasswordHash::new(pwd_hash).map_err(|_| Error::EncodingError)?;
+        if Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_err()
+        {
+            return Err(Error::MissingAuthorization);
+        }
+    }
+    let expiration = (Utc::now() + Duration::hours(ACCESS_DURATION_HOURS)).timestamp();
+    let access = format!(
+        "{:x}.{}",
+        expiration,
+        hex(&access_mac(&share, expiration, config)
+            .finalize()
+            .into_bytes())
+    );
+
+    Ok((share, access))
+}
+
+/**
+ * Returns the public link given by `token`, if it may be used with the given access token (see
+ * `unlock_share()`). Links without a password may be used without an access token.
+ *
+ * If there is no such link or the shared FsNode is in the trash, `Error::NoSuchTarget` is returned.
+ * If the link has expired, `Error::ForbiddenAction` is returned. If the link is protected by a
+ * password and `access` is missing, invalid or expired, `Error::MissingAuthorization` is returned.
+ */
+pub fn open_share(
+    token: &str,
+    access: Option<&str>,
+    db: &Database,
+    config: &Config,
+) -> Result<Share, Error> {
+    let share = get_open_share(token, db)?;
+
+    if share.pwd_hash.is_some() && !is_valid_access(&share, access, config) {
+        return Err(Error::MissingAuthorization);
+    }
+
+    Ok(share)
+}
+
+/**
+ * Returns the File given by `file_id`, if it is accessible by the given link. That is the case if
+ * it is the shared File or a descendant of the shared Dir, that the creator of the link may read
+ * (`Rights::READ`) through directories they may list (`Rights::LIST`). If `file_id` is None, the
+ * shared File itself is returned.
+ *
+ * If there is no such file or it is not accessible by the link, `Error::NoSuchFile` is returned.
+ */
+pub fn get_shared_file(share: &Share, file_id: Option<u64>, db: &Database) -> Result<File, Error> {
+    let creator = get_creator(share, db)?;
+    let file = db
+        .get_file(file_id.unwrap_or(share.fs_node_id))?
+        .ok_or(Error::NoSuchFile)?;
+    let is_shared =
+        file.id == share.fs_node_id || is_shared_descendant(share, file.parent_id, &creator, db)?;
+    if is_shared && file.has_rights(&creator, Rights::READ) {
+        Ok(file)
+    } else {
+        Err(Error::NoSuchFile)
+    }
+}
+
+/**
+ * Returns the Dir given by `dir_id`, if it is accessible by the given link. That is the case if
+ * it is the shared Dir or one of its descendants and the creator of the link may list it and all
+ * directories between it and the shared Dir (`Rights::LIST`). If `dir_id` is None, the shared Dir
+ * itself is returned.
+ *
+ * If there is no such directory or it is not accessible by the link, `Error::NoSuchDir` is
+ * returned.
+ */
+pub fn get_shared_dir(share: &Share, dir_id: Option<u64>, db: &Database) -> Result<Dir, Error> {
+    let creator = get_creator(share, db)?;
+    let dir = db
+        .get_dir(dir_id.unwrap_or(share.fs_node_id))?
+        .ok_or(Error::NoSuchDir)?;
+    if is_shared_descendant(share, dir.id, &creator, db)? {
+        Ok(dir)
+    } else {
+        Err(Error::NoSuchDir)
+    }
+}
+
+/**
+ * Returns the childs of the Dir given by `dir` (see `get_shared_dir()`), that match `query` (see
+ * `listing::list_childs()`). Only files, that the creator of the link may read, and directories,
+ * that they may list, are returned, so a page may contain less than `query.limit` entries.
+ */
+pub fn list_shared_childs(
+    share: &Share,
+    dir: &Dir,
+    query: &ListingQuery,
+    db: &Database,
+) -> Result<Listing, Error> {
+    let creator = get_creator(share, db)?;
+    let mut listing = listing::list_childs(dir, query, db)?;
+    listing.entries.retain(|node| match node {
+        PathTarget::File(file) => file.has_rights(&creator, Rights::READ),
+        PathTarget::Dir(dir) => dir.has_rights(&creator, Rights::LIST),
+    });
+    Ok(listing)
+}
+
+/**
+ * Returns the files and directories inside of the Dir given by `dir` (see `get_shared_dir()`),
+ * which are accessible by the given link: Files, that the creator of the link may read, and
+ * directories, that they may list.
+ */
+pub fn get_shared_childs(
+    share: &Share,
+    dir: &Dir,
+    db: &Database,
+) -> Result<(Vec<File>, Vec<Dir>), Error> {
+    let creator = get_creator(share, db)?;
+    let mut files = db.get_files_by_parent(dir.id)?;
+    files.retain(|file| file.has_rights(&creator, Rights::READ));
+    let mut dirs = db.get_dirs_by_parent(dir.id)?;
+    dirs.retain(|dir| dir.has_rights(&creator, Rights::LIST));
+    Ok((files, dirs))
+}
+
+/**
+ * Returns the File given by `file_id` (see `get_shared_file()`) and a handle to its content.
+ *
+ * The download is not counted, as a request may only revalidate the content or fetch a part of
+ * it. The caller has to count the actual downloads with `count_download()`. If the download limit
+ * of the link is reached, `Error::ForbiddenAction` is returned.
+ */
+pub fn get_shared_file_content(
+    share: &Share,
+    file_id: Option<u64>,
+    db: &Database,
+    config: &Config,
+) -> Result<(File, BlobReader), Error> {
+    if share.is_exhausted() {
+        return Err(Error::ForbiddenAction);
+    }
+    let file = get_shared_file(share, file_id, db)?;
+
+    let content = version::open_content(file.id, db, config)?;
+
+    Ok((file, content))
+}
+
+/**
+ * Counts a download of the public link given by `token`.
+ *
+ * If there is no such link, `Error::NoSuchTarget` is returned. If the download limit of the link
+ * is reached, `Error::ForbiddenAction` is returned.
+ */
+pub fn count_download(token: &str, db: &Database) -> Result<(), Error> {
+    db.add_share_download(token)?;
+    Ok(())
+}
+
+/**
+ * Collects the entries of an archive containing the Dir given by `dir_id` (see
+ * `get_shared_dir()`) and all its descendants, that are accessible by the link (see
+ * `archive::collect_entries()` with the creator of the link as the user).
+ *
+ * The download is counted. If the download limit of the link is reached,
+ * `Error::ForbiddenAction` is returned.
+ */
+pub fn collect_shared_entries(
+    share: &Share,
+    dir_id: Option<u64>,
+    db: &Database,
+    config: &Config,
+) -> Result<(Dir, Vec<ArchiveEntry>), Error> {
+    let dir = get_shared_dir(share, dir_id, db)?;
+    let entries = archive::collect_entries(dir.id, share.owner_id, db, config)?;
+    db.add_share_download(&share.token)?;
+
+    Ok((dir, entries))
+}
+
+/// Returns the public link given by `token`, if it has not expired, the shared FsNode exists and
+/// the creator of the link may still share it (see `open_share()`).
+fn get_open_share(token: &str, db: &Database) -> Result<Share, Error> {
+    let share = db.get_share(token)?.ok_or(Error::NoSuchTarget)?;
+
+    if share.is_expired() {
+        return Err(Error::ForbiddenAction);
+    }
+    get_creator(&share, db)?;
+
+    Ok(share)
+}
+
+/**
+ * Returns the User, who created the given link. A link is only usable, as long as its creator
+ * exists and has `Rights::SHARE` on the shared FsNode; otherwise `Error::NoSuchTarget` is
+ * returned.
+ */
+fn get_creator(share: &Share, db: &Database) -> Result<User, Error> {
+    let creator = db.get_user(share.owner_id)?.ok_or(Error::NoSuchTarget)?;
+    let may_share = if let Some(file) = db.get_file(share.fs_node_id)? {
+        file.has_rights(&creator, Rights::SHARE)
+    } else if let Some(dir) = db.get_dir(share.fs_node_id)? {
+        dir.has_rights(&creator, Rights::SHARE)
+    } else {
+        false
+    };
+    if may_share {
+        Ok(creator)
+    } else {
+        Err(Error::NoSuchTarget)
+    }
+}
+
+/// Returns true, if `access` is an access token for the given link, that has not expired yet.
+fn is_valid_access(share: &Share, access: Option<&str>, config: &Config) -> bool {
+    let (expiration, mac) = match access.and_then(|access| access.split_once('.')) {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let expiration = match i64::from_str_radix(expiration, 16) {
+        Ok(expiration) if expiration > Utc::now().timestamp() => expiration,
+        _ => return false,
+    };
+    let mac: Option<Vec<u8>> = (0..mac.len())
+        .step_by(2)
+        .map(|i| {
+            mac.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect();
+    mac.is_some_and(|mac| {
+        access_mac(share, expiration, config)
+            .verify_slice(&mac)
+            .is_ok()
+    })
+}
+
+/// Returns the MAC of an access token for the given link, that expires at `expiration` (seconds
+/// since the UNIX epoch). The MAC covers the password hash, so that changing the password revokes
+/// all access tokens.
+fn access_mac(share: &Share, expiration: i64, config: &Config) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&config.share_access_key)
+        .expect("HMAC accepts keys of any size.");
+    mac.update(share.token.as_bytes());
+    mac.update(&expiration.to_be_bytes());
+    mac.update(share.pwd_hash.as_deref().unwrap_or("").as_bytes());
+    mac
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/**
+ * Returns true, if the Dir given by `dir_id` is the shared Dir or one of its descendants and
+ * `creator` may list every Dir from it up to the shared Dir.
+ */
+fn is_shared_descendant(
+    share: &Share,
+    dir_id: u64,
+    creator: &User,
+    db: &Database,
+) -> Result<bool, Error> {
+    let mut current_id = dir_id;
+    while current_id != 0 {
+        let dir = db.get_dir(current_id)?.ok_or(Error::NoSuchDir)?;
+        if !dir.has_rights(creator, Rights::LIST) {
+            return Ok(false);
+        }
+        if current_id == share.fs_node_id {
+            return Ok(true);
+        }
+        current_id = dir.parent_id;
+    }
+    Ok(false)
+}
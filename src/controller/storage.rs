@@ -0,0 +1,315 @@
+use chrono::{offset::Utc, DateTime};
+use rocket::tokio::{self, time};
+
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    blob_store::{BlobStore, LocalStore},
+    config::Config,
+    controller::{blob, upload},
+    database::Database,
+    models::{ContentHash, FileVersion, FsNode},
+    Error,
+};
+
+/// Interval in which the storage is swept.
+const SWEEP_INTERVAL_SECS: u64 = 24 * 60 * 60;
+/// Minimal age of a blob, before it may be removed as orphaned. Younger blobs may belong to a
+/// file, that is still being written.
+const ORPHAN_GRACE_PERIOD_SECS: u64 = 60 * 60;
+
+/// Result of reconciling the `files` tree of the DB with the content in `Config::blob_store`.
+#[derive(Debug, Default)]
+pub struct SweepReport {
+    /// Names of the blobs in the storage, that are not referenced by any version in the DB.
+    pub orphaned_blobs: Vec<String>,
+    /// Names of the leftover temporary files of interrupted writes in `Config::upload_location`.
+    pub stale_temp_files: Vec<String>,
+    /// IDs of the files in the DB, whose parent directory does not exist anymore.
+    pub dangling_file_ids: Vec<u64>,
+}
+
+/**
+ * Reconciles the files and versions in the DB with the blobs in `config.blob_store`.
+ *
+ * Blobs without references are reported as orphaned, files whose parent directory does not exist
+ * are reported as dangling. Blobs and temporary files, that were modified recently, are never
+ * reported.
+ */
+pub fn sweep(db: &Database, config: &Config) -> Result<SweepReport, Error> {
+    let mut report = SweepReport::default();
+
+    for blob in config.blob_store.list()? {
+        let is_orphaned = match ContentHash::from_hex(&blob.name) {
+            Some(hash) => db.get_blob_refs(&hash)? == 0,
+            // Leftovers of an interrupted write by an older version of Kasten:
+            None if is_temp_file(&blob.name) => true,
+            // Not created by Kasten:
+            None => continue,
+        };
+        if is_orphaned && !is_recent(blob.modified) {
+            report.orphaned_blobs.push(blob.name);
+        }
+    }
+
+    if config.upload_location.is_dir() {
+        for entry in std::fs::read_dir(&config.upload_location)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Blobs, that are still being written, or leftovers of an interrupted write:
+            if is_temp_file(&name) && !is_recent(entry.metadata()?.modified()?) {
+                report.stale_temp_files.push(name);
+            }
+        }
+    }
+
+    report.dangling_file_ids = db.get_dangling_file_ids()?;
+
+    Ok(report)
+}
+
+/**
+ * Removes the orphaned blobs and stale temporary files of the given report from the storage and
+ * returns the names of the removed blobs. Blobs, which were referenced again since the report was
+ * created, are kept (see `blob::remove_unreferenced()`).
+ */
+pub fn remove_orphaned_blobs(
+    report: &SweepReport,
+    db: &Database,
+    config: &Config,
+) -> Result<Vec<String>, Error> {
+    let mut removed = Vec::new();
+    for name in &report.orphaned_blobs {
+        let is_removed = match ContentHash::from_hex(name) {
+            Some(hash) => blob::remove_unreferenced(&hash, db, config)?,
+            // Leftovers of an interrupted write, which can not be referenced:
+            None => {
+                config.blob_store.delete(name)?;
+                true
+            }
+        };
+        if is_removed {
+            removed.push(name.clone());
+        }
+    }
+    for name in &report.stale_temp_files {
+        if let Err(e) = std::fs::remove_file(config.upload_location.join(name)) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(Error::from(e));
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/**
+ * Rehashes the content of all referenced blobs in the storage and returns the hashes of the
+ * blobs, whose content does not match their hash anymore.
+ */
+pub fn verify(db: &Database, config: &Config) -> Result<Vec<ContentHash>, Error> {
+    let mut corrupted = Vec::new();
+    for blob in config.blob_store.list()? {
+        if let Some(hash) = ContentHash::from_hex(&blob.name) {
+            if db.get_blob_refs(&hash)? > 0 && !blob::verify(&hash, db, config)? {
+                corrupted.push(hash);
+            }
+        }
+    }
+    Ok(corrupted)
+}
+
+/**
+ * Records the encryption of the blobs in the storage, which were written by a version of Kasten,
+ * that detected encrypted blobs by their header (see `blob::has_encryption_header()`), and
+ * returns the number of flagged encrypted blobs. This is only done once, later blobs are flagged,
+ * when they are stored.
+ */
+pub fn flag_encrypted_blobs(db: &Database, config: &Config) -> Result<usize, Error> {
+    if db.are_encryption_flags_complete()? {
+        return Ok(0);
+    }
+    let mut flagged = 0;
+    for blob in config.blob_store.list()? {
+        if let Some(hash) = ContentHash::from_hex(&blob.name) {
+            if blob::has_encryption_header(&config.blob_store, &blob.name)? {
+                db.set_blob_encrypted(&hash, true)?;
+                flagged += 1;
+            }
+        }
+    }
+    db.set_encryption_flags_complete()?;
+    Ok(flagged)
+}
+
+/**
+ * Moves the content of files, that was saved by a version of Kasten without content-addressed
+ * storage (one blob per file named by the ID of the file in `config.file_location`), to the blob
+ * storage and adds a FileVersion for it. The IDs of the imported files are returned.
+ *
+ * The previous versions of these files (named by the ID of the file and the version number) are
+ * moved as well (see `import_legacy_versions()`). Blobs of files, which have versions already or
+ * which are not in the DB (or in the trash), are left untouched.
+ */
+pub fn import_legacy_blobs(db: &Database, config: &Config) -> Result<Vec<u64>, Error> {
+    if !config.file_location.is_dir() {
+        return Ok(Vec::new());
+    }
+    let legacy_store: Arc<dyn BlobStore> = Arc::new(LocalStore::new(config.file_location.clone()));
+
+    let mut imported = import_legacy_versions(&legacy_store, db, config)?;
+    for blob in legacy_store.list()? {
+        let file = match u64::from_str_radix(&blob.name, 16) {
+            Ok(file_id) => match db.get_file(file_id)? {
+                Some(f) => f,
+                None => continue,
+            },
+            Err(_) => continue,
+        };
+        if db.get_latest_version(file.id)?.is_some() {
+            continue;
+        }
+
+        let (content_hash, size) = blob::import(&legacy_store, &blob.name, db, config)?;
+        let mut version = FileVersion {
+            file_id: file.id,
+            version: 0,
+            creation_date: DateTime::<Utc>::from(blob.modified),
+            uploader_id: file.owner_id(),
+            size,
+            content_hash,
+            media_type: file.media_type.clone(),
+        };
+        db.insert_new_version(&mut version, false)?;
+        legacy_store.delete(&blob.name)?;
+        imported.push(file.id);
+    }
+    Ok(imported)
+}
+
+/**
+ * Moves the content of the versions, that were recorded by a version of Kasten without
+ * content-addressed storage (see `Database::get_legacy_version_ids()`), from `legacy_store` to
+ * the blob storage. The IDs of the files with imported versions are returned.
+ *
+ * The current content of a file is named by the ID of the file, previous versions by the ID of
+ * the file and the version number. Versions without content or of files, that do not exist
+ * anymore, are dropped.
+ */
+fn import_legacy_versions(
+    legacy_store: &Arc<dyn BlobStore>,
+    db: &Database,
+    config: &Config,
+) -> Result<Vec<u64>, Error> {
+    let legacy_ids = db.get_legacy_version_ids()?;
+    let file_ids: HashSet<u64> = db.get_file_ids()?.into_iter().collect();
+
+    let mut imported = Vec::new();
+    for (i, (file_id, version)) in legacy_ids.iter().copied().enumerate() {
+        // The last legacy version is the current content, unless a newer version was added since:
+        let is_current = legacy_ids
+            .get(i + 1)
+            .is_none_or(|(next_file_id, _)| *next_file_id != file_id)
+            && db
+                .get_latest_version(file_id)?
+                .is_none_or(|v| v.version < version);
+        let name = if is_current {
+            format!("{:x}", file_id)
+        } else {
+            format!("{:x}-{:x}", file_id, version)
+        };
+
+        let has_content = legacy_store.exists(&name)?;
+        if has_content && file_ids.contains(&file_id) && db.get_version(file_id, version)?.is_none()
+        {
+            let (content_hash, _) = blob::import(legacy_store, &name, db, config)?;
+            if let Err(err) = db.import_legacy_version(file_id, version, content_hash) {
+                blob::release(&content_hash, db, config)?;
+                return Err(err);
+            }
+            if imported.last() != Some(&file_id) {
+                imported.push(file_id);
+            }
+        } else {
+            db.remove_legacy_version(file_id, version)?;
+        }
+        if has_content {
+            legacy_store.delete(&name)?;
+        }
+    }
+    Ok(imported)
+}
+
+/// Returns true, if the given name is the name of a temporary file written by `blob::store()`.
+fn is_temp_file(name: &str) -> bool {
+    name.ends_with(".tmp") || name.ends_with(".plain")
+}
+
+/// Returns true, if the given modification time lies within the grace period.
+fn is_recent(modified: SystemTime) -> bool {
+    modified
+        .elapsed()
+        .map(|age| age < Duration::from_secs(ORPHAN_GRACE_PERIOD_SECS))
+        .unwrap_or(true)
+}
+
+/**
+ * Calls `sweep()` periodically and removes orphaned blobs and expired uploads. `verify()`, which
+ * reads the whole storage, is only called, if `config.verify_blobs` is set. Dangling files and
+ * corrupted blobs are only reported, since they may still be recovered manually (see
+ * `bonbon filesweep`). This function never returns.
+ */
+pub async fn sweep_periodically(db: Database, config: Config) {
+    let mut interval = time::interval(time::Duration::from_secs(SWEEP_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let (db, config) = (db.clone(), config.clone());
+        let res = tokio::task::spawn_blocking(move || {
+            let report = sweep(&db, &config)?;
+            let removed_blobs = remove_orphaned_blobs(&report, &db, &config)?;
+            let expired_uploads = upload::remove_expired_uploads(&db, &config)?;
+            let corrupted_blobs = if config.verify_blobs {
+                verify(&db, &config)?
+            } else {
+                Vec::new()
+            };
+            Ok::<_, Error>((report, removed_blobs, expired_uploads, corrupted_blobs))
+        })
+        .await;
+        match res {
+            Ok(Ok((report, removed_blobs, expired_uploads, corrupted_blobs))) => {
+                for name in &removed_blobs {
+                    // TODO: Logging
+                    println!("Removed orphaned blob from storage: {}", name);
+                }
+                for name in &report.stale_temp_files {
+                    // TODO: Logging
+                    println!("Removed stale temporary file: {}", name);
+                }
+                for upload_id in &expired_uploads {
+                    // TODO: Logging
+                    println!("Removed expired upload: {:x}", upload_id);
+                }
+                for file_id in &report.dangling_file_ids {
+                    // TODO: Logging
+                    println!("Found dangling file in DB: {:x}", file_id);
+                }
+                for hash in &corrupted_blobs {
+                    // TODO: Logging
+                    println!("Found corrupted blob in storage: {}", hash);
+                }
+            }
+            Ok(Err(err)) => {
+                // TODO: Logging
+                println!("Error while sweeping the storage: {}", err);
+            }
+            Err(err) => {
+                // TODO: Logging
+                println!("Error while sweeping the storage: {}", err);
+            }
+        }
+    }
+}
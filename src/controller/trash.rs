@@ -0,0 +1,181 @@
+use chrono::{offset::Utc, Duration};
+use rocket::tokio::{self, time};
+
+use crate::{
+    config::Config,
+    controller::{check_rights, moving, search, version},
+    database::Database,
+    models::{FsNode, Rights, TrashEntry},
+    Error,
+};
+
+/// Interval in which expired trash entries are purged.
+const PURGE_INTERVAL_SECS: u64 = 60 * 60;
+/// Number of times a restore is retried, if the name was taken in parallel.
+const MAX_RESTORE_ATTEMPTS: usize = 8;
+
+/**
+ * Moves the File or Dir given by `fs_node_id` into the trash of its owner.
+ *
+ * If there is no such FsNode, `Error::NoSuchTarget` is returned. The user needs `Rights::DELETE`
+ * on the FsNode, otherwise `Error::MissingAuthorization` is returned. The FsNode is moved into the
+ * trash of its owner, even if another user deletes it. The root directory of a user can not be
+ * deleted (`Error::ForbiddenAction`).
+ */
+pub fn move_to_trash(fs_node_id: u64, user_id: u64, db: &Database) -> Result<TrashEntry, Error> {
+    check_rights(fs_node_id, Rights::DELETE, user_id, db)?;
+
+    db.move_to_trash(fs_node_id)
+}
+
+/// Returns the entries of the trash of the user given by `user_id`, the most recently deleted
+/// first.
+pub fn get_trash(user_id: u64, db: &Database) -> Result<Vec<TrashEntry>, Error> {
+    let mut entries = db.get_trash(user_id)?;
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.deletion_date));
+    Ok(entries)
+}
+
+/**
+ * Restores the File or Dir given by `fs_node_id` from the trash of the user given by `user_id`.
+ *
+ * The FsNode is moved back into the directory from which it was deleted. If this directory does
+ * not exist anymore (or is in the trash itself) or the user may not create childs in it
+ * (`Rights::CREATE`), it is moved into the root directory of the user.
+ * If its name is used by another child of the directory in the meantime, a suffix like ` (1)` is
+ * added to the name. If there is no such entry in the trash, `Error::NoSuchTarget` is returned.
+ * Restored files, that are missing in the search index, are added to it.
+ */
+pub fn restore(
+    fs_node_id: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<TrashEntry, Error> {
+    let entry = db
+        .get_trash(user_id)?
+        .into_iter()
+        .find(|entry| entry.fs_node_id == fs_node_id)
+        .ok_or(Error::NoSuchTarget)?;
+    let user = db.get_user(user_id)?.ok_or(Error::NoSuchUser)?;
+    let root_dir_id = user.root_dir_id;
+
+    let may_create = db
+        .get_dir(entry.parent_id)?
+        .is_some_and(|dir| dir.has_rights(&user, Rights::CREATE));
+    let mut parent_id = if may_create {
+        entry.parent_id
+    } else {
+        root_dir_id
+    };
+    let mut name = entry.name.clone();
+    for _ in 0..MAX_RESTORE_ATTEMPTS {
+        match db.restore_from_trash(user_id, fs_node_id, parent_id, &name) {
+            Err(Error::NoSuchDir) if parent_id != root_dir_id => {
+                parent_id = root_dir_id;
+            }
+            Err(Error::TargetExists) => {
+                name = moving::free_name(0, parent_id, &entry.name, !entry.is_dir, db)?;
+            }
+            Ok(entry) => {
+                // Files, that were in the trash before the search index was built:
+                search::index_missing(fs_node_id, db, config)?;
+                return Ok(entry);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Err(Error::TargetExists)
+}
+
+/**
+ * Removes the File or Dir given by `fs_node_id` and all its descendants from the trash of the
+ * user given by `user_id` permanently. The content of all removed files is deleted.
+ *
+ * If there is no such entry in the trash, `Error::NoSuchTarget` is returned. If the content of a
+ * file can not be deleted, the error is returned after the content of the other files was
+ * deleted. The remaining content is deleted by the next call of `purge_expired()`.
+ */
+pub fn remove_from_trash(
+    fs_node_id: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<TrashEntry, Error> {
+    let (entry, file_ids) = db.purge_from_trash(user_id, fs_node_id)?;
+    search::remove_files(&file_ids, db)?;
+    remove_contents(&file_ids, db, config)?;
+    Ok(entry)
+}
+
+/// Removes all entries from the trash of the user given by `user_id` permanently and returns
+/// them.
+pub fn empty_trash(user_id: u64, db: &Database, config: &Config) -> Result<Vec<TrashEntry>, Error> {
+    let mut res = Vec::new();
+    for entry in db.get_trash(user_id)? {
+        match remove_from_trash(entry.fs_node_id, user_id, db, config) {
+            Ok(entry) => res.push(entry),
+            // The entry was removed in parallel:
+            Err(Error::NoSuchTarget) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(res)
+}
+
+/**
+ * Removes all entries from the trashes of all users permanently, that were deleted before the
+ * retention period given by `config.trash_retention_days`. The remaining content of files, which
+ * were removed before, but whose content could not be deleted, is deleted as well.
+ */
+pub fn purge_expired(db: &Database, config: &Config) -> Result<Vec<TrashEntry>, Error> {
+    remove_contents(&db.get_orphaned_version_file_ids()?, db, config)?;
+
+    let deadline = Utc::now() - Duration::days(config.trash_retention_days);
+    let mut res = Vec::new();
+    for entry in db.get_trash_deleted_before(deadline)? {
+        match remove_from_trash(entry.fs_node_id, entry.owner_id, db, config) {
+            Ok(entry) => res.push(entry),
+            // The entry was restored or removed in parallel:
+            Err(Error::NoSuchTarget) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(res)
+}
+
+/// Calls `purge_expired()` periodically. This function never returns.
+pub async fn purge_periodically(db: Database, config: Config) {
+    let mut interval = time::interval(time::Duration::from_secs(PURGE_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let (db, config) = (db.clone(), config.clone());
+        match tokio::task::spawn_blocking(move || purge_expired(&db, &config)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                // TODO: Logging
+                println!("Error while purging the trash: {}", err);
+            }
+            Err(err) => {
+                // TODO: Logging
+                println!("Error while purging the trash: {}", err);
+            }
+        }
+    }
+}
+
+/// Removes the content and all versions of the files given by `file_ids`. If this fails for a
+/// file, the other files are processed anyway and the first error is returned.
+fn remove_contents(file_ids: &[u64], db: &Database, config: &Config) -> Result<(), Error> {
+    let mut res = Ok(());
+    for file_id in file_ids {
+        if let Err(err) = version::remove_all_versions(*file_id, db, config) {
+            // TODO: Logging
+            println!("Could not remove content of file {:x}: {}", file_id, err);
+            if res.is_ok() {
+                res = Err(err);
+            }
+        }
+    }
+    res
+}
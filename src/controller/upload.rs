@@ -0,0 +1,292 @@
+use chrono::{offset::Utc, Duration};
+use rocket::{
+    data::{ByteUnit, Data},
+    tokio::{fs::OpenOptions, io::AsyncSeekExt},
+};
+
+use std::{io::SeekFrom, path::PathBuf};
+
+use crate::{
+    config::Config,
+    controller::{self, blob, version},
+    database::Database,
+    models::{File, FileBuilder, FsNode, Rights, Upload},
+    webapi::UploadMsg,
+    Error,
+};
+
+/// Time after which unfinished uploads are discarded.
+const UPLOAD_LIFETIME_HOURS: i64 = 24 * 7;
+
+/**
+ * Starts a new resumable upload.
+ *
+ * If `upload_infos.file_id` is given, the upload will replace the content of this file. Otherwise
+ * `upload_infos.parent_id` and `upload_infos.name` are used to create a new file, when the upload
+ * is finished. `upload_infos.length` has to contain the total number of bytes of the upload.
+ *
+ * If a necessary field is missing, the length exceeds `config.max_upload_size` or the name is
+ * invalid (see `controller::check_name()`), `Error::BadCall` is returned. If the user given by
+ * `user_id` already has `config.max_uploads_per_user` unfinished uploads,
+ * `Error::ForbiddenAction` is returned. If the user has neither `Rights::WRITE` on the given file
+ * nor `Rights::CREATE` on the given parent directory, `Error::MissingAuthorization` is returned.
+ * If the parent directory already contains a child with the given name, `Error::TargetExists` is
+ * returned. Otherwise the new Upload is returned.
+ */
+pub fn create_upload(
+    upload_infos: UploadMsg,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<Upload, Error> {
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+    let length = upload_infos.length.ok_or(Error::BadCall)?;
+    if length > config.max_upload_size {
+        return Err(Error::BadCall);
+    }
+    let open_uploads = db
+        .get_uploads()?
+        .into_iter()
+        .filter(|upload| upload.user_id == user_id && !is_expired(upload))
+        .count();
+    if open_uploads >= config.max_uploads_per_user {
+        return Err(Error::ForbiddenAction);
+    }
+
+    let mut upload = if let Some(file_id) = upload_infos.file_id {
+        let file = db.get_file(file_id.as_int())?.ok_or(Error::NoSuchFile)?;
+        if !file.has_rights(&user, Rights::WRITE) {
+            return Err(Error::MissingAuthorization);
+        }
+        Upload {
+            id: 0,
+            user_id,
+            parent_id: 0,
+            file_id: file.id,
+            name: file.name,
+            media_type: upload_infos.media_type.unwrap_or(file.media_type),
+            length,
+            creation_date: Utc::now(),
+            received: Vec::new(),
+        }
+    } else {
+        let parent = db
+            .get_dir(upload_infos.parent_id.ok_or(Error::BadCall)?.as_int())?
+            .ok_or(Error::NoSuchDir)?;
+        if !parent.has_rights(&user, Rights::CREATE) {
+            return Err(Error::MissingAuthorization);
+        }
+        let default_file = FileBuilder::new().build();
+        let name = upload_infos.name.unwrap_or(default_file.name);
+        controller::check_name(&name)?;
+        if db.get_child_id(parent.id, &name)?.is_some() {
+            return Err(Error::TargetExists);
+        }
+        Upload {
+            id: 0,
+            user_id,
+            parent_id: parent.id,
+            file_id: 0,
+            name,
+            media_type: upload_infos.media_type.unwrap_or(default_file.media_type),
+            length,
+            creation_date: Utc::now(),
+            received: Vec::new(),
+        }
+    };
+
+    db.insert_new_upload(&mut upload)?;
+
+    // Reserve the space for the content:
+    std::fs::create_dir_all(&config.upload_location)?;
+    let partial = std::fs::File::create(partial_path(config, upload.id))?;
+    partial.set_len(length)?;
+
+    Ok(upload)
+}
+
+/**
+ * Returns the Upload given by `upload_id`, if it was started by the user given by `user_id`.
+ *
+ * If there is no such upload or it has expired, `Error::NoSuchTarget` is returned. If the upload
+ * was started by a different user, `Error::MissingAuthorization` is returned.
+ */
+pub fn get_upload(
+    upload_id: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<Upload, Error> {
+    let upload = db.get_upload(upload_id)?.ok_or(Error::NoSuchTarget)?;
+
+    if is_expired(&upload) {
+        remove_upload(upload.id, db, config)?;
+        return Err(Error::NoSuchTarget);
+    }
+    if upload.user_id != user_id {
+        return Err(Error::MissingAuthorization);
+    }
+
+    Ok(upload)
+}
+
+/**
+ * Writes the given chunk of data at position `offset` into the upload given by `upload_id` and
+ * returns the updated Upload.
+ *
+ * If the chunk would exceed the length of the upload, the fitting part is written and
+ * `Error::BadCall` is returned. See `get_upload()` for other errors.
+ */
+pub async fn write_chunk(
+    upload_id: u64,
+    user_id: u64,
+    offset: u64,
+    chunk: Data<'_>,
+    db: &Database,
+    config: &Config,
+) -> Result<Upload, Error> {
+    let upload = get_upload(upload_id, user_id, db, config)?;
+    if offset > upload.length {
+        return Err(Error::BadCall);
+    }
+
+    let mut partial = OpenOptions::new()
+        .write(true)
+        .open(partial_path(config, upload.id))
+        .await?;
+    partial.seek(SeekFrom::Start(offset)).await?;
+    let written = chunk
+        .open(ByteUnit::from(upload.length - offset))
+        .stream_to(&mut partial)
+        .await?;
+    partial.sync_data().await?;
+
+    let upload = db.add_received_range(upload.id, offset, offset + written.written)?;
+    if !written.complete {
+        return Err(Error::BadCall);
+    }
+
+    Ok(upload)
+}
+
+/**
+ * Finishes the upload given by `upload_id`.
+ *
+ * The received content is moved to the permanent storage and the corresponding File is created
+ * or updated and returned. If a File is updated, its previous content is kept as a version. The
+ * permissions of the user are checked again. If the File can not be created or updated or the
+ * upload can not be removed, the stored content is released again.
+ *
+ * If not all bytes of the upload were received, `Error::ForbiddenAction` is returned. See
+ * `get_upload()` for other errors.
+ */
+pub fn finish_upload(
+    upload_id: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<File, Error> {
+    let upload = get_upload(upload_id, user_id, db, config)?;
+    if !upload.is_complete() {
+        return Err(Error::ForbiddenAction);
+    }
+    let user = db.get_user(user_id)?.ok_or(Error::BadCall)?;
+
+    let existing_file = if upload.file_id != 0 {
+        let file = db.get_file(upload.file_id)?.ok_or(Error::NoSuchFile)?;
+        if !file.has_rights(&user, Rights::WRITE) {
+            return Err(Error::MissingAuthorization);
+        }
+        Some(file)
+    } else {
+        if !db
+            .get_dir(upload.parent_id)?
+            .ok_or(Error::NoSuchDir)?
+            .has_rights(&user, Rights::CREATE)
+        {
+            return Err(Error::MissingAuthorization);
+        }
+        None
+    };
+
+    // Move received content to the blob storage:
+    let (content_hash, size) = blob::store(&partial_path(config, upload.id), db, config)?;
+
+    let res = match existing_file {
+        // The media type is set together with the new version:
+        Some(mut file) => {
+            file.media_type = upload.media_type.clone();
+            Ok(file)
+        }
+        None => {
+            let mut file = FileBuilder::new()
+                .with_parent_id(upload.parent_id)
+                .with_owner_id(user_id)
+                .with_name(upload.name.as_str())
+                .build();
+            file.media_type = upload.media_type.clone();
+            db.insert_new_file(&mut file).map(|_| file)
+        }
+    };
+    let mut file = match res.and_then(|file| remove_upload(upload.id, db, config).map(|_| file)) {
+        Ok(file) => file,
+        Err(err) => {
+            blob::release(&content_hash, db, config)?;
+            return Err(err);
+        }
+    };
+
+    version::add_version(&mut file, user_id, content_hash, size, db, config)?;
+
+    Ok(file)
+}
+
+/**
+ * Cancels the upload given by `upload_id` and removes all received data.
+ *
+ * See `get_upload()` for errors.
+ */
+pub fn cancel_upload(
+    upload_id: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<Upload, Error> {
+    let upload = get_upload(upload_id, user_id, db, config)?;
+    remove_upload(upload.id, db, config)
+}
+
+/// Removes all expired uploads and their received data and returns their IDs.
+pub fn remove_expired_uploads(db: &Database, config: &Config) -> Result<Vec<u64>, Error> {
+    let mut removed = Vec::new();
+    for upload in db.get_uploads()? {
+        if is_expired(&upload) {
+            match remove_upload(upload.id, db, config) {
+                // Finished or cancelled in the meantime:
+                Ok(_) | Err(Error::NoSuchTarget) => removed.push(upload.id),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    Ok(removed)
+}
+
+fn is_expired(upload: &Upload) -> bool {
+    Utc::now().signed_duration_since(upload.creation_date) > Duration::hours(UPLOAD_LIFETIME_HOURS)
+}
+
+fn remove_upload(upload_id: u64, db: &Database, config: &Config) -> Result<Upload, Error> {
+    let upload = db.remove_upload(upload_id)?;
+    if let Err(e) = std::fs::remove_file(partial_path(config, upload_id)) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(Error::from(e));
+        }
+    }
+    Ok(upload)
+}
+
+fn partial_path(config: &Config, upload_id: u64) -> PathBuf {
+    let mut path = config.upload_location.clone();
+    path.push(format!("{:x}", upload_id));
+    path
+}
@@ -89,10 +89,8 @@ pub fn add_user(user_infos: UserMsg, acting_user_id: u64, db: &Database) -> Resu
         id: 0, // Will be updated by `insert_new_dir()`
         parent_id: 0,
         owner_id: user_id,
-        read_group_ids: Vec::new(),
-        write_group_ids: Vec::new(),
-        child_ids: Vec::new(),
         name: String::from("home"),
+        ..Dir::default()
     };
     db.insert_new_dir(&mut root_dir)?;
 
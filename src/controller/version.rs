@@ -0,0 +1,233 @@
+use chrono::offset::Utc;
+
+use crate::{
+    config::Config,
+    controller::{
+        blob::{self, BlobReader},
+        search,
+    },
+    database::Database,
+    models::{ContentHash, File, FileVersion, FsNode, Rights},
+    Error,
+};
+
+/**
+ * Adds a FileVersion for the content given by `content_hash` and `size`, that was written by the
+ * user given by `uploader_id`, to the given File. The new version becomes the current content of
+ * the file and the content information of `file` is updated accordingly.
+ *
+ * The reference to the blob, that is held by `content_hash` (see `blob::store()`), is passed to
+ * the new version. Afterwards the oldest previous versions are removed, until the retention limit
+ * of the file is reached.
+ */
+pub fn add_version(
+    file: &mut File,
+    uploader_id: u64,
+    content_hash: ContentHash,
+    size: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<FileVersion, Error> {
+    insert_version(file, uploader_id, content_hash, size, false, db, config)
+}
+
+/**
+ * Adds a FileVersion, that shares the content of the given FileVersion, to the given File (see
+ * `add_version()`). The content is not copied, a reference to the blob is added together with the
+ * new version.
+ *
+ * If the blob was removed in the meantime, `Error::NoSuchTarget` is returned.
+ */
+pub fn add_shared_version(
+    file: &mut File,
+    uploader_id: u64,
+    content: &FileVersion,
+    db: &Database,
+    config: &Config,
+) -> Result<FileVersion, Error> {
+    let (content_hash, size) = (content.content_hash, content.size);
+    insert_version(file, uploader_id, content_hash, size, true, db, config)
+}
+
+/// Adds a FileVersion to the given File and applies the retention policy (see `add_version()`).
+/// If `add_ref` is true, a new reference to the blob is added, otherwise the reference held by
+/// `content_hash` is passed to the version.
+fn insert_version(
+    file: &mut File,
+    uploader_id: u64,
+    content_hash: ContentHash,
+    size: u64,
+    add_ref: bool,
+    db: &Database,
+    config: &Config,
+) -> Result<FileVersion, Error> {
+    let mut version = FileVersion {
+        file_id: file.id,
+        version: 0,
+        creation_date: Utc::now(),
+        uploader_id,
+        size,
+        content_hash,
+        media_type: file.media_type.clone(),
+    };
+    if let Err(err) = db.insert_new_version(&mut version, add_ref) {
+        if !add_ref {
+            blob::release(&content_hash, db, config)?;
+        }
+        return Err(err);
+    }
+    file.set_content(&version);
+
+    // Apply retention policy:
+    let limit = db
+        .get_version_limit(file.id)?
+        .unwrap_or(config.max_file_versions) as usize;
+    let previous_versions: Vec<FileVersion> = db
+        .get_versions(file.id)?
+        .into_iter()
+        .filter(|v| v.version < version.version)
+        .collect();
+    if previous_versions.len() > limit {
+        for old_version in &previous_versions[..(previous_versions.len() - limit)] {
+            remove_version(old_version, db, config)?;
+        }
+    }
+    search::index_file(file, db, config)?;
+
+    Ok(version)
+}
+
+/**
+ * Opens the current content of the file given by `file_id`.
+ *
+ * If no content was written to the file yet, `Error::NoSuchTarget` is returned. Permissions are
+ * not checked.
+ */
+pub fn open_content(file_id: u64, db: &Database, config: &Config) -> Result<BlobReader, Error> {
+    let version = db.get_latest_version(file_id)?.ok_or(Error::NoSuchTarget)?;
+    blob::open(&version, db, config)
+}
+
+/**
+ * Returns all versions of the file given by `file_id`, the current one last.
+ *
+ * If there is no such file, `Error::NoSuchFile` is returned. If the user given by `user_id` may
+ * not read the file, `Error::MissingAuthorization` is returned.
+ */
+pub fn get_versions(file_id: u64, user_id: u64, db: &Database) -> Result<Vec<FileVersion>, Error> {
+    super::get_file_info(file_id, user_id, db)?;
+    db.get_versions(file_id)
+}
+
+/**
+ * Returns the version given by `version` of the file given by `file_id` and a handle to its
+ * content.
+ *
+ * The user given by `user_id` needs `Rights::READ` on the file, otherwise
+ * `Error::MissingAuthorization` is returned. If there is no such version, `Error::NoSuchTarget`
+ * is returned. See `get_versions()` for other errors.
+ */
+pub fn get_version_content(
+    file_id: u64,
+    version: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<(FileVersion, BlobReader), Error> {
+    let file = super::get_file_info(file_id, user_id, db)?;
+    if !file.has_rights(&db.get_user(user_id)?.ok_or(Error::BadCall)?, Rights::READ) {
+        return Err(Error::MissingAuthorization);
+    }
+    let version = db
+        .get_version(file_id, version)?
+        .ok_or(Error::NoSuchTarget)?;
+    let content = blob::open(&version, db, config)?;
+
+    Ok((version, content))
+}
+
+/**
+ * Makes the content of the version given by `version` the current content of the file given by
+ * `file_id` by adding it as a new version. The updated File is returned.
+ *
+ * If there is no such file, `Error::NoSuchFile` is returned. If there is no such version,
+ * `Error::NoSuchTarget` is returned. If the user given by `user_id` may not write the file,
+ * `Error::MissingAuthorization` is returned.
+ */
+pub fn restore_version(
+    file_id: u64,
+    version: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<File, Error> {
+    let mut file = db.get_file(file_id)?.ok_or(Error::NoSuchFile)?;
+    if !file.has_rights(&db.get_user(user_id)?.ok_or(Error::BadCall)?, Rights::WRITE) {
+        return Err(Error::MissingAuthorization);
+    }
+    let version = db
+        .get_version(file_id, version)?
+        .ok_or(Error::NoSuchTarget)?;
+    if db.get_latest_version(file_id)?.map(|v| v.version) == Some(version.version) {
+        // The version is already the current one:
+        return Ok(file);
+    }
+
+    // The content is not copied, the new version references the same blob. The media type is
+    // set together with the new version:
+    file.media_type = version.media_type.clone();
+    add_shared_version(&mut file, user_id, &version, db, config)?;
+
+    Ok(file)
+}
+
+/**
+ * Returns the maximum number of previous versions, that are kept for the file given by
+ * `file_id`, if a limit is set for this file, and the default limit.
+ *
+ * See `get_versions()` for errors.
+ */
+pub fn get_version_limit(
+    file_id: u64,
+    user_id: u64,
+    db: &Database,
+    config: &Config,
+) -> Result<(Option<u32>, u32), Error> {
+    super::get_file_info(file_id, user_id, db)?;
+    Ok((db.get_version_limit(file_id)?, config.max_file_versions))
+}
+
+/**
+ * Sets the maximum number of previous versions, that are kept for the file given by `file_id`.
+ * If `limit` is None, the default limit is used for this file. Versions exceeding the limit are
+ * removed, when the content of the file is written the next time.
+ *
+ * If there is no such file, `Error::NoSuchFile` is returned. If the user given by `user_id` may
+ * not write the file, `Error::MissingAuthorization` is returned.
+ */
+pub fn set_version_limit(
+    file_id: u64,
+    limit: Option<u32>,
+    user_id: u64,
+    db: &Database,
+) -> Result<(), Error> {
+    let file = db.get_file(file_id)?.ok_or(Error::NoSuchFile)?;
+    if !file.has_rights(&db.get_user(user_id)?.ok_or(Error::BadCall)?, Rights::WRITE) {
+        return Err(Error::MissingAuthorization);
+    }
+    db.set_version_limit(file_id, limit)
+}
+
+/// Removes all versions of the file given by `file_id`, including the current content, and
+/// releases their blobs.
+pub fn remove_all_versions(file_id: u64, db: &Database, config: &Config) -> Result<(), Error> {
+    for version in db.get_versions(file_id)? {
+        remove_version(&version, db, config)?;
+    }
+    db.set_version_limit(file_id, None)
+}
+
+fn remove_version(version: &FileVersion, db: &Database, config: &Config) -> Result<(), Error> {
+    blob::remove_version(version.file_id, version.version, db, config)?;
+    Ok(())
+}
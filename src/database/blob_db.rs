@@ -0,0 +1,104 @@
+use std::convert::TryInto;
+
+use sled::{transaction::ConflictableTransactionError, Db, Tree};
+
+use crate::{models::ContentHash, Error};
+
+// Key in the `encrypted_blob_tree`, which is set, when all blobs in the storage were flagged:
+const FLAGS_COMPLETE_KEY: &[u8] = b"flags_complete";
+
+#[derive(Clone)]
+pub struct BlobDatabase {
+    blob_ref_tree: Tree,       // K: content_hash, V: reference_count
+    encrypted_blob_tree: Tree, // K: content_hash (of encrypted blobs), V: empty
+}
+
+impl BlobDatabase {
+    /// Initializes the database.
+    pub fn init(sled_db: &Db) -> Result<BlobDatabase, Error> {
+        let blob_ref_tree = sled_db
+            .open_tree("blob_refs")
+            .expect("Could not open blob_refs tree.");
+        let encrypted_blob_tree = sled_db
+            .open_tree("encrypted_blobs")
+            .expect("Could not open encrypted_blobs tree.");
+
+        Ok(BlobDatabase {
+            blob_ref_tree,
+            encrypted_blob_tree,
+        })
+    }
+
+    /// Returns true, if the blob given by `hash` was stored encrypted.
+    pub fn is_blob_encrypted(&self, hash: &ContentHash) -> Result<bool, Error> {
+        Ok(self.encrypted_blob_tree.contains_key(hash.0)?)
+    }
+
+    /// Sets, whether the blob given by `hash` is stored encrypted.
+    pub fn set_blob_encrypted(&self, hash: &ContentHash, encrypted: bool) -> Result<(), Error> {
+        if encrypted {
+            self.encrypted_blob_tree.insert(hash.0, &[])?;
+        } else {
+            self.encrypted_blob_tree.remove(hash.0)?;
+        }
+        Ok(())
+    }
+
+    /// Returns true, if the blobs, which were stored before the encryption was recorded in the
+    /// DB, were flagged already (see `set_encryption_flags_complete()`).
+    pub fn are_encryption_flags_complete(&self) -> Result<bool, Error> {
+        Ok(self.encrypted_blob_tree.contains_key(FLAGS_COMPLETE_KEY)?)
+    }
+
+    /// Records, that all blobs in the storage were flagged.
+    pub fn set_encryption_flags_complete(&self) -> Result<(), Error> {
+        self.encrypted_blob_tree.insert(FLAGS_COMPLETE_KEY, &[])?;
+        Ok(())
+    }
+
+    /// Returns the number of references to the blob given by `hash`. Unknown blobs have no
+    /// references.
+    pub fn get_blob_refs(&self, hash: &ContentHash) -> Result<u64, Error> {
+        Ok(self
+            .blob_ref_tree
+            .get(hash.0)?
+            .map(|value| parse_ref_count(&value))
+            .unwrap_or(0))
+    }
+
+    /// Adds a reference to the blob given by `hash` and returns the new number of references.
+    pub fn add_blob_ref(&self, hash: &ContentHash) -> Result<u64, Error> {
+        self.change_blob_refs(hash, 1)
+    }
+
+    /// Removes a reference to the blob given by `hash` and returns the remaining number of
+    /// references. The entry of the blob is removed, when there are no references left.
+    pub fn remove_blob_ref(&self, hash: &ContentHash) -> Result<u64, Error> {
+        self.change_blob_refs(hash, -1)
+    }
+
+    /// Adds `diff` to the number of references to the blob given by `hash` and returns the new
+    /// number. The references are also changed by the transactions of the FsDatabase, so this is
+    /// done in a transaction as well.
+    fn change_blob_refs(&self, hash: &ContentHash, diff: i64) -> Result<u64, Error> {
+        self.blob_ref_tree
+            .transaction(|blob_ref_t| {
+                let refs = blob_ref_t
+                    .get(hash.0)?
+                    .map(|value| parse_ref_count(&value))
+                    .unwrap_or(0)
+                    .saturating_add_signed(diff);
+                if refs > 0 {
+                    blob_ref_t.insert(&hash.0, &refs.to_be_bytes())?;
+                } else {
+                    blob_ref_t.remove(&hash.0)?;
+                }
+                Ok::<_, ConflictableTransactionError<Error>>(refs)
+            })
+            .map_err(Error::from)
+    }
+}
+
+fn parse_ref_count(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes[0..8].try_into().unwrap())
+}
@@ -1,17 +1,89 @@
-use std::convert::{TryFrom, TryInto};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::{TryFrom, TryInto},
+    ops::Bound,
+    sync::{Arc, RwLock},
+};
 
+use chrono::{
+    offset::{TimeZone, Utc},
+    DateTime,
+};
 use rand::{thread_rng, RngCore};
-use sled::{transaction::ConflictableTransactionError, Db, Transactional, Tree};
+use sled::{
+    transaction::{
+        ConflictableTransactionError, ConflictableTransactionResult, TransactionError,
+        TransactionalTree,
+    },
+    Db, IVec, Transactional, Tree,
+};
 
+use super::{parse_db_string, string_to_bytes};
 use crate::{
-    models::{Dir, File},
+    models::{
+        numbered_name, Acl, ContentHash, Dir, File, FileVersion, OwnershipOffer, Rights, Share,
+        TrashEntry,
+    },
     Error,
 };
 
+/// A change of the file system, that can be applied as part of a batch (see
+/// `FsDatabase::apply_changes()`).
+#[derive(Clone, Debug)]
+pub enum FsChange {
+    /// Moves the FsNode into the trash (see `FsDatabase::move_to_trash()`).
+    Trash { id: u64 },
+    /// Moves and renames the FsNode (see `FsDatabase::move_fs_node()`).
+    Move {
+        id: u64,
+        parent_id: u64,
+        name: String,
+    },
+    /// Adds the group to the readable or writeable groups of the FsNode.
+    AddGroup { id: u64, group_id: u64, write: bool },
+    /// Removes the group from the readable or writeable groups of the FsNode.
+    RemoveGroup { id: u64, group_id: u64, write: bool },
+}
+
+#[derive(Clone)]
 pub struct FsDatabase {
-    file_tree: Tree, // K: file_id, V: parent_id, owner_id, name_len, name, type_len, media_type
-    dir_tree: Tree,  // K: dir_id, V: parent_id, owner_id, child_number(u16), file/dir_ids..., name
-    permissions_tree: Tree, // K: fs_node_id, V: read_group_number (u16), read_group_ids..., write_group_number (u16), write_group_ids...,
+    file_tree: Tree, // K: file_id, V: parent_id, owner_id, name_len, name, type_len, media_type, creation_date, modification_date, size, uploader_id, content_hash (32 bytes, empty if none)
+    dir_tree: Tree, // K: dir_id, V: parent_id, owner_id, 0u16 (number of childs listed here by older versions), name
+    dir_stats_tree: Tree, // K: dir_id, V: creation_date, modification_date, size (u64), file_count (u64), child_count (u64)
+    dir_child_tree: Tree, // K: parent_id, position (u64, 0..child_count), V: child_id (only for childs, that are listed by their parent)
+    child_position_tree: Tree, // K: child_id, V: position of the child in the dir_child_tree
+    name_tree: Tree, // K: parent_id, name, V: child_id (only for childs, that are listed by their parent)
+    permissions_tree: Tree, // K: fs_node_id, V: read_group_number (u16), read_group_ids..., write_group_number (u16), write_group_ids..., flags (u8), read_user_number (u16), read_user_ids..., write_user_number (u16), write_user_ids..., group_rights_number (u16), (group_id, rights (u8))..., user_rights_number (u16), (user_id, rights (u8))... (flags, users and rights are missing in entries of older versions)
+    share_tree: Tree, // K: token, V: fs_node_id, owner_id, creation_date, expiration_date (0 if none), download_limit (u32, 0 if none), download_count (u32), pwd_hash (empty if none)
+    node_share_tree: Tree, // K: fs_node_id, token
+    offer_tree: Tree, // K: fs_node_id, V: new_owner_id, owner_id, creation_date
+    trash_tree: Tree, // K: owner_id, fs_node_id, V: parent_id, deletion_date, is_dir (u8), name
+    trashed_tree: Tree, // K: fs_node_id, V: owner_id, ID of the trashed ancestor (or itself)
+    version_tree: Tree, // K: file_id, version, V: creation_date, uploader_id, size, content_hash (32 bytes), media_type
+    version_limit_tree: Tree, // K: file_id, V: max_versions (u32)
+    legacy_version_tree: Tree, // K: file_id, version, V: creation_date, uploader_id, size, media_type (versions written before the content-addressed storage, see `FsDatabase::set_aside_legacy_versions()`)
+    blob_ref_tree: Tree,       // K: content_hash, V: reference_count (shared with the BlobDatabase)
+    meta_tree: Tree, // K: "schema_version", V: version of the layout of the trees above (u32, see `FsDatabase::migrate()`)
+    inheritance_cache: Arc<RwLock<InheritanceCache>>,
+}
+
+/// Bit of the flags of a permissions entry, that is set, if the FsNode does not inherit the
+/// groups and users of its parent.
+const NO_INHERITANCE_FLAG: u8 = 1;
+/// Maximal number of directories, whose inherited groups and users are cached.
+const MAX_CACHED_DIRS: usize = 65_536;
+/// The version of the layout of the file system trees, that is written by this version of Kasten.
+const SCHEMA_VERSION: u32 = 4;
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// Cache of the groups and users, that the childs of directories inherit (see
+/// `FsDatabase::inherited_acl()`).
+#[derive(Default)]
+struct InheritanceCache {
+    /// Is increased, whenever the cache is cleared, so that entries, which were read before, are
+    /// not inserted afterwards.
+    generation: u64,
+    acls: HashMap<u64, Acl>, // K: dir_id
 }
 
 impl FsDatabase {
@@ -23,67 +95,552 @@ impl FsDatabase {
         let dir_tree = sled_db
             .open_tree("dirs")
             .expect("Could not open dirs tree.");
+        let dir_stats_tree = sled_db
+            .open_tree("dir_stats")
+            .expect("Could not open dir-stats tree.");
+        let dir_child_tree = sled_db
+            .open_tree("dir_childs")
+            .expect("Could not open dir-childs tree.");
+        let child_position_tree = sled_db
+            .open_tree("dir_child_positions")
+            .expect("Could not open dir-child-positions tree.");
+        let name_tree = sled_db
+            .open_tree("fs_node_names")
+            .expect("Could not open fs-node-names tree.");
         let permissions_tree = sled_db
             .open_tree("fs_node_permissions")
             .expect("Could not open fs-node-permissions tree.");
-
-        Ok(FsDatabase {
+        let share_tree = sled_db
+            .open_tree("fs_node_shares")
+            .expect("Could not open fs-node-shares tree.");
+        let node_share_tree = sled_db
+            .open_tree("fs_node_share_tokens")
+            .expect("Could not open fs-node-share-tokens tree.");
+        let offer_tree = sled_db
+            .open_tree("fs_node_ownership_offers")
+            .expect("Could not open fs-node-ownership-offers tree.");
+        let trash_tree = sled_db
+            .open_tree("fs_node_trash")
+            .expect("Could not open fs-node-trash tree.");
+        let trashed_tree = sled_db
+            .open_tree("trashed_fs_nodes")
+            .expect("Could not open trashed-fs-nodes tree.");
+        let version_tree = sled_db
+            .open_tree("file_versions")
+            .expect("Could not open file-versions tree.");
+        let version_limit_tree = sled_db
+            .open_tree("file_version_limits")
+            .expect("Could not open file-version-limits tree.");
+        let legacy_version_tree = sled_db
+            .open_tree("legacy_file_versions")
+            .expect("Could not open legacy-file-versions tree.");
+        let blob_ref_tree = sled_db
+            .open_tree("blob_refs")
+            .expect("Could not open blob_refs tree.");
+        let meta_tree = sled_db
+            .open_tree("fs_meta")
+            .expect("Could not open fs-meta tree.");
+
+        let fs_db = FsDatabase {
             dir_tree,
+            dir_stats_tree,
+            dir_child_tree,
+            child_position_tree,
+            name_tree,
             file_tree,
             permissions_tree,
-        })
+            share_tree,
+            node_share_tree,
+            offer_tree,
+            trash_tree,
+            trashed_tree,
+            version_tree,
+            version_limit_tree,
+            legacy_version_tree,
+            blob_ref_tree,
+            meta_tree,
+            inheritance_cache: Arc::default(),
+        };
+        fs_db.migrate()?;
+
+        Ok(fs_db)
+    }
+
+    /**
+     * Converts the trees written by older versions of Kasten to the current layout. Each step runs
+     * only once: The version of the layout is stored after every step, so an interrupted
+     * migration continues with the step, that did not finish.
+     */
+    fn migrate(&self) -> Result<(), Error> {
+        let mut version = self
+            .meta_tree
+            .get(SCHEMA_VERSION_KEY)?
+            .map(|bytes| u32::from_be_bytes(bytes.as_ref().try_into().unwrap()))
+            .unwrap_or(0);
+        while version < SCHEMA_VERSION {
+            match version {
+                0 => self.convert_legacy_child_lists()?,
+                1 => self.build_name_index()?,
+                2 => self.set_aside_legacy_versions()?,
+                3 => self.compute_dir_stats()?,
+                _ => unreachable!(),
+            }
+            version += 1;
+            self.meta_tree
+                .insert(SCHEMA_VERSION_KEY, &version.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Moves the lists of childs, that were stored in the entries of the dir tree by older versions
+     * of Kasten, into the child tree. The childs keep their order. Childs, that do not exist
+     * anymore or are already listed, are skipped.
+     */
+    fn convert_legacy_child_lists(&self) -> Result<(), Error> {
+        for entry in self.dir_tree.iter() {
+            let (key, value) = entry?;
+            if value[16..18] == [0, 0] {
+                continue;
+            }
+            let dir_id = u64::from_be_bytes(key.as_ref().try_into().unwrap());
+
+            self.fs_transaction(|trees| {
+                let FsTrees {
+                    file_t,
+                    dir_t,
+                    trashed_t,
+                    position_t,
+                    ..
+                } = trees;
+                // Read the entry again, it may have changed in the meantime:
+                let dir_bytes = match dir_t.get(&key)? {
+                    Some(bytes) => bytes,
+                    None => return Ok(()),
+                };
+                for child_id in legacy_child_ids(&dir_bytes) {
+                    let id_bytes = child_id.to_be_bytes();
+                    let exists = file_t.get(id_bytes)?.is_some() || dir_t.get(id_bytes)?.is_some();
+                    // A FsNode in the trash is not listed by its parent:
+                    let is_trashed_root = trashed_t
+                        .get(id_bytes)?
+                        .map(|marker| marker[8..16] == id_bytes)
+                        .unwrap_or(false);
+                    if exists && !is_trashed_root && position_t.get(id_bytes)?.is_none() {
+                        add_child_t(trees, dir_id, child_id)?;
+                    }
+                }
+                dir_t.insert(&key, remove_legacy_child_ids(&dir_bytes))?;
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Adds the childs of all directories to the index of names, that are not contained yet.
+     *
+     * If a directory contains several childs with the same name (which was possible in older
+     * versions of Kasten), all but the first one are renamed.
+     */
+    fn build_name_index(&self) -> Result<(), Error> {
+        for entry in self.dir_child_tree.iter() {
+            let (key, value) = entry?;
+            let dir_id = u64::from_be_bytes(key[0..8].try_into().unwrap());
+            let child_id = u64::from_be_bytes(value.as_ref().try_into().unwrap());
+
+            let new_name = self
+                .fs_transaction(|trees| {
+                    let FsTrees {
+                        file_t,
+                        dir_t,
+                        name_t,
+                        child_t,
+                        ..
+                    } = trees;
+                    // The child may have been moved in the meantime:
+                    if child_t.get(&key)?.as_deref() != Some(value.as_ref()) {
+                        return Ok(None);
+                    }
+                    let (is_dir, child_bytes) = if let Some(b) = dir_t.get(&value)? {
+                        (true, b)
+                    } else if let Some(b) = file_t.get(&value)? {
+                        (false, b)
+                    } else {
+                        return Ok(None);
+                    };
+
+                    let name = entry_name(&child_bytes, is_dir);
+                    let mut unique_name = name.clone();
+                    let mut number = 1;
+                    while let Some(id) = name_t.get(name_key(dir_id, &unique_name))? {
+                        if id == value {
+                            return Ok(None);
+                        }
+                        unique_name = numbered_name(&name, number, !is_dir);
+                        number += 1;
+                    }
+                    if unique_name != name {
+                        let tree = if is_dir { dir_t } else { file_t };
+                        tree.insert(
+                            &value,
+                            rename_entry(&child_bytes, is_dir, dir_id, &unique_name),
+                        )?;
+                    }
+                    name_t.insert(name_key(dir_id, &unique_name), &value)?;
+                    Ok(Some(unique_name).filter(|n| *n != name))
+                })
+                .map_err(transaction_error)?;
+            if let Some(name) = new_name {
+                // TODO: Logging
+                println!(
+                    "Renamed {:x} to \"{}\", because its name is used twice.",
+                    child_id, name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Moves the versions, that were written by older versions of Kasten without content-addressed
+     * storage, from the version tree into the legacy version tree. Their content is moved to the
+     * blob storage later (see `controller::storage::import_legacy_blobs()`).
+     *
+     * Legacy versions have no content hash. They are recognized by the missing reference to the
+     * blob, that would be given by the hash.
+     */
+    fn set_aside_legacy_versions(&self) -> Result<(), Error> {
+        for entry in self.version_tree.iter() {
+            let (key, value) = entry?;
+            if value.len() >= 56 && self.blob_ref_tree.contains_key(&value[24..56])? {
+                continue;
+            }
+            (&self.version_tree, &self.legacy_version_tree)
+                .transaction(|(version_t, legacy_t)| {
+                    version_t.remove(&key)?;
+                    legacy_t.insert(&key, &value)?;
+                    Ok::<_, ConflictableTransactionError<Error>>(())
+                })
+                .map_err(transaction_error)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Computes the size, file count and child count of all directories and adds the missing
+     * content information to the files written by older versions of Kasten. Directories without
+     * timestamps get the current time.
+     */
+    fn compute_dir_stats(&self) -> Result<(), Error> {
+        for entry in self.file_tree.iter() {
+            let (key, value) = entry?;
+            let mut file = File {
+                id: u64::from_be_bytes(key.as_ref().try_into().unwrap()),
+                ..File::default()
+            };
+            let info_offset = file_info_offset(&value);
+            if !parse_file_info(&value[info_offset..], &mut file) {
+                self.file_info_from_versions(&mut file)?;
+                // Files without versions get their content information with the first version:
+                if file.content_hash.is_some() {
+                    let mut new_value = Vec::from(&value[..info_offset]);
+                    serialize_file_info(&file, &mut new_value);
+                    self.file_tree.insert(&key, new_value)?;
+                }
+            }
+        }
+
+        // The size, file count and child count of the childs, that each directory lists:
+        let mut own_stats: HashMap<u64, (u64, u64, u64)> = HashMap::new();
+        let mut sub_dirs: HashMap<u64, Vec<u64>> = HashMap::new();
+        for entry in self.dir_child_tree.iter() {
+            let (key, value) = entry?;
+            let dir_id = u64::from_be_bytes(key[0..8].try_into().unwrap());
+            let (size, file_count, child_count) = own_stats.entry(dir_id).or_default();
+            *child_count += 1;
+            if let Some(file_entry) = self.file_tree.get(&value)? {
+                *size += file_entry_size(&file_entry);
+                *file_count += 1;
+            } else {
+                let child_id = u64::from_be_bytes(value.as_ref().try_into().unwrap());
+                sub_dirs.entry(dir_id).or_default().push(child_id);
+            }
+        }
+
+        // Add the size and file count of the subdirectories, starting with the deepest ones:
+        let mut totals: HashMap<u64, (u64, u64)> = HashMap::new();
+        let mut visited = HashSet::new();
+        for entry in self.dir_tree.iter() {
+            let (key, _) = entry?;
+            let mut stack = vec![(u64::from_be_bytes(key.as_ref().try_into().unwrap()), false)];
+            while let Some((dir_id, childs_done)) = stack.pop() {
+                let childs = sub_dirs.get(&dir_id).map(Vec::as_slice).unwrap_or(&[]);
+                if !childs_done {
+                    if visited.insert(dir_id) {
+                        stack.push((dir_id, true));
+                        stack.extend(childs.iter().map(|id| (*id, false)));
+                    }
+                    continue;
+                }
+                let (mut size, mut file_count, _) =
+                    own_stats.get(&dir_id).copied().unwrap_or_default();
+                for child_id in childs {
+                    let (child_size, child_file_count) =
+                        totals.get(child_id).copied().unwrap_or_default();
+                    size += child_size;
+                    file_count += child_file_count;
+                }
+                totals.insert(dir_id, (size, file_count));
+            }
+        }
+
+        for (dir_id, (size, file_count)) in totals {
+            if !self.dir_tree.contains_key(dir_id.to_be_bytes())? {
+                continue;
+            }
+            let mut dir = Dir::default();
+            if let Some(bytes) = self.dir_stats_tree.get(dir_id.to_be_bytes())? {
+                parse_dir_stats(&bytes, &mut dir);
+            }
+            dir.size = size;
+            dir.file_count = file_count;
+            dir.child_count = own_stats.get(&dir_id).map_or(0, |stats| stats.2);
+            self.dir_stats_tree
+                .insert(dir_id.to_be_bytes(), serialize_dir_stats(&dir))?;
+        }
+        Ok(())
     }
 
-    /// Returns the File with the given ID, if it exists in the DB, or None otherwise.
+    /// Returns the File with the given ID, if it exists in the DB and is not in the trash, or None
+    /// otherwise.
     pub fn get_file(&self, id: u64) -> sled::Result<Option<File>> {
-        Ok(self
+        if self.trashed_tree.contains_key(id.to_be_bytes())? {
+            return Ok(None);
+        }
+        let (file_entry, perm_entry) = match self
             .file_tree
             .get(id.to_be_bytes())?
             .zip(self.permissions_tree.get(id.to_be_bytes())?)
-            .map(|(file_entry, perm_entry)| {
-                let (name, name_len) = parse_db_string(&file_entry[16..]);
-                let (media_type, _) = parse_db_string(&file_entry[(16 + name_len)..]);
-                File {
-                    id,
-                    parent_id: u64::from_be_bytes(file_entry[0..8].try_into().unwrap()),
-                    owner_id: u64::from_be_bytes(file_entry[8..16].try_into().unwrap()),
-                    read_group_ids: parse_read_group_ids(&perm_entry),
-                    write_group_ids: parse_write_group_ids(&perm_entry),
-                    name,
-                    media_type,
-                }
-            }))
+        {
+            Some(entries) => entries,
+            None => return Ok(None),
+        };
+
+        let (name, name_len) = parse_db_string(&file_entry[16..]);
+        let (media_type, _) = parse_db_string(&file_entry[(16 + name_len)..]);
+        let (acl, inherit_permissions) = parse_permissions(&perm_entry);
+        let mut file = File {
+            id,
+            parent_id: u64::from_be_bytes(file_entry[0..8].try_into().unwrap()),
+            owner_id: u64::from_be_bytes(file_entry[8..16].try_into().unwrap()),
+            acl,
+            inherit_permissions,
+            name,
+            media_type,
+            ..File::default()
+        };
+        if file.inherit_permissions {
+            file.inherited_acl = self.inherited_acl(file.parent_id)?;
+        }
+        if !parse_file_info(&file_entry[file_info_offset(&file_entry)..], &mut file) {
+            // Files written by older versions of Kasten have no content information:
+            self.file_info_from_versions(&mut file)?;
+        }
+        Ok(Some(file))
+    }
+
+    /// Sets the content information of the given File to the values given by its versions.
+    fn file_info_from_versions(&self, file: &mut File) -> sled::Result<()> {
+        let epoch = Utc.timestamp_opt(0, 0).unwrap();
+        file.creation_date = epoch;
+        file.modification_date = epoch;
+
+        let mut versions = self.version_tree.scan_prefix(file.id.to_be_bytes());
+        if let Some(entry) = versions.next() {
+            let (key, value) = entry?;
+            let first_version = parse_version(&key, &value);
+            file.creation_date = first_version.creation_date;
+            file.set_content(&first_version);
+        }
+        if let Some(entry) = versions.next_back() {
+            let (key, value) = entry?;
+            file.set_content(&parse_version(&key, &value));
+        }
+        Ok(())
     }
 
-    /// Returns the directory with the given id, it it exists in the DB.
+    /// Returns the directory with the given id, it it exists in the DB and is not in the trash.
     pub fn get_dir(&self, id: u64) -> Result<Option<Dir>, Error> {
-        Ok(self
+        if self.trashed_tree.contains_key(id.to_be_bytes())? {
+            return Ok(None);
+        }
+        let stats_entry = self.dir_stats_tree.get(id.to_be_bytes())?;
+        let (dir_entry, perm_entry) = match self
             .dir_tree
             .get(id.to_be_bytes())?
             .zip(self.permissions_tree.get(id.to_be_bytes())?)
-            .map(|(dir_entry, perm_entry)| {
-                let mut res = entry_to_dir_incomplete(id, &dir_entry);
-                res.read_group_ids = parse_read_group_ids(&perm_entry);
-                res.write_group_ids = parse_write_group_ids(&perm_entry);
-                res
-            }))
+        {
+            Some(entries) => entries,
+            None => return Ok(None),
+        };
+
+        let mut res = entry_to_dir_incomplete(id, &dir_entry);
+        let (acl, inherit_permissions) = parse_permissions(&perm_entry);
+        res.acl = acl;
+        res.inherit_permissions = inherit_permissions;
+        if res.inherit_permissions {
+            res.inherited_acl = self.inherited_acl(res.parent_id)?;
+        }
+        if let Some(stats_entry) = &stats_entry {
+            parse_dir_stats(stats_entry, &mut res);
+        }
+        Ok(Some(res))
+    }
+
+    /**
+     * Returns the readable and writeable groups and users, that the childs of the directory given
+     * by `dir_id` inherit: The groups and users of the directory itself and, if the directory
+     * inherits the permissions of its parent, the groups and users inherited from its parent.
+     * The owners of these directories inherit `Rights::ALL`.
+     *
+     * The results are cached until the permissions or the structure of the file system change, so
+     * that the ancestors of deep trees are not read again for every FsNode.
+     */
+    fn inherited_acl(&self, dir_id: u64) -> sled::Result<Acl> {
+        let generation = self.inheritance_cache.read().unwrap().generation;
+
+        // Collect the groups and users of the ancestors up to the first cached one:
+        let mut ancestors = Vec::new();
+        let mut acl = Acl::default();
+        let mut next_id = dir_id;
+        loop {
+            if let Some(cached) = self.inheritance_cache.read().unwrap().acls.get(&next_id) {
+                acl = cached.clone();
+                break;
+            }
+            let (dir_entry, perm_entry) = match self
+                .dir_tree
+                .get(next_id.to_be_bytes())?
+                .zip(self.permissions_tree.get(next_id.to_be_bytes())?)
+            {
+                Some(entries) => entries,
+                None => break,
+            };
+            let (mut dir_acl, inherit) = parse_permissions(&perm_entry);
+            // The owner of an ancestor has all rights on its descendants:
+            let owner_id = u64::from_be_bytes(dir_entry[8..16].try_into().unwrap());
+            dir_acl.user_rights.push((owner_id, Rights::ALL));
+            ancestors.push((next_id, dir_acl));
+            if !inherit {
+                break;
+            }
+            next_id = u64::from_be_bytes(dir_entry[0..8].try_into().unwrap());
+        }
+
+        // Add the groups and users of the ancestors from the top down:
+        let mut new_entries = Vec::with_capacity(ancestors.len());
+        for (id, dir_acl) in ancestors.into_iter().rev() {
+            acl.merge(&dir_acl);
+            new_entries.push((id, acl.clone()));
+        }
+
+        let mut cache = self.inheritance_cache.write().unwrap();
+        if cache.generation == generation {
+            if cache.acls.len() + new_entries.len() > MAX_CACHED_DIRS {
+                cache.acls.clear();
+            }
+            cache.acls.extend(new_entries);
+        }
+        Ok(acl)
+    }
+
+    /// Clears the cache of inherited groups and users. Has to be called, whenever the permissions
+    /// or the parent of a FsNode changed.
+    fn clear_inheritance_cache(&self) {
+        let mut cache = self.inheritance_cache.write().unwrap();
+        cache.generation += 1;
+        cache.acls.clear();
     }
 
     /// Returns the IDs of the given directory's childs.
     fn get_dirs_childs(&self, dir_id: u64) -> Result<Vec<u64>, Error> {
-        if let Some(dir) = self.dir_tree.get(dir_id.to_be_bytes())? {
-            let child_number = u16::from_be_bytes(dir[16..18].try_into().unwrap()) as usize;
-            let mut child_ids = Vec::with_capacity(child_number);
-            let mut i = 18;
-            for _ in 0..child_number {
-                child_ids.push(u64::from_be_bytes(dir[i..(i + 8)].try_into().unwrap()));
-                i += 8;
-            }
+        self.get_child_ids(dir_id, 0, usize::MAX)
+    }
 
-            Ok(child_ids)
-        } else {
-            Err(Error::EntryNotFound)
+    /**
+     * Returns the IDs of at most `limit` childs of the directory given by `dir_id`, starting with
+     * the child at position `start`.
+     *
+     * The childs of a directory have the positions `0..child_count` (see `Dir::child_count`) in
+     * the order in which they were added. Removing a child moves the last child to its position.
+     * If there is no such directory, `Error::EntryNotFound` is returned.
+     */
+    pub fn get_child_ids(&self, dir_id: u64, start: u64, limit: usize) -> Result<Vec<u64>, Error> {
+        if !self.dir_tree.contains_key(dir_id.to_be_bytes())? {
+            return Err(Error::EntryNotFound);
         }
+        let end = child_key(dir_id, u64::MAX);
+        self.dir_child_tree
+            .range(child_key(dir_id, start)..=end)
+            .values()
+            .take(limit)
+            .map(|value| Ok(u64::from_be_bytes(value?.as_ref().try_into().unwrap())))
+            .collect()
+    }
+
+    /// Returns the ID of the child of the directory given by `parent_id`, that is named `name`, or
+    /// None, if there is no such child.
+    pub fn get_child_id(&self, parent_id: u64, name: &str) -> Result<Option<u64>, Error> {
+        Ok(self
+            .name_tree
+            .get(name_key(parent_id, name))?
+            .map(|id| u64::from_be_bytes(id.as_ref().try_into().unwrap())))
+    }
+
+    /**
+     * Returns the names and IDs of at most `limit` childs of the directory given by `dir_id` in the
+     * (byte-wise) order of their names, starting after the child named `after`. If `descending`
+     * is true, the childs are returned in the reverse order.
+     */
+    pub fn get_childs_by_name(
+        &self,
+        dir_id: u64,
+        after: Option<&str>,
+        descending: bool,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>, Error> {
+        let prefix = dir_id.to_be_bytes();
+        let entries: Box<dyn Iterator<Item = sled::Result<(IVec, IVec)>>> =
+            match (after, descending) {
+                (None, false) => Box::new(self.name_tree.scan_prefix(prefix)),
+                (None, true) => Box::new(self.name_tree.scan_prefix(prefix).rev()),
+                (Some(name), false) => Box::new(
+                    self.name_tree
+                        .range((Bound::Excluded(name_key(dir_id, name)), Bound::Unbounded)),
+                ),
+                (Some(name), true) => Box::new(
+                    self.name_tree
+                        .range(prefix.to_vec()..name_key(dir_id, name))
+                        .rev(),
+                ),
+            };
+
+        entries
+            .take_while(|entry| {
+                entry
+                    .as_ref()
+                    .map_or(true, |(key, _)| key.starts_with(&prefix))
+            })
+            .take(limit)
+            .map(|entry| {
+                let (key, value) = entry?;
+                let name = String::from_utf8(Vec::from(&key[8..]))
+                    .expect("DB contained FsNode with non-UTF-8 name.");
+                Ok((name, u64::from_be_bytes(value.as_ref().try_into().unwrap())))
+            })
+            .collect()
     }
 
     /// Returns the IDs of all files, that are childs of the given directory.
@@ -115,11 +672,9 @@ impl FsDatabase {
     }
 
     /// Inserts a new file with the given attributes in the DB. The ID of the given file will be
-    /// updated to a new unique value.
+    /// updated to a new unique value. Names have to be unique within the parent directory.
     pub fn insert_new_file(&self, file: &mut File) -> Result<(), Error> {
-        if file.read_group_ids.len() > u16::MAX as usize
-            || file.write_group_ids.len() > u16::MAX as usize
-        {
+        if !acl_fits(&file.acl) {
             return Err(Error::BadCall);
         }
 
@@ -128,14 +683,21 @@ impl FsDatabase {
         data.extend_from_slice(&file.owner_id.to_be_bytes());
         string_to_bytes(&file.name, &mut data);
         string_to_bytes(&file.media_type, &mut data);
+        serialize_file_info(file, &mut data);
         // Byte representation of permissions:
-        let mut perm_data =
-            Vec::with_capacity(4 + 8 * (file.read_group_ids.len() + file.write_group_ids.len()));
-        serialize_id_list(file.read_group_ids.as_slice(), &mut perm_data);
-        serialize_id_list(file.write_group_ids.as_slice(), &mut perm_data);
-
-        file.id = (&self.file_tree, &self.dir_tree, &self.permissions_tree).transaction(
-            |(file_t, dir_t, perm_t)| {
+        let perm_data = serialize_permissions(&file.acl, file.inherit_permissions);
+
+        let name_key = name_key(file.parent_id, &file.name);
+
+        file.id = self
+            .fs_transaction(|trees| {
+                let FsTrees {
+                    file_t,
+                    dir_t,
+                    perm_t,
+                    name_t,
+                    ..
+                } = trees;
                 // Generate new file-id:
                 let mut rng = thread_rng();
                 let mut file_id = [0u8; 8];
@@ -147,38 +709,26 @@ impl FsDatabase {
                     rng.fill_bytes(&mut file_id);
                 }
 
-                let parent_bytes = if let Some(b) = dir_t.get(file.parent_id.to_be_bytes())? {
-                    b
-                } else {
+                if dir_t.get(file.parent_id.to_be_bytes())?.is_none() {
                     return Err(ConflictableTransactionError::Abort(Error::NoSuchDir));
-                };
-                let mut new_parent_bytes = Vec::from(&parent_bytes[0..16]);
-
-                // Increase child-number:
-                let mut child_number = u16::from_be_bytes(parent_bytes[16..18].try_into().unwrap());
-                // TODO: Handle overflow:
-                child_number += 1;
-                new_parent_bytes.push(child_number.to_be_bytes()[0]);
-                new_parent_bytes.push(child_number.to_be_bytes()[1]);
-                // Add old childs:
-                new_parent_bytes
-                    .extend_from_slice(&parent_bytes[18..(18 + (child_number as usize - 1) * 8)]);
-                // Add new child:
-                new_parent_bytes.extend_from_slice(&file_id);
-                // Add name of parent:
-                new_parent_bytes
-                    .extend_from_slice(&parent_bytes[(18 + (child_number as usize - 1) * 8)..]);
-
-                // Insert new parent directory:
-                dir_t.insert(&file.parent_id.to_be_bytes(), new_parent_bytes)?;
+                }
+                if name_t.get(name_key.as_slice())?.is_some() {
+                    return Err(ConflictableTransactionError::Abort(Error::TargetExists));
+                }
+
                 // Insert file into file-tree:
                 file_t.insert(&file_id, data.as_slice())?;
+                add_child_t(trees, file.parent_id, u64::from_be_bytes(file_id))?;
+                name_t.insert(name_key.as_slice(), &file_id)?;
                 // Insert permissions into permissions-tree:
                 perm_t.insert(&file_id, perm_data.as_slice())?;
+                // Update statistics of the ancestors:
+                add_to_dir_stats(trees, file.parent_id, file.size as i64, 1)?;
+                touch_dir(trees.stats_t, file.parent_id)?;
 
                 Ok(u64::from_be_bytes(file_id))
-            },
-        )?;
+            })
+            .map_err(transaction_error)?;
 
         Ok(())
     }
@@ -187,16 +737,14 @@ impl FsDatabase {
      * Changes the properties of the given File in the DB to the values given by the parameter
      * `file`.
      *
-     * Changeable properties include `name`, `owner_id`, read_group_ids and write_group_ids,
-     * `parent_id` and 'media_type'. The field `id` is used to identify the file to change.
+     * Changeable properties include `owner_id`, the readable and writeable groups and users and
+     * 'media_type'. The field `id` is used to identify the file to change. The information about
+     * the content of the file is only changed by `insert_new_version()`, `name` and `parent_id`
+     * are only changed by `move_fs_node()`.
      */
     pub fn update_file(&self, new_file: &File) -> Result<(), Error> {
         // Byte representation of permissions:
-        let mut perm_data = Vec::with_capacity(
-            4 + 8 * (new_file.read_group_ids.len() + new_file.write_group_ids.len()),
-        );
-        serialize_id_list(new_file.read_group_ids.as_slice(), &mut perm_data);
-        serialize_id_list(new_file.write_group_ids.as_slice(), &mut perm_data);
+        let perm_data = serialize_permissions(&new_file.acl, new_file.inherit_permissions);
 
         (&self.file_tree, &self.permissions_tree).transaction(|(file_t, perm_t)| {
             // Get current version of the file:
@@ -208,14 +756,17 @@ impl FsDatabase {
             };
 
             let mut new_bytes = Vec::with_capacity(old_bytes.len());
-            // Add parent_id:
-            new_bytes.extend_from_slice(&new_file.parent_id.to_be_bytes());
+            // Keep parent_id:
+            new_bytes.extend_from_slice(&old_bytes[0..8]);
             // Add owner_id:
             new_bytes.extend_from_slice(&new_file.owner_id.to_be_bytes());
-            // Add name:
-            string_to_bytes(&new_file.name, &mut new_bytes);
+            // Keep name:
+            let name_end = 18 + u16::from_be_bytes(old_bytes[16..18].try_into().unwrap()) as usize;
+            new_bytes.extend_from_slice(&old_bytes[16..name_end]);
             // Add media_type:
             string_to_bytes(&new_file.media_type, &mut new_bytes);
+            // Keep content information:
+            new_bytes.extend_from_slice(&old_bytes[file_info_offset(&old_bytes)..]);
 
             // Insert new File:
             file_t.insert(&new_file.id.to_be_bytes(), new_bytes)?;
@@ -228,96 +779,31 @@ impl FsDatabase {
         Ok(())
     }
 
-    /// Removes the file with the given id from the DB and returns its representation. Returns an
-    /// Error with type NoSuchFile, if there is no file with the given id in the DB.
-    pub fn remove_file(&self, id: u64) -> Result<File, Error> {
-        let mut res = (&self.file_tree, &self.dir_tree)
-            .transaction(|(file_tt, dir_tt)| {
-                // Remove file from file-tree:
-                let bytes = match file_tt.remove(&id.to_be_bytes())? {
-                    Some(b) => b,
-                    None => {
-                        return Err(ConflictableTransactionError::Abort(Error::NoSuchFile));
-                    }
-                };
-
-                let (name, name_len) = parse_db_string(&bytes[16..]);
-                let (media_type, _) = parse_db_string(&bytes[(16 + name_len)..]);
-                let res = File {
-                    id,
-                    parent_id: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
-                    owner_id: u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
-                    read_group_ids: Vec::new(),
-                    write_group_ids: Vec::new(),
-                    name,
-                    media_type,
-                };
-
-                // Remove file from parent:
-                // Get current parent representation:
-                let mut parent_bytes = match dir_tt.get(res.parent_id.to_be_bytes()) {
-                    Ok(Some(b)) => Vec::from(b.as_ref()),
-                    Ok(None) => {
-                        return Err(ConflictableTransactionError::Abort(
-                            Error::InconsistentDbState,
-                        ));
-                    }
-                    Err(e) => {
-                        return Err(ConflictableTransactionError::Abort(Error::from(e)));
-                    }
-                };
-
-                // Decrease child-number:
-                let mut child_number = u16::from_be_bytes(parent_bytes[16..18].try_into().unwrap());
-                // TODO: Handle overflow:
-                child_number -= 1;
-                parent_bytes[16] = child_number.to_be_bytes()[0];
-                parent_bytes[17] = child_number.to_be_bytes()[1];
-                // Add all childs, except the given one:
-                for i in 0..(child_number as usize + 1) {
-                    let child_id = u64::from_be_bytes(
-                        parent_bytes[(18 + i * 8)..(26 + i * 8)].try_into().unwrap(),
-                    );
-                    if child_id == id {
-                        std::mem::drop(parent_bytes.drain((18 + i * 8)..(26 + i * 8)));
-                        break;
-                    }
-                }
-
-                // Insert new parent directory:
-                if let Err(e) = dir_tt.insert(&res.parent_id.to_be_bytes(), parent_bytes) {
-                    return Err(ConflictableTransactionError::Abort(Error::from(e)));
-                }
-
-                Ok(res)
-            })
-            .map_err(Error::from)?;
-
-        if let Some(perm_bytes) = self.permissions_tree.remove(&id.to_be_bytes())? {
-            res.read_group_ids = parse_read_group_ids(&perm_bytes);
-            res.write_group_ids = parse_write_group_ids(&perm_bytes);
-        }
-
-        Ok(res)
-    }
-
     /// Inserts a new dir with the given attributes in the DB. The ID if the given Dir will be set
-    /// to a new unique value.
+    /// to a new unique value. Names have to be unique within the parent directory.
     pub fn insert_new_dir(&self, dir: &mut Dir) -> Result<(), Error> {
         // Byte representation of new dir:
         let mut data = Vec::from(&dir.parent_id.to_be_bytes()[..]);
         data.extend_from_slice(&dir.owner_id.to_be_bytes());
-        data.push(0); // Child number
-        data.push(0); // Child number
+        data.push(0); // Child number (unused)
+        data.push(0); // Child number (unused)
         data.extend_from_slice(dir.name.as_bytes());
         // Byte representation of permissions:
-        let mut perm_data =
-            Vec::with_capacity(4 + 8 * (dir.read_group_ids.len() + dir.write_group_ids.len()));
-        serialize_id_list(dir.read_group_ids.as_slice(), &mut perm_data);
-        serialize_id_list(dir.write_group_ids.as_slice(), &mut perm_data);
-
-        dir.id = (&self.dir_tree, &self.permissions_tree, &self.file_tree).transaction(
-            |(dir_t, perm_t, file_t)| {
+        let perm_data = serialize_permissions(&dir.acl, dir.inherit_permissions);
+
+        let stats_data = serialize_dir_stats(dir);
+        let name_key = name_key(dir.parent_id, &dir.name);
+
+        dir.id = self
+            .fs_transaction(|trees| {
+                let FsTrees {
+                    file_t,
+                    dir_t,
+                    perm_t,
+                    stats_t,
+                    name_t,
+                    ..
+                } = trees;
                 // Generate new dir-id:
                 let mut rng = thread_rng();
                 let mut dir_id = [0u8; 8];
@@ -328,177 +814,47 @@ impl FsDatabase {
                 {
                     rng.fill_bytes(&mut dir_id);
                 }
-                let parent_bytes = if let Some(b) = dir_t.get(dir.parent_id.to_be_bytes())? {
-                    b
-                } else {
+                if dir_t.get(dir.parent_id.to_be_bytes())?.is_none() {
                     return Err(ConflictableTransactionError::Abort(Error::NoSuchDir));
-                };
-                let mut new_parent_bytes = Vec::from(&parent_bytes[0..16]);
-
-                // Increase child-number:
-                let mut child_number = u16::from_be_bytes(parent_bytes[16..18].try_into().unwrap());
-                // TODO: Handle overflow:
-                child_number += 1;
-                new_parent_bytes.push(child_number.to_be_bytes()[0]);
-                new_parent_bytes.push(child_number.to_be_bytes()[1]);
-                // Add old childs:
-                new_parent_bytes
-                    .extend_from_slice(&parent_bytes[18..(18 + (child_number as usize - 1) * 8)]);
-                // Add new child:
-                new_parent_bytes.extend_from_slice(&dir_id);
-                // Add name of parent:
-                new_parent_bytes
-                    .extend_from_slice(&parent_bytes[(18 + (child_number as usize - 1) * 8)..]);
-
-                // Insert new parent directory:
-                dir_t.insert(&dir.parent_id.to_be_bytes(), new_parent_bytes)?;
+                }
+                if name_t.get(name_key.as_slice())?.is_some() {
+                    return Err(ConflictableTransactionError::Abort(Error::TargetExists));
+                }
+
                 // Insert directory into dir-tree:
                 dir_t.insert(&dir_id, data.as_slice())?;
+                // Insert statistics into dir-stats-tree:
+                stats_t.insert(&dir_id, stats_data.as_slice())?;
+                add_child_t(trees, dir.parent_id, u64::from_be_bytes(dir_id))?;
+                name_t.insert(name_key.as_slice(), &dir_id)?;
                 // Insert permissions into permissions-tree:
                 perm_t.insert(&dir_id, perm_data.as_slice())?;
+                touch_dir(stats_t, dir.parent_id)?;
 
                 Ok(u64::from_be_bytes(dir_id))
-            },
-        )?;
+            })
+            .map_err(transaction_error)?;
 
         Ok(())
     }
 
     /**
-     * Changes the properties of the given Dir in the DB to the values given by the parameter
-     * `dir`.
+     * Moves the File or Dir given by `id` into the directory given by `parent_id` and renames it
+     * to `name`.
      *
-     * Changeable properties include `name`, `owner_id` and `parent_id`. The field `id` is used to
-     * identify the directory to change.     *
+     * If there is no such (untrashed) FsNode, `Error::NoSuchTarget` is returned. If there is no
+     * (untrashed) directory with ID `parent_id`, `Error::NoSuchDir` is returned. If another child
+     * of the new parent already has the given name, `Error::TargetExists` is returned.
+     * `Error::ForbiddenAction` is returned, if the FsNode is the root directory of a user or if a
+     * directory would be moved into itself or one of its descendants.
      */
-    pub fn update_dir(&self, new_dir: &Dir) -> Result<(), Error> {
-        // Byte representation of permissions:
-        let mut perm_data = Vec::with_capacity(
-            4 + 8 * (new_dir.read_group_ids.len() + new_dir.write_group_ids.len()),
-        );
-        serialize_id_list(new_dir.read_group_ids.as_slice(), &mut perm_data);
-        serialize_id_list(new_dir.write_group_ids.as_slice(), &mut perm_data);
-
-        (&self.dir_tree, &self.permissions_tree).transaction(|(dir_t, perm_t)| {
-            // Get current version of the dir:
-            let old_bytes = match dir_t.get(new_dir.id.to_be_bytes())? {
-                Some(b) => b,
-                None => {
-                    return Err(ConflictableTransactionError::Abort(Error::NoSuchDir));
-                }
-            };
-
-            let mut new_bytes = Vec::with_capacity(old_bytes.len());
-            // Add parent_id:
-            new_bytes.extend_from_slice(&new_dir.parent_id.to_be_bytes());
-            // Add owner_id:
-            new_bytes.extend_from_slice(&new_dir.owner_id.to_be_bytes());
-            // Add childs:
-            let end_of_childs: usize = (18
-                + 8 * (u16::from_be_bytes(old_bytes[16..18].try_into().unwrap())))
-            .try_into()
-            .unwrap();
-            new_bytes.extend_from_slice(&old_bytes[18..end_of_childs]);
-            // Add name:
-            new_bytes.extend_from_slice(new_dir.name.as_bytes());
-
-            // Insert new Dir:
-            dir_t.insert(&new_dir.id.to_be_bytes(), new_bytes)?;
-            // Insert permissions into permissions-tree:
-            perm_t.insert(&new_dir.id.to_be_bytes(), perm_data.as_slice())?;
-
-            Ok(())
-        })?;
-
+    pub fn move_fs_node(&self, id: u64, parent_id: u64, name: &str) -> Result<(), Error> {
+        self.fs_transaction(|trees| move_fs_node_t(trees, id, parent_id, name))
+            .map_err(transaction_error)?;
+        self.clear_inheritance_cache();
         Ok(())
     }
 
-    /**
-     * Removes the directory with the given id from the DB and returns its representation.
-     * Returns an Error with type NoSuchDir, if there is no directory with the given id in the DB.
-     */
-    pub fn remove_dir(&self, id: u64) -> Result<Dir, Error> {
-        // Return an Err, if it is the root dir of an user:
-        if let Some(b) = self.dir_tree.get(id.to_be_bytes())? {
-            let dir = entry_to_dir_incomplete(id, &b);
-            if dir.parent_id == 0 {
-                return Err(Error::ForbiddenAction);
-            }
-        } else {
-            return Err(Error::NoSuchDir);
-        };
-
-        let mut res = (&self.dir_tree, &self.file_tree, &self.permissions_tree)
-            .transaction(|(dir_t, file_t, perm_t)| {
-                let dir = if let Some(b) = self.dir_tree.get(id.to_be_bytes())? {
-                    entry_to_dir_incomplete(id, &b)
-                } else {
-                    return Err(ConflictableTransactionError::Abort(Error::NoSuchDir));
-                };
-
-                // Remove directory from parent:
-                // Get current parent representation:
-                let mut parent_bytes = match dir_t.get(dir.parent_id.to_be_bytes()) {
-                    Ok(Some(b)) => Vec::from(b.as_ref()),
-                    Ok(None) => {
-                        return Err(ConflictableTransactionError::Abort(Error::NoSuchDir));
-                    }
-                    Err(e) => {
-                        return Err(ConflictableTransactionError::Abort(Error::from(e)));
-                    }
-                };
-
-                // Decrease child-number:
-                let mut child_number = u16::from_be_bytes(parent_bytes[16..18].try_into().unwrap());
-                // TODO: Handle overflow:
-                child_number -= 1;
-                parent_bytes[16] = child_number.to_be_bytes()[0];
-                parent_bytes[17] = child_number.to_be_bytes()[1];
-                // Add all childs, except the given one:
-                for i in 0..(child_number as usize + 1) {
-                    let child_id = u64::from_be_bytes(
-                        parent_bytes[(18 + i * 8)..(26 + i * 8)].try_into().unwrap(),
-                    );
-                    if child_id == id {
-                        std::mem::drop(parent_bytes.drain((18 + i * 8)..(26 + i * 8)));
-                        break;
-                    }
-                }
-
-                // Insert new parent directory:
-                if let Err(e) = dir_t.insert(&dir.parent_id.to_be_bytes(), parent_bytes) {
-                    return Err(ConflictableTransactionError::Abort(Error::from(e)));
-                }
-
-                // Remove childs from DB:
-                let mut todo_stack: Vec<u64> = dir.child_ids.clone();
-
-                while !todo_stack.is_empty() {
-                    let next_id = todo_stack.pop().unwrap();
-                    if let Some(bytes) = dir_t.remove(&next_id.to_be_bytes())? {
-                        let dir = entry_to_dir_incomplete(next_id, &bytes);
-                        for ch in dir.child_ids {
-                            todo_stack.push(ch);
-                        }
-                    } else {
-                        file_t.remove(&next_id.to_be_bytes())?;
-                        // TODO: Remove the file from storage
-                    }
-                    perm_t.remove(&next_id.to_be_bytes())?;
-                }
-
-                Ok(dir)
-            })
-            .map_err(Error::from)?;
-
-        if let Some(perm_bytes) = self.permissions_tree.remove(&id.to_be_bytes())? {
-            res.read_group_ids = parse_read_group_ids(&perm_bytes);
-            res.write_group_ids = parse_write_group_ids(&perm_bytes);
-        }
-
-        Ok(res)
-    }
-
     /**
      * Adds the group Id `group_id` to the list of readable groups for the file or directory given
      * by `fs_node_id`.
@@ -507,29 +863,7 @@ impl FsDatabase {
      * `Err(Error::NoSuchTarget)` is returned.
      */
     pub fn add_readable_group(&self, fs_node_id: u64, group_id: u64) -> Result<(), Error> {
-        self.permissions_tree
-            .transaction(|perm_t| {
-                let old_bytes = perm_t
-                    .get(&fs_node_id.to_be_bytes())?
-                    .ok_or(ConflictableTransactionError::Abort(Error::NoSuchTarget))?;
-                let mut new_bytes = Vec::with_capacity(old_bytes.len());
-                // Increase number of IDs:
-                let new_len = 1 + u16::from_be_bytes(old_bytes[..2].try_into().unwrap()); // TODO: Handle overflow
-                new_bytes.extend_from_slice(&new_len.to_be_bytes());
-                let second_list_start: usize =
-                    (2 + u16::from_be_bytes(old_bytes[..2].try_into().unwrap()) * 8)
-                        .try_into()
-                        .unwrap();
-                new_bytes.extend_from_slice(&old_bytes[2..second_list_start]);
-                // Append new ID:
-                new_bytes.extend_from_slice(&group_id.to_be_bytes());
-                new_bytes.extend_from_slice(&old_bytes[second_list_start..]);
-                // Write changes:
-                perm_t.insert(&fs_node_id.to_be_bytes(), new_bytes.as_slice())?;
-
-                Ok(())
-            })
-            .map_err(Error::from)
+        self.change_acl(fs_node_id, group_id, false, false, true)
     }
 
     /**
@@ -540,57 +874,1033 @@ impl FsDatabase {
      * `Err(Error::NoSuchTarget)` is returned.
      */
     pub fn add_writeable_group(&self, fs_node_id: u64, group_id: u64) -> Result<(), Error> {
-        self.permissions_tree
-            .transaction(|perm_t| {
-                let old_bytes = perm_t
-                    .get(&fs_node_id.to_be_bytes())?
-                    .ok_or(ConflictableTransactionError::Abort(Error::NoSuchTarget))?;
-                let mut new_bytes = Vec::from(old_bytes.as_ref());
-                // Increase number of IDs:
-                let second_list_start: usize =
-                    (2 + u16::from_be_bytes(old_bytes[..2].try_into().unwrap()) * 8)
-                        .try_into()
-                        .unwrap();
-                let new_len = 1 + u16::from_be_bytes(
-                    old_bytes[second_list_start..(second_list_start + 2)]
-                        .try_into()
-                        .unwrap(),
-                ); // TODO: Handle overflow
-                new_bytes[second_list_start] = new_len.to_be_bytes()[0];
-                new_bytes[second_list_start + 1] = new_len.to_be_bytes()[1];
-                // Append new ID:
-                new_bytes.extend_from_slice(&group_id.to_be_bytes());
-                // Write changes:
-                perm_t.insert(&fs_node_id.to_be_bytes(), new_bytes.as_slice())?;
-
-                Ok(())
-            })
-            .map_err(Error::from)
+        self.change_acl(fs_node_id, group_id, false, true, true)
     }
-}
-
-//
-// Helper functions for serialization and deserialization:
-//
 
-fn parse_read_group_ids(bytes: &[u8]) -> Vec<u64> {
-    parse_id_list(bytes)
-}
-fn parse_write_group_ids(bytes: &[u8]) -> Vec<u64> {
-    let start = 2 + 8 * (u16::from_be_bytes(bytes[0..2].try_into().unwrap()) as usize);
-    parse_id_list(&bytes[start..])
-}
+    /**
+     * Removes the group Id `group_id` from the list of readable groups for the file or directory
+     * given by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn remove_readable_group(&self, fs_node_id: u64, group_id: u64) -> Result<(), Error> {
+        self.change_acl(fs_node_id, group_id, false, false, false)
+    }
 
-fn parse_id_list(bytes: &[u8]) -> Vec<u64> {
-    let list_len = u16::from_be_bytes(bytes[0..2].try_into().unwrap())
-        .try_into()
-        .unwrap();
-    let mut id_list = Vec::with_capacity(list_len);
-    for i in (2..(2 + 8 * list_len)).step_by(8) {
-        id_list.push(u64::from_be_bytes(bytes[i..(i + 8)].try_into().unwrap()));
+    /**
+     * Removes the group Id `group_id` from the list of writeable groups for the file or directory
+     * given by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn remove_writeable_group(&self, fs_node_id: u64, group_id: u64) -> Result<(), Error> {
+        self.change_acl(fs_node_id, group_id, false, true, false)
     }
-    id_list
-}
+
+    /**
+     * Adds the user Id `user_id` to the list of readable users for the file or directory given
+     * by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn add_readable_user(&self, fs_node_id: u64, user_id: u64) -> Result<(), Error> {
+        self.change_acl(fs_node_id, user_id, true, false, true)
+    }
+
+    /**
+     * Adds the user Id `user_id` to the list of writeable users for the file or directory given
+     * by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn add_writeable_user(&self, fs_node_id: u64, user_id: u64) -> Result<(), Error> {
+        self.change_acl(fs_node_id, user_id, true, true, true)
+    }
+
+    /**
+     * Removes the user Id `user_id` from the list of readable users for the file or directory given
+     * by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn remove_readable_user(&self, fs_node_id: u64, user_id: u64) -> Result<(), Error> {
+        self.change_acl(fs_node_id, user_id, true, false, false)
+    }
+
+    /**
+     * Removes the user Id `user_id` from the list of writeable users for the file or directory
+     * given by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn remove_writeable_user(&self, fs_node_id: u64, user_id: u64) -> Result<(), Error> {
+        self.change_acl(fs_node_id, user_id, true, true, false)
+    }
+
+    /// Adds the group or user to or removes it from the readable or writeable groups or users of
+    /// the FsNode given by `fs_node_id` (see `change_acl_t()`).
+    fn change_acl(
+        &self,
+        fs_node_id: u64,
+        id: u64,
+        user: bool,
+        write: bool,
+        add: bool,
+    ) -> Result<(), Error> {
+        self.permissions_tree
+            .transaction(|perm_t| change_acl_t(perm_t, fs_node_id, id, user, write, add))
+            .map_err(transaction_error)?;
+        self.clear_inheritance_cache();
+        Ok(())
+    }
+
+    /**
+     * Sets the additional rights of the group or user (if `user` is true) given by `id` on the
+     * file or directory given by `fs_node_id` to `rights`. If `rights` is empty, the entry is
+     * removed.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn set_rights(
+        &self,
+        fs_node_id: u64,
+        id: u64,
+        user: bool,
+        rights: Rights,
+    ) -> Result<(), Error> {
+        self.permissions_tree
+            .transaction(|perm_t| {
+                let old_bytes = perm_t
+                    .get(fs_node_id.to_be_bytes())?
+                    .ok_or(ConflictableTransactionError::Abort(Error::NoSuchTarget))?;
+                let (mut acl, inherit) = parse_permissions(&old_bytes);
+
+                let entries = if user {
+                    &mut acl.user_rights
+                } else {
+                    &mut acl.group_rights
+                };
+                match entries.iter().position(|(entry_id, _)| *entry_id == id) {
+                    Some(index) if rights.is_empty() => {
+                        entries.remove(index);
+                    }
+                    Some(index) => entries[index].1 = rights,
+                    None if rights.is_empty() => {}
+                    None if entries.len() >= u16::MAX as usize => {
+                        return Err(ConflictableTransactionError::Abort(Error::ForbiddenAction));
+                    }
+                    None => entries.push((id, rights)),
+                }
+
+                perm_t.insert(
+                    &fs_node_id.to_be_bytes(),
+                    serialize_permissions(&acl, inherit),
+                )?;
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+        self.clear_inheritance_cache();
+        Ok(())
+    }
+
+    /**
+     * Replaces the groups, users and rights for the file or directory given by `fs_node_id` by
+     * the given ones. If `inherit` is not None, it also sets, whether the FsNode inherits the
+     * groups, users and rights of its parent.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned. If a list is too long for the DB,
+     * `Err(Error::ForbiddenAction)` is returned.
+     */
+    pub fn set_permissions(
+        &self,
+        fs_node_id: u64,
+        acl: &Acl,
+        inherit: Option<bool>,
+    ) -> Result<(), Error> {
+        if !acl_fits(acl) {
+            return Err(Error::ForbiddenAction);
+        }
+
+        self.permissions_tree
+            .transaction(|perm_t| {
+                let old_bytes = perm_t
+                    .get(fs_node_id.to_be_bytes())?
+                    .ok_or(ConflictableTransactionError::Abort(Error::NoSuchTarget))?;
+                let inherit = inherit.unwrap_or_else(|| parse_permissions(&old_bytes).1);
+                let new_bytes = serialize_permissions(acl, inherit);
+                perm_t.insert(&fs_node_id.to_be_bytes(), new_bytes)?;
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+        self.clear_inheritance_cache();
+        Ok(())
+    }
+
+    /**
+     * Applies all given changes in a single transaction, so that either all or none of them are
+     * applied. Every change sees the results of the preceding changes.
+     *
+     * If a change fails, no change is applied and the index of the failed change and its Error
+     * (see the functions for the single changes) are returned in the inner `Err`. Adding a group
+     * to a FsNode, which already contains it, or removing a group, which it does not contain, does
+     * not change anything.
+     */
+    pub fn apply_changes(&self, changes: &[FsChange]) -> Result<Result<(), (usize, Error)>, Error> {
+        let res = self.fs_transaction(|trees| {
+            for (i, change) in changes.iter().enumerate() {
+                let res = match change {
+                    FsChange::Trash { id } => move_to_trash_t(trees, *id).map(|_| ()),
+                    FsChange::Move {
+                        id,
+                        parent_id,
+                        name,
+                    } => move_fs_node_t(trees, *id, *parent_id, name),
+                    FsChange::AddGroup {
+                        id,
+                        group_id,
+                        write,
+                    } => change_acl_t(trees.perm_t, *id, *group_id, false, *write, true),
+                    FsChange::RemoveGroup {
+                        id,
+                        group_id,
+                        write,
+                    } => change_acl_t(trees.perm_t, *id, *group_id, false, *write, false),
+                };
+                res.map_err(|err| match err {
+                    ConflictableTransactionError::Abort(err) => {
+                        ConflictableTransactionError::Abort((i, err))
+                    }
+                    ConflictableTransactionError::Storage(err) => {
+                        ConflictableTransactionError::Storage(err)
+                    }
+                    _ => ConflictableTransactionError::Conflict,
+                })?;
+            }
+            Ok(())
+        });
+
+        self.clear_inheritance_cache();
+        match res {
+            Ok(()) => Ok(Ok(())),
+            Err(TransactionError::Abort(err)) => Ok(Err(err)),
+            Err(TransactionError::Storage(err)) => Err(Error::from(err)),
+        }
+    }
+
+    /// Returns the Share with the given token, if it exists in the DB, or None otherwise.
+    pub fn get_share(&self, token: &str) -> Result<Option<Share>, Error> {
+        Ok(self
+            .share_tree
+            .get(token.as_bytes())?
+            .map(|bytes| parse_share(token, &bytes)))
+    }
+
+    /// Returns all Shares of the file or directory given by `fs_node_id`.
+    pub fn get_shares_by_node(&self, fs_node_id: u64) -> Result<Vec<Share>, Error> {
+        let mut res = Vec::new();
+        for entry in self.node_share_tree.scan_prefix(fs_node_id.to_be_bytes()) {
+            let (key, _) = entry?;
+            let token = String::from_utf8(Vec::from(&key[8..]))?;
+            if let Some(share) = self.get_share(&token)? {
+                res.push(share);
+            }
+        }
+        Ok(res)
+    }
+
+    /**
+     * Inserts the given Share into the DB.
+     * The function generates a new unguessable token for the Share and updates the token field
+     * accordingly.
+     */
+    pub fn insert_new_share(&self, share: &mut Share) -> Result<(), Error> {
+        share.token =
+            (&self.share_tree, &self.node_share_tree).transaction(|(share_t, node_t)| {
+                // Generate new token:
+                let mut token = generate_token();
+                while share_t.get(token.as_bytes())?.is_some() {
+                    token = generate_token();
+                }
+
+                share_t.insert(token.as_bytes(), serialize_share(share))?;
+                let mut node_key = Vec::from(share.fs_node_id.to_be_bytes());
+                node_key.extend_from_slice(token.as_bytes());
+                node_t.insert(node_key, &[])?;
+
+                let res: Result<String, ConflictableTransactionError> = Ok(token);
+                res
+            })?;
+
+        Ok(())
+    }
+
+    /// Returns the OwnershipOffer for the file or directory given by `fs_node_id`, if there is one.
+    pub fn get_ownership_offer(&self, fs_node_id: u64) -> Result<Option<OwnershipOffer>, Error> {
+        Ok(self
+            .offer_tree
+            .get(fs_node_id.to_be_bytes())?
+            .map(|bytes| parse_ownership_offer(fs_node_id, &bytes)))
+    }
+
+    /// Returns all OwnershipOffers, that were made by or to the User given by `user_id`. All
+    /// offers are read to find them.
+    pub fn get_ownership_offers_by_user(&self, user_id: u64) -> Result<Vec<OwnershipOffer>, Error> {
+        let mut res = Vec::new();
+        for entry in self.offer_tree.iter() {
+            let (key, value) = entry?;
+            let offer =
+                parse_ownership_offer(u64::from_be_bytes(key[..].try_into().unwrap()), &value);
+            if offer.owner_id == user_id || offer.new_owner_id == user_id {
+                res.push(offer);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Inserts the given OwnershipOffer into the DB. A previous offer for the same file or
+    /// directory is replaced.
+    pub fn insert_ownership_offer(&self, offer: &OwnershipOffer) -> Result<(), Error> {
+        self.offer_tree.insert(
+            offer.fs_node_id.to_be_bytes(),
+            serialize_ownership_offer(offer),
+        )?;
+        Ok(())
+    }
+
+    /// Removes the OwnershipOffer for the file or directory given by `fs_node_id` from the DB and
+    /// returns it. If there is no such offer, `Error::NoSuchTarget` is returned.
+    pub fn remove_ownership_offer(&self, fs_node_id: u64) -> Result<OwnershipOffer, Error> {
+        self.offer_tree
+            .remove(fs_node_id.to_be_bytes())?
+            .map(|bytes| parse_ownership_offer(fs_node_id, &bytes))
+            .ok_or(Error::NoSuchTarget)
+    }
+
+    /**
+     * Accepts the OwnershipOffer for the file or directory given by `fs_node_id`: The User given
+     * by `new_owner_id` becomes the owner of the FsNode and of all its descendants, which belong
+     * to the previous owner. Descendants in the trash are not changed. The offer is removed and
+     * returned together with the IDs of all files, whose owner was changed.
+     *
+     * If there is no offer for the FsNode to the given User or the FsNode does not exist or
+     * belong to the User, who made the offer, anymore, `Error::NoSuchTarget` is returned.
+     */
+    pub fn accept_ownership_offer(
+        &self,
+        fs_node_id: u64,
+        new_owner_id: u64,
+    ) -> Result<(OwnershipOffer, Vec<u64>), Error> {
+        // Collect the descendants, the transaction skips the ones trashed in the meantime:
+        let mut node_ids = vec![fs_node_id];
+        let mut dir_ids = Vec::new();
+        if self.dir_tree.contains_key(fs_node_id.to_be_bytes())? {
+            dir_ids.push(fs_node_id);
+        }
+        while let Some(dir_id) = dir_ids.pop() {
+            let child_ids = match self.get_dirs_childs(dir_id) {
+                Ok(ids) => ids,
+                // The directory was removed in parallel:
+                Err(Error::EntryNotFound) => continue,
+                Err(err) => return Err(err),
+            };
+            for child_id in child_ids {
+                node_ids.push(child_id);
+                if self.dir_tree.contains_key(child_id.to_be_bytes())? {
+                    dir_ids.push(child_id);
+                }
+            }
+        }
+
+        let res = (
+            &self.offer_tree,
+            &self.file_tree,
+            &self.dir_tree,
+            &self.trashed_tree,
+        )
+            .transaction(|(offer_t, file_t, dir_t, trashed_t)| {
+                let offer = match offer_t.remove(&fs_node_id.to_be_bytes())? {
+                    Some(bytes) => parse_ownership_offer(fs_node_id, &bytes),
+                    None => {
+                        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                    }
+                };
+                if offer.new_owner_id != new_owner_id {
+                    return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                }
+
+                let mut file_ids = Vec::new();
+                for id in &node_ids {
+                    if trashed_t.get(id.to_be_bytes())?.is_some() {
+                        if *id == fs_node_id {
+                            return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                        }
+                        continue;
+                    }
+                    let (is_file, mut bytes) = if let Some(bytes) = file_t.get(id.to_be_bytes())? {
+                        (true, bytes.to_vec())
+                    } else if let Some(bytes) = dir_t.get(id.to_be_bytes())? {
+                        (false, bytes.to_vec())
+                    } else if *id == fs_node_id {
+                        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                    } else {
+                        continue;
+                    };
+
+                    // Files and directories store their owner after their parent:
+                    let owner_id = u64::from_be_bytes(bytes[8..16].try_into().unwrap());
+                    if owner_id != offer.owner_id {
+                        if *id == fs_node_id {
+                            return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                        }
+                        continue;
+                    }
+                    bytes[8..16].copy_from_slice(&new_owner_id.to_be_bytes());
+                    if is_file {
+                        file_t.insert(&id.to_be_bytes(), bytes)?;
+                        file_ids.push(*id);
+                    } else {
+                        dir_t.insert(&id.to_be_bytes(), bytes)?;
+                    }
+                }
+
+                Ok((offer, file_ids))
+            })
+            .map_err(transaction_error);
+        // The owners of the directories are inherited by their descendants:
+        self.clear_inheritance_cache();
+        res
+    }
+
+    /**
+     * Increases the download counter of the Share given by `token` and returns the updated Share.
+     *
+     * If there is no Share with the given token, `Error::NoSuchTarget` is returned. If the download
+     * limit of the Share is already reached, `Error::ForbiddenAction` is returned.
+     */
+    pub fn add_share_download(&self, token: &str) -> Result<Share, Error> {
+        self.share_tree
+            .transaction(|share_t| {
+                let mut share = match share_t.get(token.as_bytes())? {
+                    Some(bytes) => parse_share(token, &bytes),
+                    None => {
+                        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                    }
+                };
+                if share.is_exhausted() {
+                    return Err(ConflictableTransactionError::Abort(Error::ForbiddenAction));
+                }
+                share.download_count += 1;
+                share_t.insert(token.as_bytes(), serialize_share(&share))?;
+
+                Ok(share)
+            })
+            .map_err(transaction_error)
+    }
+
+    /// Removes the Share with the given token from the DB and returns its representation.
+    /// Returns an Error with type NoSuchTarget, if there is no Share with the given token.
+    pub fn remove_share(&self, token: &str) -> Result<Share, Error> {
+        (&self.share_tree, &self.node_share_tree)
+            .transaction(|(share_t, node_t)| {
+                let share = match share_t.remove(token.as_bytes())? {
+                    Some(bytes) => parse_share(token, &bytes),
+                    None => {
+                        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                    }
+                };
+                let mut node_key = Vec::from(share.fs_node_id.to_be_bytes());
+                node_key.extend_from_slice(token.as_bytes());
+                node_t.remove(node_key)?;
+
+                Ok(share)
+            })
+            .map_err(transaction_error)
+    }
+
+    /// Removes all Shares of the file or directory given by `fs_node_id`.
+    fn remove_shares_of_node(&self, fs_node_id: u64) -> Result<(), Error> {
+        for entry in self.node_share_tree.scan_prefix(fs_node_id.to_be_bytes()) {
+            let (key, _) = entry?;
+            self.share_tree.remove(&key[8..])?;
+            self.node_share_tree.remove(key)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the IDs of all files, that are not reachable anymore: The files are not in the trash
+     * and their parent directory does not exist.
+     */
+    pub fn get_dangling_file_ids(&self) -> Result<Vec<u64>, Error> {
+        let mut res = Vec::new();
+        for entry in self.file_tree.iter() {
+            let (key, value) = entry?;
+            let id = u64::from_be_bytes(key.as_ref().try_into().unwrap());
+            let parent_id = u64::from_be_bytes(value[0..8].try_into().unwrap());
+            if !self.trashed_tree.contains_key(&key)?
+                && !self.dir_tree.contains_key(parent_id.to_be_bytes())?
+            {
+                res.push(id);
+            }
+        }
+        Ok(res)
+    }
+
+    /**
+     * Returns the IDs of all files, which do not exist anymore, but still have versions. This is
+     * the case, if removing the content of a file failed after it was removed from the trash.
+     */
+    pub fn get_orphaned_version_file_ids(&self) -> Result<Vec<u64>, Error> {
+        let mut res = Vec::new();
+        for key in self.version_tree.iter().keys() {
+            let key = key?;
+            let file_id = u64::from_be_bytes(key[0..8].try_into().unwrap());
+            // The versions are ordered by their file:
+            if res.last() != Some(&file_id) && !self.file_tree.contains_key(&key[0..8])? {
+                res.push(file_id);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the IDs of all files, including the files in the trash.
+    pub fn get_file_ids(&self) -> Result<Vec<u64>, Error> {
+        let mut res = Vec::new();
+        for key in self.file_tree.iter().keys() {
+            res.push(u64::from_be_bytes(key?.as_ref().try_into().unwrap()));
+        }
+        Ok(res)
+    }
+
+    /// Returns all FileVersions of the file given by `file_id`, ordered by their version number.
+    pub fn get_versions(&self, file_id: u64) -> Result<Vec<FileVersion>, Error> {
+        let mut res = Vec::new();
+        for entry in self.version_tree.scan_prefix(file_id.to_be_bytes()) {
+            let (key, value) = entry?;
+            res.push(parse_version(&key, &value));
+        }
+        Ok(res)
+    }
+
+    /// Returns the newest FileVersion of the file given by `file_id`, if there is one.
+    pub fn get_latest_version(&self, file_id: u64) -> Result<Option<FileVersion>, Error> {
+        Ok(self
+            .version_tree
+            .scan_prefix(file_id.to_be_bytes())
+            .next_back()
+            .transpose()?
+            .map(|(key, value)| parse_version(&key, &value)))
+    }
+
+    /// Returns the FileVersion with the given version number of the file given by `file_id`, if
+    /// it exists in the DB.
+    pub fn get_version(&self, file_id: u64, version: u64) -> Result<Option<FileVersion>, Error> {
+        Ok(self
+            .version_tree
+            .get(version_key(file_id, version))?
+            .map(|value| parse_version(&version_key(file_id, version), &value)))
+    }
+
+    /**
+     * Inserts the given FileVersion into the DB.
+     * The function assigns the next version number of the file to the FileVersion and updates the
+     * version field accordingly. The content information and the media type of the file are set
+     * to the values of the new version.
+     *
+     * If `add_ref` is true, a reference to the blob of the version is added in the same
+     * transaction, which fails with `Error::NoSuchTarget`, if the blob has no references.
+     * Otherwise the reference, that is held by the caller, is passed to the version.
+     */
+    pub fn insert_new_version(
+        &self,
+        version: &mut FileVersion,
+        add_ref: bool,
+    ) -> Result<(), Error> {
+        loop {
+            version.version = self
+                .get_latest_version(version.file_id)?
+                .map(|v| v.version + 1)
+                .unwrap_or(1);
+            match self.fs_transaction(|trees| insert_version_t(trees, version, add_ref)) {
+                Ok(()) => return Ok(()),
+                // The version number was taken in parallel:
+                Err(TransactionError::Abort(Error::TargetExists)) => {}
+                Err(err) => return Err(transaction_error(err)),
+            }
+        }
+    }
+
+    /**
+     * Removes the FileVersion with the given version number of the file given by `file_id` and
+     * its reference to its blob from the DB in a single transaction. The removed FileVersion and
+     * the remaining number of references to its blob are returned.
+     *
+     * Returns an Error with type NoSuchTarget, if there is no such FileVersion.
+     */
+    pub fn remove_version(&self, file_id: u64, version: u64) -> Result<(FileVersion, u64), Error> {
+        let key = version_key(file_id, version);
+        self.fs_transaction(|trees| {
+            let version = match trees.version_t.remove(key.as_slice())? {
+                Some(value) => parse_version(&key, &value),
+                None => return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget)),
+            };
+            let refs = change_blob_refs_t(trees.blob_ref_t, &version.content_hash, -1)?;
+            Ok((version, refs))
+        })
+        .map_err(transaction_error)
+    }
+
+    /// Returns the file IDs and version numbers of all versions, that were set aside by
+    /// `set_aside_legacy_versions()`, ordered by file and version number.
+    pub fn get_legacy_version_ids(&self) -> Result<Vec<(u64, u64)>, Error> {
+        let mut res = Vec::new();
+        for key in self.legacy_version_tree.iter().keys() {
+            let key = key?;
+            res.push((
+                u64::from_be_bytes(key[0..8].try_into().unwrap()),
+                u64::from_be_bytes(key[8..16].try_into().unwrap()),
+            ));
+        }
+        Ok(res)
+    }
+
+    /**
+     * Moves the legacy version given by `file_id` and `version` (see `get_legacy_version_ids()`)
+     * back into the version tree with the given content hash and returns it. The version keeps
+     * its number and the reference to the blob, that is held by the caller, is passed to it. The
+     * content information of the file is updated as by `insert_new_version()`.
+     *
+     * If there is no such legacy version, `Error::NoSuchTarget` is returned. If the version tree
+     * already contains a version with the same number, `Error::TargetExists` is returned.
+     */
+    pub fn import_legacy_version(
+        &self,
+        file_id: u64,
+        version: u64,
+        content_hash: ContentHash,
+    ) -> Result<FileVersion, Error> {
+        let key = version_key(file_id, version);
+        let value = self
+            .legacy_version_tree
+            .get(&key)?
+            .ok_or(Error::NoSuchTarget)?;
+        let version = FileVersion {
+            file_id,
+            version,
+            creation_date: Utc
+                .timestamp_opt(i64::from_be_bytes(value[0..8].try_into().unwrap()), 0)
+                .unwrap(),
+            uploader_id: u64::from_be_bytes(value[8..16].try_into().unwrap()),
+            size: u64::from_be_bytes(value[16..24].try_into().unwrap()),
+            content_hash,
+            media_type: String::from_utf8(Vec::from(&value[24..]))
+                .expect("DB contained file version with non-UTF-8 media type."),
+        };
+
+        self.fs_transaction(|trees| insert_version_t(trees, &version, false))
+            .map_err(transaction_error)?;
+        self.legacy_version_tree.remove(&key)?;
+        Ok(version)
+    }
+
+    /// Removes the legacy version given by `file_id` and `version` (see
+    /// `get_legacy_version_ids()`) from the DB.
+    pub fn remove_legacy_version(&self, file_id: u64, version: u64) -> Result<(), Error> {
+        self.legacy_version_tree
+            .remove(version_key(file_id, version))?;
+        Ok(())
+    }
+
+    /// Returns the maximum number of previous versions, that should be kept for the file given by
+    /// `file_id`, if there is such a limit for this file.
+    pub fn get_version_limit(&self, file_id: u64) -> Result<Option<u32>, Error> {
+        Ok(self
+            .version_limit_tree
+            .get(file_id.to_be_bytes())?
+            .map(|value| u32::from_be_bytes(value.as_ref().try_into().unwrap())))
+    }
+
+    /// Sets the maximum number of previous versions, that should be kept for the file given by
+    /// `file_id`. If `limit` is None, the limit of the file is removed.
+    pub fn set_version_limit(&self, file_id: u64, limit: Option<u32>) -> Result<(), Error> {
+        match limit {
+            Some(limit) => self
+                .version_limit_tree
+                .insert(file_id.to_be_bytes(), &limit.to_be_bytes())?,
+            None => self.version_limit_tree.remove(file_id.to_be_bytes())?,
+        };
+        Ok(())
+    }
+
+    /// Returns the entries of the trash of the user given by `owner_id`.
+    pub fn get_trash(&self, owner_id: u64) -> Result<Vec<TrashEntry>, Error> {
+        let mut res = Vec::new();
+        for entry in self.trash_tree.scan_prefix(owner_id.to_be_bytes()) {
+            let (key, value) = entry?;
+            res.push(parse_trash_entry(&key, &value));
+        }
+        Ok(res)
+    }
+
+    /// Returns the entries of the trashes of all users, that were deleted before the given date.
+    pub fn get_trash_deleted_before(&self, date: DateTime<Utc>) -> Result<Vec<TrashEntry>, Error> {
+        let mut res = Vec::new();
+        for entry in self.trash_tree.iter() {
+            let (key, value) = entry?;
+            let trash_entry = parse_trash_entry(&key, &value);
+            if trash_entry.deletion_date < date {
+                res.push(trash_entry);
+            }
+        }
+        Ok(res)
+    }
+
+    /**
+     * Moves the file or directory given by `id` into the trash of its owner and returns the new
+     * trash entry.
+     *
+     * The FsNode is removed from its parent directory and it and all its descendants are hidden,
+     * until it is restored or purged. If there is no such FsNode, `Error::NoSuchTarget` is
+     * returned. If the FsNode is the root directory of a user, `Error::ForbiddenAction` is
+     * returned.
+     */
+    pub fn move_to_trash(&self, id: u64) -> Result<TrashEntry, Error> {
+        self.fs_transaction(|trees| move_to_trash_t(trees, id))
+            .map_err(transaction_error)
+    }
+
+    /**
+     * Restores the FsNode given by `id` from the trash of the user given by `owner_id` into the
+     * directory given by `parent_id` under the name `name` and returns the updated trash entry.
+     *
+     * If there is no such entry in the trash, `Error::NoSuchTarget` is returned. If there is no
+     * (untrashed) directory with ID `parent_id`, `Error::NoSuchDir` is returned. If the directory
+     * already contains a child with the given name, `Error::TargetExists` is returned.
+     */
+    pub fn restore_from_trash(
+        &self,
+        owner_id: u64,
+        id: u64,
+        parent_id: u64,
+        name: &str,
+    ) -> Result<TrashEntry, Error> {
+        let mut key = Vec::from(owner_id.to_be_bytes());
+        key.extend_from_slice(&id.to_be_bytes());
+        let name_key = name_key(parent_id, name);
+
+        let entry = self
+            .fs_transaction(|trees| {
+                let FsTrees {
+                    file_t,
+                    dir_t,
+                    trash_t,
+                    trashed_t,
+                    stats_t,
+                    name_t,
+                    ..
+                } = trees;
+                let mut entry = match trash_t.remove(key.as_slice())? {
+                    Some(value) => parse_trash_entry(&key, &value),
+                    None => {
+                        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                    }
+                };
+
+                // Add FsNode to new parent:
+                if trashed_t.get(parent_id.to_be_bytes())?.is_some()
+                    || dir_t.get(parent_id.to_be_bytes())?.is_none()
+                {
+                    return Err(ConflictableTransactionError::Abort(Error::NoSuchDir));
+                }
+                if name_t.get(name_key.as_slice())?.is_some() {
+                    return Err(ConflictableTransactionError::Abort(Error::TargetExists));
+                }
+                add_child_t(trees, parent_id, id)?;
+                name_t.insert(name_key.as_slice(), &id.to_be_bytes())?;
+
+                // Update parent and name of the FsNode:
+                let node_t = if entry.is_dir { dir_t } else { file_t };
+                let node_bytes =
+                    node_t
+                        .get(id.to_be_bytes())?
+                        .ok_or(ConflictableTransactionError::Abort(
+                            Error::InconsistentDbState,
+                        ))?;
+                node_t.insert(
+                    &id.to_be_bytes(),
+                    rename_entry(&node_bytes, entry.is_dir, parent_id, name),
+                )?;
+                entry.parent_id = parent_id;
+                entry.name = String::from(name);
+
+                // Update statistics of the new ancestors:
+                let (size, file_count) = if entry.is_dir {
+                    dir_entry_stats(stats_t, id)?
+                } else {
+                    (file_entry_size(&node_bytes) as i64, 1)
+                };
+                add_to_dir_stats(trees, parent_id, size, file_count)?;
+                touch_dir(stats_t, parent_id)?;
+
+                // Unhide FsNode and all descendants:
+                let mut todo_stack = vec![id];
+                while let Some(next_id) = todo_stack.pop() {
+                    trashed_t.remove(&next_id.to_be_bytes())?;
+                    todo_stack.extend(child_ids_t(trees, next_id)?);
+                }
+
+                Ok(entry)
+            })
+            .map_err(transaction_error)?;
+        self.clear_inheritance_cache();
+        Ok(entry)
+    }
+
+    /**
+     * Removes the FsNode given by `id` and all its descendants from the trash of the user given
+     * by `owner_id` and from the DB. The removed trash entry and the IDs of all removed files are
+     * returned, so that their content can be removed.
+     *
+     * If there is no such entry in the trash, `Error::NoSuchTarget` is returned.
+     */
+    pub fn purge_from_trash(
+        &self,
+        owner_id: u64,
+        id: u64,
+    ) -> Result<(TrashEntry, Vec<u64>), Error> {
+        let mut key = Vec::from(owner_id.to_be_bytes());
+        key.extend_from_slice(&id.to_be_bytes());
+
+        let (entry, removed_ids, file_ids) = self
+            .fs_transaction(|trees| {
+                let FsTrees {
+                    file_t,
+                    dir_t,
+                    perm_t,
+                    stats_t,
+                    name_t,
+                    trash_t,
+                    trashed_t,
+                    child_t,
+                    position_t,
+                    ..
+                } = trees;
+                let entry = match trash_t.remove(key.as_slice())? {
+                    Some(value) => parse_trash_entry(&key, &value),
+                    None => {
+                        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                    }
+                };
+
+                // Remove FsNode and all descendants:
+                let mut removed_ids = Vec::new();
+                let mut file_ids = Vec::new();
+                let mut todo_stack = vec![id];
+                while let Some(next_id) = todo_stack.pop() {
+                    trashed_t.remove(&next_id.to_be_bytes())?;
+                    position_t.remove(&next_id.to_be_bytes())?;
+                    let node_name_key = if let Some(b) = dir_t.remove(&next_id.to_be_bytes())? {
+                        let child_ids = child_ids_t(trees, next_id)?;
+                        for position in 0..(child_ids.len() as u64) {
+                            child_t.remove(child_key(next_id, position))?;
+                        }
+                        todo_stack.extend(child_ids);
+                        stats_t.remove(&next_id.to_be_bytes())?;
+                        Some(name_key(
+                            u64::from_be_bytes(b[0..8].try_into().unwrap()),
+                            &entry_name(&b, true),
+                        ))
+                    } else if let Some(b) = file_t.remove(&next_id.to_be_bytes())? {
+                        file_ids.push(next_id);
+                        Some(name_key(
+                            u64::from_be_bytes(b[0..8].try_into().unwrap()),
+                            &entry_name(&b, false),
+                        ))
+                    } else {
+                        None
+                    };
+                    // The name of the trashed FsNode itself may be used by another FsNode:
+                    if let Some(node_name_key) = node_name_key {
+                        if name_t.get(node_name_key.as_slice())?.as_deref()
+                            == Some(&next_id.to_be_bytes()[..])
+                        {
+                            name_t.remove(node_name_key)?;
+                        }
+                    }
+                    perm_t.remove(&next_id.to_be_bytes())?;
+                    removed_ids.push(next_id);
+                }
+
+                Ok((entry, removed_ids, file_ids))
+            })
+            .map_err(transaction_error)?;
+
+        self.clear_inheritance_cache();
+        for removed_id in removed_ids {
+            self.remove_shares_of_node(removed_id)?;
+            self.offer_tree.remove(removed_id.to_be_bytes())?;
+        }
+
+        Ok((entry, file_ids))
+    }
+
+    /// Executes `f` in a transaction over all trees, that describe the structure of the file
+    /// system.
+    fn fs_transaction<T, E, F>(&self, f: F) -> Result<T, TransactionError<E>>
+    where
+        F: Fn(&FsTrees) -> ConflictableTransactionResult<T, E>,
+    {
+        (
+            &self.file_tree,
+            &self.dir_tree,
+            &self.permissions_tree,
+            &self.dir_stats_tree,
+            &self.name_tree,
+            &self.trash_tree,
+            &self.trashed_tree,
+            &self.dir_child_tree,
+            &self.child_position_tree,
+            &self.version_tree,
+            &self.blob_ref_tree,
+        )
+            .transaction(
+                |(
+                    file_t,
+                    dir_t,
+                    perm_t,
+                    stats_t,
+                    name_t,
+                    trash_t,
+                    trashed_t,
+                    child_t,
+                    position_t,
+                    version_t,
+                    blob_ref_t,
+                )| {
+                    f(&FsTrees {
+                        file_t,
+                        dir_t,
+                        perm_t,
+                        stats_t,
+                        name_t,
+                        trash_t,
+                        trashed_t,
+                        child_t,
+                        position_t,
+                        version_t,
+                        blob_ref_t,
+                    })
+                },
+            )
+    }
+}
+
+//
+// Helper functions for serialization and deserialization:
+//
+
+/// Returns the groups and users of the given permissions entry and false, if the FsNode does not
+/// inherit the groups and users of its parent.
+fn parse_permissions(bytes: &[u8]) -> (Acl, bool) {
+    let mut acl = Acl {
+        read_group_ids: parse_id_list(bytes),
+        ..Acl::default()
+    };
+    let mut offset = 2 + 8 * acl.read_group_ids.len();
+    acl.write_group_ids = parse_id_list(&bytes[offset..]);
+    offset += 2 + 8 * acl.write_group_ids.len();
+
+    // Entries written by older versions of Kasten have no flags and users:
+    let flags = match bytes.get(offset) {
+        Some(flags) => *flags,
+        None => return (acl, true),
+    };
+    offset += 1;
+    if offset < bytes.len() {
+        acl.read_user_ids = parse_id_list(&bytes[offset..]);
+        offset += 2 + 8 * acl.read_user_ids.len();
+        acl.write_user_ids = parse_id_list(&bytes[offset..]);
+        offset += 2 + 8 * acl.write_user_ids.len();
+    }
+    if offset < bytes.len() {
+        acl.group_rights = parse_rights_list(&bytes[offset..]);
+        offset += 2 + 9 * acl.group_rights.len();
+        acl.user_rights = parse_rights_list(&bytes[offset..]);
+    }
+    (acl, flags & NO_INHERITANCE_FLAG == 0)
+}
+
+/// Parses a list of (ID, rights) entries, which starts with the number of entries (u16).
+fn parse_rights_list(bytes: &[u8]) -> Vec<(u64, Rights)> {
+    let list_len = u16::from_be_bytes(bytes[0..2].try_into().unwrap()) as usize;
+    bytes[2..(2 + 9 * list_len)]
+        .chunks(9)
+        .map(|entry| {
+            (
+                u64::from_be_bytes(entry[0..8].try_into().unwrap()),
+                Rights::from_bits(entry[8]),
+            )
+        })
+        .collect()
+}
+
+/// Returns the byte representation of the permissions of a FsNode.
+fn serialize_permissions(acl: &Acl, inherit: bool) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(
+        13 + 8
+            * (acl.read_group_ids.len()
+                + acl.write_group_ids.len()
+                + acl.read_user_ids.len()
+                + acl.write_user_ids.len())
+            + 9 * (acl.group_rights.len() + acl.user_rights.len()),
+    );
+    serialize_id_list(&acl.read_group_ids, &mut buf);
+    serialize_id_list(&acl.write_group_ids, &mut buf);
+    buf.push(if inherit { 0 } else { NO_INHERITANCE_FLAG });
+    serialize_id_list(&acl.read_user_ids, &mut buf);
+    serialize_id_list(&acl.write_user_ids, &mut buf);
+    for entries in [&acl.group_rights, &acl.user_rights] {
+        buf.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        for (id, rights) in entries {
+            buf.extend_from_slice(&id.to_be_bytes());
+            buf.push(rights.bits());
+        }
+    }
+    buf
+}
+
+/// Returns true, if all lists of the given Acl are short enough to be stored in the DB.
+fn acl_fits(acl: &Acl) -> bool {
+    [
+        acl.read_group_ids.len(),
+        acl.write_group_ids.len(),
+        acl.read_user_ids.len(),
+        acl.write_user_ids.len(),
+        acl.group_rights.len(),
+        acl.user_rights.len(),
+    ]
+    .iter()
+    .all(|len| *len <= u16::MAX as usize)
+}
+
+fn parse_id_list(bytes: &[u8]) -> Vec<u64> {
+    let list_len = u16::from_be_bytes(bytes[0..2].try_into().unwrap())
+        .try_into()
+        .unwrap();
+    let mut id_list = Vec::with_capacity(list_len);
+    for i in (2..(2 + 8 * list_len)).step_by(8) {
+        id_list.push(u64::from_be_bytes(bytes[i..(i + 8)].try_into().unwrap()));
+    }
+    id_list
+}
 
 fn serialize_id_list(list: &[u64], buf: &mut Vec<u8>) {
     buf.extend_from_slice(
@@ -603,47 +1913,722 @@ fn serialize_id_list(list: &[u64], buf: &mut Vec<u8>) {
     }
 }
 
-fn entry_to_dir_incomplete(id: u64, bytes: &[u8]) -> Dir {
-    let parent_id = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
-    let owner_id = u64::from_be_bytes(bytes[8..16].try_into().unwrap());
-    let child_number = u16::from_be_bytes(bytes[16..18].try_into().unwrap()) as usize;
+fn version_key(file_id: u64, version: u64) -> Vec<u8> {
+    let mut key = Vec::from(&file_id.to_be_bytes()[..]);
+    key.extend_from_slice(&version.to_be_bytes());
+    key
+}
+
+/// Returns the offset of the content information in the given value of the file tree.
+fn file_info_offset(entry: &[u8]) -> usize {
+    let name_end = 18 + u16::from_be_bytes(entry[16..18].try_into().unwrap()) as usize;
+    name_end + 2 + u16::from_be_bytes(entry[name_end..(name_end + 2)].try_into().unwrap()) as usize
+}
 
-    let mut child_ids = Vec::with_capacity(child_number);
-    for i in 0..child_number {
-        child_ids.push(u64::from_be_bytes(
-            bytes[(20 + i * 8)..(28 + i * 8)].try_into().unwrap(),
-        ));
+fn serialize_file_info(file: &File, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&file.creation_date.timestamp().to_be_bytes());
+    buf.extend_from_slice(&file.modification_date.timestamp().to_be_bytes());
+    buf.extend_from_slice(&file.size.to_be_bytes());
+    buf.extend_from_slice(&file.uploader_id.to_be_bytes());
+    if let Some(hash) = &file.content_hash {
+        buf.extend_from_slice(&hash.0);
     }
+}
+
+/// Sets the content information of the given File to the values given by `bytes`. Returns false,
+/// if `bytes` contains no content information.
+fn parse_file_info(bytes: &[u8], file: &mut File) -> bool {
+    if bytes.len() < 32 {
+        return false;
+    }
+    file.creation_date = Utc
+        .timestamp_opt(i64::from_be_bytes(bytes[0..8].try_into().unwrap()), 0)
+        .unwrap();
+    file.modification_date = Utc
+        .timestamp_opt(i64::from_be_bytes(bytes[8..16].try_into().unwrap()), 0)
+        .unwrap();
+    file.size = u64::from_be_bytes(bytes[16..24].try_into().unwrap());
+    file.uploader_id = u64::from_be_bytes(bytes[24..32].try_into().unwrap());
+    file.content_hash = bytes
+        .get(32..64)
+        .map(|hash| ContentHash(hash.try_into().unwrap()));
+    true
+}
 
-    let name = String::from_utf8(Vec::from(&bytes[(18 + child_number * 8)..]))
-        .expect("DB contained Dir with non-UTF-8 name.");
+/// Returns the size of the content of the file given by an entry of the file tree.
+fn file_entry_size(entry: &[u8]) -> u64 {
+    entry
+        .get((file_info_offset(entry) + 16)..(file_info_offset(entry) + 24))
+        .map(|size| u64::from_be_bytes(size.try_into().unwrap()))
+        .unwrap_or(0)
+}
 
-    Dir {
-        id,
-        parent_id,
-        owner_id,
-        read_group_ids: vec![],
-        write_group_ids: vec![],
-        child_ids,
+fn serialize_dir_stats(dir: &Dir) -> Vec<u8> {
+    let mut value = Vec::from(&dir.creation_date.timestamp().to_be_bytes()[..]);
+    value.extend_from_slice(&dir.modification_date.timestamp().to_be_bytes());
+    value.extend_from_slice(&dir.size.to_be_bytes());
+    value.extend_from_slice(&dir.file_count.to_be_bytes());
+    value.extend_from_slice(&dir.child_count.to_be_bytes());
+    value
+}
+
+/// Sets the timestamps, size, file count and child count of the given Dir to the values given by
+/// `bytes`.
+fn parse_dir_stats(bytes: &[u8], dir: &mut Dir) {
+    dir.creation_date = Utc
+        .timestamp_opt(i64::from_be_bytes(bytes[0..8].try_into().unwrap()), 0)
+        .unwrap();
+    dir.modification_date = Utc
+        .timestamp_opt(i64::from_be_bytes(bytes[8..16].try_into().unwrap()), 0)
+        .unwrap();
+    dir.size = u64::from_be_bytes(bytes[16..24].try_into().unwrap());
+    dir.file_count = u64::from_be_bytes(bytes[24..32].try_into().unwrap());
+    // Statistics written by older versions of Kasten have no child count:
+    dir.child_count = bytes
+        .get(32..40)
+        .map(|count| u64::from_be_bytes(count.try_into().unwrap()))
+        .unwrap_or(0);
+}
+
+/// Returns the size and file count of the directory given by `dir_id`.
+fn dir_entry_stats(
+    stats_t: &TransactionalTree,
+    dir_id: u64,
+) -> ConflictableTransactionResult<(i64, i64), Error> {
+    let mut dir = Dir::default();
+    if let Some(bytes) = stats_t.get(dir_id.to_be_bytes())? {
+        parse_dir_stats(&bytes, &mut dir);
+    }
+    Ok((dir.size as i64, dir.file_count as i64))
+}
+
+/**
+ * Adds the given differences to the size and file count of the directory given by `dir_id` and
+ * its ancestors. Ancestors, that do not contain the previous directory anymore (because it is in
+ * the trash), are not changed.
+ */
+fn add_to_dir_stats(
+    trees: &FsTrees,
+    dir_id: u64,
+    size_diff: i64,
+    count_diff: i64,
+) -> ConflictableTransactionResult<(), Error> {
+    let FsTrees { dir_t, stats_t, .. } = trees;
+    let mut current_id = dir_id;
+    while let Some(dir_bytes) = dir_t.get(current_id.to_be_bytes())? {
+        let mut dir = Dir::default();
+        if let Some(bytes) = stats_t.get(current_id.to_be_bytes())? {
+            parse_dir_stats(&bytes, &mut dir);
+        }
+        dir.size = dir.size.saturating_add_signed(size_diff);
+        dir.file_count = dir.file_count.saturating_add_signed(count_diff);
+        stats_t.insert(&current_id.to_be_bytes(), serialize_dir_stats(&dir))?;
+
+        let parent_id = u64::from_be_bytes(dir_bytes[0..8].try_into().unwrap());
+        if !is_child_t(trees, parent_id, current_id)? {
+            break;
+        }
+        current_id = parent_id;
+    }
+    Ok(())
+}
+
+/// Sets the modification date of the directory given by `dir_id` to the current time.
+fn touch_dir(stats_t: &TransactionalTree, dir_id: u64) -> ConflictableTransactionResult<(), Error> {
+    // Directories without statistics (e.g. the parent of a root directory) are ignored:
+    if let Some(bytes) = stats_t.get(dir_id.to_be_bytes())? {
+        let mut dir = Dir::default();
+        parse_dir_stats(&bytes, &mut dir);
+        dir.modification_date = Utc::now();
+        stats_t.insert(&dir_id.to_be_bytes(), serialize_dir_stats(&dir))?;
+    }
+    Ok(())
+}
+
+/// Returns the number of childs of the directory given by `dir_id`.
+fn child_count_t(
+    stats_t: &TransactionalTree,
+    dir_id: u64,
+) -> ConflictableTransactionResult<u64, Error> {
+    let mut dir = Dir::default();
+    if let Some(bytes) = stats_t.get(dir_id.to_be_bytes())? {
+        parse_dir_stats(&bytes, &mut dir);
+    }
+    Ok(dir.child_count)
+}
+
+/// Sets the number of childs of the directory given by `dir_id` to `child_count`.
+fn set_child_count_t(
+    stats_t: &TransactionalTree,
+    dir_id: u64,
+    child_count: u64,
+) -> ConflictableTransactionResult<(), Error> {
+    let mut dir = Dir::default();
+    if let Some(bytes) = stats_t.get(dir_id.to_be_bytes())? {
+        parse_dir_stats(&bytes, &mut dir);
+    }
+    dir.child_count = child_count;
+    stats_t.insert(&dir_id.to_be_bytes(), serialize_dir_stats(&dir))?;
+    Ok(())
+}
+
+/// Returns the IDs of the childs of the directory given by `dir_id` (or an empty Vec for files).
+fn child_ids_t(trees: &FsTrees, dir_id: u64) -> ConflictableTransactionResult<Vec<u64>, Error> {
+    let FsTrees {
+        stats_t, child_t, ..
+    } = trees;
+    (0..child_count_t(stats_t, dir_id)?)
+        .map(|position| {
+            child_t
+                .get(child_key(dir_id, position))?
+                .map(|id| u64::from_be_bytes(id.as_ref().try_into().unwrap()))
+                .ok_or(ConflictableTransactionError::Abort(
+                    Error::InconsistentDbState,
+                ))
+        })
+        .collect()
+}
+
+/// Returns true, if the FsNode given by `child_id` is listed as child of the directory given by
+/// `dir_id`.
+fn is_child_t(
+    trees: &FsTrees,
+    dir_id: u64,
+    child_id: u64,
+) -> ConflictableTransactionResult<bool, Error> {
+    let FsTrees {
+        child_t,
+        position_t,
+        ..
+    } = trees;
+    let position = match position_t.get(child_id.to_be_bytes())? {
+        Some(position) => u64::from_be_bytes(position.as_ref().try_into().unwrap()),
+        None => return Ok(false),
+    };
+    Ok(child_t.get(child_key(dir_id, position))?.as_deref() == Some(&child_id.to_be_bytes()[..]))
+}
+
+/// Adds the FsNode given by `child_id` as last child to the directory given by `dir_id`.
+fn add_child_t(
+    trees: &FsTrees,
+    dir_id: u64,
+    child_id: u64,
+) -> ConflictableTransactionResult<(), Error> {
+    let FsTrees {
+        stats_t,
+        child_t,
+        position_t,
+        ..
+    } = trees;
+    let position = child_count_t(stats_t, dir_id)?;
+    child_t.insert(child_key(dir_id, position), &child_id.to_be_bytes())?;
+    position_t.insert(&child_id.to_be_bytes(), &position.to_be_bytes())?;
+    set_child_count_t(stats_t, dir_id, position + 1)
+}
+
+/// Removes the FsNode given by `child_id` from the childs of the directory given by `dir_id`. The
+/// last child of the directory takes its position.
+fn remove_child_t(
+    trees: &FsTrees,
+    dir_id: u64,
+    child_id: u64,
+) -> ConflictableTransactionResult<(), Error> {
+    if !is_child_t(trees, dir_id, child_id)? {
+        return Ok(());
+    }
+    let FsTrees {
+        stats_t,
+        child_t,
+        position_t,
+        ..
+    } = trees;
+    let inconsistent = || ConflictableTransactionError::Abort(Error::InconsistentDbState);
+
+    let position = position_t
+        .remove(&child_id.to_be_bytes())?
+        .map(|position| u64::from_be_bytes(position.as_ref().try_into().unwrap()))
+        .ok_or_else(inconsistent)?;
+    let last_position = child_count_t(stats_t, dir_id)?
+        .checked_sub(1)
+        .ok_or_else(inconsistent)?;
+    let last_id = child_t
+        .remove(child_key(dir_id, last_position))?
+        .ok_or_else(inconsistent)?;
+    if position != last_position {
+        child_t.insert(child_key(dir_id, position), last_id.clone())?;
+        position_t.insert(last_id, &position.to_be_bytes())?;
+    }
+    set_child_count_t(stats_t, dir_id, last_position)
+}
+
+fn serialize_version(version: &FileVersion) -> Vec<u8> {
+    let mut value = Vec::from(&version.creation_date.timestamp().to_be_bytes()[..]);
+    value.extend_from_slice(&version.uploader_id.to_be_bytes());
+    value.extend_from_slice(&version.size.to_be_bytes());
+    value.extend_from_slice(&version.content_hash.0);
+    value.extend_from_slice(version.media_type.as_bytes());
+    value
+}
+
+fn parse_version(key: &[u8], value: &[u8]) -> FileVersion {
+    FileVersion {
+        file_id: u64::from_be_bytes(key[0..8].try_into().unwrap()),
+        version: u64::from_be_bytes(key[8..16].try_into().unwrap()),
+        creation_date: Utc
+            .timestamp_opt(i64::from_be_bytes(value[0..8].try_into().unwrap()), 0)
+            .unwrap(),
+        uploader_id: u64::from_be_bytes(value[8..16].try_into().unwrap()),
+        size: u64::from_be_bytes(value[16..24].try_into().unwrap()),
+        content_hash: ContentHash(value[24..56].try_into().unwrap()),
+        media_type: String::from_utf8(Vec::from(&value[56..]))
+            .expect("DB contained file version with non-UTF-8 media type."),
+    }
+}
+
+/// The transactional trees, that describe the structure of the file system (see
+/// `FsDatabase::fs_transaction()`).
+struct FsTrees<'a> {
+    file_t: &'a TransactionalTree,
+    dir_t: &'a TransactionalTree,
+    perm_t: &'a TransactionalTree,
+    stats_t: &'a TransactionalTree,
+    name_t: &'a TransactionalTree,
+    trash_t: &'a TransactionalTree,
+    trashed_t: &'a TransactionalTree,
+    child_t: &'a TransactionalTree,
+    position_t: &'a TransactionalTree,
+    version_t: &'a TransactionalTree,
+    blob_ref_t: &'a TransactionalTree,
+}
+
+/**
+ * Inserts the given FileVersion under its version number inside of a transaction and sets the
+ * content information and the media type of its file (see `FsDatabase::insert_new_version()`).
+ * If the number is already taken, `Error::TargetExists` is returned.
+ */
+fn insert_version_t(
+    trees: &FsTrees,
+    version: &FileVersion,
+    add_ref: bool,
+) -> ConflictableTransactionResult<(), Error> {
+    let key = version_key(version.file_id, version.version);
+    if trees.version_t.get(key.as_slice())?.is_some() {
+        return Err(ConflictableTransactionError::Abort(Error::TargetExists));
+    }
+    if add_ref && change_blob_refs_t(trees.blob_ref_t, &version.content_hash, 1)? == 1 {
+        // The blob has no other references, so it may have been removed already:
+        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+    }
+    trees.version_t.insert(key, serialize_version(version))?;
+
+    // Update the content information of the file and the statistics of its ancestors:
+    let file_t = trees.file_t;
+    let entry = match file_t.get(version.file_id.to_be_bytes())? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    let info_offset = file_info_offset(&entry);
+    let mut file = File {
+        id: version.file_id,
+        ..File::default()
+    };
+    if !parse_file_info(&entry[info_offset..], &mut file) {
+        file.creation_date = version.creation_date;
+    }
+    let old_size = file.size;
+    file.set_content(version);
+
+    // The media type of the version becomes the media type of the file:
+    let name_end = 18 + u16::from_be_bytes(entry[16..18].try_into().unwrap()) as usize;
+    let mut new_entry = Vec::from(&entry[..name_end]);
+    string_to_bytes(&version.media_type, &mut new_entry);
+    serialize_file_info(&file, &mut new_entry);
+    file_t.insert(&version.file_id.to_be_bytes(), new_entry)?;
+
+    let parent_id = u64::from_be_bytes(entry[0..8].try_into().unwrap());
+    if is_child_t(trees, parent_id, file.id)? {
+        add_to_dir_stats(trees, parent_id, file.size as i64 - old_size as i64, 0)?;
+    }
+    Ok(())
+}
+
+/// Adds `diff` to the number of references to the blob given by `hash` inside of a transaction
+/// and returns the new number. The entry of the blob is removed, when there are no references
+/// left.
+fn change_blob_refs_t(
+    blob_ref_t: &TransactionalTree,
+    hash: &ContentHash,
+    diff: i64,
+) -> ConflictableTransactionResult<u64, Error> {
+    let refs = blob_ref_t
+        .get(hash.0)?
+        .map(|value| u64::from_be_bytes(value[0..8].try_into().unwrap()))
+        .unwrap_or(0)
+        .saturating_add_signed(diff);
+    if refs > 0 {
+        blob_ref_t.insert(&hash.0, &refs.to_be_bytes())?;
+    } else {
+        blob_ref_t.remove(&hash.0)?;
+    }
+    Ok(refs)
+}
+
+/// Moves the FsNode given by `id` inside of a transaction (see `FsDatabase::move_fs_node()`).
+fn move_fs_node_t(
+    trees: &FsTrees,
+    id: u64,
+    parent_id: u64,
+    name: &str,
+) -> ConflictableTransactionResult<(), Error> {
+    let FsTrees {
+        file_t,
+        dir_t,
+        trashed_t,
+        stats_t,
+        name_t,
+        ..
+    } = trees;
+    if trashed_t.get(id.to_be_bytes())?.is_some() {
+        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+    }
+    let (is_dir, node_bytes) = if let Some(b) = dir_t.get(id.to_be_bytes())? {
+        (true, b)
+    } else if let Some(b) = file_t.get(id.to_be_bytes())? {
+        (false, b)
+    } else {
+        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+    };
+    let old_parent_id = u64::from_be_bytes(node_bytes[0..8].try_into().unwrap());
+    if old_parent_id == 0 {
+        return Err(ConflictableTransactionError::Abort(Error::ForbiddenAction));
+    }
+
+    // Make sure the new parent exists:
+    if trashed_t.get(parent_id.to_be_bytes())?.is_some()
+        || dir_t.get(parent_id.to_be_bytes())?.is_none()
+    {
+        return Err(ConflictableTransactionError::Abort(Error::NoSuchDir));
+    }
+
+    // Make sure a directory is not moved into its own subtree:
+    if is_dir {
+        let mut ancestor_id = parent_id;
+        while ancestor_id != 0 {
+            if ancestor_id == id {
+                return Err(ConflictableTransactionError::Abort(Error::ForbiddenAction));
+            }
+            let ancestor_bytes = dir_t.get(ancestor_id.to_be_bytes())?.ok_or(
+                ConflictableTransactionError::Abort(Error::InconsistentDbState),
+            )?;
+            ancestor_id = u64::from_be_bytes(ancestor_bytes[0..8].try_into().unwrap());
+        }
+    }
+
+    // Make sure the name is not used by another child of the new parent:
+    let new_name_key = name_key(parent_id, name);
+    match name_t.get(new_name_key.as_slice())? {
+        Some(child_id) if child_id.as_ref() != id.to_be_bytes() => {
+            return Err(ConflictableTransactionError::Abort(Error::TargetExists));
+        }
+        _ => {}
+    }
+    name_t.remove(name_key(old_parent_id, &entry_name(&node_bytes, is_dir)))?;
+    name_t.insert(new_name_key, &id.to_be_bytes())?;
+
+    if old_parent_id != parent_id {
+        // Move FsNode from its old parent to the new one:
+        remove_child_t(trees, old_parent_id, id)?;
+        add_child_t(trees, parent_id, id)?;
+
+        // Update statistics of the old and new ancestors:
+        let (size, file_count) = if is_dir {
+            dir_entry_stats(stats_t, id)?
+        } else {
+            (file_entry_size(&node_bytes) as i64, 1)
+        };
+        add_to_dir_stats(trees, old_parent_id, -size, -file_count)?;
+        add_to_dir_stats(trees, parent_id, size, file_count)?;
+        touch_dir(stats_t, old_parent_id)?;
+    }
+    touch_dir(stats_t, parent_id)?;
+
+    // Update parent and name of the FsNode:
+    let node_t = if is_dir { dir_t } else { file_t };
+    node_t.insert(
+        &id.to_be_bytes(),
+        rename_entry(&node_bytes, is_dir, parent_id, name),
+    )?;
+
+    Ok(())
+}
+
+/// Moves the FsNode given by `id` into the trash inside of a transaction (see
+/// `FsDatabase::move_to_trash()`).
+fn move_to_trash_t(trees: &FsTrees, id: u64) -> ConflictableTransactionResult<TrashEntry, Error> {
+    let FsTrees {
+        file_t,
+        dir_t,
+        trash_t,
+        trashed_t,
+        stats_t,
+        name_t,
+        ..
+    } = trees;
+    if trashed_t.get(id.to_be_bytes())?.is_some() {
+        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+    }
+    let (name, is_dir, bytes) = if let Some(b) = dir_t.get(id.to_be_bytes())? {
+        (entry_to_dir_incomplete(id, &b).name, true, b)
+    } else if let Some(b) = file_t.get(id.to_be_bytes())? {
+        (parse_db_string(&b[16..]).0, false, b)
+    } else {
+        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+    };
+    let entry = TrashEntry {
+        fs_node_id: id,
+        owner_id: u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+        parent_id: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
         name,
+        is_dir,
+        deletion_date: Utc::now(),
+    };
+    if entry.parent_id == 0 {
+        return Err(ConflictableTransactionError::Abort(Error::ForbiddenAction));
+    }
+
+    // Update statistics of the ancestors:
+    let (size, file_count) = if is_dir {
+        dir_entry_stats(stats_t, id)?
+    } else {
+        (file_entry_size(&bytes) as i64, 1)
+    };
+    add_to_dir_stats(trees, entry.parent_id, -size, -file_count)?;
+    touch_dir(stats_t, entry.parent_id)?;
+
+    // Remove FsNode from parent:
+    remove_child_t(trees, entry.parent_id, id)?;
+    name_t.remove(name_key(entry.parent_id, &entry.name))?;
+
+    // Insert trash entry:
+    let (key, value) = serialize_trash_entry(&entry);
+    trash_t.insert(key, value)?;
+
+    // Hide FsNode and all descendants:
+    let mut marker = Vec::from(entry.owner_id.to_be_bytes());
+    marker.extend_from_slice(&id.to_be_bytes());
+    let mut todo_stack = vec![id];
+    while let Some(next_id) = todo_stack.pop() {
+        trashed_t.insert(&next_id.to_be_bytes(), marker.as_slice())?;
+        todo_stack.extend(child_ids_t(trees, next_id)?);
     }
+
+    Ok(entry)
 }
 
-fn parse_db_string(bytes: &[u8]) -> (String, usize) {
-    let length = u16::from_be_bytes(bytes[0..2].try_into().unwrap()) as usize;
+/// Adds the group or user (if `user` is true) given by `id` to (or removes it from, if `add` is
+/// false) the readable or writeable (if `write` is true) groups or users of the FsNode given by
+/// `fs_node_id`.
+fn change_acl_t(
+    perm_t: &TransactionalTree,
+    fs_node_id: u64,
+    id: u64,
+    user: bool,
+    write: bool,
+    add: bool,
+) -> ConflictableTransactionResult<(), Error> {
+    let old_bytes = perm_t
+        .get(fs_node_id.to_be_bytes())?
+        .ok_or(ConflictableTransactionError::Abort(Error::NoSuchTarget))?;
+    let (mut acl, inherit) = parse_permissions(&old_bytes);
+
+    let ids = match (user, write) {
+        (false, false) => &mut acl.read_group_ids,
+        (false, true) => &mut acl.write_group_ids,
+        (true, false) => &mut acl.read_user_ids,
+        (true, true) => &mut acl.write_user_ids,
+    };
+    if !add {
+        ids.retain(|entry| *entry != id);
+    } else if !ids.contains(&id) {
+        if ids.len() >= u16::MAX as usize {
+            return Err(ConflictableTransactionError::Abort(Error::ForbiddenAction));
+        }
+        ids.push(id);
+    }
 
-    (
-        String::from_utf8(Vec::from(&bytes[2..(2 + length)]))
-            .expect("DB contains non-UTF-8 string."),
-        length + 2,
-    )
+    let new_bytes = serialize_permissions(&acl, inherit);
+    perm_t.insert(&fs_node_id.to_be_bytes(), new_bytes)?;
+
+    Ok(())
 }
-fn string_to_bytes(string: &str, buf: &mut Vec<u8>) {
-    let length: u16 = string
-        .as_bytes()
-        .len()
-        .try_into()
-        .expect("Trying to write a string to the DB, that is to long for u16.");
-    buf.extend_from_slice(&length.to_be_bytes());
-    buf.extend_from_slice(string.as_bytes());
+
+/// Returns the Error, that caused a transaction to abort.
+fn transaction_error(err: TransactionError<Error>) -> Error {
+    match err {
+        TransactionError::Abort(err) => err,
+        TransactionError::Storage(err) => Error::from(err),
+    }
+}
+
+fn serialize_trash_entry(entry: &TrashEntry) -> (Vec<u8>, Vec<u8>) {
+    let mut key = Vec::from(&entry.owner_id.to_be_bytes()[..]);
+    key.extend_from_slice(&entry.fs_node_id.to_be_bytes());
+
+    let mut value = Vec::from(&entry.parent_id.to_be_bytes()[..]);
+    value.extend_from_slice(&entry.deletion_date.timestamp().to_be_bytes());
+    value.push(entry.is_dir as u8);
+    value.extend_from_slice(entry.name.as_bytes());
+
+    (key, value)
+}
+
+fn parse_trash_entry(key: &[u8], value: &[u8]) -> TrashEntry {
+    TrashEntry {
+        owner_id: u64::from_be_bytes(key[0..8].try_into().unwrap()),
+        fs_node_id: u64::from_be_bytes(key[8..16].try_into().unwrap()),
+        parent_id: u64::from_be_bytes(value[0..8].try_into().unwrap()),
+        deletion_date: Utc
+            .timestamp_opt(i64::from_be_bytes(value[8..16].try_into().unwrap()), 0)
+            .unwrap(),
+        is_dir: value[16] != 0,
+        name: String::from_utf8(Vec::from(&value[17..]))
+            .expect("DB contained trash entry with non-UTF-8 name."),
+    }
+}
+
+fn name_key(parent_id: u64, name: &str) -> Vec<u8> {
+    let mut key = Vec::from(&parent_id.to_be_bytes()[..]);
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+fn child_key(dir_id: u64, position: u64) -> Vec<u8> {
+    let mut key = Vec::from(&dir_id.to_be_bytes()[..]);
+    key.extend_from_slice(&position.to_be_bytes());
+    key
+}
+
+/// Returns the name of a FsNode given by its value of the dir tree (if `is_dir` is true) or the
+/// file tree.
+fn entry_name(bytes: &[u8], is_dir: bool) -> String {
+    if is_dir {
+        String::from_utf8(Vec::from(&bytes[18..])).expect("DB contained Dir with non-UTF-8 name.")
+    } else {
+        parse_db_string(&bytes[16..]).0
+    }
+}
+
+/// Returns the given value of the dir tree (if `is_dir` is true) or the file tree with the parent
+/// and the name replaced by `parent_id` and `name`.
+fn rename_entry(bytes: &[u8], is_dir: bool, parent_id: u64, name: &str) -> Vec<u8> {
+    let mut new_bytes = Vec::with_capacity(bytes.len() + name.len());
+    new_bytes.extend_from_slice(&parent_id.to_be_bytes());
+    if is_dir {
+        new_bytes.extend_from_slice(&bytes[8..18]);
+        new_bytes.extend_from_slice(name.as_bytes());
+    } else {
+        let name_end = 18 + u16::from_be_bytes(bytes[16..18].try_into().unwrap()) as usize;
+        new_bytes.extend_from_slice(&bytes[8..16]);
+        string_to_bytes(name, &mut new_bytes);
+        new_bytes.extend_from_slice(&bytes[name_end..]);
+    }
+    new_bytes
+}
+
+/// Returns the IDs of the childs, that were listed in the given value of the dir tree by older
+/// versions of Kasten.
+fn legacy_child_ids(dir_bytes: &[u8]) -> Vec<u64> {
+    let child_number = u16::from_be_bytes(dir_bytes[16..18].try_into().unwrap()) as usize;
+    dir_bytes[18..(18 + child_number * 8)]
+        .chunks_exact(8)
+        .map(|id| u64::from_be_bytes(id.try_into().unwrap()))
+        .collect()
+}
+
+/// Returns the given value of the dir tree without the list of childs, that was stored in it by
+/// older versions of Kasten.
+fn remove_legacy_child_ids(dir_bytes: &[u8]) -> Vec<u8> {
+    let child_number = u16::from_be_bytes(dir_bytes[16..18].try_into().unwrap()) as usize;
+    let mut new_bytes = Vec::from(&dir_bytes[0..16]);
+    new_bytes.extend_from_slice(&0u16.to_be_bytes());
+    new_bytes.extend_from_slice(&dir_bytes[(18 + child_number * 8)..]);
+    new_bytes
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn serialize_ownership_offer(offer: &OwnershipOffer) -> Vec<u8> {
+    let mut data = Vec::from(&offer.new_owner_id.to_be_bytes()[..]);
+    data.extend_from_slice(&offer.owner_id.to_be_bytes());
+    data.extend_from_slice(&offer.creation_date.timestamp().to_be_bytes());
+    data
+}
+
+fn parse_ownership_offer(fs_node_id: u64, bytes: &[u8]) -> OwnershipOffer {
+    OwnershipOffer {
+        fs_node_id,
+        new_owner_id: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+        owner_id: u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+        creation_date: Utc
+            .timestamp_opt(i64::from_be_bytes(bytes[16..24].try_into().unwrap()), 0)
+            .unwrap(),
+    }
+}
+
+fn serialize_share(share: &Share) -> Vec<u8> {
+    let mut data = Vec::from(&share.fs_node_id.to_be_bytes()[..]);
+    data.extend_from_slice(&share.owner_id.to_be_bytes());
+    data.extend_from_slice(&share.creation_date.timestamp().to_be_bytes());
+    data.extend_from_slice(
+        &share
+            .expiration_date
+            .map(|date| date.timestamp())
+            .unwrap_or(0)
+            .to_be_bytes(),
+    );
+    data.extend_from_slice(&share.download_limit.unwrap_or(0).to_be_bytes());
+    data.extend_from_slice(&share.download_count.to_be_bytes());
+    if let Some(pwd_hash) = &share.pwd_hash {
+        data.extend_from_slice(pwd_hash.as_bytes());
+    }
+    data
+}
+
+fn parse_share(token: &str, bytes: &[u8]) -> Share {
+    let expiration_date = i64::from_be_bytes(bytes[24..32].try_into().unwrap());
+    let download_limit = u32::from_be_bytes(bytes[32..36].try_into().unwrap());
+    let pwd_hash = String::from_utf8(Vec::from(&bytes[40..]))
+        .expect("DB contained Share with non-UTF-8 password hash.");
+
+    Share {
+        token: String::from(token),
+        fs_node_id: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+        owner_id: u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+        creation_date: Utc
+            .timestamp_opt(i64::from_be_bytes(bytes[16..24].try_into().unwrap()), 0)
+            .unwrap(),
+        expiration_date: Some(expiration_date)
+            .filter(|date| *date != 0)
+            .map(|date| Utc.timestamp_opt(date, 0).unwrap()),
+        pwd_hash: Some(pwd_hash).filter(|hash| !hash.is_empty()),
+        download_limit: Some(download_limit).filter(|limit| *limit != 0),
+        download_count: u32::from_be_bytes(bytes[36..40].try_into().unwrap()),
+    }
+}
+
+fn entry_to_dir_incomplete(id: u64, bytes: &[u8]) -> Dir {
+    let parent_id = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
+    let owner_id = u64::from_be_bytes(bytes[8..16].try_into().unwrap());
+
+    Dir {
+        id,
+        parent_id,
+        owner_id,
+        name: entry_name(bytes, true),
+        ..Dir::default()
+    }
 }
@@ -1,27 +1,44 @@
-use std::convert::TryInto;
+use std::{collections::HashMap, convert::TryInto};
 
-use chrono::offset::{TimeZone, Utc};
+use chrono::{
+    offset::{TimeZone, Utc},
+    DateTime,
+};
 use rand::{thread_rng, RngCore};
 use sled::{transaction::ConflictableTransactionError, Db, Transactional, Tree};
 
 use crate::{
     config::Config,
-    models::{Dir, File, Group, User, UserSession},
+    models::{
+        Acl, ContentHash, Dir, File, FileVersion, Group, OwnershipOffer, Rights, Share, TrashEntry,
+        Upload, User, UserSession,
+    },
     Error,
 };
 
+mod blob_db;
+use blob_db::BlobDatabase;
 mod fs_db;
+pub use fs_db::FsChange;
 use fs_db::FsDatabase;
+mod search_db;
+use search_db::SearchDatabase;
+mod upload_db;
+use upload_db::UploadDatabase;
 mod user_db;
 use user_db::UserDatabase;
 
+#[derive(Clone)]
 pub struct Database {
     _sled_db: Db,
     session_tree: Tree,      // K: session_id, V: user_id, creation_date
     user_session_tree: Tree, // K: user_id, session_id
 
     fs_db: FsDatabase,
+    blob_db: BlobDatabase,
     user_db: UserDatabase,
+    upload_db: UploadDatabase,
+    search_db: SearchDatabase,
 }
 
 impl Database {
@@ -38,14 +55,20 @@ impl Database {
             .expect("Could not open sessions tree.");
 
         let fs_db = FsDatabase::init(&sled_db)?;
+        let blob_db = BlobDatabase::init(&sled_db)?;
         let user_db = UserDatabase::init(&sled_db)?;
+        let upload_db = UploadDatabase::init(&sled_db)?;
+        let search_db = SearchDatabase::init(&sled_db)?;
 
         Ok(Database {
             _sled_db: sled_db,
             session_tree,
             user_session_tree,
             fs_db,
+            blob_db,
             user_db,
+            upload_db,
+            search_db,
         })
     }
 
@@ -180,16 +203,40 @@ impl Database {
         self.user_db.insert_group(group)
     }
 
-    /// Returns the File with the given ID, if it exists in the DB, or None otherwise.
+    /// Returns the File with the given ID, if it exists in the DB, or None otherwise. The groups
+    /// and users, which the File inherits from its ancestors, are included.
     pub fn get_file(&self, id: u64) -> sled::Result<Option<File>> {
         self.fs_db.get_file(id)
     }
 
-    /// Returns the directory with the given id, it it exists in the DB.
+    /// Returns the directory with the given id, it it exists in the DB. The groups and users,
+    /// which the directory inherits from its ancestors, are included.
     pub fn get_dir(&self, id: u64) -> Result<Option<Dir>, Error> {
         self.fs_db.get_dir(id)
     }
 
+    /// Returns the ID of the file or directory named `name` in the directory given by `parent_id`,
+    /// if there is such a child.
+    pub fn get_child_id(&self, parent_id: u64, name: &str) -> Result<Option<u64>, Error> {
+        self.fs_db.get_child_id(parent_id, name)
+    }
+
+    /**
+     * Returns the names and IDs of at most `limit` childs of the directory given by `dir_id` in the
+     * (byte-wise) order of their names, starting after the child named `after`. If `descending`
+     * is true, the childs are returned in the reverse order.
+     */
+    pub fn get_childs_by_name(
+        &self,
+        dir_id: u64,
+        after: Option<&str>,
+        descending: bool,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>, Error> {
+        self.fs_db
+            .get_childs_by_name(dir_id, after, descending, limit)
+    }
+
     /// Returns the IDs of all files, that are childs of the given directory.
     pub fn get_files_by_parent(&self, parent_id: u64) -> Result<Vec<File>, Error> {
         self.fs_db.get_files_by_parent(parent_id)
@@ -203,6 +250,8 @@ impl Database {
     /**
      * Inserts the given File into the DB.
      * The function finds a new id for the File and updates the id field accordingly.
+     * If the parent directory already contains a child with the same name, `Error::TargetExists`
+     * is returned.
      */
     pub fn insert_new_file(&self, file: &mut File) -> Result<(), Error> {
         self.fs_db.insert_new_file(file)?;
@@ -213,23 +262,20 @@ impl Database {
      * Changes the properties of the given File in the DB to the values given by the parameter
      * `file`.
      *
-     * Changeable properties include `name`, `owner_id` and `parent_id`. The field `id` is used to
-     * identify the file to change.
+     * Changeable properties include `owner_id`, the groups and users, `inherit_permissions` and
+     * `media_type`. The field `id` is used to identify the file to change. Use `move_fs_node()`
+     * to change `name` or `parent_id`.
      */
     pub fn update_file(&self, file: &File) -> Result<(), Error> {
         self.fs_db.update_file(file)
     }
 
-    /// Removes the file with the given id from the DB and returns its representation. Returns an
-    /// Error with type NoSuchFile, if there is no file with the given id in the DB.
-    pub fn remove_file(&self, id: u64) -> Result<File, Error> {
-        self.fs_db.remove_file(id)
-    }
-
     /**
      * Inserts the given Dir into the DB.
      * The function finds a new id for the Dir and updates the id field accordingly.
-     * Ids in the child_ids Vec will be ignored and not written to the DB.
+     * The field `child_count` will be ignored, the new Dir has no childs.
+     * If the parent directory already contains a child with the same name, `Error::TargetExists`
+     * is returned.
      */
     pub fn insert_new_dir(&self, dir: &mut Dir) -> Result<(), Error> {
         self.fs_db.insert_new_dir(dir)?;
@@ -237,20 +283,16 @@ impl Database {
     }
 
     /**
-     * Changes the properties of the given Dir in the DB to the values given by the parameter
-     * `dir`.
+     * Moves the file or directory given by `id` into the directory given by `parent_id` and
+     * renames it to `name`.
      *
-     * Changeable properties include `name`, `owner_id` and `parent_id`. The field `id` is used to
-     * identify the directory to change. The field `child_ids` will be ignored.
+     * If there is no such FsNode or directory, `Error::NoSuchTarget` or `Error::NoSuchDir` is
+     * returned. If the new parent already contains another child with the given name,
+     * `Error::TargetExists` is returned. Moving a root directory, moving a directory into its own
+     * subtree or moving into a full directory results in `Error::ForbiddenAction`.
      */
-    pub fn update_dir(&self, dir: &Dir) -> Result<(), Error> {
-        self.fs_db.update_dir(dir)
-    }
-
-    /// Removes the directory with the given id from the DB and returns its representation.
-    /// Returns an Error with type NoSuchDir, if there is no directory with the given id in the DB.
-    pub fn remove_dir(&self, id: u64) -> Result<Dir, Error> {
-        self.fs_db.remove_dir(id)
+    pub fn move_fs_node(&self, id: u64, parent_id: u64, name: &str) -> Result<(), Error> {
+        self.fs_db.move_fs_node(id, parent_id, name)
     }
 
     /**
@@ -274,4 +316,486 @@ impl Database {
     pub fn add_writeable_group(&self, fs_node_id: u64, group_id: u64) -> Result<(), Error> {
         self.fs_db.add_writeable_group(fs_node_id, group_id)
     }
+
+    /**
+     * Removes the group Id `group_id` from the list of readable groups for the file or directory
+     * given by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn remove_readable_group(&self, fs_node_id: u64, group_id: u64) -> Result<(), Error> {
+        self.fs_db.remove_readable_group(fs_node_id, group_id)
+    }
+
+    /**
+     * Removes the group Id `group_id` from the list of writeable groups for the file or directory
+     * given by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn remove_writeable_group(&self, fs_node_id: u64, group_id: u64) -> Result<(), Error> {
+        self.fs_db.remove_writeable_group(fs_node_id, group_id)
+    }
+
+    /**
+     * Adds the user Id `user_id` to the list of readable users for the file or directory given
+     * by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn add_readable_user(&self, fs_node_id: u64, user_id: u64) -> Result<(), Error> {
+        self.fs_db.add_readable_user(fs_node_id, user_id)
+    }
+
+    /**
+     * Adds the user Id `user_id` to the list of writeable users for the file or directory given
+     * by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn add_writeable_user(&self, fs_node_id: u64, user_id: u64) -> Result<(), Error> {
+        self.fs_db.add_writeable_user(fs_node_id, user_id)
+    }
+
+    /**
+     * Removes the user Id `user_id` from the list of readable users for the file or directory given
+     * by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn remove_readable_user(&self, fs_node_id: u64, user_id: u64) -> Result<(), Error> {
+        self.fs_db.remove_readable_user(fs_node_id, user_id)
+    }
+
+    /**
+     * Removes the user Id `user_id` from the list of writeable users for the file or directory
+     * given by `fs_node_id`.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn remove_writeable_user(&self, fs_node_id: u64, user_id: u64) -> Result<(), Error> {
+        self.fs_db.remove_writeable_user(fs_node_id, user_id)
+    }
+
+    /**
+     * Sets the additional rights of the group given by `group_id` on the file or directory given
+     * by `fs_node_id` to `rights`. Empty rights remove the entry of the group.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn set_group_rights(
+        &self,
+        fs_node_id: u64,
+        group_id: u64,
+        rights: Rights,
+    ) -> Result<(), Error> {
+        self.fs_db.set_rights(fs_node_id, group_id, false, rights)
+    }
+
+    /**
+     * Sets the additional rights of the user given by `user_id` on the file or directory given by
+     * `fs_node_id` to `rights`. Empty rights remove the entry of the user.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn set_user_rights(
+        &self,
+        fs_node_id: u64,
+        user_id: u64,
+        rights: Rights,
+    ) -> Result<(), Error> {
+        self.fs_db.set_rights(fs_node_id, user_id, true, rights)
+    }
+
+    /**
+     * Replaces the groups, users and rights for the file or directory given by `fs_node_id` by
+     * the ones given by `acl`. If `inherit` is not None, it also sets, whether the FsNode inherits
+     * the groups, users and rights of its parent.
+     *
+     * If there is no entry for a FsNode with the given ID in the permission table,
+     * `Err(Error::NoSuchTarget)` is returned.
+     */
+    pub fn set_permissions(
+        &self,
+        fs_node_id: u64,
+        acl: &Acl,
+        inherit: Option<bool>,
+    ) -> Result<(), Error> {
+        self.fs_db.set_permissions(fs_node_id, acl, inherit)
+    }
+
+    /**
+     * Applies all given changes of the file system in a single transaction, so that either all or
+     * none of them are applied.
+     *
+     * If a change fails, the index of the failed change and its Error are returned in the inner
+     * `Err`.
+     */
+    pub fn apply_changes(&self, changes: &[FsChange]) -> Result<Result<(), (usize, Error)>, Error> {
+        self.fs_db.apply_changes(changes)
+    }
+
+    /// Returns the Share with the given token, if it exists in the DB.
+    pub fn get_share(&self, token: &str) -> Result<Option<Share>, Error> {
+        self.fs_db.get_share(token)
+    }
+
+    /// Returns all Shares of the file or directory given by `fs_node_id`.
+    pub fn get_shares_by_node(&self, fs_node_id: u64) -> Result<Vec<Share>, Error> {
+        self.fs_db.get_shares_by_node(fs_node_id)
+    }
+
+    /**
+     * Inserts the given Share into the DB.
+     * The function generates a new unguessable token for the Share and updates the token field
+     * accordingly.
+     */
+    pub fn insert_new_share(&self, share: &mut Share) -> Result<(), Error> {
+        self.fs_db.insert_new_share(share)
+    }
+
+    /**
+     * Increases the download counter of the Share given by `token` and returns the updated Share.
+     *
+     * If there is no Share with the given token, `Error::NoSuchTarget` is returned. If the download
+     * limit of the Share is already reached, `Error::ForbiddenAction` is returned.
+     */
+    pub fn add_share_download(&self, token: &str) -> Result<Share, Error> {
+        self.fs_db.add_share_download(token)
+    }
+
+    /// Removes the Share with the given token from the DB and returns its representation.
+    /// Returns an Error with type NoSuchTarget, if there is no Share with the given token.
+    pub fn remove_share(&self, token: &str) -> Result<Share, Error> {
+        self.fs_db.remove_share(token)
+    }
+
+    /// Returns the OwnershipOffer for the file or directory given by `fs_node_id`, if there is one.
+    pub fn get_ownership_offer(&self, fs_node_id: u64) -> Result<Option<OwnershipOffer>, Error> {
+        self.fs_db.get_ownership_offer(fs_node_id)
+    }
+
+    /// Returns all OwnershipOffers, that were made by or to the User given by `user_id`.
+    pub fn get_ownership_offers_by_user(&self, user_id: u64) -> Result<Vec<OwnershipOffer>, Error> {
+        self.fs_db.get_ownership_offers_by_user(user_id)
+    }
+
+    /// Inserts the given OwnershipOffer into the DB. A previous offer for the same file or
+    /// directory is replaced.
+    pub fn insert_ownership_offer(&self, offer: &OwnershipOffer) -> Result<(), Error> {
+        self.fs_db.insert_ownership_offer(offer)
+    }
+
+    /// Removes the OwnershipOffer for the file or directory given by `fs_node_id` from the DB and
+    /// returns it. If there is no such offer, `Error::NoSuchTarget` is returned.
+    pub fn remove_ownership_offer(&self, fs_node_id: u64) -> Result<OwnershipOffer, Error> {
+        self.fs_db.remove_ownership_offer(fs_node_id)
+    }
+
+    /**
+     * Accepts the OwnershipOffer for the file or directory given by `fs_node_id`: The User given
+     * by `new_owner_id` becomes the owner of the FsNode and of all its descendants, which belong
+     * to the previous owner. The offer is removed and returned together with the IDs of all
+     * files, whose owner was changed.
+     *
+     * If there is no valid offer for the FsNode to the given User, `Error::NoSuchTarget` is
+     * returned.
+     */
+    pub fn accept_ownership_offer(
+        &self,
+        fs_node_id: u64,
+        new_owner_id: u64,
+    ) -> Result<(OwnershipOffer, Vec<u64>), Error> {
+        self.fs_db.accept_ownership_offer(fs_node_id, new_owner_id)
+    }
+
+    /**
+     * Returns the IDs of all files, that are not reachable anymore: The files are not in the trash
+     * and their parent directory does not exist.
+     */
+    pub fn get_dangling_file_ids(&self) -> Result<Vec<u64>, Error> {
+        self.fs_db.get_dangling_file_ids()
+    }
+
+    /// Returns the IDs of all files, which do not exist anymore, but still have versions.
+    pub fn get_orphaned_version_file_ids(&self) -> Result<Vec<u64>, Error> {
+        self.fs_db.get_orphaned_version_file_ids()
+    }
+
+    /// Returns the IDs of all files, including the files in the trash.
+    pub fn get_file_ids(&self) -> Result<Vec<u64>, Error> {
+        self.fs_db.get_file_ids()
+    }
+
+    /// Returns all FileVersions of the file given by `file_id`, ordered by their version number.
+    pub fn get_versions(&self, file_id: u64) -> Result<Vec<FileVersion>, Error> {
+        self.fs_db.get_versions(file_id)
+    }
+
+    /// Returns the newest FileVersion of the file given by `file_id`, if there is one.
+    pub fn get_latest_version(&self, file_id: u64) -> Result<Option<FileVersion>, Error> {
+        self.fs_db.get_latest_version(file_id)
+    }
+
+    /// Returns the FileVersion with the given version number of the file given by `file_id`, if
+    /// it exists in the DB.
+    pub fn get_version(&self, file_id: u64, version: u64) -> Result<Option<FileVersion>, Error> {
+        self.fs_db.get_version(file_id, version)
+    }
+
+    /**
+     * Inserts the given FileVersion into the DB.
+     * The function assigns the next version number of the file to the FileVersion and updates the
+     * version field accordingly. The content information and the media type of the file are set
+     * to the values of the new version.
+     *
+     * If `add_ref` is true, a reference to the blob of the version is added in the same
+     * transaction, which fails with `Error::NoSuchTarget`, if the blob has no references.
+     * Otherwise the reference, that is held by the caller, is passed to the version.
+     */
+    pub fn insert_new_version(
+        &self,
+        version: &mut FileVersion,
+        add_ref: bool,
+    ) -> Result<(), Error> {
+        self.fs_db.insert_new_version(version, add_ref)
+    }
+
+    /**
+     * Removes the FileVersion with the given version number of the file given by `file_id` and
+     * its reference to its blob from the DB in a single transaction. The removed FileVersion and
+     * the remaining number of references to its blob are returned.
+     *
+     * Returns an Error with type NoSuchTarget, if there is no such FileVersion.
+     */
+    pub fn remove_version(&self, file_id: u64, version: u64) -> Result<(FileVersion, u64), Error> {
+        self.fs_db.remove_version(file_id, version)
+    }
+
+    /// Returns the file IDs and version numbers of all versions, that were written by versions of
+    /// Kasten without content-addressed storage and were not moved to the blob storage yet.
+    pub fn get_legacy_version_ids(&self) -> Result<Vec<(u64, u64)>, Error> {
+        self.fs_db.get_legacy_version_ids()
+    }
+
+    /**
+     * Moves the legacy version given by `file_id` and `version` (see `get_legacy_version_ids()`)
+     * to the other FileVersions with the given content hash and returns it. The reference to the
+     * blob, that is held by the caller, is passed to the version.
+     *
+     * If there is no such legacy version, `Error::NoSuchTarget` is returned. If the file already
+     * has a version with the same number, `Error::TargetExists` is returned.
+     */
+    pub fn import_legacy_version(
+        &self,
+        file_id: u64,
+        version: u64,
+        content_hash: ContentHash,
+    ) -> Result<FileVersion, Error> {
+        self.fs_db
+            .import_legacy_version(file_id, version, content_hash)
+    }
+
+    /// Removes the legacy version given by `file_id` and `version` (see
+    /// `get_legacy_version_ids()`) from the DB.
+    pub fn remove_legacy_version(&self, file_id: u64, version: u64) -> Result<(), Error> {
+        self.fs_db.remove_legacy_version(file_id, version)
+    }
+
+    /// Returns the number of references (FileVersions) to the blob given by `hash`.
+    pub fn get_blob_refs(&self, hash: &ContentHash) -> Result<u64, Error> {
+        self.blob_db.get_blob_refs(hash)
+    }
+
+    /// Adds a reference to the blob given by `hash` and returns the new number of references.
+    pub fn add_blob_ref(&self, hash: &ContentHash) -> Result<u64, Error> {
+        self.blob_db.add_blob_ref(hash)
+    }
+
+    /// Removes a reference to the blob given by `hash` and returns the remaining number of
+    /// references.
+    pub fn remove_blob_ref(&self, hash: &ContentHash) -> Result<u64, Error> {
+        self.blob_db.remove_blob_ref(hash)
+    }
+
+    /// Returns true, if the blob given by `hash` was stored encrypted.
+    pub fn is_blob_encrypted(&self, hash: &ContentHash) -> Result<bool, Error> {
+        self.blob_db.is_blob_encrypted(hash)
+    }
+
+    /// Sets, whether the blob given by `hash` is stored encrypted.
+    pub fn set_blob_encrypted(&self, hash: &ContentHash, encrypted: bool) -> Result<(), Error> {
+        self.blob_db.set_blob_encrypted(hash, encrypted)
+    }
+
+    /// Returns true, if the blobs, which were stored before the encryption was recorded in the
+    /// DB, were flagged already.
+    pub fn are_encryption_flags_complete(&self) -> Result<bool, Error> {
+        self.blob_db.are_encryption_flags_complete()
+    }
+
+    /// Records, that all blobs in the storage were flagged.
+    pub fn set_encryption_flags_complete(&self) -> Result<(), Error> {
+        self.blob_db.set_encryption_flags_complete()
+    }
+
+    /// Returns the maximum number of previous versions, that should be kept for the file given by
+    /// `file_id`, if there is such a limit for this file.
+    pub fn get_version_limit(&self, file_id: u64) -> Result<Option<u32>, Error> {
+        self.fs_db.get_version_limit(file_id)
+    }
+
+    /// Sets the maximum number of previous versions, that should be kept for the file given by
+    /// `file_id`. If `limit` is None, the limit of the file is removed.
+    pub fn set_version_limit(&self, file_id: u64, limit: Option<u32>) -> Result<(), Error> {
+        self.fs_db.set_version_limit(file_id, limit)
+    }
+
+    /// Returns the entries of the trash of the user given by `owner_id`.
+    pub fn get_trash(&self, owner_id: u64) -> Result<Vec<TrashEntry>, Error> {
+        self.fs_db.get_trash(owner_id)
+    }
+
+    /// Returns the entries of the trashes of all users, that were deleted before the given date.
+    pub fn get_trash_deleted_before(&self, date: DateTime<Utc>) -> Result<Vec<TrashEntry>, Error> {
+        self.fs_db.get_trash_deleted_before(date)
+    }
+
+    /**
+     * Moves the file or directory given by `id` into the trash of its owner and returns the new
+     * trash entry. The FsNode and all its descendants are hidden until it is restored or purged.
+     *
+     * If there is no such FsNode, `Error::NoSuchTarget` is returned. If the FsNode is the root
+     * directory of a user, `Error::ForbiddenAction` is returned.
+     */
+    pub fn move_to_trash(&self, id: u64) -> Result<TrashEntry, Error> {
+        self.fs_db.move_to_trash(id)
+    }
+
+    /**
+     * Restores the FsNode given by `id` from the trash of the user given by `owner_id` into the
+     * directory given by `parent_id` under the name `name` and returns the updated trash entry.
+     *
+     * If there is no such entry in the trash, `Error::NoSuchTarget` is returned. If there is no
+     * such directory, `Error::NoSuchDir` is returned. If the name is already used in the
+     * directory, `Error::TargetExists` is returned.
+     */
+    pub fn restore_from_trash(
+        &self,
+        owner_id: u64,
+        id: u64,
+        parent_id: u64,
+        name: &str,
+    ) -> Result<TrashEntry, Error> {
+        self.fs_db.restore_from_trash(owner_id, id, parent_id, name)
+    }
+
+    /**
+     * Removes the FsNode given by `id` and all its descendants from the trash of the user given
+     * by `owner_id` and from the DB. The removed trash entry and the IDs of all removed files are
+     * returned.
+     *
+     * If there is no such entry in the trash, `Error::NoSuchTarget` is returned.
+     */
+    pub fn purge_from_trash(
+        &self,
+        owner_id: u64,
+        id: u64,
+    ) -> Result<(TrashEntry, Vec<u64>), Error> {
+        self.fs_db.purge_from_trash(owner_id, id)
+    }
+
+    /// Returns the Upload with the given ID, if it exists in the DB.
+    pub fn get_upload(&self, id: u64) -> Result<Option<Upload>, Error> {
+        self.upload_db.get_upload(id)
+    }
+
+    /// Returns all Uploads in the DB.
+    pub fn get_uploads(&self) -> Result<Vec<Upload>, Error> {
+        self.upload_db.get_uploads()
+    }
+
+    /**
+     * Inserts the given Upload into the DB.
+     * The function finds a new id for the Upload and updates the id field accordingly.
+     */
+    pub fn insert_new_upload(&self, upload: &mut Upload) -> Result<(), Error> {
+        self.upload_db.insert_new_upload(upload)
+    }
+
+    /**
+     * Marks the bytes `start..end` of the upload given by `id` as received and returns the updated
+     * upload.
+     *
+     * If there is no upload with the given ID in the DB, `Error::NoSuchTarget` is returned.
+     */
+    pub fn add_received_range(&self, id: u64, start: u64, end: u64) -> Result<Upload, Error> {
+        self.upload_db.add_received_range(id, start, end)
+    }
+
+    /// Removes the upload with the given id from the DB and returns its representation. Returns
+    /// an Error with type NoSuchTarget, if there is no upload with the given id in the DB.
+    pub fn remove_upload(&self, id: u64) -> Result<Upload, Error> {
+        self.upload_db.remove_upload(id)
+    }
+
+    /// Returns the indexed terms of the file given by `file_id` and the fields (as bit set), in
+    /// which they occur, or None, if the file is not indexed.
+    pub fn get_search_terms(&self, file_id: u64) -> Result<Option<HashMap<String, u8>>, Error> {
+        self.search_db.get_search_terms(file_id)
+    }
+
+    /// Replaces the indexed terms of the file given by `file_id` by `terms`.
+    pub fn set_search_terms(&self, file_id: u64, terms: &HashMap<String, u8>) -> Result<(), Error> {
+        self.search_db.set_search_terms(file_id, terms)
+    }
+
+    /// Removes the file given by `file_id` from the search index. If the file is not indexed, it
+    /// will still return Ok(()).
+    pub fn remove_search_terms(&self, file_id: u64) -> Result<(), Error> {
+        self.search_db.remove_search_terms(file_id)
+    }
+
+    /**
+     * Returns the IDs of all files, whose indexed terms contain `term`, and the fields (as bit
+     * set), in which it occurs. If `prefix` is true, all terms starting with `term` are matched.
+     */
+    pub fn find_search_term(&self, term: &str, prefix: bool) -> Result<HashMap<u64, u8>, Error> {
+        self.search_db.find_search_term(term, prefix)
+    }
+
+    /// Returns the IDs of all indexed files.
+    pub fn get_indexed_file_ids(&self) -> Result<Vec<u64>, Error> {
+        self.search_db.get_indexed_file_ids()
+    }
+}
+
+//
+// Helper functions for serialization and deserialization:
+//
+
+fn parse_db_string(bytes: &[u8]) -> (String, usize) {
+    let length = u16::from_be_bytes(bytes[0..2].try_into().unwrap()) as usize;
+
+    (
+        String::from_utf8(Vec::from(&bytes[2..(2 + length)]))
+            .expect("DB contains non-UTF-8 string."),
+        length + 2,
+    )
+}
+fn string_to_bytes(string: &str, buf: &mut Vec<u8>) {
+    let length: u16 = string
+        .len()
+        .try_into()
+        .expect("Trying to write a string to the DB, that is to long for u16.");
+    buf.extend_from_slice(&length.to_be_bytes());
+    buf.extend_from_slice(string.as_bytes());
 }
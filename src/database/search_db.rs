@@ -0,0 +1,135 @@
+use std::{collections::HashMap, convert::TryInto};
+
+use sled::{transaction::ConflictableTransactionError, Db, Transactional, Tree};
+
+use crate::Error;
+
+#[derive(Clone)]
+pub struct SearchDatabase {
+    term_tree: Tree, // K: term, 0u8, file_id, V: fields (u8)
+    doc_tree: Tree,  // K: file_id, V: list of (fields (u8), term, 0u8)
+}
+
+impl SearchDatabase {
+    /// Initializes the database.
+    pub fn init(sled_db: &Db) -> Result<SearchDatabase, Error> {
+        let term_tree = sled_db
+            .open_tree("search_terms")
+            .expect("Could not open search_terms tree.");
+        let doc_tree = sled_db
+            .open_tree("search_docs")
+            .expect("Could not open search_docs tree.");
+
+        Ok(SearchDatabase {
+            term_tree,
+            doc_tree,
+        })
+    }
+
+    /// Returns the indexed terms of the file given by `file_id` and the fields (as bit set), in
+    /// which they occur, or None, if the file is not indexed.
+    pub fn get_search_terms(&self, file_id: u64) -> Result<Option<HashMap<String, u8>>, Error> {
+        match self.doc_tree.get(file_id.to_be_bytes())? {
+            Some(value) => Ok(Some(parse_terms(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Replaces the indexed terms of the file given by `file_id` by `terms`.
+    pub fn set_search_terms(&self, file_id: u64, terms: &HashMap<String, u8>) -> Result<(), Error> {
+        let doc = serialize_terms(terms);
+        (&self.term_tree, &self.doc_tree).transaction(|(term_t, doc_t)| {
+            if let Some(old_doc) = doc_t.get(file_id.to_be_bytes())? {
+                for term in parse_terms(&old_doc)
+                    .map_err(ConflictableTransactionError::Abort)?
+                    .keys()
+                {
+                    term_t.remove(term_key(term, file_id))?;
+                }
+            }
+            for (term, fields) in terms {
+                term_t.insert(term_key(term, file_id), &[*fields])?;
+            }
+            doc_t.insert(&file_id.to_be_bytes(), doc.as_slice())?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Removes the file given by `file_id` from the search index. If the file is not indexed, it
+    /// will still return Ok(()).
+    pub fn remove_search_terms(&self, file_id: u64) -> Result<(), Error> {
+        (&self.term_tree, &self.doc_tree).transaction(|(term_t, doc_t)| {
+            if let Some(old_doc) = doc_t.remove(&file_id.to_be_bytes())? {
+                for term in parse_terms(&old_doc)
+                    .map_err(ConflictableTransactionError::Abort)?
+                    .keys()
+                {
+                    term_t.remove(term_key(term, file_id))?;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /**
+     * Returns the IDs of all files, whose indexed terms contain `term`, and the fields (as bit
+     * set), in which it occurs. If `prefix` is true, all terms starting with `term` are matched.
+     */
+    pub fn find_search_term(&self, term: &str, prefix: bool) -> Result<HashMap<u64, u8>, Error> {
+        let mut key_prefix = Vec::from(term.as_bytes());
+        if !prefix {
+            key_prefix.push(0);
+        }
+
+        let mut res: HashMap<u64, u8> = HashMap::new();
+        for entry in self.term_tree.scan_prefix(key_prefix) {
+            let (key, value) = entry?;
+            let file_id = u64::from_be_bytes(key[(key.len() - 8)..].try_into().unwrap());
+            *res.entry(file_id).or_insert(0) |= value[0];
+        }
+        Ok(res)
+    }
+
+    /// Returns the IDs of all indexed files.
+    pub fn get_indexed_file_ids(&self) -> Result<Vec<u64>, Error> {
+        let mut res = Vec::new();
+        for key in self.doc_tree.iter().keys() {
+            res.push(u64::from_be_bytes(key?.as_ref().try_into().unwrap()));
+        }
+        Ok(res)
+    }
+}
+
+fn term_key(term: &str, file_id: u64) -> Vec<u8> {
+    let mut key = Vec::from(term.as_bytes());
+    key.push(0);
+    key.extend_from_slice(&file_id.to_be_bytes());
+    key
+}
+
+fn serialize_terms(terms: &HashMap<String, u8>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (term, fields) in terms {
+        buf.push(*fields);
+        buf.extend_from_slice(term.as_bytes());
+        buf.push(0);
+    }
+    buf
+}
+
+fn parse_terms(bytes: &[u8]) -> Result<HashMap<String, u8>, Error> {
+    let mut res = HashMap::new();
+    let mut rest = bytes;
+    while let Some((fields, tail)) = rest.split_first() {
+        let end = tail
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or(Error::InconsistentDbState)?;
+        let term = String::from_utf8(Vec::from(&tail[..end]))?;
+        res.insert(term, *fields);
+        rest = &tail[(end + 1)..];
+    }
+    Ok(res)
+}
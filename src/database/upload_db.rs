@@ -0,0 +1,153 @@
+use std::convert::{TryFrom, TryInto};
+
+use chrono::offset::{TimeZone, Utc};
+use rand::{thread_rng, RngCore};
+use sled::{transaction::ConflictableTransactionError, Db, Tree};
+
+use super::{parse_db_string, string_to_bytes};
+use crate::{models::Upload, Error};
+
+#[derive(Clone)]
+pub struct UploadDatabase {
+    upload_tree: Tree, // K: upload_id, V: user_id, parent_id, file_id, length, creation_date, range_number (u32), ranges..., name_len, name, type_len, media_type
+}
+
+impl UploadDatabase {
+    /// Initializes the database.
+    pub fn init(sled_db: &Db) -> Result<UploadDatabase, Error> {
+        let upload_tree = sled_db
+            .open_tree("uploads")
+            .expect("Could not open uploads tree.");
+
+        Ok(UploadDatabase { upload_tree })
+    }
+
+    /// Returns the Upload with the given ID, if it exists in the DB, or None otherwise.
+    pub fn get_upload(&self, id: u64) -> Result<Option<Upload>, Error> {
+        Ok(self
+            .upload_tree
+            .get(id.to_be_bytes())?
+            .map(|bytes| parse_upload(id, &bytes)))
+    }
+
+    /// Returns all uploads in the DB.
+    pub fn get_uploads(&self) -> Result<Vec<Upload>, Error> {
+        self.upload_tree
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry?;
+                Ok(parse_upload(
+                    u64::from_be_bytes(key.as_ref().try_into().unwrap()),
+                    &value,
+                ))
+            })
+            .collect()
+    }
+
+    /// Inserts a new upload with the given attributes in the DB. The ID of the given upload will
+    /// be updated to a new unique value.
+    pub fn insert_new_upload(&self, upload: &mut Upload) -> Result<(), Error> {
+        let data = serialize_upload(upload);
+
+        upload.id = self.upload_tree.transaction(|upload_t| {
+            // Generate new upload-id:
+            let mut rng = thread_rng();
+            let mut upload_id = rng.next_u64();
+            while upload_t.get(upload_id.to_be_bytes())?.is_some() || upload_id == 0 {
+                upload_id = rng.next_u64();
+            }
+
+            upload_t.insert(&upload_id.to_be_bytes(), data.as_slice())?;
+
+            let res: Result<u64, ConflictableTransactionError> = Ok(upload_id);
+            res
+        })?;
+
+        Ok(())
+    }
+
+    /**
+     * Marks the bytes `start..end` of the upload given by `id` as received and returns the updated
+     * upload.
+     *
+     * If there is no upload with the given ID in the DB, `Error::NoSuchTarget` is returned.
+     */
+    pub fn add_received_range(&self, id: u64, start: u64, end: u64) -> Result<Upload, Error> {
+        self.upload_tree
+            .transaction(|upload_t| {
+                let mut upload = match upload_t.get(id.to_be_bytes())? {
+                    Some(bytes) => parse_upload(id, &bytes),
+                    None => {
+                        return Err(ConflictableTransactionError::Abort(Error::NoSuchTarget));
+                    }
+                };
+                upload.add_range(start, end);
+                upload_t.insert(&id.to_be_bytes(), serialize_upload(&upload))?;
+
+                Ok(upload)
+            })
+            .map_err(Error::from)
+    }
+
+    /// Removes the upload with the given id from the DB and returns its representation. Returns
+    /// an Error with type NoSuchTarget, if there is no upload with the given id in the DB.
+    pub fn remove_upload(&self, id: u64) -> Result<Upload, Error> {
+        self.upload_tree
+            .remove(id.to_be_bytes())?
+            .map(|bytes| parse_upload(id, &bytes))
+            .ok_or(Error::NoSuchTarget)
+    }
+}
+
+//
+// Helper functions for serialization and deserialization:
+//
+
+fn serialize_upload(upload: &Upload) -> Vec<u8> {
+    let mut data = Vec::from(&upload.user_id.to_be_bytes()[..]);
+    data.extend_from_slice(&upload.parent_id.to_be_bytes());
+    data.extend_from_slice(&upload.file_id.to_be_bytes());
+    data.extend_from_slice(&upload.length.to_be_bytes());
+    data.extend_from_slice(&upload.creation_date.timestamp().to_be_bytes());
+    data.extend_from_slice(
+        &u32::try_from(upload.received.len())
+            .expect("An upload contained to many ranges for the DB.")
+            .to_be_bytes(),
+    );
+    for (start, end) in upload.received.iter() {
+        data.extend_from_slice(&start.to_be_bytes());
+        data.extend_from_slice(&end.to_be_bytes());
+    }
+    string_to_bytes(&upload.name, &mut data);
+    string_to_bytes(&upload.media_type, &mut data);
+    data
+}
+
+fn parse_upload(id: u64, bytes: &[u8]) -> Upload {
+    let range_number = u32::from_be_bytes(bytes[40..44].try_into().unwrap()) as usize;
+    let mut received = Vec::with_capacity(range_number);
+    let mut index = 44;
+    for _ in 0..range_number {
+        received.push((
+            u64::from_be_bytes(bytes[index..(index + 8)].try_into().unwrap()),
+            u64::from_be_bytes(bytes[(index + 8)..(index + 16)].try_into().unwrap()),
+        ));
+        index += 16;
+    }
+    let (name, name_len) = parse_db_string(&bytes[index..]);
+    let (media_type, _) = parse_db_string(&bytes[(index + name_len)..]);
+
+    Upload {
+        id,
+        user_id: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+        parent_id: u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+        file_id: u64::from_be_bytes(bytes[16..24].try_into().unwrap()),
+        length: u64::from_be_bytes(bytes[24..32].try_into().unwrap()),
+        creation_date: Utc
+            .timestamp_opt(i64::from_be_bytes(bytes[32..40].try_into().unwrap()), 0)
+            .unwrap(),
+        received,
+        name,
+        media_type,
+    }
+}
@@ -11,6 +11,7 @@ use crate::{
     Error,
 };
 
+#[derive(Clone)]
 pub struct UserDatabase {
     username_id_tree: Tree,    // K: username, V: user_id
     userid_name_tree: Tree,    // K: user_id, V: username
@@ -90,7 +91,7 @@ impl UserDatabase {
                     .get_group(id)?
                     .expect("Expected a nonexisting DB entry.")
                     .member_ids
-                    .contains(&id)
+                    .contains(&user_id)
                 {
                     group_ids.push(id);
                 }
@@ -160,6 +161,7 @@ impl UserDatabase {
                 index += 8;
             }
             let admin_count = u16::from_be_bytes(bytes[index..(index + 2)].try_into().unwrap());
+            index += 2;
             let mut admin_ids = Vec::new();
             for _ in 0..admin_count {
                 admin_ids.push(u64::from_be_bytes(
@@ -248,7 +250,7 @@ impl UserDatabase {
                             continue 'users;
                         }
                     }
-                    group_list_bytes.extend_from_slice(&user_id.to_be_bytes());
+                    group_list_bytes.extend_from_slice(&group_id);
                     user_g_t.insert(&user_id.to_be_bytes(), group_list_bytes.as_slice())?;
                 }
 
@@ -323,7 +325,7 @@ impl UserDatabase {
                             continue 'users;
                         }
                     }
-                    group_list_bytes.extend_from_slice(&user_id.to_be_bytes());
+                    group_list_bytes.extend_from_slice(&group.id.to_be_bytes());
                     user_g_t.insert(&user_id.to_be_bytes(), group_list_bytes.as_slice())?;
                 }
 
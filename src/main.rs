@@ -6,6 +6,7 @@ use std::{
     fmt::{Display, Formatter},
 };
 
+mod blob_store;
 mod config;
 mod controller;
 mod database;
@@ -17,6 +18,37 @@ async fn main() {
     let config = config::Config::new();
 
     let db = database::Database::init(&config).unwrap();
+    let flagged_count = controller::storage::flag_encrypted_blobs(&db, &config)
+        .expect("Could not record the encryption of existing blobs.");
+    if flagged_count > 0 {
+        // TODO: Logging
+        println!("Recorded the encryption of {} blobs.", flagged_count);
+    }
+    let imported_ids = controller::storage::import_legacy_blobs(&db, &config)
+        .expect("Could not import the content of existing files.");
+    if !imported_ids.is_empty() {
+        // TODO: Logging
+        println!(
+            "Moved content of {} files to the blob storage.",
+            imported_ids.len()
+        );
+    }
+
+    let indexed_count =
+        controller::search::update_index(&db, &config).expect("Could not update the search index.");
+    if indexed_count > 0 {
+        // TODO: Logging
+        println!("Added {} files to the search index.", indexed_count);
+    }
+
+    rocket::tokio::spawn(controller::trash::purge_periodically(
+        db.clone(),
+        config.clone(),
+    ));
+    rocket::tokio::spawn(controller::storage::sweep_periodically(
+        db.clone(),
+        config.clone(),
+    ));
 
     webapi::init(db, config).await.unwrap();
 }
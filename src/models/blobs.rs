@@ -0,0 +1,58 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::fmt;
+
+/**
+ * The SHA-256 hash of the (plaintext) content of a blob.
+ *
+ * Blobs are stored content-addressed: A blob is saved under the hexadecimal representation of
+ * its hash, so that identical content is only saved once.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ContentHash(pub [u8; 32]);
+
+impl ContentHash {
+    /// Parses a ContentHash given as 64 hexadecimal digits.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut hash = [0u8; 32];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[(2 * i)..(2 * i + 2)], 16).ok()?;
+        }
+        Some(ContentHash(hash))
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for ContentHash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+impl<'de> Deserialize<'de> for ContentHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        ContentHash::from_hex(&hex).ok_or_else(|| {
+            serde::de::Error::invalid_value(
+                serde::de::Unexpected::Str(&hex),
+                &"a SHA-256 hash as 64 hex digits",
+            )
+        })
+    }
+}
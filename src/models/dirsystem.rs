@@ -1,44 +1,57 @@
+use chrono::{offset::Utc, DateTime};
 use serde::{Deserialize, Serialize};
 
-use super::User;
+use super::{Acl, ContentHash, FileVersion, Rights, User};
 
 pub trait FsNode {
     fn id(&self) -> u64;
     fn name(&self) -> &str;
     fn parent_id(&self) -> u64;
     fn owner_id(&self) -> u64;
+    /// Returns the own (not inherited) groups, users and rights of the FsNode.
+    fn acl(&self) -> &Acl;
     /**
-     * Returns a list containing the IDs of all Groups, which members are allowed to read the given
-     * FsNode.
+     * Returns the groups, users and rights of the ancestors, which the FsNode inherits. It is
+     * empty, if the FsNode does not inherit the permissions of its parent directory.
      */
-    fn readable_groups(&self) -> &[u64];
+    fn inherited_acl(&self) -> &Acl;
+
     /**
-     * Returns a list containing the IDs of all Groups, which members are allowed to write the
-     * given FsNode.
+     * Returns the rights of the given User on the FsNode: The owner has all rights, readable
+     * groups and users have `Rights::READ_ACCESS`, writeable ones `Rights::WRITE_ACCESS`. The
+     * additional rights of the user and their groups are added. The owners of the ancestors
+     * inherit all rights (see `inherited_acl()`).
      */
-    fn writeable_groups(&self) -> &[u64];
-
-    fn may_read(&self, user: &User) -> bool {
+    fn rights(&self, user: &User) -> Rights {
         if self.owner_id() == user.id {
-            return true;
+            return Rights::ALL;
         }
-        for g_id in self.readable_groups() {
-            if user.group_ids.contains(g_id) {
-                return true;
+        let is_member = |g_id: &u64| user.group_ids.contains(g_id);
+
+        let mut rights = Rights::NONE;
+        for acl in [self.acl(), self.inherited_acl()] {
+            if acl.read_user_ids.contains(&user.id) || acl.read_group_ids.iter().any(is_member) {
+                rights |= Rights::READ_ACCESS;
             }
-        }
-        false
-    }
-    fn may_write(&self, user: &User) -> bool {
-        if self.owner_id() == user.id {
-            return true;
-        }
-        for g_id in self.writeable_groups() {
-            if user.group_ids.contains(g_id) {
-                return true;
+            if acl.write_user_ids.contains(&user.id) || acl.write_group_ids.iter().any(is_member) {
+                rights |= Rights::WRITE_ACCESS;
+            }
+            for (u_id, user_rights) in &acl.user_rights {
+                if *u_id == user.id {
+                    rights |= *user_rights;
+                }
+            }
+            for (g_id, group_rights) in &acl.group_rights {
+                if is_member(g_id) {
+                    rights |= *group_rights;
+                }
             }
         }
-        false
+        rights
+    }
+    /// Returns true, if the given User has all of the given rights on the FsNode.
+    fn has_rights(&self, user: &User, rights: Rights) -> bool {
+        self.rights(user).contains(rights)
     }
 }
 
@@ -48,10 +61,35 @@ pub struct File {
     pub id: u64,
     pub parent_id: u64,
     pub owner_id: u64,
-    pub read_group_ids: Vec<u64>,
-    pub write_group_ids: Vec<u64>,
+    /// The own groups, users and rights (see `FsNode`).
+    pub acl: Acl,
+    /// False, if the groups and users of the parent directory are not inherited.
+    pub inherit_permissions: bool,
+    /// The groups, users and rights of the ancestors, that are inherited (see `FsNode`).
+    pub inherited_acl: Acl,
     pub name: String,
     pub media_type: String,
+    /// The size of the current content in bytes.
+    pub size: u64,
+    pub creation_date: DateTime<Utc>,
+    /// The time at which the current content was written.
+    pub modification_date: DateTime<Utc>,
+    /// The ID of the user, that wrote the current content, or 0 if no content was written yet.
+    pub uploader_id: u64,
+    /// The SHA-256 hash of the current content, or None if no content was written yet.
+    pub content_hash: Option<ContentHash>,
+}
+
+impl File {
+    /// Sets the information about the current content and the media type of the File to the
+    /// values of the given FileVersion, which became the current content.
+    pub fn set_content(&mut self, version: &FileVersion) {
+        self.media_type = version.media_type.clone();
+        self.size = version.size;
+        self.modification_date = version.creation_date;
+        self.uploader_id = version.uploader_id;
+        self.content_hash = Some(version.content_hash);
+    }
 }
 
 impl FsNode for File {
@@ -70,24 +108,31 @@ impl FsNode for File {
     fn owner_id(&self) -> u64 {
         self.owner_id
     }
-    fn readable_groups(&self) -> &[u64] {
-        self.read_group_ids.as_slice()
+    fn acl(&self) -> &Acl {
+        &self.acl
     }
-    fn writeable_groups(&self) -> &[u64] {
-        self.write_group_ids.as_slice()
+    fn inherited_acl(&self) -> &Acl {
+        &self.inherited_acl
     }
 }
 
 impl Default for File {
     fn default() -> Self {
+        let now = Utc::now();
         File {
             id: 0,
             parent_id: 0,
             owner_id: 0,
-            read_group_ids: Vec::new(),
-            write_group_ids: Vec::new(),
+            acl: Acl::default(),
+            inherit_permissions: true,
+            inherited_acl: Acl::default(),
             name: String::from("[new_file]"),
             media_type: String::from("*/*"),
+            size: 0,
+            creation_date: now,
+            modification_date: now,
+            uploader_id: 0,
+            content_hash: None,
         }
     }
 }
@@ -130,22 +175,40 @@ pub struct Dir {
     pub id: u64,
     pub parent_id: u64,
     pub owner_id: u64,
-    pub read_group_ids: Vec<u64>,
-    pub write_group_ids: Vec<u64>,
-    pub child_ids: Vec<u64>,
+    /// The own groups, users and rights (see `FsNode`).
+    pub acl: Acl,
+    /// False, if the groups and users of the parent directory are not inherited.
+    pub inherit_permissions: bool,
+    /// The groups, users and rights of the ancestors, that are inherited (see `FsNode`).
+    pub inherited_acl: Acl,
     pub name: String,
+    pub creation_date: DateTime<Utc>,
+    /// The time at which a child was added to, removed from or renamed in the directory.
+    pub modification_date: DateTime<Utc>,
+    /// The total size of all files in the directory and its descendants in bytes.
+    pub size: u64,
+    /// The number of files in the directory and its descendants.
+    pub file_count: u64,
+    /// The number of files and directories directly in the directory.
+    pub child_count: u64,
 }
 
 impl Default for Dir {
     fn default() -> Self {
+        let now = Utc::now();
         Dir {
             id: 0,
             parent_id: 0,
             owner_id: 0,
-            read_group_ids: Vec::new(),
-            write_group_ids: Vec::new(),
-            child_ids: Vec::new(),
+            acl: Acl::default(),
+            inherit_permissions: true,
+            inherited_acl: Acl::default(),
             name: String::from("[new_dir]"),
+            creation_date: now,
+            modification_date: now,
+            size: 0,
+            file_count: 0,
+            child_count: 0,
         }
     }
 }
@@ -166,11 +229,11 @@ impl FsNode for Dir {
     fn owner_id(&self) -> u64 {
         self.owner_id
     }
-    fn readable_groups(&self) -> &[u64] {
-        self.read_group_ids.as_slice()
+    fn acl(&self) -> &Acl {
+        &self.acl
     }
-    fn writeable_groups(&self) -> &[u64] {
-        self.write_group_ids.as_slice()
+    fn inherited_acl(&self) -> &Acl {
+        &self.inherited_acl
     }
 }
 
@@ -213,3 +276,15 @@ impl DirBuilder {
         self
     }
 }
+
+/**
+ * Returns the given name of a File or Dir with the suffix ` (<number>)`, which is used to make
+ * names unique within a directory. For files the suffix is inserted before the extension.
+ */
+pub fn numbered_name(name: &str, number: u64, is_file: bool) -> String {
+    let (stem, extension) = match name.rfind('.') {
+        Some(i) if is_file && i > 0 => name.split_at(i),
+        _ => (name, ""),
+    };
+    format!("{} ({}){}", stem, number, extension)
+}
@@ -5,11 +5,25 @@ use std::{convert::TryFrom, fmt, num::ParseIntError};
 
 use crate::Error;
 
+mod blobs;
 mod dirsystem;
+mod ownership;
+mod rights;
+mod shares;
+mod trash;
+mod uploads;
 mod users;
+mod versions;
 
-pub use dirsystem::{Dir, DirBuilder, File, FileBuilder, FsNode};
+pub use blobs::ContentHash;
+pub use dirsystem::{numbered_name, Dir, DirBuilder, File, FileBuilder, FsNode};
+pub use ownership::OwnershipOffer;
+pub use rights::{Acl, Rights};
+pub use shares::Share;
+pub use trash::TrashEntry;
+pub use uploads::Upload;
 pub use users::{Group, User, UserSession};
+pub use versions::FileVersion;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Id(u64);
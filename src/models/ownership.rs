@@ -0,0 +1,16 @@
+use chrono::{offset::Utc, DateTime};
+
+/**
+ * An offer of the owner of a File or Dir to transfer its ownership to another User.
+ *
+ * The ownership is only transferred, when the new owner accepts the offer. The offer becomes
+ * invalid, if the owner of the FsNode changes in the meantime.
+ */
+#[derive(Debug)]
+pub struct OwnershipOffer {
+    pub fs_node_id: u64,
+    pub new_owner_id: u64,
+    /// The owner of the FsNode, who made the offer.
+    pub owner_id: u64,
+    pub creation_date: DateTime<Utc>,
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+
+use std::ops::{BitOr, BitOrAssign};
+
+/// A single right on a File or Dir.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Right {
+    /// See the metadata of the FsNode and list the childs of a directory.
+    List,
+    /// Read the content of a file.
+    Read,
+    /// Change the content and the metadata of the FsNode.
+    Write,
+    /// Add new childs to a directory (by creating, moving or copying them).
+    Create,
+    /// Move the FsNode into the trash of its owner or out of its directory.
+    Delete,
+    /// Change the name of the FsNode.
+    Rename,
+    /// Manage the permissions and public links of the FsNode.
+    Share,
+}
+
+/// All rights in the order of their bits.
+const RIGHTS: [Right; 7] = [
+    Right::List,
+    Right::Read,
+    Right::Write,
+    Right::Create,
+    Right::Delete,
+    Right::Rename,
+    Right::Share,
+];
+
+/**
+ * A set of rights on a File or Dir. Its byte representation has one bit per right in the order of
+ * `Right`, starting with the lowest bit. In messages, it is represented by a list of the names of
+ * the rights.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "Vec<Right>", into = "Vec<Right>")]
+pub struct Rights(u8);
+
+impl Rights {
+    pub const NONE: Rights = Rights(0);
+    pub const LIST: Rights = Rights(1);
+    pub const READ: Rights = Rights(2);
+    pub const WRITE: Rights = Rights(4);
+    pub const CREATE: Rights = Rights(8);
+    pub const DELETE: Rights = Rights(16);
+    pub const RENAME: Rights = Rights(32);
+    pub const SHARE: Rights = Rights(64);
+    /// The rights of the owner of a FsNode.
+    pub const ALL: Rights = Rights(127);
+    /// The rights of the readable groups and users of a FsNode.
+    pub const READ_ACCESS: Rights = Rights(1 | 2);
+    /// The rights of the writeable groups and users of a FsNode.
+    pub const WRITE_ACCESS: Rights = Rights(4 | 8 | 16 | 32);
+
+    /// Returns the rights given by the bits of `bits`. Unknown bits are ignored.
+    pub fn from_bits(bits: u8) -> Rights {
+        Rights(bits & Rights::ALL.0)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns true, if this set contains all rights of `other`.
+    pub fn contains(self, other: Rights) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Rights {
+    type Output = Rights;
+
+    fn bitor(self, other: Rights) -> Rights {
+        Rights(self.0 | other.0)
+    }
+}
+impl BitOrAssign for Rights {
+    fn bitor_assign(&mut self, other: Rights) {
+        self.0 |= other.0;
+    }
+}
+impl From<Right> for Rights {
+    fn from(right: Right) -> Self {
+        let position = RIGHTS.iter().position(|r| *r == right).unwrap();
+        Rights(1 << position)
+    }
+}
+impl From<Vec<Right>> for Rights {
+    fn from(rights: Vec<Right>) -> Self {
+        rights
+            .into_iter()
+            .fold(Rights::NONE, |set, right| set | Rights::from(right))
+    }
+}
+impl From<Rights> for Vec<Right> {
+    fn from(rights: Rights) -> Self {
+        RIGHTS
+            .iter()
+            .copied()
+            .filter(|right| rights.contains(Rights::from(*right)))
+            .collect()
+    }
+}
+
+/**
+ * The groups and users, which have permissions on a File or Dir (see `FsNode`).
+ *
+ * The rights in `group_rights` and `user_rights` are granted in addition to the rights of the
+ * readable and writeable groups and users.
+ */
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Acl {
+    pub read_group_ids: Vec<u64>,
+    pub write_group_ids: Vec<u64>,
+    pub read_user_ids: Vec<u64>,
+    pub write_user_ids: Vec<u64>,
+    pub group_rights: Vec<(u64, Rights)>,
+    pub user_rights: Vec<(u64, Rights)>,
+}
+
+impl Acl {
+    /// Returns the rights, which are granted to the group given by `group_id` by this Acl.
+    pub fn rights_of_group(&self, group_id: u64) -> Rights {
+        granted_rights(
+            group_id,
+            &self.read_group_ids,
+            &self.write_group_ids,
+            &self.group_rights,
+        )
+    }
+
+    /// Returns the rights, which are granted to the user given by `user_id` by this Acl (without
+    /// the rights of their groups).
+    pub fn rights_of_user(&self, user_id: u64) -> Rights {
+        granted_rights(
+            user_id,
+            &self.read_user_ids,
+            &self.write_user_ids,
+            &self.user_rights,
+        )
+    }
+
+    /// Adds all groups, users and rights of `other`, which are not contained yet.
+    pub fn merge(&mut self, other: &Acl) {
+        merge_ids(&mut self.read_group_ids, &other.read_group_ids);
+        merge_ids(&mut self.write_group_ids, &other.write_group_ids);
+        merge_ids(&mut self.read_user_ids, &other.read_user_ids);
+        merge_ids(&mut self.write_user_ids, &other.write_user_ids);
+        for (id, rights) in &other.group_rights {
+            add_rights(&mut self.group_rights, *id, *rights);
+        }
+        for (id, rights) in &other.user_rights {
+            add_rights(&mut self.user_rights, *id, *rights);
+        }
+    }
+}
+
+/// Returns the rights of `id` given by the lists of readable and writeable IDs and additional
+/// rights.
+fn granted_rights(id: u64, read_ids: &[u64], write_ids: &[u64], list: &[(u64, Rights)]) -> Rights {
+    let mut rights = Rights::NONE;
+    if read_ids.contains(&id) {
+        rights |= Rights::READ_ACCESS;
+    }
+    if write_ids.contains(&id) {
+        rights |= Rights::WRITE_ACCESS;
+    }
+    for (entry_id, entry_rights) in list {
+        if *entry_id == id {
+            rights |= *entry_rights;
+        }
+    }
+    rights
+}
+
+/// Appends all IDs of `ids`, which `list` does not contain yet, to `list`.
+fn merge_ids(list: &mut Vec<u64>, ids: &[u64]) {
+    for id in ids {
+        if !list.contains(id) {
+            list.push(*id);
+        }
+    }
+}
+
+/// Adds `rights` to the entry of `id` in the given list or appends a new entry.
+fn add_rights(list: &mut Vec<(u64, Rights)>, id: u64, rights: Rights) {
+    match list.iter_mut().find(|(entry_id, _)| *entry_id == id) {
+        Some((_, entry_rights)) => *entry_rights |= rights,
+        None => list.push((id, rights)),
+    }
+}
@@ -0,0 +1,37 @@
+use chrono::{offset::Utc, DateTime};
+
+/**
+ * A public link to a File or Dir, that can be used without a user session.
+ *
+ * The link is identified by an unguessable `token`. If the shared FsNode is a directory, the link
+ * gives read access to all of its descendants.
+ */
+#[derive(Debug)]
+pub struct Share {
+    pub token: String,
+    pub fs_node_id: u64,
+    pub owner_id: u64,
+    pub creation_date: DateTime<Utc>,
+    pub expiration_date: Option<DateTime<Utc>>,
+    /// Hash of the password, that is needed to use the link, if there is one.
+    pub pwd_hash: Option<String>,
+    /// Maximum number of downloads, if the downloads are limited.
+    pub download_limit: Option<u32>,
+    pub download_count: u32,
+}
+
+impl Share {
+    /// Returns true, if the expiration date of the link has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expiration_date
+            .map(|date| date <= Utc::now())
+            .unwrap_or(false)
+    }
+
+    /// Returns true, if the link may not be used for further downloads.
+    pub fn is_exhausted(&self) -> bool {
+        self.download_limit
+            .map(|limit| self.download_count >= limit)
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,17 @@
+use chrono::{offset::Utc, DateTime};
+
+/**
+ * A File or Dir, that was moved into the trash of its owner.
+ *
+ * Trashed FsNodes and their descendants are hidden until they are restored or purged.
+ * `parent_id` is the ID of the directory, from which the FsNode was removed.
+ */
+#[derive(Debug)]
+pub struct TrashEntry {
+    pub fs_node_id: u64,
+    pub owner_id: u64,
+    pub parent_id: u64,
+    pub name: String,
+    pub is_dir: bool,
+    pub deletion_date: DateTime<Utc>,
+}
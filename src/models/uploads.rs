@@ -0,0 +1,57 @@
+use chrono::{offset::Utc, DateTime};
+
+/**
+ * A resumable upload, whose content is received in multiple chunks.
+ *
+ * An upload either creates a new file named `name` inside the directory given by `parent_id` or
+ * replaces the content of the existing file given by `file_id`. The other one of these two fields
+ * is 0.
+ */
+#[derive(Debug)]
+pub struct Upload {
+    pub id: u64,
+    pub user_id: u64,
+    pub parent_id: u64,
+    pub file_id: u64,
+    pub name: String,
+    pub media_type: String,
+    pub length: u64,
+    pub creation_date: DateTime<Utc>,
+    /// Sorted and disjoint ranges (`start..end`) of bytes, that were already received.
+    pub received: Vec<(u64, u64)>,
+}
+
+impl Upload {
+    /// Adds the range `start..end` to the received ranges and merges overlapping ranges.
+    pub fn add_range(&mut self, start: u64, end: u64) {
+        if start >= end {
+            return;
+        }
+        self.received.push((start, end));
+        self.received.sort_unstable();
+
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.received.len());
+        for (start, end) in self.received.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => {
+                    last.1 = last.1.max(end);
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+        self.received = merged;
+    }
+
+    /// Returns the offset up to which all bytes were received.
+    pub fn offset(&self) -> u64 {
+        match self.received.first() {
+            Some((0, end)) => *end,
+            _ => 0,
+        }
+    }
+
+    /// Returns true, if all bytes of the upload were received.
+    pub fn is_complete(&self) -> bool {
+        self.offset() >= self.length
+    }
+}
@@ -0,0 +1,22 @@
+use chrono::{offset::Utc, DateTime};
+
+use super::ContentHash;
+
+/**
+ * A revision of the content of a File.
+ *
+ * Every time the content of a File is written, a new FileVersion with an increasing `version`
+ * number is added. The FileVersion with the highest number describes the current content.
+ * `content_hash` identifies the blob containing the content, which may be shared with other
+ * versions and files.
+ */
+#[derive(Debug)]
+pub struct FileVersion {
+    pub file_id: u64,
+    pub version: u64,
+    pub creation_date: DateTime<Utc>,
+    pub uploader_id: u64,
+    pub size: u64,
+    pub content_hash: ContentHash,
+    pub media_type: String,
+}
@@ -1,12 +1,16 @@
-use rocket::response::content::Html;
+use rocket::{http::RawStr, response::content::Html};
 use rocket_dyn_templates::tera::Context;
 use rocket_dyn_templates::Template;
 use serde::Serialize;
 
 use crate::{
-    controller,
+    controller::{
+        self,
+        listing::{self, ListingQuery},
+        path::PathTarget,
+    },
     database::Database,
-    models::{Dir, File, FsNode, User},
+    models::{Dir, File, FsNode, Rights, Share, User},
     Error,
 };
 
@@ -16,14 +20,31 @@ struct DirContext {
     name: String,
     may_read: bool,
     may_write: bool,
+    size: Option<u64>,
+    file_count: Option<u64>,
+    modification_date: Option<i64>,
 }
 impl DirContext {
     fn from_dir(dir: &Dir, user: &User) -> Self {
         DirContext {
             id: dir.id,
             name: dir.name.clone(),
-            may_read: dir.may_read(user),
-            may_write: dir.may_write(user),
+            may_read: dir.has_rights(user, Rights::LIST),
+            may_write: dir.has_rights(user, Rights::CREATE),
+            size: Some(dir.size),
+            file_count: Some(dir.file_count),
+            modification_date: Some(dir.modification_date.timestamp()),
+        }
+    }
+    fn shared(dir: &Dir) -> Self {
+        DirContext {
+            id: dir.id,
+            name: dir.name.clone(),
+            may_read: true,
+            may_write: false,
+            size: Some(dir.size),
+            file_count: Some(dir.file_count),
+            modification_date: Some(dir.modification_date.timestamp()),
         }
     }
 }
@@ -35,20 +56,97 @@ struct FileContext {
     may_read: bool,
     may_write: bool,
     size: u64,
+    modification_date: i64,
 }
 impl FileContext {
     fn from_file(file: &File, user: &User) -> Self {
         FileContext {
             id: file.id,
             name: file.name.clone(),
-            may_read: file.may_read(user),
-            may_write: file.may_write(user),
-            size: 64, // TODO: Change to real value, when saving a files size is implemented.
+            may_read: file.has_rights(user, Rights::READ),
+            may_write: file.has_rights(user, Rights::WRITE),
+            size: file.size,
+            modification_date: file.modification_date.timestamp(),
+        }
+    }
+    fn shared(file: &File) -> Self {
+        FileContext {
+            id: file.id,
+            name: file.name.clone(),
+            may_read: true,
+            may_write: false,
+            size: file.size,
+            modification_date: file.modification_date.timestamp(),
         }
     }
 }
 
-pub fn dir_page(db: &Database, user_id: u64, dir_id: u64) -> Result<Html<Template>, Error> {
+/// A child of a directory in a listing, whose type is given by the field `type`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ChildContext {
+    Dir(DirContext),
+    File(FileContext),
+}
+impl ChildContext {
+    fn from_node(node: &PathTarget, user: &User) -> Self {
+        match node {
+            PathTarget::Dir(dir) => ChildContext::Dir(DirContext::from_dir(dir, user)),
+            PathTarget::File(file) => ChildContext::File(FileContext::from_file(file, user)),
+        }
+    }
+}
+
+/// A group or user in the permission lists of a directory.
+#[derive(Debug, Serialize)]
+struct AclEntryContext {
+    id: u64,
+    name: String,
+    /// True, if the entry is inherited from an ancestor.
+    inherited: bool,
+}
+impl AclEntryContext {
+    /**
+     * Returns the entries of the groups or users given by `own_ids` and `inherited_ids`. Their
+     * names are given by `get_name`, entries without name (e.g. removed groups) are skipped.
+     */
+    fn list<F>(own_ids: &[u64], inherited_ids: &[u64], get_name: F) -> Result<Vec<Self>, Error>
+    where
+        F: Fn(u64) -> Result<Option<String>, Error>,
+    {
+        let mut res = Vec::new();
+        for id in own_ids {
+            if let Some(name) = get_name(*id)? {
+                res.push(AclEntryContext {
+                    id: *id,
+                    name,
+                    inherited: false,
+                });
+            }
+        }
+        for id in inherited_ids.iter().filter(|id| !own_ids.contains(id)) {
+            if let Some(name) = get_name(*id)? {
+                res.push(AclEntryContext {
+                    id: *id,
+                    name,
+                    inherited: true,
+                });
+            }
+        }
+        Ok(res)
+    }
+}
+
+/**
+ * Returns the view of the directory given by `dir_id` for the user given by `user_id`. The childs
+ * of the directory are listed as given by `query`.
+ */
+pub fn dir_page(
+    db: &Database,
+    user_id: u64,
+    dir_id: u64,
+    query: &ListingQuery,
+) -> Result<Html<Template>, Error> {
     let user = if let Some(u) = db.get_user(user_id)? {
         u
     } else {
@@ -63,6 +161,12 @@ pub fn dir_page(db: &Database, user_id: u64, dir_id: u64) -> Result<Html<Templat
     cont.insert("USERNAME", &user.name);
     cont.insert("USERID", &user.id);
 
+    // Insert timestamps and statistics:
+    cont.insert("CREATION_DATE", &dir.creation_date.timestamp());
+    cont.insert("MODIFICATION_DATE", &dir.modification_date.timestamp());
+    cont.insert("SIZE", &dir.size);
+    cont.insert("FILE_COUNT", &dir.file_count);
+
     // Insert owner:
     cont.insert("OWNERID", &dir.owner_id);
     cont.insert(
@@ -88,41 +192,42 @@ pub fn dir_page(db: &Database, user_id: u64, dir_id: u64) -> Result<Html<Templat
     cont.insert("PATH_NODES", &path_nodes);
 
     // Insert permission lists:
-    let read_groups: Vec<_> = dir
-        .read_group_ids
-        .iter()
-        .filter_map(|id| {
-            controller::user::resolve_user_name(
-                *id,
-                || controller::get_dir_info(dir_id, Some(user_id), db),
-                db,
-            )
-            .ok()
-        })
-        .collect();
-    cont.insert("READABLE_GROUPS", &read_groups);
-    let write_groups: Vec<_> = dir
-        .read_group_ids
-        .iter()
-        .filter_map(|id| {
-            controller::user::resolve_user_name(
-                *id,
-                || controller::get_dir_info(dir_id, Some(user_id), db),
-                db,
-            )
-            .ok()
-        })
-        .collect();
-    cont.insert("WRITEABLE_GROUPS", &write_groups);
+    let group_name = |id| Ok(db.get_group(id)?.map(|group| group.name));
+    let user_name = |id| Ok(db.get_user(id)?.map(|user| user.name));
+    cont.insert(
+        "READABLE_GROUPS",
+        &AclEntryContext::list(
+            &dir.acl.read_group_ids,
+            &dir.inherited_acl.read_group_ids,
+            group_name,
+        )?,
+    );
+    cont.insert(
+        "WRITEABLE_GROUPS",
+        &AclEntryContext::list(
+            &dir.acl.write_group_ids,
+            &dir.inherited_acl.write_group_ids,
+            group_name,
+        )?,
+    );
+    cont.insert(
+        "READABLE_USERS",
+        &AclEntryContext::list(
+            &dir.acl.read_user_ids,
+            &dir.inherited_acl.read_user_ids,
+            user_name,
+        )?,
+    );
+    cont.insert(
+        "WRITEABLE_USERS",
+        &AclEntryContext::list(
+            &dir.acl.write_user_ids,
+            &dir.inherited_acl.write_user_ids,
+            user_name,
+        )?,
+    );
 
-    // Insert list of contained files:
-    let files: Vec<FileContext> = db
-        .get_files_by_parent(dir_id)?
-        .into_iter()
-        .map(|f| FileContext::from_file(&f, &user))
-        .collect();
-    cont.insert("FILES", &files);
-    // Insert list of contained directories:
+    // Insert the "." and ".." entries:
     let mut self_dir = dir.clone();
     self_dir.name = String::from(".");
     let dirs: Vec<DirContext> = std::iter::once(self_dir)
@@ -134,10 +239,96 @@ pub fn dir_page(db: &Database, user_id: u64, dir_id: u64) -> Result<Html<Templat
                     d
                 }),
         )
-        .chain(db.get_dirs_by_parent(dir_id)?)
         .map(|d| DirContext::from_dir(&d, &user))
         .collect();
     cont.insert("DIRS", &dirs);
+    // Insert the requested page of contained files and directories:
+    let listing = listing::list_childs(&dir, query, db)?;
+    let childs: Vec<ChildContext> = listing
+        .entries
+        .iter()
+        .map(|node| ChildContext::from_node(node, &user))
+        .collect();
+    cont.insert("CHILDS", &childs);
+    cont.insert("NEXT_CURSOR", &listing.next_cursor);
+
+    // Insert the listing parameters for the sort links, the filter form and the next page link:
+    cont.insert("SORT", &query.sort);
+    cont.insert("ORDER", if query.descending { "desc" } else { "asc" });
+    cont.insert("LIMIT", &query.limit);
+    cont.insert(
+        "MEDIA_TYPE",
+        query.media_type.as_deref().unwrap_or_default(),
+    );
+    cont.insert(
+        "NAME_PATTERN",
+        query.name_pattern.as_deref().unwrap_or_default(),
+    );
+    let mut filter_query = String::new();
+    if let Some(media_type) = &query.media_type {
+        filter_query += &format!("&media_type={}", RawStr::new(media_type).percent_encode());
+    }
+    if let Some(pattern) = &query.name_pattern {
+        filter_query += &format!("&name={}", RawStr::new(pattern).percent_encode());
+    }
+    cont.insert("FILTER_QUERY", &filter_query);
 
     Ok(Html(Template::render("dirview", cont.into_json())))
 }
+
+/**
+ * Returns a read-only view of the directory `dir`, which is accessed through the public link
+ * `share`. All links on the page stay inside the shared directory.
+ */
+pub fn shared_dir_page(db: &Database, share: &Share, dir: Dir) -> Result<Html<Template>, Error> {
+    let mut cont = Context::new();
+    cont.insert("TOKEN", &share.token);
+
+    // Create Vec of ancestors up to the shared directory:
+    let mut path_nodes = vec![DirContext::shared(&dir)];
+    let mut current_node = dir.clone();
+    while current_node.id != share.fs_node_id {
+        current_node = db
+            .get_dir(current_node.parent_id)?
+            .ok_or(Error::NoSuchDir)?;
+        path_nodes.push(DirContext::shared(&current_node));
+    }
+    cont.insert("PATH_NODES", &path_nodes);
+
+    let (child_files, child_dirs) = controller::share::get_shared_childs(share, &dir, db)?;
+
+    // Insert list of contained files:
+    let files: Vec<FileContext> = child_files.iter().map(FileContext::shared).collect();
+    cont.insert("FILES", &files);
+    // Insert list of contained directories:
+    let mut dirs = vec![DirContext {
+        name: String::from("."),
+        ..DirContext::shared(&dir)
+    }];
+    if dir.id != share.fs_node_id {
+        dirs.push(DirContext {
+            id: dir.parent_id,
+            name: String::from(".."),
+            may_read: true,
+            may_write: false,
+            size: None,
+            file_count: None,
+            modification_date: None,
+        });
+    }
+    dirs.extend(child_dirs.iter().map(DirContext::shared));
+    cont.insert("DIRS", &dirs);
+
+    Ok(Html(Template::render("shareview", cont.into_json())))
+}
+
+/// Returns a page asking for the password of the public link given by `token`.
+pub fn share_password_page(token: &str, warning: Option<&str>) -> Html<Template> {
+    let mut cont = Context::new();
+    cont.insert("TOKEN", token);
+    cont.insert("ASK_PASSWORD", &true);
+    if let Some(w) = warning {
+        cont.insert("WARNING", w);
+    }
+    Html(Template::render("shareview", cont.into_json()))
+}
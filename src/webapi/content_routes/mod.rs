@@ -6,25 +6,28 @@ use chrono::{offset::Utc, Duration};
 use rocket::{
     form::{Form, FromForm},
     fs::TempFile,
-    http::{Cookie, CookieJar, SameSite, Status},
+    http::{ContentType, Cookie, CookieJar, SameSite, Status},
     response::content::Html,
     serde::json::Json,
     Route, State,
 };
 use rocket_dyn_templates::{tera::Context, Template};
 
+use super::responses::{ArchiveStream, FileContent};
 use crate::{
     config::Config,
+    controller::{self, archive::ArchiveFormat, listing::ListingQuery},
     database::Database,
     models::{DirBuilder, FileBuilder, Id, UserSession},
-    webapi::{DirMsg, FileMsg},
+    webapi::{DirMsg, FileMsg, ListingParams},
     Error,
 };
 
 mod content_pages;
+mod share_routes;
 
 pub fn get_routes() -> Vec<Route> {
-    routes![
+    let mut res = routes![
         index_login,
         get_login,
         index,
@@ -35,9 +38,13 @@ pub fn get_routes() -> Vec<Route> {
         mkdir,
         upload_file,
         download_file,
+        download_dir_zip,
+        download_dir_tar,
         remove_dir,
         remove_file,
-    ]
+    ];
+    res.extend(share_routes::get_routes());
+    res
 }
 
 // Show login form:
@@ -123,17 +130,19 @@ fn login(
                 .finish(),
         );
         // Send response:
-        content_pages::dir_page(db, user.id, user.root_dir_id).map_err(|err| {
-            if let Error::DbError(e) = err {
-                // TODO: Add logging
-                //error!("DB-Error while GET /: {}", e);
-                println!("DB-Error while GET /: {}", e);
-
-                Status::InternalServerError
-            } else {
-                panic!("Error: {}", err);
-            }
-        })
+        content_pages::dir_page(db, user.id, user.root_dir_id, &ListingQuery::default()).map_err(
+            |err| {
+                if let Error::DbError(e) = err {
+                    // TODO: Add logging
+                    //error!("DB-Error while GET /: {}", e);
+                    println!("DB-Error while GET /: {}", e);
+
+                    Status::InternalServerError
+                } else {
+                    panic!("Error: {}", err);
+                }
+            },
+        )
     } else {
         // Wrong password:
         let mut context = Context::new();
@@ -184,23 +193,27 @@ fn index(db: &State<Database>, session: UserSession) -> Result<Html<Template>, S
         }
     };
 
-    content_pages::dir_page(db, user.id, user.root_dir_id).map_err(|err| {
-        if let Error::DbError(e) = err {
-            // TODO: Add logging
-            //error!("DB-Error while GET /: {}", e);
-            println!("DB-Error while GET /: {}", e);
+    content_pages::dir_page(db, user.id, user.root_dir_id, &ListingQuery::default()).map_err(
+        |err| {
+            if let Error::DbError(e) = err {
+                // TODO: Add logging
+                //error!("DB-Error while GET /: {}", e);
+                println!("DB-Error while GET /: {}", e);
 
-            Status::InternalServerError
-        } else {
-            panic!("Error: {}", err);
-        }
-    })
+                Status::InternalServerError
+            } else {
+                panic!("Error: {}", err);
+            }
+        },
+    )
 }
 
-// Shows the contents of the given directory.
-#[get("/dirs/<dir_id>/view.html")]
+// Shows the contents of the given directory, sorted and filtered as given by the query parameters
+// (see ListingParams).
+#[get("/dirs/<dir_id>/view.html?<params..>")]
 fn dir_view(
     dir_id: Id,
+    params: ListingParams,
     session: UserSession,
     db: &State<Database>,
 ) -> Result<Html<Template>, Status> {
@@ -222,7 +235,8 @@ fn dir_view(
     }
 
     // Responde with dirview page:
-    content_pages::dir_page(db, session.user_id, dir_id.inner()).map_err(|err| {
+    let query = params.to_query().map_err(|_| Status::BadRequest)?;
+    content_pages::dir_page(db, session.user_id, dir_id.inner(), &query).map_err(|err| {
         match err {
             Error::DbError(e) => {
                 // TODO: Add logging
@@ -232,6 +246,7 @@ fn dir_view(
                 Status::InternalServerError
             }
             Error::NoSuchDir => Status::NotFound,
+            Error::BadCall => Status::BadRequest,
             err => {
                 panic!("Error: {}", err);
             }
@@ -246,6 +261,7 @@ fn mkdir(
     session: UserSession,
     db: &State<Database>,
 ) -> Result<Json<DirMsg>, Status> {
+    controller::check_name(dir_name).map_err(|_| Status::BadRequest)?;
     // Insert new dir to DB:
     let mut new_dir = DirBuilder::new()
         .with_parent_id(parent_id.inner())
@@ -253,8 +269,10 @@ fn mkdir(
         .with_name(dir_name)
         .build();
     // TODO: Check the users rights
-    if db.insert_new_dir(&mut new_dir).is_err() {
-        return Err(Status::InternalServerError);
+    match db.insert_new_dir(&mut new_dir) {
+        Ok(()) => {}
+        Err(Error::TargetExists) => return Err(Status::Conflict),
+        Err(_) => return Err(Status::InternalServerError),
     };
 
     Ok(Json(DirMsg::from(new_dir)))
@@ -269,6 +287,7 @@ async fn upload_file(
     config: &State<Config>,
     mut tmp_file: TempFile<'_>,
 ) -> Result<Json<FileMsg>, Status> {
+    controller::check_name(upload_name).map_err(|_| Status::BadRequest)?;
     // Insert new file to DB:
     let mut new_file = FileBuilder::new()
         .with_parent_id(parent_id.inner())
@@ -276,28 +295,44 @@ async fn upload_file(
         .with_name(upload_name)
         .build();
     // TODO: Check the users rights
-    if db.insert_new_file(&mut new_file).is_err() {
-        return Err(Status::InternalServerError);
+    match db.insert_new_file(&mut new_file) {
+        Ok(()) => {}
+        Err(Error::TargetExists) => return Err(Status::Conflict),
+        Err(_) => return Err(Status::InternalServerError),
     };
 
-    // Copy temporary file to new location:
-    let mut new_path = config.file_location.clone();
-    new_path.push(format!("{:x}", new_file.id));
-    if tmp_file.persist_to(new_path).await.is_err() {
-        // TODO: Logging and remove from DB
-        return Err(Status::InternalServerError);
+    // Move temporary file to the blob storage:
+    let (content_hash, size) =
+        match controller::blob::store_temp_file(&mut tmp_file, db, config).await {
+            Ok(v) => v,
+            Err(_) => {
+                // TODO: Logging and remove from DB
+                return Err(Status::InternalServerError);
+            }
+        };
+    let user_id = session.user_id;
+    match controller::blob::run_blocking(db, config, move |db, config| {
+        controller::version::add_version(&mut new_file, user_id, content_hash, size, db, config)?;
+        Ok(new_file)
+    })
+    .await
+    {
+        Ok(new_file) => Ok(Json(FileMsg::from(new_file))),
+        Err(e) => {
+            // TODO: Logging
+            println!("Error on POST /upload/<parent_id>/<upload_name>: {}", e);
+            Err(Status::InternalServerError)
+        }
     }
-
-    Ok(Json(FileMsg::from(new_file)))
 }
 
 #[get("/files/<file_id>")]
-fn download_file(
+async fn download_file(
     file_id: Id,
     session: UserSession,
     db: &State<Database>,
     config: &State<Config>,
-) -> Result<std::fs::File, Status> {
+) -> Result<FileContent, Status> {
     let file_id = file_id.inner();
 
     // Check, if the user is allowed to access the file:
@@ -318,10 +353,18 @@ fn download_file(
     }
 
     // Respond with streamed file:
-    let mut file_path = config.file_location.clone();
-    file_path.push(format!("{:x}", file_id));
-    match std::fs::File::open(file_path) {
-        Ok(file) => Ok(file),
+    match controller::blob::run_blocking(db, config, move |db, config| {
+        controller::version::open_content(file_id, db, config)
+    })
+    .await
+    {
+        Ok(content) => Ok(FileContent::new(
+            file_id,
+            ContentType::parse_flexible(&file.media_type).unwrap_or(ContentType::Binary),
+            content,
+        )),
+        // No content was written to the file yet:
+        Err(Error::NoSuchTarget) => Err(Status::NotFound),
         Err(e) => {
             // TODO: Logging
             println!("Error on GET /files/...: {}", e);
@@ -330,6 +373,78 @@ fn download_file(
     }
 }
 
+// Download the given directory and all readable descendants as a ZIP archive:
+#[get("/dirs/<dir_id>/zip")]
+async fn download_dir_zip(
+    dir_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<ArchiveStream, Status> {
+    download_dir(dir_id, ArchiveFormat::Zip, session, db, config).await
+}
+
+// Download the given directory and all readable descendants as a tar archive:
+#[get("/dirs/<dir_id>/tar")]
+async fn download_dir_tar(
+    dir_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<ArchiveStream, Status> {
+    download_dir(dir_id, ArchiveFormat::Tar, session, db, config).await
+}
+
+async fn download_dir(
+    dir_id: Id,
+    format: ArchiveFormat,
+    session: UserSession,
+    db: &Database,
+    config: &Config,
+) -> Result<ArchiveStream, Status> {
+    let dir_id = dir_id.inner();
+
+    let user_id = session.user_id;
+    let entries = match controller::blob::run_blocking(db, config, move |db, config| {
+        controller::archive::collect_entries(dir_id, user_id, db, config)
+    })
+    .await
+    {
+        Ok(e) => e,
+        Err(Error::NoSuchDir) => {
+            return Err(Status::NotFound);
+        }
+        Err(Error::MissingAuthorization) => {
+            return Err(Status::Forbidden);
+        }
+        Err(e) => {
+            // TODO: Logging
+            println!("Error on GET /dirs/<dir_id>/{}: {}", format.extension(), e);
+            return Err(Status::InternalServerError);
+        }
+    };
+    let name = match db.get_dir(dir_id) {
+        Ok(Some(d)) => d.name,
+        Ok(None) => {
+            return Err(Status::NotFound);
+        }
+        Err(e) => {
+            // TODO: Logging
+            println!("Error on GET /dirs/<dir_id>/{}: {}", format.extension(), e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    Ok(ArchiveStream::new(
+        format,
+        name,
+        entries,
+        db.clone(),
+        config.clone(),
+    ))
+}
+
+// Moves a directory into the trash of its owner:
 #[delete("/dirs/<dir_id>")]
 fn remove_dir(
     dir_id: Id,
@@ -348,32 +463,29 @@ fn remove_dir(
             return Err(Status::InternalServerError);
         }
     };
-    if dir.owner_id != session.user_id {
-        // TODO: Match against existing rules
-        return Err(Status::Unauthorized);
-    }
-
-    // Remove directory:
-    match db.remove_dir(dir_id.inner()) {
-        Ok(dir) => {
+    // Move directory into the trash:
+    match controller::trash::move_to_trash(dir_id.inner(), session.user_id, db) {
+        Ok(_) => {
             // Send directory as response:
             Ok(Json(DirMsg::from(dir)))
         }
-        Err(Error::NoSuchDir) => Err(Status::NotFound),
+        Err(Error::NoSuchTarget) => Err(Status::NotFound),
+        Err(Error::MissingAuthorization) => Err(Status::Unauthorized),
+        Err(Error::ForbiddenAction) => Err(Status::Forbidden),
         Err(e) => {
             // TODO: Logging
-            println!("Error on GET /files/...: {}", e);
+            println!("Error on DELETE /dirs/<dir_id>: {}", e);
             Err(Status::InternalServerError)
         }
     }
 }
 
+// Moves a file into the trash of its owner:
 #[delete("/files/<file_id>")]
 fn remove_file(
     file_id: Id,
     session: UserSession,
     db: &State<Database>,
-    config: &State<Config>,
 ) -> Result<Json<FileMsg>, Status> {
     // Check, if the user is allowed to access the file:
     let file = match db.get_file(file_id.inner()) {
@@ -387,32 +499,15 @@ fn remove_file(
             return Err(Status::InternalServerError);
         }
     };
-    if file.owner_id != session.user_id {
-        // TODO: Match against existing rules
-        return Err(Status::Unauthorized);
-    }
-
-    // Remove file from DB:
-    let res = match db.remove_file(file_id.inner()) {
-        Ok(file) => file,
-        Err(Error::NoSuchDir) => {
-            return Err(Status::NotFound);
-        }
+    // Move file into the trash:
+    match controller::trash::move_to_trash(file_id.inner(), session.user_id, db) {
+        Ok(_) => Ok(Json(FileMsg::from(file))),
+        Err(Error::NoSuchTarget) => Err(Status::NotFound),
+        Err(Error::MissingAuthorization) => Err(Status::Unauthorized),
         Err(e) => {
             // TODO: Logging
             println!("Error on DELETE /files/<file_id>: {}", e);
-            return Err(Status::InternalServerError);
+            Err(Status::InternalServerError)
         }
-    };
-
-    // Remove file from FS:
-    let mut file_path = config.file_location.clone();
-    file_path.push(format!("{:x}", file_id.inner()));
-    if let Err(e) = std::fs::remove_file(file_path) {
-        // TODO: Logging
-        println!("Error on DELETE /files/<file_id>: {}", e);
-        return Err(Status::InternalServerError);
     }
-
-    Ok(Json(FileMsg::from(res)))
 }
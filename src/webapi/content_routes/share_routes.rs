@@ -0,0 +1,200 @@
+use rocket::{
+    form::{Form, FromForm},
+    http::{ContentType, CookieJar, Status},
+    response::{content::Html, Redirect},
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+
+use super::{
+    super::{
+        add_share_access,
+        errors::share_error_status,
+        responses::{ArchiveStream, FileContent, SharedContent},
+        share_access,
+    },
+    content_pages,
+};
+use crate::{
+    config::Config,
+    controller::{self, archive::ArchiveFormat, blob::BlobReader},
+    database::Database,
+    models::{File, Id, Share},
+    Error,
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        open_share,
+        unlock_share,
+        shared_dir_view,
+        download_shared_file,
+        download_shared_dir_zip,
+        download_shared_dir_tar
+    ]
+}
+
+// Opens a public link: A shared file is downloaded, a shared directory is shown. If the link is
+// protected by a password and the request has no valid access cookie, a password form is shown.
+#[get("/shares/<token>")]
+async fn open_share(
+    token: &str,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<SharedContent, Status> {
+    let access = share_access(cookies, token);
+    let share = match controller::share::open_share(token, access.as_deref(), db, config) {
+        Ok(s) => s,
+        Err(Error::MissingAuthorization) => {
+            return Ok(SharedContent::Page(content_pages::share_password_page(
+                token, None,
+            )));
+        }
+        Err(err) => {
+            return Err(share_error_status("GET /shares/<token>", err));
+        }
+    };
+
+    let is_file = db
+        .get_file(share.fs_node_id)
+        .map_err(|err| share_error_status("GET /shares/<token>", Error::from(err)))?
+        .is_some();
+    if is_file {
+        controller::blob::run_blocking(db, config, move |db, config| {
+            let (file, content) =
+                controller::share::get_shared_file_content(&share, None, db, config)?;
+            Ok(SharedContent::File(file_content(&share, file, content)))
+        })
+        .await
+        .map_err(|err| share_error_status("GET /shares/<token>", err))
+    } else {
+        controller::share::get_shared_dir(&share, None, db)
+            .and_then(|dir| content_pages::shared_dir_page(db, &share, dir))
+            .map(SharedContent::Page)
+            .map_err(|err| share_error_status("GET /shares/<token>", err))
+    }
+}
+
+#[derive(FromForm)]
+struct ShareCreds {
+    password: String,
+}
+
+// Checks the password of a public link. On success, an access cookie is set and the client is
+// redirected to the link. Otherwise the password form is shown again.
+#[post("/shares/<token>", data = "<credentials>")]
+fn unlock_share(
+    token: &str,
+    credentials: Form<ShareCreds>,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<SharedContent, Status> {
+    match controller::share::unlock_share(token, &credentials.password, db, config) {
+        Ok((share, access)) => {
+            add_share_access(cookies, &share.token, access);
+            Ok(SharedContent::Redirect(Redirect::to(format!(
+                "/shares/{}",
+                share.token
+            ))))
+        }
+        Err(Error::MissingAuthorization) => Ok(SharedContent::Page(
+            content_pages::share_password_page(token, Some("The password was wrong.")),
+        )),
+        Err(err) => Err(share_error_status("POST /shares/<token>", err)),
+    }
+}
+
+// Shows the contents of a directory inside of a shared directory.
+#[get("/shares/<token>/dirs/<dir_id>/view.html")]
+fn shared_dir_view(
+    token: &str,
+    dir_id: Id,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Html<Template>, Status> {
+    let access = share_access(cookies, token);
+    controller::share::open_share(token, access.as_deref(), db, config)
+        .and_then(|share| {
+            let dir = controller::share::get_shared_dir(&share, Some(dir_id.inner()), db)?;
+            content_pages::shared_dir_page(db, &share, dir)
+        })
+        .map_err(|err| share_error_status("GET /shares/<token>/dirs/<dir_id>/view.html", err))
+}
+
+// Downloads a file inside of a shared directory.
+#[get("/shares/<token>/files/<file_id>")]
+async fn download_shared_file(
+    token: &str,
+    file_id: Id,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<FileContent, Status> {
+    let access = share_access(cookies, token);
+    let share = controller::share::open_share(token, access.as_deref(), db, config)
+        .map_err(|err| share_error_status("GET /shares/<token>/files/<file_id>", err))?;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        let (file, content) =
+            controller::share::get_shared_file_content(&share, Some(file_id.inner()), db, config)?;
+        Ok(file_content(&share, file, content))
+    })
+    .await
+    .map_err(|err| share_error_status("GET /shares/<token>/files/<file_id>", err))
+}
+
+// Downloads a directory inside of a shared directory as a ZIP archive:
+#[get("/shares/<token>/dirs/<dir_id>/zip")]
+async fn download_shared_dir_zip(
+    token: &str,
+    dir_id: Id,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<ArchiveStream, Status> {
+    let access = share_access(cookies, token);
+    download_shared_dir(token, dir_id, access, ArchiveFormat::Zip, db, config).await
+}
+
+// Downloads a directory inside of a shared directory as a tar archive:
+#[get("/shares/<token>/dirs/<dir_id>/tar")]
+async fn download_shared_dir_tar(
+    token: &str,
+    dir_id: Id,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<ArchiveStream, Status> {
+    let access = share_access(cookies, token);
+    download_shared_dir(token, dir_id, access, ArchiveFormat::Tar, db, config).await
+}
+
+async fn download_shared_dir(
+    token: &str,
+    dir_id: Id,
+    access: Option<String>,
+    format: ArchiveFormat,
+    db: &Database,
+    config: &Config,
+) -> Result<ArchiveStream, Status> {
+    let route = format!("GET /shares/<token>/dirs/<dir_id>/{}", format.extension());
+    let share = controller::share::open_share(token, access.as_deref(), db, config)
+        .map_err(|err| share_error_status(&route, err))?;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::share::collect_shared_entries(&share, Some(dir_id.inner()), db, config)
+    })
+    .await
+    .map(|(dir, entries)| ArchiveStream::new(format, dir.name, entries, db.clone(), config.clone()))
+    .map_err(|err| share_error_status(&route, err))
+}
+
+fn file_content(share: &Share, file: File, content: BlobReader) -> FileContent {
+    FileContent::shared(
+        file.id,
+        ContentType::parse_flexible(&file.media_type).unwrap_or(ContentType::Binary),
+        content,
+        share.token.clone(),
+    )
+}
@@ -6,6 +6,8 @@ use rocket::{
 };
 use rocket_dyn_templates::{tera::Context, Template};
 
+use crate::Error;
+
 pub fn error_catchers() -> Vec<Catcher> {
     catchers![internal_server_error]
 }
@@ -21,3 +23,72 @@ fn internal_server_error() -> status::Custom<Html<Template>> {
         Html(Template::render("error", context.into_json())),
     )
 }
+
+/**
+ * Maps the Errors of the controller functions to HTTP Status codes. Routes, whose functions give
+ * an Error another meaning, handle it before passing the others to this function (see e.g.
+ * `share_error_status()`).
+ */
+pub fn error_status(route: &str, err: Error) -> Status {
+    match err {
+        Error::NoSuchTarget | Error::NoSuchFile | Error::NoSuchDir => {
+            // TODO: Logging
+            println!("Error on {}: Nonexisting file, directory or target.", route);
+            Status::NotFound
+        }
+        Error::MissingAuthorization => {
+            // TODO: Logging
+            println!("Error on {}: Missing rights.", route);
+            Status::Forbidden
+        }
+        Error::TargetExists => {
+            // TODO: Logging
+            println!("Error on {}: Target name is already used.", route);
+            Status::Conflict
+        }
+        Error::ForbiddenAction => {
+            // TODO: Logging
+            println!("Error on {}: Forbidden action.", route);
+            Status::Conflict
+        }
+        Error::BadCall => {
+            // TODO: Logging
+            println!("Error on {}: Missing or invalid fields.", route);
+            Status::BadRequest
+        }
+        err => {
+            // TODO: Logging
+            println!("Error on {}: {}", route, err);
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Maps the Errors of the functions in `controller::share` to HTTP Status codes: Expired links
+/// and reached download limits result in `Status::Gone`.
+pub fn share_error_status(route: &str, err: Error) -> Status {
+    match err {
+        Error::ForbiddenAction => {
+            // TODO: Logging
+            println!(
+                "Error on {}: Link expired or download limit reached.",
+                route
+            );
+            Status::Gone
+        }
+        err => error_status(route, err),
+    }
+}
+
+/// Maps the Errors of the functions, that change the permissions of a File or Dir, to HTTP Status
+/// codes: Too many groups or users result in `Status::BadRequest`.
+pub fn permission_error_status(route: &str, err: Error) -> Status {
+    match err {
+        Error::ForbiddenAction => {
+            // TODO: Logging
+            println!("Error on {}: Too many groups or users.", route);
+            Status::BadRequest
+        }
+        err => error_status(route, err),
+    }
+}
@@ -4,7 +4,9 @@ use argon2::{
 };
 use rand::thread_rng;
 use rocket::{
+    form::FromForm,
     fs::{self, FileServer},
+    http::{Cookie, CookieJar, SameSite},
     Rocket,
 };
 use rocket_dyn_templates::{
@@ -17,12 +19,23 @@ use std::collections::{hash_map::RandomState, HashMap};
 
 use crate::{
     config::Config,
+    controller::{
+        batch::Permission,
+        listing::{Listing, ListingQuery, DEFAULT_LIMIT},
+        moving::ConflictPolicy,
+        path::PathTarget,
+        search::SearchHit,
+    },
     database::Database,
-    models::{Dir, File, Group, Id, User},
+    models::{
+        Dir, File, FileVersion, Group, Id, OwnershipOffer, Rights, Share, TrashEntry, Upload, User,
+    },
+    Error,
 };
 
 mod content_routes;
 mod errors;
+mod responses;
 mod rest_api;
 use errors::error_catchers;
 
@@ -89,8 +102,36 @@ fn init_template_engine(engines: &mut Engines) {
     engines.tera.register_filter("second", SecondFilter);
 }
 
+/// Returns the access token for the public link given by `token` (see
+/// `controller::share::unlock_share()`), that is stored in the cookies of a request.
+fn share_access(cookies: &CookieJar<'_>, token: &str) -> Option<String> {
+    cookies
+        .get(&share_access_cookie(token))
+        .map(|cookie| String::from(cookie.value()))
+}
+
+/// Stores the access token for the public link given by `token` in a cookie. The access token
+/// expires on its own, the cookie is removed with the end of the browser session.
+fn add_share_access(cookies: &CookieJar<'_>, token: &str, access: String) {
+    cookies.add(
+        Cookie::build(share_access_cookie(token), access)
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(true)
+            .finish(),
+    );
+}
+
+fn share_access_cookie(token: &str) -> String {
+    format!("share_access_{}", token)
+}
+
 /**
  * Representation of a possibly incomplete Dir that the server got as a requests body.
+ * The dates, `size`, `file_count`, `child_count` and the permissions are ignored in requests
+ * (see `PermissionsMsg`), the owner is changed by an `OwnershipOfferMsg`. Dates are given as UNIX
+ * timestamps.
  */
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DirMsg {
@@ -99,41 +140,107 @@ pub struct DirMsg {
     pub owner_id: Option<Id>,
     pub read_group_ids: Option<Vec<Id>>,
     pub write_group_ids: Option<Vec<Id>>,
-    pub child_ids: Option<Vec<Id>>,
+    pub read_user_ids: Option<Vec<Id>>,
+    pub write_user_ids: Option<Vec<Id>>,
+    pub inherit_permissions: Option<bool>,
+    pub inherited_read_group_ids: Option<Vec<Id>>,
+    pub inherited_write_group_ids: Option<Vec<Id>>,
+    pub inherited_read_user_ids: Option<Vec<Id>>,
+    pub inherited_write_user_ids: Option<Vec<Id>>,
+    pub group_rights: Option<Vec<RightsEntryMsg>>,
+    pub user_rights: Option<Vec<RightsEntryMsg>>,
+    pub inherited_group_rights: Option<Vec<RightsEntryMsg>>,
+    pub inherited_user_rights: Option<Vec<RightsEntryMsg>>,
     pub name: Option<String>,
+    pub creation_date: Option<i64>,
+    pub modification_date: Option<i64>,
+    pub size: Option<u64>,
+    pub file_count: Option<u64>,
+    pub child_count: Option<u64>,
 }
 
-impl DirMsg {
-    pub fn apply_changes(self, dir: &mut Dir) {
-        assert!(self.id.is_none() || self.id.unwrap().as_int() == dir.id);
-
-        if let Some(parent) = self.parent_id {
-            dir.parent_id = parent.as_int();
-        }
-        if let Some(owner) = self.owner_id {
-            dir.owner_id = owner.as_int();
-        }
-        if let Some(name) = self.name {
-            dir.name = name;
-        }
-    }
-}
 impl From<Dir> for DirMsg {
     fn from(dir: Dir) -> Self {
         DirMsg {
             id: Some(Id::from(dir.id)),
             parent_id: Some(Id::from(dir.parent_id)),
             owner_id: Some(Id::from(dir.owner_id)),
-            read_group_ids: Some(dir.read_group_ids.into_iter().map(Id::from).collect()),
-            write_group_ids: Some(dir.write_group_ids.into_iter().map(Id::from).collect()),
-            child_ids: Some(dir.child_ids.into_iter().map(Id::from).collect()),
+            read_group_ids: Some(dir.acl.read_group_ids.into_iter().map(Id::from).collect()),
+            write_group_ids: Some(dir.acl.write_group_ids.into_iter().map(Id::from).collect()),
+            read_user_ids: Some(dir.acl.read_user_ids.into_iter().map(Id::from).collect()),
+            write_user_ids: Some(dir.acl.write_user_ids.into_iter().map(Id::from).collect()),
+            inherit_permissions: Some(dir.inherit_permissions),
+            inherited_read_group_ids: Some(
+                dir.inherited_acl
+                    .read_group_ids
+                    .into_iter()
+                    .map(Id::from)
+                    .collect(),
+            ),
+            inherited_write_group_ids: Some(
+                dir.inherited_acl
+                    .write_group_ids
+                    .into_iter()
+                    .map(Id::from)
+                    .collect(),
+            ),
+            inherited_read_user_ids: Some(
+                dir.inherited_acl
+                    .read_user_ids
+                    .into_iter()
+                    .map(Id::from)
+                    .collect(),
+            ),
+            inherited_write_user_ids: Some(
+                dir.inherited_acl
+                    .write_user_ids
+                    .into_iter()
+                    .map(Id::from)
+                    .collect(),
+            ),
+            group_rights: Some(
+                dir.acl
+                    .group_rights
+                    .into_iter()
+                    .map(RightsEntryMsg::from)
+                    .collect(),
+            ),
+            user_rights: Some(
+                dir.acl
+                    .user_rights
+                    .into_iter()
+                    .map(RightsEntryMsg::from)
+                    .collect(),
+            ),
+            inherited_group_rights: Some(
+                dir.inherited_acl
+                    .group_rights
+                    .into_iter()
+                    .map(RightsEntryMsg::from)
+                    .collect(),
+            ),
+            inherited_user_rights: Some(
+                dir.inherited_acl
+                    .user_rights
+                    .into_iter()
+                    .map(RightsEntryMsg::from)
+                    .collect(),
+            ),
             name: Some(dir.name),
+            creation_date: Some(dir.creation_date.timestamp()),
+            modification_date: Some(dir.modification_date.timestamp()),
+            size: Some(dir.size),
+            file_count: Some(dir.file_count),
+            child_count: Some(dir.child_count),
         }
     }
 }
 
 /**
  * Representation of a possibly incomplete File that the server got as a requests body.
+ * The information about the content (`size`, the dates, `uploader_id` and `sha256`) and the
+ * permissions are ignored in requests (see `PermissionsMsg`), the owner is changed by an
+ * `OwnershipOfferMsg`. Dates are given as UNIX timestamps.
  */
 #[derive(Debug, Deserialize, Serialize)]
 pub struct FileMsg {
@@ -142,39 +249,241 @@ pub struct FileMsg {
     pub owner_id: Option<Id>,
     pub read_group_ids: Option<Vec<Id>>,
     pub write_group_ids: Option<Vec<Id>>,
+    pub read_user_ids: Option<Vec<Id>>,
+    pub write_user_ids: Option<Vec<Id>>,
+    pub inherit_permissions: Option<bool>,
+    pub inherited_read_group_ids: Option<Vec<Id>>,
+    pub inherited_write_group_ids: Option<Vec<Id>>,
+    pub inherited_read_user_ids: Option<Vec<Id>>,
+    pub inherited_write_user_ids: Option<Vec<Id>>,
+    pub group_rights: Option<Vec<RightsEntryMsg>>,
+    pub user_rights: Option<Vec<RightsEntryMsg>>,
+    pub inherited_group_rights: Option<Vec<RightsEntryMsg>>,
+    pub inherited_user_rights: Option<Vec<RightsEntryMsg>>,
     pub name: Option<String>,
     pub size: Option<u64>,
+    pub creation_date: Option<i64>,
+    pub modification_date: Option<i64>,
+    pub uploader_id: Option<Id>,
+    pub sha256: Option<String>,
 }
 
-impl FileMsg {
-    pub fn apply_changes(self, file: &mut File) {
-        assert!(self.id.is_none() || self.id.unwrap().as_int() == file.id);
-
-        if let Some(parent) = self.parent_id {
-            file.parent_id = parent.as_int();
-        }
-        if let Some(owner) = self.owner_id {
-            file.owner_id = owner.as_int();
-        }
-        if let Some(name) = self.name {
-            file.name = name;
-        }
-    }
-}
 impl From<File> for FileMsg {
     fn from(file: File) -> Self {
         FileMsg {
             id: Some(Id::from(file.id)),
             parent_id: Some(Id::from(file.parent_id)),
             owner_id: Some(Id::from(file.owner_id)),
-            read_group_ids: Some(file.read_group_ids.into_iter().map(Id::from).collect()),
-            write_group_ids: Some(file.write_group_ids.into_iter().map(Id::from).collect()),
+            read_group_ids: Some(file.acl.read_group_ids.into_iter().map(Id::from).collect()),
+            write_group_ids: Some(file.acl.write_group_ids.into_iter().map(Id::from).collect()),
+            read_user_ids: Some(file.acl.read_user_ids.into_iter().map(Id::from).collect()),
+            write_user_ids: Some(file.acl.write_user_ids.into_iter().map(Id::from).collect()),
+            inherit_permissions: Some(file.inherit_permissions),
+            inherited_read_group_ids: Some(
+                file.inherited_acl
+                    .read_group_ids
+                    .into_iter()
+                    .map(Id::from)
+                    .collect(),
+            ),
+            inherited_write_group_ids: Some(
+                file.inherited_acl
+                    .write_group_ids
+                    .into_iter()
+                    .map(Id::from)
+                    .collect(),
+            ),
+            inherited_read_user_ids: Some(
+                file.inherited_acl
+                    .read_user_ids
+                    .into_iter()
+                    .map(Id::from)
+                    .collect(),
+            ),
+            inherited_write_user_ids: Some(
+                file.inherited_acl
+                    .write_user_ids
+                    .into_iter()
+                    .map(Id::from)
+                    .collect(),
+            ),
+            group_rights: Some(
+                file.acl
+                    .group_rights
+                    .into_iter()
+                    .map(RightsEntryMsg::from)
+                    .collect(),
+            ),
+            user_rights: Some(
+                file.acl
+                    .user_rights
+                    .into_iter()
+                    .map(RightsEntryMsg::from)
+                    .collect(),
+            ),
+            inherited_group_rights: Some(
+                file.inherited_acl
+                    .group_rights
+                    .into_iter()
+                    .map(RightsEntryMsg::from)
+                    .collect(),
+            ),
+            inherited_user_rights: Some(
+                file.inherited_acl
+                    .user_rights
+                    .into_iter()
+                    .map(RightsEntryMsg::from)
+                    .collect(),
+            ),
             name: Some(file.name),
-            size: None, // TODO: Change to files size, when adding file attribute to File struct.
+            size: Some(file.size),
+            creation_date: Some(file.creation_date.timestamp()),
+            modification_date: Some(file.modification_date.timestamp()),
+            uploader_id: Some(file.uploader_id).filter(|id| *id != 0).map(Id::from),
+            sha256: file.content_hash.map(|hash| hash.to_string()),
+        }
+    }
+}
+
+/**
+ * Representation of a File or Dir, whose type is given by the field `type` (`"file"` or `"dir"`).
+ */
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FsNodeMsg {
+    File(FileMsg),
+    Dir(DirMsg),
+}
+impl From<PathTarget> for FsNodeMsg {
+    fn from(target: PathTarget) -> Self {
+        match target {
+            PathTarget::File(file) => FsNodeMsg::File(FileMsg::from(file)),
+            PathTarget::Dir(dir) => FsNodeMsg::Dir(DirMsg::from(dir)),
         }
     }
 }
 
+/// Representation of the query parameters of a listing of the childs of a directory. `sort` is
+/// `"name"`, `"size"`, `"mtime"` or `"type"`, `order` is `"asc"` or `"desc"`. Only childs with the
+/// given `media_type` (like `image/png` or `image/*`) and a name matching the pattern `name` (with
+/// the wildcards `*` and `?`) are listed. `cursor` is the `next_cursor` of the previous page.
+#[derive(Debug, FromForm)]
+pub struct ListingParams {
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub media_type: Option<String>,
+    pub name: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+impl ListingParams {
+    /// Returns the ListingQuery given by the parameters or `Error::BadCall`, if a parameter is
+    /// invalid.
+    pub fn to_query(&self) -> Result<ListingQuery, Error> {
+        let descending = match self.order.as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return Err(Error::BadCall),
+        };
+        Ok(ListingQuery {
+            sort: match &self.sort {
+                Some(sort) => sort.parse()?,
+                None => ListingQuery::default().sort,
+            },
+            descending,
+            media_type: self.media_type.clone().filter(|t| !t.is_empty()),
+            name_pattern: self.name.clone().filter(|n| !n.is_empty()),
+            cursor: self.cursor.clone(),
+            limit: self.limit.unwrap_or(DEFAULT_LIMIT),
+        })
+    }
+}
+
+/**
+ * Representation of a page of the childs of a directory. If there are more childs, `next_cursor`
+ * is the cursor of the next page.
+ */
+#[derive(Debug, Serialize)]
+pub struct ListingMsg {
+    pub childs: Vec<FsNodeMsg>,
+    pub next_cursor: Option<String>,
+}
+impl From<Listing> for ListingMsg {
+    fn from(listing: Listing) -> Self {
+        ListingMsg {
+            childs: listing.entries.into_iter().map(FsNodeMsg::from).collect(),
+            next_cursor: listing.next_cursor,
+        }
+    }
+}
+
+/**
+ * Representation of a File found by a search. `score` ranks the results (matches in the name are
+ * ranked highest) and `matches` lists the fields (`"name"`, `"media_type"`, `"owner"` or
+ * `"content"`), in which the search terms were found.
+ */
+#[derive(Debug, Serialize)]
+pub struct SearchHitMsg {
+    #[serde(flatten)]
+    pub file: FileMsg,
+    pub score: u32,
+    pub matches: Vec<String>,
+}
+impl From<SearchHit> for SearchHitMsg {
+    fn from(hit: SearchHit) -> Self {
+        SearchHitMsg {
+            file: FileMsg::from(hit.file),
+            score: hit.score,
+            matches: hit.fields.into_iter().map(String::from).collect(),
+        }
+    }
+}
+
+/**
+ * Representation of the complete group and user permissions of a File or Dir, which replace the
+ * current ones. Missing user and rights lists are empty. If `inherit_permissions` is missing, it
+ * is not changed.
+ */
+#[derive(Debug, Deserialize)]
+pub struct PermissionsMsg {
+    pub read_group_ids: Vec<Id>,
+    pub write_group_ids: Vec<Id>,
+    #[serde(default)]
+    pub read_user_ids: Vec<Id>,
+    #[serde(default)]
+    pub write_user_ids: Vec<Id>,
+    #[serde(default)]
+    pub group_rights: Vec<RightsEntryMsg>,
+    #[serde(default)]
+    pub user_rights: Vec<RightsEntryMsg>,
+    pub inherit_permissions: Option<bool>,
+}
+
+/**
+ * Representation of the additional rights of the Group or User given by `id` on a File or Dir.
+ * The rights are given by their names (e.g. `["list", "create"]`).
+ */
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RightsEntryMsg {
+    pub id: Id,
+    pub rights: Rights,
+}
+
+impl From<(u64, Rights)> for RightsEntryMsg {
+    fn from((id, rights): (u64, Rights)) -> Self {
+        RightsEntryMsg {
+            id: Id::from(id),
+            rights,
+        }
+    }
+}
+
+/// Representation of the new additional rights of a single Group or User on a File or Dir.
+#[derive(Debug, Deserialize)]
+pub struct RightsMsg {
+    pub rights: Rights,
+}
+
 /**
  * Representation of a possibly incomplete User that the server got as a requests body.
  */
@@ -241,3 +550,235 @@ impl From<Group> for GroupMsg {
         }
     }
 }
+
+/**
+ * Representation of a possibly incomplete Upload that the server got as a requests body.
+ */
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UploadMsg {
+    pub id: Option<Id>,
+    pub parent_id: Option<Id>,
+    pub file_id: Option<Id>,
+    pub name: Option<String>,
+    pub media_type: Option<String>,
+    pub length: Option<u64>,
+    pub offset: Option<u64>,
+    pub received: Option<Vec<(u64, u64)>>,
+}
+
+impl From<Upload> for UploadMsg {
+    fn from(upload: Upload) -> Self {
+        UploadMsg {
+            id: Some(Id::from(upload.id)),
+            parent_id: Some(upload.parent_id).filter(|id| *id != 0).map(Id::from),
+            file_id: Some(upload.file_id).filter(|id| *id != 0).map(Id::from),
+            name: Some(upload.name.clone()),
+            media_type: Some(upload.media_type.clone()),
+            length: Some(upload.length),
+            offset: Some(upload.offset()),
+            received: Some(upload.received),
+        }
+    }
+}
+
+/**
+ * Representation of a possibly incomplete Share that the server got as a requests body.
+ * Dates are given in seconds since the UNIX epoch. The password is never sent to the client.
+ */
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ShareMsg {
+    pub token: Option<String>,
+    pub fs_node_id: Option<Id>,
+    pub owner_id: Option<Id>,
+    pub creation_date: Option<i64>,
+    pub expiration_date: Option<i64>,
+    pub password: Option<String>,
+    pub password_protected: Option<bool>,
+    pub download_limit: Option<u32>,
+    pub download_count: Option<u32>,
+}
+
+impl From<Share> for ShareMsg {
+    fn from(share: Share) -> Self {
+        ShareMsg {
+            token: Some(share.token),
+            fs_node_id: Some(Id::from(share.fs_node_id)),
+            owner_id: Some(Id::from(share.owner_id)),
+            creation_date: Some(share.creation_date.timestamp()),
+            expiration_date: share.expiration_date.map(|date| date.timestamp()),
+            password: None,
+            password_protected: Some(share.pwd_hash.is_some()),
+            download_limit: share.download_limit,
+            download_count: Some(share.download_count),
+        }
+    }
+}
+
+/**
+ * Representation of a possibly incomplete OwnershipOffer that the server got as a requests body.
+ * `owner_id` is the user, who made the offer. The creation date is given in seconds since the
+ * UNIX epoch. Only `fs_node_id` and `new_owner_id` are used in requests.
+ */
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OwnershipOfferMsg {
+    pub fs_node_id: Option<Id>,
+    pub new_owner_id: Option<Id>,
+    pub owner_id: Option<Id>,
+    pub creation_date: Option<i64>,
+}
+
+impl From<OwnershipOffer> for OwnershipOfferMsg {
+    fn from(offer: OwnershipOffer) -> Self {
+        OwnershipOfferMsg {
+            fs_node_id: Some(Id::from(offer.fs_node_id)),
+            new_owner_id: Some(Id::from(offer.new_owner_id)),
+            owner_id: Some(Id::from(offer.owner_id)),
+            creation_date: Some(offer.creation_date.timestamp()),
+        }
+    }
+}
+
+/**
+ * Representation of an entry of a users trash, that is sent to the client.
+ * `parent_id` is the ID of the directory, from which the file or directory was removed. The
+ * deletion date is given in seconds since the UNIX epoch.
+ */
+#[derive(Debug, Serialize)]
+pub struct TrashMsg {
+    pub fs_node_id: Id,
+    pub parent_id: Id,
+    pub name: String,
+    pub is_dir: bool,
+    pub deletion_date: i64,
+}
+
+impl From<TrashEntry> for TrashMsg {
+    fn from(entry: TrashEntry) -> Self {
+        TrashMsg {
+            fs_node_id: Id::from(entry.fs_node_id),
+            parent_id: Id::from(entry.parent_id),
+            name: entry.name,
+            is_dir: entry.is_dir,
+            deletion_date: entry.deletion_date.timestamp(),
+        }
+    }
+}
+
+/**
+ * Representation of a version of the content of a file, that is sent to the client.
+ * The creation date is given in seconds since the UNIX epoch.
+ */
+#[derive(Debug, Serialize)]
+pub struct VersionMsg {
+    pub file_id: Id,
+    pub version: u64,
+    pub creation_date: i64,
+    pub uploader_id: Id,
+    pub size: u64,
+    pub media_type: String,
+}
+
+impl From<FileVersion> for VersionMsg {
+    fn from(version: FileVersion) -> Self {
+        VersionMsg {
+            file_id: Id::from(version.file_id),
+            version: version.version,
+            creation_date: version.creation_date.timestamp(),
+            uploader_id: Id::from(version.uploader_id),
+            size: version.size,
+            media_type: version.media_type,
+        }
+    }
+}
+
+/**
+ * Representation of the retention policy for the versions of a file.
+ * `max_versions` is the maximum number of previous versions, that are kept for the file, or None,
+ * if the default limit `default_max_versions` is used. `default_max_versions` is ignored in
+ * requests.
+ */
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VersionLimitMsg {
+    pub max_versions: Option<u32>,
+    pub default_max_versions: Option<u32>,
+}
+
+/**
+ * Representation of a request to move or rename a File or Dir.
+ * If `parent_id` or `name` is None, the current parent or name is kept. If `on_conflict`
+ * (`"fail"`, `"rename"` or `"overwrite"`) is None, the configured default policy is used.
+ */
+#[derive(Debug, Deserialize)]
+pub struct MoveMsg {
+    pub parent_id: Option<Id>,
+    pub name: Option<String>,
+    pub on_conflict: Option<ConflictPolicy>,
+}
+
+/**
+ * Representation of a request to copy a File or Dir into the directory given by `parent_id`.
+ * If `name` is None, the name of the original is used. Name conflicts are handled as for a
+ * `MoveMsg`. Group and user permissions are only copied, if `copy_permissions` is true.
+ */
+#[derive(Debug, Deserialize)]
+pub struct CopyMsg {
+    pub parent_id: Option<Id>,
+    pub name: Option<String>,
+    pub on_conflict: Option<ConflictPolicy>,
+    pub copy_permissions: Option<bool>,
+}
+
+/**
+ * Representation of a batch of operations on Files and Dirs. If `atomic` is true, either all or
+ * none of the operations are executed (see `controller::batch::run_batch()`).
+ */
+#[derive(Debug, Deserialize)]
+pub struct BatchMsg {
+    pub atomic: Option<bool>,
+    pub operations: Vec<BatchOperationMsg>,
+}
+
+/**
+ * Representation of a single operation of a batch on the File or Dir given by `id`. The kind of
+ * the operation is given by the field `op` (`"delete"`, `"move"`, `"copy"`, `"grant"` or
+ * `"revoke"`), the other fields are the same as for the corresponding single request.
+ */
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOperationMsg {
+    Delete {
+        id: Id,
+    },
+    Move {
+        id: Id,
+        #[serde(flatten)]
+        move_msg: MoveMsg,
+    },
+    Copy {
+        id: Id,
+        #[serde(flatten)]
+        copy_msg: CopyMsg,
+    },
+    Grant {
+        id: Id,
+        group_id: Id,
+        permission: Permission,
+    },
+    Revoke {
+        id: Id,
+        group_id: Id,
+        permission: Permission,
+    },
+}
+
+/**
+ * Representation of the result of a single operation of a batch. `status` is the HTTP status
+ * code, which the corresponding single request would have returned, or 424 (Failed Dependency),
+ * if the operation was not executed, because another operation of an atomic batch failed. `node`
+ * is the File or Dir after a successful operation (before it was deleted for `"delete"`).
+ */
+#[derive(Debug, Serialize)]
+pub struct BatchResultMsg {
+    pub status: u16,
+    pub node: Option<FsNodeMsg>,
+}
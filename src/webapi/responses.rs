@@ -0,0 +1,472 @@
+use chrono::{offset::Utc, DateTime};
+use rocket::{
+    http::{ContentType, Header, Method, Status},
+    request::Request,
+    response::{self, content::Html, Redirect, Responder, Response},
+    tokio::{
+        io::{AsyncRead, AsyncSeek, ReadBuf},
+        sync::mpsc,
+        task,
+    },
+};
+use rocket_dyn_templates::Template;
+
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::errors::share_error_status;
+use crate::{
+    config::Config,
+    controller::{
+        self,
+        archive::{self, ArchiveEntry, ArchiveFormat},
+        blob::BlobReader,
+    },
+    database::Database,
+};
+
+// Size of the chunks in which streamed responses are sent:
+const CHUNK_SIZE: usize = 64 * 1024;
+// Number of chunks, that may be buffered before the producing thread has to wait:
+const CHUNK_BUFFER: usize = 4;
+
+/**
+ * A response containing an archive, which is built by a blocking task while it is streamed to the
+ * client. If building the archive fails, the connection is aborted, so that the client never
+ * mistakes a truncated archive for a complete one.
+ */
+pub struct ArchiveStream {
+    format: ArchiveFormat,
+    name: String,
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+}
+
+impl ArchiveStream {
+    /**
+     * Starts writing an archive of the given format containing the given entries and returns a
+     * response, that streams the archive. `name` is used as the suggested filename (without
+     * extension).
+     */
+    pub fn new(
+        format: ArchiveFormat,
+        name: String,
+        entries: Vec<ArchiveEntry>,
+        db: Database,
+        config: Config,
+    ) -> Self {
+        let (sender, chunks) = mpsc::channel(CHUNK_BUFFER);
+
+        task::spawn_blocking(move || {
+            let writer = ChannelWriter {
+                sender: sender.clone(),
+                buffer: Vec::with_capacity(CHUNK_SIZE),
+            };
+            if let Err(e) = archive::write_archive(format, &entries, &db, &config, writer) {
+                // TODO: Logging
+                println!("Error while streaming archive: {}", e);
+                let _ = sender.blocking_send(Err(io::Error::other(e.to_string())));
+            }
+        });
+
+        ArchiveStream {
+            format,
+            name,
+            chunks,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'r> for ArchiveStream {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'r> {
+        let mut response = Response::build().finalize();
+        response.set_streamed_body(ContentStream::from_chunks(self.chunks));
+
+        response.set_header(
+            ContentType::parse_flexible(self.format.media_type()).unwrap_or(ContentType::Binary),
+        );
+        response.set_header(Header::new(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{}.{}\"",
+                self.name.replace(['"', '\\'], "_"),
+                self.format.extension()
+            ),
+        ));
+
+        Ok(response)
+    }
+}
+
+/// The response to a public link, which is either a page, the content of a shared file or a
+/// redirect after the password of the link was entered.
+#[derive(Responder)]
+pub enum SharedContent {
+    Page(Html<Template>),
+    File(FileContent),
+    Redirect(Redirect),
+}
+
+/**
+ * A response containing the content of a file, that supports conditional requests (`ETag`,
+ * `Last-Modified`, `If-None-Match`, `If-Modified-Since`) and single byte ranges (`Range`,
+ * `If-Range`).
+ */
+pub struct FileContent {
+    file_id: u64,
+    content_type: ContentType,
+    content: BlobReader,
+    // Token of the public link, whose downloads are counted by this response:
+    share_token: Option<String>,
+}
+
+impl FileContent {
+    /// Creates a new response for the content of the file given by `file_id`.
+    pub fn new(file_id: u64, content_type: ContentType, content: BlobReader) -> Self {
+        FileContent {
+            file_id,
+            content_type,
+            content,
+            share_token: None,
+        }
+    }
+
+    /**
+     * Creates a new response for the content of the file given by `file_id`, that was requested
+     * by the public link given by `share_token`.
+     *
+     * A download of the link is counted for every response, that sends the content up to its
+     * last byte, so splitting a download into ranges can not avoid the count. Revalidations
+     * (`304 Not Modified`) and ranges, that end before the last byte, are not counted.
+     */
+    pub fn shared(
+        file_id: u64,
+        content_type: ContentType,
+        content: BlobReader,
+        share_token: String,
+    ) -> Self {
+        FileContent {
+            share_token: Some(share_token),
+            ..Self::new(file_id, content_type, content)
+        }
+    }
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for FileContent {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let content = self.content;
+        let length = content.len();
+        let modified: DateTime<Utc> = DateTime::from(content.modified());
+        let etag = format!(
+            "\"{:x}-{:x}-{:x}\"",
+            self.file_id,
+            length,
+            modified.timestamp_nanos_opt().unwrap_or_default()
+        );
+        let last_modified = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+
+        let mut response = Response::build()
+            .header(Header::new("ETag", etag.clone()))
+            .header(Header::new("Last-Modified", last_modified.clone()))
+            .header(Header::new("Accept-Ranges", "bytes"))
+            .finalize();
+
+        if is_not_modified(req, &etag, &modified) {
+            response.set_status(Status::NotModified);
+            return Ok(response);
+        }
+        response.set_header(self.content_type);
+
+        // Only honor the Range header, if the representation did not change since the client
+        // fetched the first part:
+        let range = match req.headers().get_one("If-Range") {
+            Some(validator) if validator != etag && validator != last_modified => None,
+            _ => req.headers().get_one("Range"),
+        };
+        let (start, end) = match range.and_then(|range| parse_range(range, length)) {
+            Some(Ok(range)) => range,
+            Some(Err(())) => {
+                response.set_status(Status::RangeNotSatisfiable);
+                response.set_header(Header::new("Content-Range", format!("bytes */{}", length)));
+                return Ok(response);
+            }
+            // No or unsupported Range header:
+            None => {
+                count_share_download(req, self.share_token.as_deref())?;
+                response.set_sized_body(length as usize, ContentStream::new(content, 0, length));
+                return Ok(response);
+            }
+        };
+
+        if end == length {
+            count_share_download(req, self.share_token.as_deref())?;
+        }
+        response.set_status(Status::PartialContent);
+        response.set_header(Header::new(
+            "Content-Range",
+            format!("bytes {}-{}/{}", start, end - 1, length),
+        ));
+        response.set_sized_body(
+            (end - start) as usize,
+            ContentStream::new(content, start, end),
+        );
+
+        Ok(response)
+    }
+}
+
+/**
+ * A reader for content, that is sent in chunks by a blocking task. Errors sent by the task are
+ * returned by the reader, which aborts the response.
+ *
+ * The size of the body is always known in advance or not at all, so seeking is not supported.
+ */
+struct ContentStream {
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    // Position inside of `chunk`:
+    position: usize,
+}
+
+impl ContentStream {
+    /// Returns a reader for the bytes `start..end` of a blob. The (possibly encrypted) blob is read
+    /// by a blocking task, which sends the plaintext in chunks of `CHUNK_SIZE`.
+    fn new(mut content: BlobReader, start: u64, end: u64) -> Self {
+        let (sender, chunks) = mpsc::channel(CHUNK_BUFFER);
+
+        task::spawn_blocking(move || {
+            if let Err(e) = content.seek(SeekFrom::Start(start)) {
+                let _ = sender.blocking_send(Err(e));
+                return;
+            }
+            let mut remaining = end - start;
+            while remaining > 0 {
+                let mut chunk = vec![0u8; remaining.min(CHUNK_SIZE as u64) as usize];
+                let res = content.read_exact(&mut chunk).map(|_| chunk);
+                let failed = res.is_err();
+                remaining -= res.as_ref().map(|c| c.len() as u64).unwrap_or(0);
+                if sender.blocking_send(res).is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        Self::from_chunks(chunks)
+    }
+
+    fn from_chunks(chunks: mpsc::Receiver<io::Result<Vec<u8>>>) -> Self {
+        ContentStream {
+            chunks,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl AsyncRead for ContentStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.position >= self.chunk.len() {
+            match self.chunks.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e)),
+                // All chunks were received:
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let read = buf.remaining().min(self.chunk.len() - self.position);
+        let position = self.position;
+        buf.put_slice(&self.chunk[position..(position + read)]);
+        self.position += read;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for ContentStream {
+    fn start_seek(self: Pin<&mut Self>, _: SeekFrom) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Content streams are not seekable.",
+        ))
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Content streams are not seekable.",
+        )))
+    }
+}
+
+/// Counts a download of the public link given by `share_token`, if there is one. HEAD requests
+/// are not counted, as they do not transfer the content.
+fn count_share_download(req: &Request<'_>, share_token: Option<&str>) -> Result<(), Status> {
+    let token = match share_token {
+        Some(token) if req.method() != Method::Head => token,
+        _ => return Ok(()),
+    };
+    let db = req
+        .rocket()
+        .state::<Database>()
+        .ok_or(Status::InternalServerError)?;
+    controller::share::count_download(token, db)
+        .map_err(|err| share_error_status("GET shared file content", err))
+}
+
+/// Returns true, if the validators of the request show, that the client already has the current
+/// representation of the content.
+fn is_not_modified(req: &Request<'_>, etag: &str, modified: &DateTime<Utc>) -> bool {
+    if let Some(tags) = req.headers().get_one("If-None-Match") {
+        // Weak comparison as required by RFC 7232:
+        let etag = etag.trim_start_matches("W/");
+        return tags
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag);
+    }
+    if let Some(since) = req.headers().get_one("If-Modified-Since") {
+        if let Ok(since) = DateTime::parse_from_rfc2822(since) {
+            return modified.timestamp() <= since.timestamp();
+        }
+    }
+    false
+}
+
+/**
+ * Parses the value of a Range header for content of the given length.
+ *
+ * Returns None, if the header is malformed or requests multiple ranges, in which case the whole
+ * content should be sent. Returns `Some(Err(()))` if the range is not satisfiable. Otherwise the
+ * requested range `start..end` is returned.
+ */
+fn parse_range(range: &str, length: u64) -> Option<Result<(u64, u64), ()>> {
+    let range = range.trim().strip_prefix("bytes=")?;
+    if range.contains(',') {
+        return None;
+    }
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let (start, end) = if start.is_empty() {
+        // Suffix range containing the last bytes:
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 {
+            return Some(Err(()));
+        }
+        (length.saturating_sub(suffix), length)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            length
+        } else {
+            let last: u64 = end.parse().ok()?;
+            if last < start {
+                return None;
+            }
+            length.min(last.saturating_add(1))
+        };
+        (start, end)
+    };
+
+    if start >= length {
+        Some(Err(()))
+    } else {
+        Some(Ok((start, end)))
+    }
+}
+
+/// A `Write` that sends everything written to it through a channel in chunks of `CHUNK_SIZE`.
+struct ChannelWriter {
+    sender: mpsc::Sender<io::Result<Vec<u8>>>,
+    buffer: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.sender
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "The receiver was dropped."))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+impl Drop for ChannelWriter {
+    fn drop(&mut self) {
+        let _ = self.send_buffer();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_accepts_closed_and_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=0-0", 10), Some(Ok((0, 1))));
+        assert_eq!(parse_range("bytes=2-5", 10), Some(Ok((2, 6))));
+        assert_eq!(parse_range("bytes=5-20", 10), Some(Ok((5, 10))));
+        assert_eq!(parse_range("bytes=3-", 10), Some(Ok((3, 10))));
+    }
+
+    #[test]
+    fn parse_range_accepts_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-4", 10), Some(Ok((6, 10))));
+        assert_eq!(parse_range("bytes=-20", 10), Some(Ok((0, 10))));
+        assert_eq!(parse_range("bytes=-0", 10), Some(Err(())));
+    }
+
+    #[test]
+    fn parse_range_does_not_overflow() {
+        assert_eq!(
+            parse_range("bytes=0-18446744073709551615", 10),
+            Some(Ok((0, 10)))
+        );
+        assert_eq!(
+            parse_range("bytes=-18446744073709551615", 10),
+            Some(Ok((0, 10)))
+        );
+        assert_eq!(parse_range("bytes=0-18446744073709551616", 10), None);
+    }
+
+    #[test]
+    fn parse_range_rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=10-", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=10-12", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn parse_range_ignores_malformed_and_multiple_ranges() {
+        assert_eq!(parse_range("items=0-1", 10), None);
+        assert_eq!(parse_range("bytes=5-2", 10), None);
+        assert_eq!(parse_range("bytes=a-b", 10), None);
+        assert_eq!(parse_range("bytes=0-1,3-4", 10), None);
+    }
+}
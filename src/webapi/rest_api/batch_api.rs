@@ -0,0 +1,62 @@
+use rocket::{http::Status, serde::json::Json, Route, State};
+
+use super::super::{errors::error_status, BatchMsg, BatchResultMsg, FsNodeMsg};
+use crate::{
+    config::Config,
+    controller::{self, batch::BatchResult},
+    database::Database,
+    models::UserSession,
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![run_batch]
+}
+
+/*
+ * Executes a list of operations (delete, move, copy, grant or revoke a permission) on files and
+ * directories and returns a list with the result of every operation. If the field `atomic` is
+ * true, either all or none of the operations are executed, copies are not supported and an
+ * operation may not depend on the changes of a previous one.
+ */
+#[post("/batch", data = "<batch>")]
+async fn run_batch(
+    batch: Json<BatchMsg>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<Vec<BatchResultMsg>>, Status> {
+    let batch = batch.into_inner();
+    let user_id = session.user_id;
+    // Copies access the blob storage:
+    let results = controller::blob::run_blocking(db, config, move |db, config| {
+        controller::batch::run_batch(
+            batch.operations,
+            batch.atomic.unwrap_or(false),
+            user_id,
+            db,
+            config,
+        )
+    })
+    .await
+    .map_err(|err| error_status("POST /rest_api/batch", err))?;
+
+    Ok(Json(
+        results
+            .into_iter()
+            .map(|result| match result {
+                BatchResult::Done(node) => BatchResultMsg {
+                    status: Status::Ok.code,
+                    node: Some(FsNodeMsg::from(*node)),
+                },
+                BatchResult::Failed(err) => BatchResultMsg {
+                    status: error_status("POST /rest_api/batch", err).code,
+                    node: None,
+                },
+                BatchResult::Skipped => BatchResultMsg {
+                    status: Status::FailedDependency.code,
+                    node: None,
+                },
+            })
+            .collect(),
+    ))
+}
@@ -1,7 +1,16 @@
-use rocket::{http::Status, serde::json::Json, Route, State};
+use rocket::{
+    http::{CookieJar, Status},
+    serde::json::Json,
+    Route, State,
+};
 
-use super::super::{DirMsg, GroupMsg};
+use super::super::{
+    errors::{error_status, permission_error_status, share_error_status},
+    share_access, CopyMsg, DirMsg, GroupMsg, ListingMsg, ListingParams, MoveMsg, PermissionsMsg,
+    RightsMsg, UserMsg,
+};
 use crate::{
+    config::Config,
     controller,
     database::Database,
     models::{Id, UserSession},
@@ -12,9 +21,21 @@ pub fn get_routes() -> Vec<Route> {
     routes![
         add_dir,
         get_dir_info,
+        get_dir_childs,
         update_dir_infos,
+        move_dir,
+        copy_dir,
         add_read_permission,
-        add_write_permission
+        add_write_permission,
+        remove_read_permission,
+        remove_write_permission,
+        add_user_read_permission,
+        add_user_write_permission,
+        remove_user_read_permission,
+        remove_user_write_permission,
+        set_permissions,
+        set_group_rights,
+        set_user_rights
     ]
 }
 
@@ -22,17 +43,31 @@ pub fn get_routes() -> Vec<Route> {
  * If the requests cookies correspond to a valid User (building a UserSession succeeds) who does
  * not have the necessary rights for this action, an appropriate HTTP error Status code is
  * returned.
+ * Without a user session the directory may be accessed by the token of a public link (<share>),
+ * if the directory is accessible by this link. Password protected links need the access cookie of
+ * POST /rest_api/shares/<token>/access.
  * Otherwise the metadata of the directory given by <dir_id> (JSON representation of a Dir) is
  * returned.
  */
-#[get("/dirs/<dir_id>")]
+#[get("/dirs/<dir_id>?<share>")]
 async fn get_dir_info(
     dir_id: Id,
+    share: Option<&str>,
+    cookies: &CookieJar<'_>,
     session: Option<UserSession>,
     db: &State<Database>,
+    config: &State<Config>,
 ) -> Result<Json<DirMsg>, Status> {
     let dir_id = dir_id.inner();
 
+    if let (None, Some(token)) = (&session, share) {
+        let access = share_access(cookies, token);
+        return controller::share::open_share(token, access.as_deref(), db, config)
+            .and_then(|share| controller::share::get_shared_dir(&share, Some(dir_id), db))
+            .map(|dir| Json(DirMsg::from(dir)))
+            .map_err(|err| share_error_status("GET /rest_api/dirs/...", err));
+    }
+
     match controller::get_dir_info(dir_id, session.as_ref().map(|s| s.user_id), db) {
         Ok(dir) => Ok(Json(DirMsg::from(dir))),
         Err(Error::NoSuchDir) => Err(Status::NotFound),
@@ -47,6 +82,63 @@ async fn get_dir_info(
     }
 }
 
+/*
+ * Returns a page of the childs of the directory given by <dir_id> as JSON representations of
+ * Files and Dirs (see ListingParams for the query parameters). If there are more childs, the
+ * field `next_cursor` of the response has to be given as <cursor> to get the next page.
+ * The user needs the right `list` on the directory. Like for GET /dirs/<dir_id>, the directory
+ * may be accessed by the token of a public link (<share>) without a user session.
+ */
+#[get("/dirs/<dir_id>/childs?<share>&<params..>")]
+async fn get_dir_childs(
+    dir_id: Id,
+    share: Option<&str>,
+    cookies: &CookieJar<'_>,
+    params: ListingParams,
+    session: Option<UserSession>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<ListingMsg>, Status> {
+    let dir_id = dir_id.inner();
+
+    if let (None, Some(token)) = (&session, share) {
+        let access = share_access(cookies, token);
+        return controller::share::open_share(token, access.as_deref(), db, config)
+            .and_then(|share| {
+                let dir = controller::share::get_shared_dir(&share, Some(dir_id), db)?;
+                controller::share::list_shared_childs(&share, &dir, &params.to_query()?, db)
+            })
+            .map(|listing| Json(ListingMsg::from(listing)))
+            .map_err(|err| share_error_status("GET /rest_api/dirs/.../childs", err));
+    }
+
+    let user_id = session.as_ref().map(|s| s.user_id);
+    match params
+        .to_query()
+        .and_then(|query| controller::listing::list_dir(dir_id, &query, user_id, db))
+    {
+        Ok(listing) => Ok(Json(ListingMsg::from(listing))),
+        Err(Error::NoSuchDir) => Err(Status::NotFound),
+        Err(Error::MissingAuthorization) => {
+            if session.is_some() {
+                Err(Status::Forbidden)
+            } else {
+                Err(Status::Unauthorized)
+            }
+        }
+        Err(Error::BadCall) => {
+            // TODO: Logging
+            println!("Error on GET /rest_api/dirs/.../childs: Invalid query parameters.");
+            Err(Status::BadRequest)
+        }
+        Err(err) => {
+            // TODO: Logging
+            println!("Error on GET /rest_api/dirs/.../childs: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
 /*
  * Adds a directory (Dir) to the database.
  * The new directory receives a new unique id and has no childs. Other fields like name and
@@ -84,6 +176,11 @@ async fn add_dir(
             println!("Trying to add a dir without parent.");
             Err(Status::BadRequest) // Maybe Status::NotFound would be more secure?
         }
+        Err(Error::TargetExists) => {
+            // TODO: Logging
+            println!("Trying to add a dir with a name, that is already used.");
+            Err(Status::Conflict)
+        }
         Err(_) => {
             // TODO: Logging
             println!("Could not insert Dir to DB.");
@@ -97,9 +194,10 @@ async fn add_dir(
  * body.
  * The body should follow the format of a Dir struct. Missing fields will be intantiated with
  * default values. Fields with default values will not be updated.
- * This function will ignore the child_ids field of Dir and therefore it will not remove or add any
- * childs from a directory, even if the child_ids field in the request body does not contain all or
- * none of the directory's childs.
+ * This function will ignore the child_count field of Dir, childs are only added or removed by
+ * adding, moving or deleting them.
+ * The owner can not be changed this way (see POST /ownership_offers), if the field `owner_id`
+ * differs from the current owner, HTTP 400 is returned.
  * The given updates will be written to the database.
  */
 #[put("/dirs/<dir_id>", data = "<dir_infos>")]
@@ -108,6 +206,7 @@ async fn update_dir_infos(
     dir_infos: Json<DirMsg>,
     session: UserSession,
     db: &State<Database>,
+    config: &State<Config>,
 ) -> Result<Json<DirMsg>, Status> {
     let mut dir_info = dir_infos.into_inner();
 
@@ -123,7 +222,7 @@ async fn update_dir_infos(
     }
 
     // Performe update:
-    match controller::update_dir_infos(dir_info, session.user_id, db) {
+    match controller::update_dir_infos(dir_info, session.user_id, db, config) {
         Ok(dir) => Ok(Json(DirMsg::from(dir))),
         Err(Error::NoSuchDir) => {
             // TODO: Logging
@@ -137,14 +236,44 @@ async fn update_dir_infos(
             println!("User tried to update a directory which he doesn't own.");
             Err(Status::Forbidden) // Maybe Status::NotFound would be more secure?
         }
-        Err(err) => {
-            // TODO: Logging
-            println!("Error when updating directory: {}", err);
-            Err(Status::InternalServerError)
-        }
+        Err(err) => Err(error_status("PUT /rest_api/dirs/<dir_id>", err)),
     }
 }
 
+/*
+ * Moves the directory given by <dir_id> into the directory given by the field `parent_id` of the
+ * requests body and renames it to the value of the field `name`. Missing fields are not changed.
+ * Renaming requires the right `rename` on the directory, moving it into another directory requires
+ * the right `delete` on the directory and the right `create` on the new parent directory.
+ * If the new parent already contains a file or directory with the same name, the conflict is
+ * handled as given by the field `on_conflict`: `"fail"` (HTTP 409), `"rename"` (a suffix like
+ * " (1)" is added) or `"overwrite"` (the existing file or directory is moved into the trash). If
+ * the field is missing, the configured default policy is used.
+ * A directory can not be moved into itself or one of its descendants and root directories can not
+ * be moved at all (HTTP 409).
+ * A JSON representation of the moved directory is returned.
+ */
+#[post("/dirs/<dir_id>/move", data = "<move_msg>")]
+async fn move_dir(
+    dir_id: Id,
+    move_msg: Json<MoveMsg>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<DirMsg>, Status> {
+    let move_msg = move_msg.into_inner();
+    controller::moving::move_dir(
+        dir_id.inner(),
+        move_msg.parent_id.map(|id| id.inner()),
+        move_msg.name,
+        move_msg.on_conflict.unwrap_or(config.name_conflict_policy),
+        session.user_id,
+        db,
+    )
+    .map(|dir| Json(DirMsg::from(dir)))
+    .map_err(|err| error_status("POST /rest_api/dirs/<dir_id>/move", err))
+}
+
 /*
  * Give read permissions for a given directory to a given group.
  *
@@ -189,7 +318,7 @@ async fn add_read_permission(
 
     match controller::add_read_permission(dir_id, group_id, session.user_id, db) {
         Ok(()) => {
-            dir.read_group_ids.push(group_id);
+            dir.acl.read_group_ids.push(group_id);
             Ok(Json(DirMsg::from(dir)))
         }
         Err(Error::NoSuchTarget) => {
@@ -256,7 +385,7 @@ async fn add_write_permission(
 
     match controller::add_write_permission(dir_id, group_id, session.user_id, db) {
         Ok(()) => {
-            dir.write_group_ids.push(group_id);
+            dir.acl.write_group_ids.push(group_id);
             Ok(Json(DirMsg::from(dir)))
         }
         Err(Error::NoSuchTarget) => {
@@ -279,3 +408,254 @@ async fn add_write_permission(
         }
     }
 }
+
+/*
+ * Take read permissions for a given directory away from a given group.
+ *
+ * Remove the group given by <group_id> from the list of readable groups of the directory given by
+ * <dir_id>. Only users with the right `share` on the directory may do this. If the group was not in
+ * the list, nothing changes.
+ * Otherwise a JSON representation of the new directory is returned.
+ */
+#[delete("/dirs/<dir_id>/permissions/read/<group_id>")]
+async fn remove_read_permission(
+    dir_id: Id,
+    group_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "DELETE /rest_api/dirs/<dir_id>/permissions/read/<group_id>";
+    controller::remove_read_permission(dir_id.inner(), group_id.inner(), session.user_id, db)
+        .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+        .map(|dir| Json(DirMsg::from(dir)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Take write permissions for a given directory away from a given group.
+ *
+ * Remove the group given by <group_id> from the list of writeable groups of the directory given by
+ * <dir_id>. Only users with the right `share` on the directory may do this. If the group was not in
+ * the list, nothing changes.
+ * Otherwise a JSON representation of the new directory is returned.
+ */
+#[delete("/dirs/<dir_id>/permissions/write/<group_id>")]
+async fn remove_write_permission(
+    dir_id: Id,
+    group_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "DELETE /rest_api/dirs/<dir_id>/permissions/write/<group_id>";
+    controller::remove_write_permission(dir_id.inner(), group_id.inner(), session.user_id, db)
+        .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+        .map(|dir| Json(DirMsg::from(dir)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Give read permissions for a given directory to a single user.
+ *
+ * Add the user given by the field `id` of the requests body to the list of readable users of the
+ * directory given by <dir_id>. Only users with the right `share` on the directory may do this. If
+ * the user does not exist, HTTP 400 is returned.
+ * Otherwise a JSON representation of the new directory is returned.
+ */
+#[post("/dirs/<dir_id>/permissions/read/users", data = "<user>")]
+async fn add_user_read_permission(
+    dir_id: Id,
+    user: Json<UserMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "POST /rest_api/dirs/<dir_id>/permissions/read/users";
+    let user_id = user.into_inner().id.ok_or(Status::BadRequest)?.as_int();
+    controller::add_user_read_permission(dir_id.inner(), user_id, session.user_id, db)
+        .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+        .map(|dir| Json(DirMsg::from(dir)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Give write permissions for a given directory to a single user.
+ *
+ * Add the user given by the field `id` of the requests body to the list of writeable users of the
+ * directory given by <dir_id>. Only users with the right `share` on the directory may do this. If
+ * the user does not exist, HTTP 400 is returned.
+ * Otherwise a JSON representation of the new directory is returned.
+ */
+#[post("/dirs/<dir_id>/permissions/write/users", data = "<user>")]
+async fn add_user_write_permission(
+    dir_id: Id,
+    user: Json<UserMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "POST /rest_api/dirs/<dir_id>/permissions/write/users";
+    let user_id = user.into_inner().id.ok_or(Status::BadRequest)?.as_int();
+    controller::add_user_write_permission(dir_id.inner(), user_id, session.user_id, db)
+        .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+        .map(|dir| Json(DirMsg::from(dir)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Take read permissions for a given directory away from a single user.
+ *
+ * Remove the user given by <user_id> from the list of readable users of the directory given by
+ * <dir_id>. Only users with the right `share` on the directory may do this. If the user was not in
+ * the list, nothing changes.
+ * Otherwise a JSON representation of the new directory is returned.
+ */
+#[delete("/dirs/<dir_id>/permissions/read/users/<user_id>")]
+async fn remove_user_read_permission(
+    dir_id: Id,
+    user_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "DELETE /rest_api/dirs/<dir_id>/permissions/read/users/<user_id>";
+    controller::remove_user_read_permission(dir_id.inner(), user_id.inner(), session.user_id, db)
+        .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+        .map(|dir| Json(DirMsg::from(dir)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Take write permissions for a given directory away from a single user.
+ *
+ * Remove the user given by <user_id> from the list of writeable users of the directory given by
+ * <dir_id>. Only users with the right `share` on the directory may do this. If the user was not in
+ * the list, nothing changes.
+ * Otherwise a JSON representation of the new directory is returned.
+ */
+#[delete("/dirs/<dir_id>/permissions/write/users/<user_id>")]
+async fn remove_user_write_permission(
+    dir_id: Id,
+    user_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "DELETE /rest_api/dirs/<dir_id>/permissions/write/users/<user_id>";
+    controller::remove_user_write_permission(dir_id.inner(), user_id.inner(), session.user_id, db)
+        .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+        .map(|dir| Json(DirMsg::from(dir)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Replace all group and user permissions of a given directory.
+ *
+ * The lists of readable and writeable groups and users of the directory given by <dir_id> are
+ * replaced by the fields `read_group_ids`, `write_group_ids`, `read_user_ids` and `write_user_ids`
+ * of the requests body. The additional rights of groups and users are replaced by the fields
+ * `group_rights` and `user_rights`, lists of objects with the fields `id` and `rights` (see
+ * PUT /dirs/<dir_id>/permissions/rights/groups/<group_id>). Missing user lists and rights
+ * are treated as empty. If the field `inherit_permissions` is given, it sets whether the directory
+ * inherits the groups and users of its parent. Only users with the right `share` on the directory
+ * may do this. If one of the groups or users does not exist, HTTP 400 is returned.
+ * Otherwise a JSON representation of the new directory is returned.
+ */
+#[put("/dirs/<dir_id>/permissions", data = "<permissions>")]
+async fn set_permissions(
+    dir_id: Id,
+    permissions: Json<PermissionsMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "PUT /rest_api/dirs/<dir_id>/permissions";
+    controller::set_permissions(
+        dir_id.inner(),
+        permissions.into_inner(),
+        session.user_id,
+        db,
+    )
+    .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+    .map(|dir| Json(DirMsg::from(dir)))
+    .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Set the additional rights of a given group on a given directory.
+ *
+ * The group given by <group_id> gets the rights given by the field `rights` of the requests body
+ * on the directory given by <dir_id> and its descendants, in addition to the permissions of the
+ * readable and writeable groups. The rights are a list of `"list"`, `"read"`, `"write"`,
+ * `"create"`, `"delete"`, `"rename"` and `"share"`, an empty list removes the rights of the group.
+ * Only users with the right `share` on the directory may do this. If the group does not exist,
+ * HTTP 400 is returned.
+ * Otherwise a JSON representation of the new directory is returned.
+ */
+#[put(
+    "/dirs/<dir_id>/permissions/rights/groups/<group_id>",
+    data = "<rights>"
+)]
+async fn set_group_rights(
+    dir_id: Id,
+    group_id: Id,
+    rights: Json<RightsMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "PUT /rest_api/dirs/<dir_id>/permissions/rights/groups/<group_id>";
+    let rights = rights.into_inner().rights;
+    controller::set_group_rights(
+        dir_id.inner(),
+        group_id.inner(),
+        rights,
+        session.user_id,
+        db,
+    )
+    .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+    .map(|dir| Json(DirMsg::from(dir)))
+    .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Set the additional rights of a single user on a given directory.
+ *
+ * Like PUT /dirs/<dir_id>/permissions/rights/groups/<group_id>, but for the user given by
+ * <user_id>.
+ */
+#[put("/dirs/<dir_id>/permissions/rights/users/<user_id>", data = "<rights>")]
+async fn set_user_rights(
+    dir_id: Id,
+    user_id: Id,
+    rights: Json<RightsMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let route = "PUT /rest_api/dirs/<dir_id>/permissions/rights/users/<user_id>";
+    let rights = rights.into_inner().rights;
+    controller::set_user_rights(dir_id.inner(), user_id.inner(), rights, session.user_id, db)
+        .and_then(|()| controller::get_dir_info(dir_id.inner(), Some(session.user_id), db))
+        .map(|dir| Json(DirMsg::from(dir)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Copies the directory given by <dir_id> into the directory given by the field `parent_id` of the
+ * requests body. The copy is named like the original or as given by the field `name` and is
+ * owned by the user. All descendants, which the user may list or read, are copied as well. A
+ * directory can not be copied into itself or one of its descendants (HTTP 409).
+ * Group and user permissions are copied, if the field `copy_permissions` is true, otherwise the
+ * copy only has the groups and users inherited from its new parent. Name conflicts are handled
+ * as given by the field `on_conflict` (see `POST /dirs/<dir_id>/move`).
+ * A JSON representation of the copy is returned.
+ */
+#[post("/dirs/<dir_id>/copy", data = "<copy_msg>")]
+async fn copy_dir(
+    dir_id: Id,
+    copy_msg: Json<CopyMsg>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<DirMsg>, Status> {
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::copy::copy_dir(dir_id.inner(), copy_msg.into_inner(), user_id, db, config)
+    })
+    .await
+    .map(|dir| Json(DirMsg::from(dir)))
+    .map_err(|err| error_status("POST /rest_api/dirs/<dir_id>/copy", err))
+}
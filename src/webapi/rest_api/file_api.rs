@@ -1,13 +1,17 @@
 use rocket::{
     fs::TempFile,
-    http::{ContentType, MediaType, Status},
+    http::{ContentType, CookieJar, MediaType, Status},
     serde::json::Json,
     Route, State,
 };
 
 use std::str::FromStr;
 
-use super::super::{FileMsg, GroupMsg};
+use super::super::{
+    errors::{error_status, permission_error_status, share_error_status},
+    responses::FileContent,
+    share_access, CopyMsg, FileMsg, GroupMsg, MoveMsg, PermissionsMsg, RightsMsg, UserMsg,
+};
 use crate::{
     config::Config,
     controller,
@@ -23,8 +27,19 @@ pub fn get_routes() -> Vec<Route> {
         get_file_content,
         get_file_info,
         update_file_infos,
+        move_file,
+        copy_file,
         add_read_permission,
-        add_write_permission
+        add_write_permission,
+        remove_read_permission,
+        remove_write_permission,
+        add_user_read_permission,
+        add_user_write_permission,
+        remove_user_read_permission,
+        remove_user_write_permission,
+        set_permissions,
+        set_group_rights,
+        set_user_rights
     ]
 }
 
@@ -32,25 +47,40 @@ pub fn get_routes() -> Vec<Route> {
  * If the requests cookies correspond to a valid User (building a UserSession succeeds) who does
  * not have the necessary rights for this action, an appropriate HTTP error Status code is
  * returned.
+ * Without a user session the file may be accessed by the token of a public link (<share>), if
+ * the file is accessible by this link. Password protected links need the access cookie of
+ * POST /rest_api/shares/<token>/access.
  * Otherwise the metadata of the file given by <file_id> (JSON representation of a File) is
  * returned.
  */
-#[get("/files/<file_id>")]
+#[get("/files/<file_id>?<share>")]
 async fn get_file_info(
     file_id: Id,
+    share: Option<&str>,
+    cookies: &CookieJar<'_>,
     session: Option<UserSession>,
     db: &State<Database>,
+    config: &State<Config>,
 ) -> Result<Json<FileMsg>, Status> {
     let file_id = file_id.inner();
 
-    // TODO: Handle public files (session == None)
-    if session.is_none() {
-        // TODO: Logging
-        println!("Error on GET /rest_api/files/...: No user session.");
-        return Err(Status::Unauthorized);
-    }
+    let session = match (session, share) {
+        (Some(s), _) => s,
+        (None, Some(token)) => {
+            let access = share_access(cookies, token);
+            return controller::share::open_share(token, access.as_deref(), db, config)
+                .and_then(|share| controller::share::get_shared_file(&share, Some(file_id), db))
+                .map(|file| Json(FileMsg::from(file)))
+                .map_err(|err| share_error_status("GET /rest_api/files/...", err));
+        }
+        (None, None) => {
+            // TODO: Logging
+            println!("Error on GET /rest_api/files/...: No user session.");
+            return Err(Status::Unauthorized);
+        }
+    };
 
-    match controller::get_file_info(file_id, session.unwrap().user_id, db) {
+    match controller::get_file_info(file_id, session.user_id, db) {
         Ok(file) => Ok(Json(FileMsg::from(file))),
         Err(Error::NoSuchFile) => {
             // TODO: Logging
@@ -102,6 +132,11 @@ async fn add_file(
             println!("Trying to add a dir without parent.");
             Err(Status::BadRequest) // Maybe Status::NotFound would be more secure?
         }
+        Err(Error::TargetExists) => {
+            // TODO: Logging
+            println!("Trying to add a file with a name, that is already used.");
+            Err(Status::Conflict)
+        }
         Err(_) => {
             // TODO: Logging
             println!("Could not insert Dir to DB.");
@@ -114,6 +149,8 @@ async fn add_file(
  * Updates the metadata of a file given by <file_id> to the values given inside the requests body.
  * The body should follow the format of a File struct. Missing fields will be intantiated with
  * default values. Fields with default values will not be updated.
+ * The owner can not be changed this way (see POST /ownership_offers), if the field `owner_id`
+ * differs from the current owner, HTTP 400 is returned.
  * The given updates will be written to the database.
  */
 #[put("/files/<file_id>", data = "<file_info>")]
@@ -122,6 +159,7 @@ async fn update_file_infos(
     file_info: Json<FileMsg>,
     session: UserSession,
     db: &State<Database>,
+    config: &State<Config>,
 ) -> Result<Json<FileMsg>, Status> {
     let mut file_info = file_info.into_inner();
 
@@ -137,7 +175,7 @@ async fn update_file_infos(
     }
 
     // Performe update:
-    match controller::update_file_infos(file_info, session.user_id, db) {
+    match controller::update_file_infos(file_info, session.user_id, db, config) {
         Ok(file) => Ok(Json(FileMsg::from(file))),
         Err(Error::NoSuchFile) => {
             // TODO: Logging
@@ -149,14 +187,42 @@ async fn update_file_infos(
             println!("User tried to update a file which he doesn't own.");
             Err(Status::Forbidden) // Maybe Status::NotFound would be more secure?
         }
-        Err(err) => {
-            // TODO: Logging
-            println!("Error when updating file: {}", err);
-            Err(Status::InternalServerError)
-        }
+        Err(err) => Err(error_status("PUT /rest_api/files/<file_id>", err)),
     }
 }
 
+/*
+ * Moves the file given by <file_id> into the directory given by the field `parent_id` of the
+ * requests body and renames it to the value of the field `name`. Missing fields are not changed.
+ * Renaming requires the right `rename` on the file, moving it into another directory requires the
+ * right `delete` on the file and the right `create` on the new parent directory.
+ * If the new parent already contains a file or directory with the same name, the conflict is
+ * handled as given by the field `on_conflict`: `"fail"` (HTTP 409), `"rename"` (a suffix like
+ * " (1)" is added) or `"overwrite"` (the existing file or directory is moved into the trash). If
+ * the field is missing, the configured default policy is used.
+ * A JSON representation of the moved file is returned.
+ */
+#[post("/files/<file_id>/move", data = "<move_msg>")]
+async fn move_file(
+    file_id: Id,
+    move_msg: Json<MoveMsg>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<FileMsg>, Status> {
+    let move_msg = move_msg.into_inner();
+    controller::moving::move_file(
+        file_id.inner(),
+        move_msg.parent_id.map(|id| id.inner()),
+        move_msg.name,
+        move_msg.on_conflict.unwrap_or(config.name_conflict_policy),
+        session.user_id,
+        db,
+    )
+    .map(|file| Json(FileMsg::from(file)))
+    .map_err(|err| error_status("POST /rest_api/files/<file_id>/move", err))
+}
+
 /*
  * Set the content of a file given by <file_id> to the content of the reqest body (given by
  * file_content).
@@ -203,16 +269,55 @@ async fn update_file_content(
 
 /*
  * Get the content of a file given by <file_id> as the response to the given request.
+ * Range requests and conditional requests (If-None-Match, If-Modified-Since, If-Range) are
+ * supported.
  * Fails with an appropriate HTTP Status, if the cookies of the request correspond to a User
  * (building a UserSession succeeds) which does not have the necessary rights for this action.
+ * Without a user session the file may be downloaded by the token of a public link (<share>), if
+ * the file is accessible by this link. Password protected links need the access cookie of
+ * POST /rest_api/shares/<token>/access.
  */
-#[get("/files/<file_id>/data")]
+#[get("/files/<file_id>/data?<share>")]
 async fn get_file_content(
     file_id: Id,
-    session: UserSession,
+    share: Option<&str>,
+    cookies: &CookieJar<'_>,
+    session: Option<UserSession>,
     db: &State<Database>,
     config: &State<Config>,
-) -> Result<(ContentType, std::fs::File), Status> {
+) -> Result<FileContent, Status> {
+    let session = match (session, share) {
+        (Some(s), _) => s,
+        (None, Some(token)) => {
+            let access = share_access(cookies, token);
+            let share = controller::share::open_share(token, access.as_deref(), db, config)
+                .map_err(|err| share_error_status("GET /rest_api/files/.../data", err))?;
+            return controller::blob::run_blocking(db, config, move |db, config| {
+                let (file, content) = controller::share::get_shared_file_content(
+                    &share,
+                    Some(file_id.inner()),
+                    db,
+                    config,
+                )?;
+                let media_type =
+                    MediaType::from_str(&file.media_type).or(Err(Error::EncodingError))?;
+                Ok(FileContent::shared(
+                    file.id,
+                    ContentType(media_type),
+                    content,
+                    share.token,
+                ))
+            })
+            .await
+            .map_err(|err| share_error_status("GET /rest_api/files/.../data", err));
+        }
+        (None, None) => {
+            // TODO: Logging
+            println!("Error on GET /rest_api/files/.../data: No user session.");
+            return Err(Status::Unauthorized);
+        }
+    };
+
     // TODO: Refactor as soon as Result.flatten is stabilized.
     match controller::get_file_content(file_id.inner(), session.user_id, db, config)
         .await
@@ -221,10 +326,14 @@ async fn get_file_content(
                 &controller::get_file_info(file_id.inner(), session.user_id, db)?.media_type,
             )
             .or(Err(Error::EncodingError))?;
-            Ok((ContentType(media_type), file))
+            Ok(FileContent::new(
+                file_id.inner(),
+                ContentType(media_type),
+                file,
+            ))
         }) {
         Ok(file) => Ok(file),
-        Err(Error::NoSuchFile) => {
+        Err(Error::NoSuchFile) | Err(Error::NoSuchTarget) => {
             // TODO: Logging
             println!("User tried to download non-existing file.");
             Err(Status::NotFound)
@@ -285,7 +394,7 @@ async fn add_read_permission(
 
     match controller::add_read_permission(file_id, group_id, session.user_id, db) {
         Ok(()) => {
-            file.read_group_ids.push(group_id);
+            file.acl.read_group_ids.push(group_id);
             Ok(Json(FileMsg::from(file)))
         }
         Err(Error::NoSuchTarget) => {
@@ -351,7 +460,7 @@ async fn add_write_permission(
 
     match controller::add_write_permission(file_id, group_id, session.user_id, db) {
         Ok(()) => {
-            file.write_group_ids.push(group_id);
+            file.acl.write_group_ids.push(group_id);
             Ok(Json(FileMsg::from(file)))
         }
         Err(Error::NoSuchTarget) => {
@@ -374,3 +483,263 @@ async fn add_write_permission(
         }
     }
 }
+
+/*
+ * Take read permissions for a given file away from a given group.
+ *
+ * Remove the group given by <group_id> from the list of readable groups of the file given by
+ * <file_id>. Only users with the right `share` on the file may do this. If the group was not in the
+ * list, nothing changes.
+ * Otherwise a JSON representation of the new file is returned.
+ */
+#[delete("/files/<file_id>/permissions/read/<group_id>")]
+async fn remove_read_permission(
+    file_id: Id,
+    group_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "DELETE /rest_api/files/<file_id>/permissions/read/<group_id>";
+    controller::remove_read_permission(file_id.inner(), group_id.inner(), session.user_id, db)
+        .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+        .map(|file| Json(FileMsg::from(file)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Take write permissions for a given file away from a given group.
+ *
+ * Remove the group given by <group_id> from the list of writeable groups of the file given by
+ * <file_id>. Only users with the right `share` on the file may do this. If the group was not in the
+ * list, nothing changes.
+ * Otherwise a JSON representation of the new file is returned.
+ */
+#[delete("/files/<file_id>/permissions/write/<group_id>")]
+async fn remove_write_permission(
+    file_id: Id,
+    group_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "DELETE /rest_api/files/<file_id>/permissions/write/<group_id>";
+    controller::remove_write_permission(file_id.inner(), group_id.inner(), session.user_id, db)
+        .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+        .map(|file| Json(FileMsg::from(file)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Give read permissions for a given file to a single user.
+ *
+ * Add the user given by the field `id` of the requests body to the list of readable users of the
+ * file given by <file_id>. Only users with the right `share` on the file may do this. If the user
+ * does not exist, HTTP 400 is returned.
+ * Otherwise a JSON representation of the new file is returned.
+ */
+#[post("/files/<file_id>/permissions/read/users", data = "<user>")]
+async fn add_user_read_permission(
+    file_id: Id,
+    user: Json<UserMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "POST /rest_api/files/<file_id>/permissions/read/users";
+    let user_id = user.into_inner().id.ok_or(Status::BadRequest)?.as_int();
+    controller::add_user_read_permission(file_id.inner(), user_id, session.user_id, db)
+        .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+        .map(|file| Json(FileMsg::from(file)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Give write permissions for a given file to a single user.
+ *
+ * Add the user given by the field `id` of the requests body to the list of writeable users of the
+ * file given by <file_id>. Only users with the right `share` on the file may do this. If the user
+ * does not exist, HTTP 400 is returned.
+ * Otherwise a JSON representation of the new file is returned.
+ */
+#[post("/files/<file_id>/permissions/write/users", data = "<user>")]
+async fn add_user_write_permission(
+    file_id: Id,
+    user: Json<UserMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "POST /rest_api/files/<file_id>/permissions/write/users";
+    let user_id = user.into_inner().id.ok_or(Status::BadRequest)?.as_int();
+    controller::add_user_write_permission(file_id.inner(), user_id, session.user_id, db)
+        .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+        .map(|file| Json(FileMsg::from(file)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Take read permissions for a given file away from a single user.
+ *
+ * Remove the user given by <user_id> from the list of readable users of the file given by
+ * <file_id>. Only users with the right `share` on the file may do this. If the user was not in the
+ * list, nothing changes.
+ * Otherwise a JSON representation of the new file is returned.
+ */
+#[delete("/files/<file_id>/permissions/read/users/<user_id>")]
+async fn remove_user_read_permission(
+    file_id: Id,
+    user_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "DELETE /rest_api/files/<file_id>/permissions/read/users/<user_id>";
+    controller::remove_user_read_permission(file_id.inner(), user_id.inner(), session.user_id, db)
+        .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+        .map(|file| Json(FileMsg::from(file)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Take write permissions for a given file away from a single user.
+ *
+ * Remove the user given by <user_id> from the list of writeable users of the file given by
+ * <file_id>. Only users with the right `share` on the file may do this. If the user was not in the
+ * list, nothing changes.
+ * Otherwise a JSON representation of the new file is returned.
+ */
+#[delete("/files/<file_id>/permissions/write/users/<user_id>")]
+async fn remove_user_write_permission(
+    file_id: Id,
+    user_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "DELETE /rest_api/files/<file_id>/permissions/write/users/<user_id>";
+    controller::remove_user_write_permission(file_id.inner(), user_id.inner(), session.user_id, db)
+        .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+        .map(|file| Json(FileMsg::from(file)))
+        .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Replace all group and user permissions of a given file.
+ *
+ * The lists of readable and writeable groups and users of the file given by <file_id> are
+ * replaced by the fields `read_group_ids`, `write_group_ids`, `read_user_ids` and `write_user_ids`
+ * of the requests body. The additional rights of groups and users are replaced by the fields
+ * `group_rights` and `user_rights`, lists of objects with the fields `id` and `rights` (see
+ * PUT /files/<file_id>/permissions/rights/groups/<group_id>). Missing user lists and rights
+ * are treated as empty. If the field `inherit_permissions` is given, it sets whether the file
+ * inherits the groups and users of its parent. Only users with the right `share` on the file
+ * may do this. If one of the groups or users does not exist, HTTP 400 is returned.
+ * Otherwise a JSON representation of the new file is returned.
+ */
+#[put("/files/<file_id>/permissions", data = "<permissions>")]
+async fn set_permissions(
+    file_id: Id,
+    permissions: Json<PermissionsMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "PUT /rest_api/files/<file_id>/permissions";
+    controller::set_permissions(
+        file_id.inner(),
+        permissions.into_inner(),
+        session.user_id,
+        db,
+    )
+    .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+    .map(|file| Json(FileMsg::from(file)))
+    .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Set the additional rights of a given group on a given file.
+ *
+ * The group given by <group_id> gets the rights given by the field `rights` of the requests body
+ * on the file given by <file_id> and its descendants, in addition to the permissions of the
+ * readable and writeable groups. The rights are a list of `"list"`, `"read"`, `"write"`,
+ * `"create"`, `"delete"`, `"rename"` and `"share"`, an empty list removes the rights of the group.
+ * Only users with the right `share` on the file may do this. If the group does not exist,
+ * HTTP 400 is returned.
+ * Otherwise a JSON representation of the new file is returned.
+ */
+#[put(
+    "/files/<file_id>/permissions/rights/groups/<group_id>",
+    data = "<rights>"
+)]
+async fn set_group_rights(
+    file_id: Id,
+    group_id: Id,
+    rights: Json<RightsMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "PUT /rest_api/files/<file_id>/permissions/rights/groups/<group_id>";
+    let rights = rights.into_inner().rights;
+    controller::set_group_rights(
+        file_id.inner(),
+        group_id.inner(),
+        rights,
+        session.user_id,
+        db,
+    )
+    .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+    .map(|file| Json(FileMsg::from(file)))
+    .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Set the additional rights of a single user on a given file.
+ *
+ * Like PUT /files/<file_id>/permissions/rights/groups/<group_id>, but for the user given by
+ * <user_id>.
+ */
+#[put(
+    "/files/<file_id>/permissions/rights/users/<user_id>",
+    data = "<rights>"
+)]
+async fn set_user_rights(
+    file_id: Id,
+    user_id: Id,
+    rights: Json<RightsMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FileMsg>, Status> {
+    let route = "PUT /rest_api/files/<file_id>/permissions/rights/users/<user_id>";
+    let rights = rights.into_inner().rights;
+    controller::set_user_rights(
+        file_id.inner(),
+        user_id.inner(),
+        rights,
+        session.user_id,
+        db,
+    )
+    .and_then(|()| controller::get_file_info(file_id.inner(), session.user_id, db))
+    .map(|file| Json(FileMsg::from(file)))
+    .map_err(|err| permission_error_status(route, err))
+}
+
+/*
+ * Copies the file given by <file_id> into the directory given by the field `parent_id` of the
+ * requests body. The copy is named like the original or as given by the field `name` and is
+ * owned by the user. The copy references the same content as the original, previous versions
+ * are not copied.
+ * Group and user permissions are copied, if the field `copy_permissions` is true, otherwise the
+ * copy only has the groups and users inherited from its new parent. Name conflicts are handled
+ * as given by the field `on_conflict` (see `POST /files/<file_id>/move`).
+ * A JSON representation of the copy is returned.
+ */
+#[post("/files/<file_id>/copy", data = "<copy_msg>")]
+async fn copy_file(
+    file_id: Id,
+    copy_msg: Json<CopyMsg>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<FileMsg>, Status> {
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::copy::copy_file(file_id.inner(), copy_msg.into_inner(), user_id, db, config)
+    })
+    .await
+    .map(|file| Json(FileMsg::from(file)))
+    .map_err(|err| error_status("POST /rest_api/files/<file_id>/copy", err))
+}
@@ -1,14 +1,30 @@
 use rocket::Route;
 
+mod batch_api;
 mod dir_api;
 mod file_api;
 mod group_api;
+mod ownership_api;
+mod path_api;
+mod search_api;
+mod share_api;
+mod trash_api;
+mod upload_api;
 mod user_api;
+mod version_api;
 
 pub fn get_routes() -> Vec<Route> {
     let mut res = file_api::get_routes();
     res.extend(dir_api::get_routes());
     res.extend(user_api::get_routes());
     res.extend(group_api::get_routes());
+    res.extend(upload_api::get_routes());
+    res.extend(share_api::get_routes());
+    res.extend(ownership_api::get_routes());
+    res.extend(trash_api::get_routes());
+    res.extend(version_api::get_routes());
+    res.extend(path_api::get_routes());
+    res.extend(batch_api::get_routes());
+    res.extend(search_api::get_routes());
     res
 }
@@ -0,0 +1,77 @@
+use rocket::{http::Status, serde::json::Json, Route, State};
+
+use super::super::{errors::error_status, OwnershipOfferMsg};
+use crate::{
+    controller,
+    database::Database,
+    models::{Id, UserSession},
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![create_offer, get_offers, accept_offer, remove_offer]
+}
+
+/*
+ * Offers the ownership of the file or directory given by the field `fs_node_id` of the requests
+ * body to the user given by the field `new_owner_id`. A previous offer for the same file or
+ * directory is replaced. Only the owner may do this (HTTP 403). If the new owner does not exist
+ * or already owns the file or directory, HTTP 400 is returned. Root directories can not be
+ * transferred (HTTP 409).
+ * Otherwise a JSON representation of the new offer is returned.
+ */
+#[post("/ownership_offers", data = "<offer_info>")]
+async fn create_offer(
+    offer_info: Json<OwnershipOfferMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<OwnershipOfferMsg>, Status> {
+    controller::ownership::offer_ownership(offer_info.into_inner(), session.user_id, db)
+        .map(|offer| Json(OwnershipOfferMsg::from(offer)))
+        .map_err(|err| error_status("POST /rest_api/ownership_offers", err))
+}
+
+/*
+ * Returns a list of all pending offers, that were made by or to the user, the most recent first.
+ */
+#[get("/ownership_offers")]
+async fn get_offers(
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<Vec<OwnershipOfferMsg>>, Status> {
+    controller::ownership::get_offers(session.user_id, db)
+        .map(|offers| Json(offers.into_iter().map(OwnershipOfferMsg::from).collect()))
+        .map_err(|err| error_status("GET /rest_api/ownership_offers", err))
+}
+
+/*
+ * Accepts the offer for the file or directory given by <fs_node_id>, that was made to the user.
+ * The user becomes the owner of the file or directory and of all its descendants, which belong
+ * to the user, who made the offer. If there is no such offer or it is not valid anymore, HTTP 404
+ * is returned.
+ * Otherwise a JSON representation of the accepted offer is returned.
+ */
+#[post("/ownership_offers/<fs_node_id>/accept")]
+async fn accept_offer(
+    fs_node_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<OwnershipOfferMsg>, Status> {
+    controller::ownership::accept_offer(fs_node_id.inner(), session.user_id, db)
+        .map(|offer| Json(OwnershipOfferMsg::from(offer)))
+        .map_err(|err| error_status("POST /rest_api/ownership_offers/<fs_node_id>/accept", err))
+}
+
+/*
+ * Removes the offer for the file or directory given by <fs_node_id>. The offer may be withdrawn
+ * by the user, who made it, and declined by the user, to whom it was made.
+ */
+#[delete("/ownership_offers/<fs_node_id>")]
+async fn remove_offer(
+    fs_node_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<OwnershipOfferMsg>, Status> {
+    controller::ownership::remove_offer(fs_node_id.inner(), session.user_id, db)
+        .map(|offer| Json(OwnershipOfferMsg::from(offer)))
+        .map_err(|err| error_status("DELETE /rest_api/ownership_offers/<fs_node_id>", err))
+}
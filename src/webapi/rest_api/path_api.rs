@@ -0,0 +1,104 @@
+use rocket::{
+    fs::TempFile,
+    http::{
+        uri::{fmt::Path, Segments},
+        ContentType, MediaType, Status,
+    },
+    serde::json::Json,
+    Route, State,
+};
+
+use std::str::FromStr;
+
+use super::super::{errors::error_status, responses::FileContent, DirMsg, FileMsg, FsNodeMsg};
+use crate::{config::Config, controller, database::Database, models::UserSession, Error};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![get_path_info, make_dirs, get_path_content, put_path_content]
+}
+
+/*
+ * Returns the metadata of the file or directory given by <path> relative to the root directory
+ * of the user (e.g. `/rest_api/paths/projects/2026/report.pdf`). The field `type` of the JSON
+ * representation is "file" or "dir". The user needs the right `list` on every directory along
+ * the path and on the target.
+ */
+#[get("/paths/<path..>")]
+async fn get_path_info(
+    path: Segments<'_, Path>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<FsNodeMsg>, Status> {
+    let path: Vec<&str> = path.collect();
+    controller::path::resolve_path(&path, session.user_id, db)
+        .map(|target| Json(FsNodeMsg::from(target)))
+        .map_err(|err| error_status("GET /rest_api/paths/<path..>", err))
+}
+
+/*
+ * Creates the directory given by <path> and all missing directories along the path (like
+ * `mkdir -p`). Existing directories are kept. A JSON representation of the directory is returned.
+ */
+#[post("/paths/<path..>")]
+async fn make_dirs(
+    path: Segments<'_, Path>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<DirMsg>, Status> {
+    let path: Vec<&str> = path.collect();
+    controller::path::make_dirs(&path, session.user_id, db)
+        .map(|dir| Json(DirMsg::from(dir)))
+        .map_err(|err| error_status("POST /rest_api/paths/<path..>", err))
+}
+
+/*
+ * Returns the content of the file given by <path> like `GET /rest_api/files/<file_id>/data`.
+ */
+#[get("/contents/<path..>")]
+async fn get_path_content(
+    path: Segments<'_, Path>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<FileContent, Status> {
+    let path: Vec<String> = path.map(String::from).collect();
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        controller::path::get_content(&path, user_id, db, config)
+    })
+    .await
+    .and_then(|(file, content)| {
+        let media_type = MediaType::from_str(&file.media_type).or(Err(Error::EncodingError))?;
+        Ok(FileContent::new(file.id, ContentType(media_type), content))
+    })
+    .map_err(|err| error_status("GET /rest_api/contents/<path..>", err))
+}
+
+/*
+ * Sets the content of the file given by <path> to the content of the request body. If there is no
+ * such file, it is created in the existing directory given by the rest of the path. A JSON
+ * representation of the file is returned.
+ */
+#[put("/contents/<path..>", data = "<file_content>")]
+async fn put_path_content(
+    path: Segments<'_, Path>,
+    file_content: TempFile<'_>,
+    content_type: &ContentType,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<FileMsg>, Status> {
+    let path: Vec<&str> = path.collect();
+    controller::path::put_content(
+        &path,
+        session.user_id,
+        db,
+        config,
+        file_content,
+        content_type.media_type(),
+    )
+    .await
+    .map(|file| Json(FileMsg::from(file)))
+    .map_err(|err| error_status("PUT /rest_api/contents/<path..>", err))
+}
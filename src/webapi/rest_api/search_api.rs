@@ -0,0 +1,43 @@
+use rocket::{http::Status, serde::json::Json, Route, State};
+
+use super::super::{errors::error_status, SearchHitMsg};
+use crate::{
+    controller::{
+        self,
+        search::{SearchQuery, DEFAULT_LIMIT},
+    },
+    database::Database,
+    models::UserSession,
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![search]
+}
+
+/*
+ * Searches the files, which the user may read, for the words given by <q>. A file matches, if
+ * every word is the beginning of a word in its name, media type, owner name or (for text files)
+ * content. The results can be restricted to a <media_type> (a complete media type like
+ * `image/png` or a main type with the wildcard subtype `*`) and to the files of the user named
+ * <owner>. At most <limit> results are returned, the best matches first (as JSON representations
+ * of Files with the additional fields `score` and `matches`).
+ */
+#[get("/search?<q>&<media_type>&<owner>&<limit>")]
+async fn search(
+    q: &str,
+    media_type: Option<&str>,
+    owner: Option<&str>,
+    limit: Option<usize>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<Vec<SearchHitMsg>>, Status> {
+    let query = SearchQuery {
+        text: String::from(q),
+        media_type: media_type.map(String::from),
+        owner: owner.map(String::from),
+        limit: limit.unwrap_or(DEFAULT_LIMIT),
+    };
+    controller::search::search(&query, session.user_id, db)
+        .map(|hits| Json(hits.into_iter().map(SearchHitMsg::from).collect()))
+        .map_err(|err| error_status("GET /rest_api/search", err))
+}
@@ -0,0 +1,130 @@
+use rocket::{
+    http::{CookieJar, Status},
+    serde::json::Json,
+    Route, State,
+};
+
+use super::super::{add_share_access, errors::share_error_status, share_access, ShareMsg};
+use crate::{
+    config::Config,
+    controller,
+    database::Database,
+    models::{Id, UserSession},
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        create_share,
+        get_share,
+        unlock_share,
+        get_file_shares,
+        get_dir_shares,
+        remove_share
+    ]
+}
+
+/*
+ * Creates a new public link for the file or directory given by the field `fs_node_id` of the
+ * requests body. The optional fields `expiration_date` (seconds since the UNIX epoch), `password`
+ * and `download_limit` restrict the usage of the link.
+ * If the requests cookies correspond to a valid User (building a UserSession succeeds) who does
+ * not have the right `share` on the file or directory, an appropriate HTTP error Status code is
+ * returned.
+ * Otherwise a JSON representation of the new link including its token is returned.
+ */
+#[post("/shares", data = "<share_info>")]
+async fn create_share(
+    share_info: Json<ShareMsg>,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<ShareMsg>, Status> {
+    controller::share::create_share(share_info.into_inner(), session.user_id, db)
+        .map(|share| Json(ShareMsg::from(share)))
+        .map_err(|err| share_error_status("POST /rest_api/shares", err))
+}
+
+/*
+ * Returns a JSON representation of the public link given by <token>. This route does not need a
+ * user session. If the link is protected by a password, the request needs the access cookie of
+ * POST /shares/<token>/access.
+ */
+#[get("/shares/<token>")]
+async fn get_share(
+    token: &str,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<ShareMsg>, Status> {
+    let access = share_access(cookies, token);
+    controller::share::open_share(token, access.as_deref(), db, config)
+        .map(|share| Json(ShareMsg::from(share)))
+        .map_err(|err| share_error_status("GET /rest_api/shares/<token>", err))
+}
+
+/*
+ * Checks the field `password` of the requests body against the password of the public link given
+ * by <token>. On success, a cookie granting access to the link (and to the routes with the query
+ * parameter <share>) is set and a JSON representation of the link is returned. This route does
+ * not need a user session.
+ */
+#[post("/shares/<token>/access", data = "<credentials>")]
+async fn unlock_share(
+    token: &str,
+    credentials: Json<ShareMsg>,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<ShareMsg>, Status> {
+    let password = credentials.password.as_deref().unwrap_or("");
+    controller::share::unlock_share(token, password, db, config)
+        .map(|(share, access)| {
+            add_share_access(cookies, &share.token, access);
+            Json(ShareMsg::from(share))
+        })
+        .map_err(|err| share_error_status("POST /rest_api/shares/<token>/access", err))
+}
+
+/*
+ * Returns a list of all public links of the file given by <file_id>.
+ * Only users with the right `share` on the file may list its links.
+ */
+#[get("/files/<file_id>/shares")]
+async fn get_file_shares(
+    file_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<Vec<ShareMsg>>, Status> {
+    controller::share::get_shares(file_id.inner(), session.user_id, db)
+        .map(|shares| Json(shares.into_iter().map(ShareMsg::from).collect()))
+        .map_err(|err| share_error_status("GET /rest_api/files/<file_id>/shares", err))
+}
+
+/*
+ * Returns a list of all public links of the directory given by <dir_id>.
+ * Only users with the right `share` on the directory may list its links.
+ */
+#[get("/dirs/<dir_id>/shares")]
+async fn get_dir_shares(
+    dir_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<Vec<ShareMsg>>, Status> {
+    controller::share::get_shares(dir_id.inner(), session.user_id, db)
+        .map(|shares| Json(shares.into_iter().map(ShareMsg::from).collect()))
+        .map_err(|err| share_error_status("GET /rest_api/dirs/<dir_id>/shares", err))
+}
+
+/*
+ * Removes the public link given by <token>. The link may be removed by its creator and by the
+ * users with the right `share` on the shared file or directory.
+ */
+#[delete("/shares/<token>")]
+async fn remove_share(
+    token: &str,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<ShareMsg>, Status> {
+    controller::share::remove_share(token, session.user_id, db)
+        .map(|share| Json(ShareMsg::from(share)))
+        .map_err(|err| share_error_status("DELETE /rest_api/shares/<token>", err))
+}
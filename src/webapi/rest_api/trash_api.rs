@@ -0,0 +1,89 @@
+use rocket::{http::Status, serde::json::Json, Route, State};
+
+use super::super::{errors::error_status, TrashMsg};
+use crate::{
+    config::Config,
+    controller,
+    database::Database,
+    models::{Id, UserSession},
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![get_trash, restore, remove_from_trash, empty_trash]
+}
+
+/*
+ * Returns a list of all files and directories in the trash of the user, the most recently deleted
+ * first. Files and directories are moved into the trash by `DELETE /files/<file_id>` and
+ * `DELETE /dirs/<dir_id>` and are removed permanently after the configured retention period.
+ */
+#[get("/trash")]
+async fn get_trash(
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<Vec<TrashMsg>>, Status> {
+    controller::trash::get_trash(session.user_id, db)
+        .map(|entries| Json(entries.into_iter().map(TrashMsg::from).collect()))
+        .map_err(|err| error_status("GET /rest_api/trash", err))
+}
+
+/*
+ * Restores the file or directory given by <fs_node_id> from the trash of the user into the
+ * directory from which it was deleted. If this directory does not exist anymore, the file or
+ * directory is restored into the users root directory. A JSON representation of the trash entry
+ * with the new parent is returned.
+ */
+#[post("/trash/<fs_node_id>/restore")]
+async fn restore(
+    fs_node_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<TrashMsg>, Status> {
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::trash::restore(fs_node_id.inner(), user_id, db, config)
+    })
+    .await
+    .map(|entry| Json(TrashMsg::from(entry)))
+    .map_err(|err| error_status("POST /rest_api/trash/<fs_node_id>/restore", err))
+}
+
+/*
+ * Removes the file or directory given by <fs_node_id> and all its descendants from the trash of
+ * the user permanently.
+ */
+#[delete("/trash/<fs_node_id>")]
+async fn remove_from_trash(
+    fs_node_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<TrashMsg>, Status> {
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::trash::remove_from_trash(fs_node_id.inner(), user_id, db, config)
+    })
+    .await
+    .map(|entry| Json(TrashMsg::from(entry)))
+    .map_err(|err| error_status("DELETE /rest_api/trash/<fs_node_id>", err))
+}
+
+/*
+ * Removes all files and directories from the trash of the user permanently and returns a list of
+ * the removed entries.
+ */
+#[delete("/trash")]
+async fn empty_trash(
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<Vec<TrashMsg>>, Status> {
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::trash::empty_trash(user_id, db, config)
+    })
+    .await
+    .map(|entries| Json(entries.into_iter().map(TrashMsg::from).collect()))
+    .map_err(|err| error_status("DELETE /rest_api/trash", err))
+}
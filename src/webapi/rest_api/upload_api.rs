@@ -0,0 +1,165 @@
+use rocket::{data::Data, http::Status, serde::json::Json, Route, State};
+
+use super::super::{errors::error_status, FileMsg, UploadMsg};
+use crate::{
+    config::Config,
+    controller,
+    database::Database,
+    models::{Id, UserSession},
+    Error,
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        create_upload,
+        get_upload,
+        write_chunk,
+        finish_upload,
+        cancel_upload
+    ]
+}
+
+/*
+ * Starts a new resumable upload.
+ * The requests body should contain the total `length` of the upload and either the `file_id` of
+ * an existing file, whose content should be replaced, or the `parent_id` and `name` of a new file.
+ * If the requests cookies correspond to a valid User (building a UserSession succeeds) who does
+ * not have the necessary rights for this action, an appropriate HTTP error Status code is
+ * returned.
+ * Otherwise a JSON representation of the new upload including its ID is returned.
+ */
+#[post("/uploads", data = "<upload_info>")]
+async fn create_upload(
+    upload_info: Json<UploadMsg>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<UploadMsg>, Status> {
+    match controller::upload::create_upload(upload_info.into_inner(), session.user_id, db, config) {
+        Ok(upload) => Ok(Json(UploadMsg::from(upload))),
+        Err(Error::BadCall) => {
+            // TODO: Logging
+            println!("Error on POST /rest_api/uploads: Missing fields or invalid length.");
+            Err(Status::BadRequest)
+        }
+        Err(Error::ForbiddenAction) => {
+            // TODO: Logging
+            println!("Error on POST /rest_api/uploads: Too many unfinished uploads.");
+            Err(Status::TooManyRequests)
+        }
+        Err(Error::NoSuchFile) | Err(Error::NoSuchDir) => {
+            // TODO: Logging
+            println!("Error on POST /rest_api/uploads: Nonexisting target.");
+            Err(Status::NotFound)
+        }
+        Err(Error::MissingAuthorization) => {
+            // TODO: Logging
+            println!("Error on POST /rest_api/uploads: Missing rights to write target.");
+            Err(Status::Forbidden) // Maybe Status::NotFound would be more secure?
+        }
+        Err(Error::TargetExists) => {
+            // TODO: Logging
+            println!("Error on POST /rest_api/uploads: Name is already used.");
+            Err(Status::Conflict)
+        }
+        Err(err) => {
+            // TODO: Logging
+            println!("Error on POST /rest_api/uploads: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/*
+ * Returns a JSON representation of the upload given by <upload_id> including the ranges of bytes,
+ * that were already received, and the offset up to which all bytes were received.
+ */
+#[get("/uploads/<upload_id>")]
+async fn get_upload(
+    upload_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<UploadMsg>, Status> {
+    controller::upload::get_upload(upload_id.inner(), session.user_id, db, config)
+        .map(|upload| Json(UploadMsg::from(upload)))
+        .map_err(|err| upload_error_status("GET /rest_api/uploads/<upload_id>", err))
+}
+
+/*
+ * Writes the requests body into the upload given by <upload_id>, starting at byte <offset>.
+ * Chunks may be sent in any order and may be repeated, if a connection was dropped.
+ * A JSON representation of the updated upload is returned.
+ */
+#[put("/uploads/<upload_id>?<offset>", data = "<chunk>")]
+async fn write_chunk(
+    upload_id: Id,
+    offset: u64,
+    chunk: Data<'_>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<UploadMsg>, Status> {
+    controller::upload::write_chunk(
+        upload_id.inner(),
+        session.user_id,
+        offset,
+        chunk,
+        db,
+        config,
+    )
+    .await
+    .map(|upload| Json(UploadMsg::from(upload)))
+    .map_err(|err| upload_error_status("PUT /rest_api/uploads/<upload_id>", err))
+}
+
+/*
+ * Finishes the upload given by <upload_id> after all bytes were received. The corresponding file
+ * is created or its content is replaced and a JSON representation of the file is returned.
+ */
+#[post("/uploads/<upload_id>/finish")]
+async fn finish_upload(
+    upload_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<FileMsg>, Status> {
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::upload::finish_upload(upload_id.inner(), user_id, db, config)
+    })
+    .await
+    .map(|file| Json(FileMsg::from(file)))
+    .map_err(|err| upload_error_status("POST /rest_api/uploads/<upload_id>/finish", err))
+}
+
+/*
+ * Cancels the upload given by <upload_id> and removes all received data.
+ */
+#[delete("/uploads/<upload_id>")]
+async fn cancel_upload(
+    upload_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<UploadMsg>, Status> {
+    controller::upload::cancel_upload(upload_id.inner(), session.user_id, db, config)
+        .map(|upload| Json(UploadMsg::from(upload)))
+        .map_err(|err| upload_error_status("DELETE /rest_api/uploads/<upload_id>", err))
+}
+
+fn upload_error_status(route: &str, err: Error) -> Status {
+    match err {
+        Error::BadCall => {
+            // TODO: Logging
+            println!("Error on {}: Data exceeds the length of the upload.", route);
+            Status::PayloadTooLarge
+        }
+        Error::ForbiddenAction => {
+            // TODO: Logging
+            println!("Error on {}: Upload is incomplete.", route);
+            Status::Conflict
+        }
+        err => error_status(route, err),
+    }
+}
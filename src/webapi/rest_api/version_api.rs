@@ -0,0 +1,140 @@
+use rocket::{
+    http::{ContentType, Status},
+    serde::json::Json,
+    Route, State,
+};
+
+use super::super::{
+    errors::error_status, responses::FileContent, FileMsg, VersionLimitMsg, VersionMsg,
+};
+use crate::{
+    config::Config,
+    controller,
+    database::Database,
+    models::{Id, UserSession},
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_versions,
+        get_version_content,
+        restore_version,
+        get_version_limit,
+        set_version_limit
+    ]
+}
+
+/*
+ * Returns a list of all versions of the content of the file given by <file_id>, ordered by their
+ * version number. The last version is the current content of the file.
+ */
+#[get("/files/<file_id>/versions")]
+async fn get_versions(
+    file_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+) -> Result<Json<Vec<VersionMsg>>, Status> {
+    controller::version::get_versions(file_id.inner(), session.user_id, db)
+        .map(|versions| Json(versions.into_iter().map(VersionMsg::from).collect()))
+        .map_err(|err| error_status("GET /rest_api/files/<file_id>/versions", err))
+}
+
+/*
+ * Get the content of the version <version> of the file given by <file_id> as the response to the
+ * given request. Range requests and conditional requests are supported.
+ */
+#[get("/files/<file_id>/versions/<version>/data")]
+async fn get_version_content(
+    file_id: Id,
+    version: u64,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<FileContent, Status> {
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::version::get_version_content(file_id.inner(), version, user_id, db, config)
+    })
+    .await
+    .map(|(version, content)| {
+        FileContent::new(
+            version.file_id,
+            ContentType::parse_flexible(&version.media_type).unwrap_or(ContentType::Binary),
+            content,
+        )
+    })
+    .map_err(|err| error_status("GET /rest_api/files/<file_id>/versions/<version>/data", err))
+}
+
+/*
+ * Makes the content of the version <version> the current content of the file given by <file_id>.
+ * The previous content is kept as a version. A JSON representation of the updated file is
+ * returned.
+ */
+#[post("/files/<file_id>/versions/<version>/restore")]
+async fn restore_version(
+    file_id: Id,
+    version: u64,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<FileMsg>, Status> {
+    let user_id = session.user_id;
+    controller::blob::run_blocking(db, config, move |db, config| {
+        controller::version::restore_version(file_id.inner(), version, user_id, db, config)
+    })
+    .await
+    .map(|file| Json(FileMsg::from(file)))
+    .map_err(|err| {
+        error_status(
+            "POST /rest_api/files/<file_id>/versions/<version>/restore",
+            err,
+        )
+    })
+}
+
+/*
+ * Returns the maximum number of previous versions, that are kept for the file given by
+ * <file_id>. If no limit is set for the file, `max_versions` is null and the default limit
+ * `default_max_versions` is used.
+ */
+#[get("/files/<file_id>/versions/limit")]
+async fn get_version_limit(
+    file_id: Id,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<VersionLimitMsg>, Status> {
+    controller::version::get_version_limit(file_id.inner(), session.user_id, db, config)
+        .map(|(max_versions, default_max_versions)| {
+            Json(VersionLimitMsg {
+                max_versions,
+                default_max_versions: Some(default_max_versions),
+            })
+        })
+        .map_err(|err| error_status("GET /rest_api/files/<file_id>/versions/limit", err))
+}
+
+/*
+ * Sets the maximum number of previous versions, that are kept for the file given by <file_id>,
+ * to the field `max_versions` of the request body. If it is null, the default limit is used for
+ * the file. Versions exceeding the limit are removed, when the content of the file is updated the
+ * next time.
+ */
+#[put("/files/<file_id>/versions/limit", data = "<limit>")]
+async fn set_version_limit(
+    file_id: Id,
+    limit: Json<VersionLimitMsg>,
+    session: UserSession,
+    db: &State<Database>,
+    config: &State<Config>,
+) -> Result<Json<VersionLimitMsg>, Status> {
+    controller::version::set_version_limit(file_id.inner(), limit.max_versions, session.user_id, db)
+        .map(|_| {
+            Json(VersionLimitMsg {
+                max_versions: limit.max_versions,
+                default_max_versions: Some(config.max_file_versions),
+            })
+        })
+        .map_err(|err| error_status("PUT /rest_api/files/<file_id>/versions/limit", err))
+}
@@ -141,6 +141,11 @@ function verifyFile(file) {
 	return true;
 }
 
+// Formats a UNIX timestamp like the date column of the content list.
+function formatDate(timestamp) {
+	return new Date(timestamp * 1000).toISOString().slice(0, 16).replace("T", " ");
+}
+
 function serializeBigInt(key, value) {
 	if (typeof value === "bigint") {
 		return value.toString();
@@ -196,6 +201,10 @@ function showNewFile() {
 	sizeField.setAttribute("class", "size");
 	sizeField.innerHTML = "&#160;";
 
+	let dateField = newRow.insertCell(-1);
+	dateField.setAttribute("class", "date");
+	dateField.innerHTML = "&#160;";
+
 	let nameField = newRow.insertCell(-1);
 	nameField.setAttribute("class", "list");
 	nameField.innerHTML = '<input type="file" class="new-file-input" id="new-file" autocomplete="off" placeholder="FILE">';
@@ -243,7 +252,11 @@ function uploadFile() {
 				if (res.status == 200) {
 					return res.json()
 				} else {
+					if (res.status == 409) {
+						alert("There already is a file or directory named \"" + file.name + "\".");
+					}
 					console.log(res);
+					return Promise.reject(res);
 				}
 			})
 			.then(function(res) {
@@ -293,7 +306,11 @@ function onUploadFile(req) {
 
 	let sizeField = newRow.insertCell(-1);
 	sizeField.setAttribute("class", "size");
-	sizeField.innerHTML = req.size ? req.size.toString() : "?";
+	sizeField.innerHTML = req.size != null ? req.size.toString() : "?";
+
+	let dateField = newRow.insertCell(-1);
+	dateField.setAttribute("class", "date");
+	dateField.innerHTML = req.modification_date != null ? formatDate(req.modification_date) : "?";
 
 	let nameField = newRow.insertCell(-1);
 	nameField.setAttribute("class", "list");
@@ -354,6 +371,10 @@ function showNewDir() {
 	sizeField.setAttribute("class", "size");
 	sizeField.innerHTML = "&#160;";
 
+	let dateField = newRow.insertCell(-1);
+	dateField.setAttribute("class", "date");
+	dateField.innerHTML = "&#160;";
+
 	let nameField = newRow.insertCell(-1);
 	nameField.setAttribute("class", "list");
 	nameField.innerHTML = '<input type="text" class="new-name-input" id="new-name" autocomplete="off" placeholder="NAME">';
@@ -398,7 +419,11 @@ function pushDir() {
 			if (res.status == 200) {
 				return res.json()
 			} else {
-				// TODO: Show error
+				if (res.status == 409) {
+					alert("There already is a file or directory named \"" + dirName + "\".");
+				}
+				// TODO: Show other errors
+				return Promise.reject(res);
 			}
 		})
 		.then(function(jsonRes) {
@@ -431,6 +456,10 @@ function onPushDir(req) {
 	sizeField.setAttribute("class", "size");
 	sizeField.innerHTML = "&#160;";
 
+	let dateField = newRow.insertCell(-1);
+	dateField.setAttribute("class", "date");
+	dateField.innerHTML = "&#160;";
+
 	let nameField = newRow.insertCell(-1);
 	nameField.setAttribute("class", "list");
 	nameField.innerHTML = '<a href="/dirs/' + req.id + '/view.html">' + req.name + '</a>';